@@ -0,0 +1,189 @@
+//! CBOR ([RFC 8949](https://www.rfc-editor.org/rfc/rfc8949)) conversions.
+//!
+//! Semantic tags are dropped and their content decoded as usual, except for bignums (tags 2 and
+//! 3) which are rejected since they cannot fit a json number.
+
+use ciborium::value::{Integer, Value};
+
+use crate::{
+    binary::{
+        Number,
+        classify_number,
+        domain::{BinaryError, Result},
+        float_to_number,
+        integer_to_number,
+    },
+    parser::JsonAST,
+};
+
+const POSITIVE_BIGNUM_TAG: u64 = 2;
+const NEGATIVE_BIGNUM_TAG: u64 = 3;
+
+pub fn to_cbor(ast: &JsonAST) -> Result<Vec<u8>> {
+    let mut output = Vec::new();
+    ciborium::ser::into_writer(&to_value(ast), &mut output)
+        .map_err(|e| BinaryError::EncodingError(e.to_string()))?;
+    Ok(output)
+}
+
+pub fn from_cbor(input: &[u8]) -> Result<JsonAST> {
+    let mut reader = input;
+    let value: Value = ciborium::de::from_reader(&mut reader)
+        .map_err(|e| BinaryError::DecodingError(e.to_string()))?;
+    if !reader.is_empty() {
+        return Err(BinaryError::TrailingBytes(reader.len()));
+    }
+    from_value(value)
+}
+
+fn to_value(ast: &JsonAST) -> Value {
+    match ast {
+        JsonAST::Null => Value::Null,
+        JsonAST::Boolean(b) => Value::Bool(*b),
+        JsonAST::Number(n) => match classify_number(*n) {
+            Number::Integer(i) => Value::Integer(Integer::from(i)),
+            Number::Float(f) => Value::Float(f),
+        },
+        JsonAST::String(s) => Value::Text(s.clone()),
+        JsonAST::Array(elements) => Value::Array(elements.iter().map(to_value).collect()),
+        JsonAST::Object(members) => Value::Map(
+            members
+                .iter()
+                .map(|(key, value)| (Value::Text(key.clone()), to_value(value)))
+                .collect(),
+        ),
+    }
+}
+
+fn from_value(value: Value) -> Result<JsonAST> {
+    match value {
+        Value::Null => Ok(JsonAST::Null),
+        Value::Bool(b) => Ok(JsonAST::Boolean(b)),
+        Value::Integer(i) => integer_to_number(i.into()).map(JsonAST::Number),
+        Value::Float(f) => float_to_number(f).map(JsonAST::Number),
+        Value::Text(s) => Ok(JsonAST::String(s)),
+        Value::Bytes(_) => Err(BinaryError::UnsupportedBinary),
+        Value::Tag(POSITIVE_BIGNUM_TAG | NEGATIVE_BIGNUM_TAG, _) => {
+            Err(BinaryError::UnsupportedType("CBOR bignum".to_string()))
+        }
+        Value::Tag(_, inner) => from_value(*inner),
+        Value::Array(elements) => elements
+            .into_iter()
+            .map(from_value)
+            .collect::<Result<Vec<_>>>()
+            .map(JsonAST::Array),
+        Value::Map(entries) => entries
+            .into_iter()
+            .map(|(key, value)| match key {
+                Value::Text(key) => Ok((key, from_value(value)?)),
+                other => Err(BinaryError::NonStringKey(format!("{:?}", other))),
+            })
+            .collect::<Result<Vec<_>>>()
+            .map(JsonAST::Object),
+        other => Err(BinaryError::UnsupportedType(format!(
+            "CBOR value {:?}",
+            other
+        ))),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        binary::{
+            cbor::{from_cbor, to_cbor},
+            domain::BinaryError,
+            sample,
+        },
+        parser::JsonAST,
+    };
+
+    #[test]
+    fn cbor_round_trip_should_preserve_the_ast() {
+        let ast = sample();
+        let bytes = to_cbor(&ast).unwrap();
+        assert_eq!(from_cbor(&bytes).unwrap(), ast);
+    }
+
+    #[test]
+    fn to_cbor_should_encode_integral_numbers_as_integers() {
+        assert_eq!(to_cbor(&JsonAST::Number(10.0)).unwrap(), vec![0x0a]);
+        assert_eq!(to_cbor(&JsonAST::Number(-500.0)).unwrap(), vec![
+            0x39, 0x01, 0xf3
+        ]);
+        assert_eq!(
+            to_cbor(&JsonAST::Array(vec![JsonAST::Number(1.0), JsonAST::Null])).unwrap(),
+            vec![0x82, 0x01, 0xf6]
+        );
+    }
+
+    #[test]
+    fn from_cbor_should_decode_rfc_8949_examples() {
+        // {"a": 1, "b": [2, 3]}
+        let bytes = [0xa2, 0x61, 0x61, 0x01, 0x61, 0x62, 0x82, 0x02, 0x03];
+        assert_eq!(
+            from_cbor(&bytes).unwrap(),
+            JsonAST::Object(vec![
+                ("a".to_string(), JsonAST::Number(1.0)),
+                (
+                    "b".to_string(),
+                    JsonAST::Array(vec![JsonAST::Number(2.0), JsonAST::Number(3.0)])
+                ),
+            ])
+        );
+        // Half precision 1.5
+        assert_eq!(
+            from_cbor(&[0xf9, 0x3e, 0x00]).unwrap(),
+            JsonAST::Number(1.5)
+        );
+        // Indefinite length array [_ 1, 2]
+        assert_eq!(
+            from_cbor(&[0x9f, 0x01, 0x02, 0xff]).unwrap(),
+            JsonAST::Array(vec![JsonAST::Number(1.0), JsonAST::Number(2.0)])
+        );
+        // 0("2013-03-21T20:04:00Z") keeps the tagged string
+        let mut tagged = vec![0xc0, 0x74];
+        tagged.extend_from_slice(b"2013-03-21T20:04:00Z");
+        assert_eq!(
+            from_cbor(&tagged).unwrap(),
+            JsonAST::String("2013-03-21T20:04:00Z".to_string())
+        );
+    }
+
+    #[test]
+    fn from_cbor_should_reject_values_without_json_equivalent() {
+        // h'01020304'
+        assert_eq!(
+            from_cbor(&[0x44, 0x01, 0x02, 0x03, 0x04]),
+            Err(BinaryError::UnsupportedBinary)
+        );
+        // 18446744073709551615
+        assert_eq!(
+            from_cbor(&[0x1b, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff]),
+            Err(BinaryError::IntegerOutOfRange(18446744073709551615))
+        );
+        // {1: 2}
+        assert!(matches!(
+            from_cbor(&[0xa1, 0x01, 0x02]),
+            Err(BinaryError::NonStringKey(_))
+        ));
+        // Infinity
+        assert_eq!(
+            from_cbor(&[0xf9, 0x7c, 0x00]),
+            Err(BinaryError::NonFiniteNumber(f64::INFINITY))
+        );
+    }
+
+    #[test]
+    fn from_cbor_should_reject_trailing_bytes() {
+        assert_eq!(from_cbor(&[0x01, 0x02]), Err(BinaryError::TrailingBytes(1)));
+    }
+
+    #[test]
+    fn from_cbor_should_reject_truncated_input() {
+        assert!(matches!(
+            from_cbor(&[0x82, 0x01]),
+            Err(BinaryError::DecodingError(_))
+        ));
+    }
+}
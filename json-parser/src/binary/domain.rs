@@ -0,0 +1,21 @@
+#[derive(Debug, thiserror::Error, PartialEq)]
+pub enum BinaryError {
+    #[error("Unable to encode the value: {0}")]
+    EncodingError(String),
+    #[error("Unable to decode the input: {0}")]
+    DecodingError(String),
+    #[error("Unexpected {0} bytes after the end of the value")]
+    TrailingBytes(usize),
+    #[error("Integer `{0}` cannot be represented as a json number without losing precision")]
+    IntegerOutOfRange(i128),
+    #[error("Number `{0}` cannot be represented as a json number")]
+    NonFiniteNumber(f64),
+    #[error("Binary data cannot be represented in json")]
+    UnsupportedBinary,
+    #[error("Unsupported {0}")]
+    UnsupportedType(String),
+    #[error("Map keys must be strings but got {0}")]
+    NonStringKey(String),
+}
+
+pub type Result<T> = std::result::Result<T, BinaryError>;
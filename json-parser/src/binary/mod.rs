@@ -0,0 +1,74 @@
+//! Conversions between [`JsonAST`](crate::parser::JsonAST) and binary encodings.
+//!
+//! Each encoding lives behind its own cargo feature (`cbor`, `msgpack`). Both formats can hold
+//! values json cannot, so decoding is strict rather than lossy:
+//!  - integers are accepted only while they fit in the 53 bits of precision a json number has,
+//!  - NaN and infinities are rejected,
+//!  - byte strings and extension types are rejected,
+//!  - map keys must be strings.
+//!
+//! When encoding, numbers with no fractional part are written as integers and every other number
+//! as a 64 bit float, so a round trip always gives back the same `JsonAST`.
+
+use domain::{BinaryError, Result};
+
+#[cfg(feature = "cbor")] pub mod cbor;
+pub mod domain;
+#[cfg(feature = "msgpack")] pub mod msgpack;
+
+/// 2^53, the largest magnitude up to which every integer is exactly representable by an `f64`.
+const MAX_SAFE_INTEGER: i128 = 1 << 53;
+
+enum Number {
+    Integer(i64),
+    Float(f64),
+}
+
+fn classify_number(n: f64) -> Number {
+    let integral = n.fract() == 0.0 && !(n == 0.0 && n.is_sign_negative());
+    if integral && n.abs() <= MAX_SAFE_INTEGER as f64 {
+        Number::Integer(n as i64)
+    } else {
+        Number::Float(n)
+    }
+}
+
+fn integer_to_number(i: i128) -> Result<f64> {
+    if i.abs() <= MAX_SAFE_INTEGER {
+        Ok(i as f64)
+    } else {
+        Err(BinaryError::IntegerOutOfRange(i))
+    }
+}
+
+fn float_to_number(f: f64) -> Result<f64> {
+    if f.is_finite() {
+        Ok(f)
+    } else {
+        Err(BinaryError::NonFiniteNumber(f))
+    }
+}
+
+/// A value with every kind of node, which each encoding has to give back unchanged.
+#[cfg(test)]
+fn sample() -> crate::parser::JsonAST {
+    use crate::parser::JsonAST;
+
+    JsonAST::Object(vec![
+        ("name".to_string(), JsonAST::String("aloha".to_string())),
+        ("count".to_string(), JsonAST::Number(42.0)),
+        ("negative".to_string(), JsonAST::Number(-70000.0)),
+        ("ratio".to_string(), JsonAST::Number(0.25)),
+        ("huge".to_string(), JsonAST::Number(1e300)),
+        ("negative_zero".to_string(), JsonAST::Number(-0.0)),
+        ("enabled".to_string(), JsonAST::Boolean(true)),
+        ("nothing".to_string(), JsonAST::Null),
+        (
+            "list".to_string(),
+            JsonAST::Array(vec![
+                JsonAST::Object(Vec::new()),
+                JsonAST::Array(Vec::new()),
+            ]),
+        ),
+    ])
+}
@@ -0,0 +1,174 @@
+//! [MessagePack](https://github.com/msgpack/msgpack/blob/master/spec.md) conversions.
+//!
+//! Extension types have no json equivalent and are rejected, as are strings holding invalid
+//! UTF-8.
+
+use rmpv::{Integer, Value};
+
+use crate::{
+    binary::{
+        Number,
+        classify_number,
+        domain::{BinaryError, Result},
+        float_to_number,
+        integer_to_number,
+    },
+    parser::JsonAST,
+};
+
+pub fn to_msgpack(ast: &JsonAST) -> Result<Vec<u8>> {
+    let mut output = Vec::new();
+    rmpv::encode::write_value(&mut output, &to_value(ast))
+        .map_err(|e| BinaryError::EncodingError(e.to_string()))?;
+    Ok(output)
+}
+
+pub fn from_msgpack(input: &[u8]) -> Result<JsonAST> {
+    let mut reader = input;
+    let value = rmpv::decode::read_value(&mut reader)
+        .map_err(|e| BinaryError::DecodingError(e.to_string()))?;
+    if !reader.is_empty() {
+        return Err(BinaryError::TrailingBytes(reader.len()));
+    }
+    from_value(value)
+}
+
+fn to_value(ast: &JsonAST) -> Value {
+    match ast {
+        JsonAST::Null => Value::Nil,
+        JsonAST::Boolean(b) => Value::Boolean(*b),
+        JsonAST::Number(n) => match classify_number(*n) {
+            Number::Integer(i) => Value::Integer(Integer::from(i)),
+            Number::Float(f) => Value::F64(f),
+        },
+        JsonAST::String(s) => Value::from(s.as_str()),
+        JsonAST::Array(elements) => Value::Array(elements.iter().map(to_value).collect()),
+        JsonAST::Object(members) => Value::Map(
+            members
+                .iter()
+                .map(|(key, value)| (Value::from(key.as_str()), to_value(value)))
+                .collect(),
+        ),
+    }
+}
+
+fn from_value(value: Value) -> Result<JsonAST> {
+    match value {
+        Value::Nil => Ok(JsonAST::Null),
+        Value::Boolean(b) => Ok(JsonAST::Boolean(b)),
+        Value::Integer(i) => {
+            let i = match (i.as_i64(), i.as_u64()) {
+                (Some(i), _) => i as i128,
+                (None, Some(u)) => u as i128,
+                (None, None) => unreachable!("MessagePack integers are either i64 or u64"),
+            };
+            integer_to_number(i).map(JsonAST::Number)
+        }
+        Value::F32(f) => float_to_number(f as f64).map(JsonAST::Number),
+        Value::F64(f) => float_to_number(f).map(JsonAST::Number),
+        Value::String(s) => s
+            .into_str()
+            .map(JsonAST::String)
+            .ok_or_else(|| BinaryError::DecodingError("String is not valid UTF-8".to_string())),
+        Value::Binary(_) => Err(BinaryError::UnsupportedBinary),
+        Value::Ext(kind, _) => Err(BinaryError::UnsupportedType(format!(
+            "MessagePack extension type {}",
+            kind
+        ))),
+        Value::Array(elements) => elements
+            .into_iter()
+            .map(from_value)
+            .collect::<Result<Vec<_>>>()
+            .map(JsonAST::Array),
+        Value::Map(entries) => entries
+            .into_iter()
+            .map(|(key, value)| match key {
+                Value::String(key) => match key.into_str() {
+                    Some(key) => Ok((key, from_value(value)?)),
+                    None => Err(BinaryError::DecodingError(
+                        "Key is not valid UTF-8".to_string(),
+                    )),
+                },
+                other => Err(BinaryError::NonStringKey(other.to_string())),
+            })
+            .collect::<Result<Vec<_>>>()
+            .map(JsonAST::Object),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        binary::{
+            domain::BinaryError,
+            msgpack::{from_msgpack, to_msgpack},
+            sample,
+        },
+        parser::JsonAST,
+    };
+
+    #[test]
+    fn msgpack_round_trip_should_preserve_the_ast() {
+        let ast = sample();
+        let bytes = to_msgpack(&ast).unwrap();
+        assert_eq!(from_msgpack(&bytes).unwrap(), ast);
+    }
+
+    #[test]
+    fn to_msgpack_should_use_compact_encodings() {
+        // {"a": [1, -1, 0.5]}
+        let ast = JsonAST::Object(vec![(
+            "a".to_string(),
+            JsonAST::Array(vec![
+                JsonAST::Number(1.0),
+                JsonAST::Number(-1.0),
+                JsonAST::Number(0.5),
+            ]),
+        )]);
+        assert_eq!(to_msgpack(&ast).unwrap(), vec![
+            0x81, 0xa1, b'a', 0x93, 0x01, 0xff, 0xcb, 0x3f, 0xe0, 0x00, 0x00, 0x00, 0x00, 0x00,
+            0x00
+        ]);
+    }
+
+    #[test]
+    fn from_msgpack_should_widen_32_bit_floats() {
+        // float 32 with value 1.5
+        assert_eq!(
+            from_msgpack(&[0xca, 0x3f, 0xc0, 0x00, 0x00]).unwrap(),
+            JsonAST::Number(1.5)
+        );
+    }
+
+    #[test]
+    fn from_msgpack_should_reject_values_without_json_equivalent() {
+        // bin 8 with two bytes
+        assert_eq!(
+            from_msgpack(&[0xc4, 0x02, 0x01, 0x02]),
+            Err(BinaryError::UnsupportedBinary)
+        );
+        // fixext 1 of type 5
+        assert!(matches!(
+            from_msgpack(&[0xd4, 0x05, 0x00]),
+            Err(BinaryError::UnsupportedType(_))
+        ));
+        // uint 64 max
+        assert_eq!(
+            from_msgpack(&[0xcf, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff]),
+            Err(BinaryError::IntegerOutOfRange(u64::MAX as i128))
+        );
+        // {1: 2}
+        assert!(matches!(
+            from_msgpack(&[0x81, 0x01, 0x02]),
+            Err(BinaryError::NonStringKey(_))
+        ));
+    }
+
+    #[test]
+    fn from_msgpack_should_reject_trailing_bytes() {
+        assert_eq!(
+            from_msgpack(&[0xc0, 0xc0, 0xc0]),
+            Err(BinaryError::TrailingBytes(2))
+        );
+    }
+}
@@ -0,0 +1,9 @@
+#[derive(Debug, thiserror::Error, PartialEq)]
+pub enum CanonicalError {
+    #[error("Number `{0}` cannot be represented in canonical JSON")]
+    NonFiniteNumber(f64),
+    #[error("Duplicated key `{0}` is not allowed in canonical JSON")]
+    DuplicatedKey(String),
+}
+
+pub type Result<T> = std::result::Result<T, CanonicalError>;
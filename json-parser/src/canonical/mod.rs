@@ -0,0 +1,327 @@
+//! RFC 8785 JSON Canonicalization Scheme (JCS).
+//!
+//! Produces a deterministic representation of a [`JsonAST`] suitable for hashing and signing:
+//! no whitespace, object keys sorted by their UTF-16 code units, numbers formatted following
+//! the ECMAScript `Number.prototype.toString` rules and strings with minimal escaping.
+
+use std::fmt::Write;
+
+use domain::{CanonicalError, Result};
+
+use crate::parser::JsonAST;
+
+pub mod domain;
+
+pub fn to_canonical_string(ast: &JsonAST) -> Result<String> {
+    let mut output = String::new();
+    write_value(ast, &mut output)?;
+    Ok(output)
+}
+
+pub fn to_canonical_bytes(ast: &JsonAST) -> Result<Vec<u8>> {
+    to_canonical_string(ast).map(String::into_bytes)
+}
+
+fn write_value(ast: &JsonAST, output: &mut String) -> Result<()> {
+    match ast {
+        JsonAST::Null => output.push_str("null"),
+        JsonAST::Boolean(b) => output.push_str(if *b { "true" } else { "false" }),
+        JsonAST::Number(n) => output.push_str(&format_number(*n)?),
+        JsonAST::String(s) => write_string(s, output),
+        JsonAST::Array(elements) => {
+            output.push('[');
+            for (i, element) in elements.iter().enumerate() {
+                if i > 0 {
+                    output.push(',');
+                }
+                write_value(element, output)?;
+            }
+            output.push(']');
+        }
+        JsonAST::Object(members) => write_object(members, output)?,
+    }
+    Ok(())
+}
+
+fn write_object(members: &[(String, JsonAST)], output: &mut String) -> Result<()> {
+    // Keys are compared as arrays of UTF-16 code units, which differs from the UTF-8 byte order
+    // for characters outside the Basic Multilingual Plane.
+    let mut sorted: Vec<(Vec<u16>, &String, &JsonAST)> = members
+        .iter()
+        .map(|(key, value)| (key.encode_utf16().collect(), key, value))
+        .collect();
+    sorted.sort_by(|a, b| a.0.cmp(&b.0));
+
+    if let Some(pair) = sorted.windows(2).find(|pair| pair[0].0 == pair[1].0) {
+        return Err(CanonicalError::DuplicatedKey(pair[0].1.clone()));
+    }
+
+    output.push('{');
+    for (i, (_, key, value)) in sorted.into_iter().enumerate() {
+        if i > 0 {
+            output.push(',');
+        }
+        write_string(key, output);
+        output.push(':');
+        write_value(value, output)?;
+    }
+    output.push('}');
+    Ok(())
+}
+
+pub(crate) fn write_string(s: &str, output: &mut String) {
+    output.push('"');
+    for c in s.chars() {
+        match c {
+            '"' => output.push_str("\\\""),
+            '\\' => output.push_str("\\\\"),
+            '\u{08}' => output.push_str("\\b"),
+            '\u{0C}' => output.push_str("\\f"),
+            '\n' => output.push_str("\\n"),
+            '\r' => output.push_str("\\r"),
+            '\t' => output.push_str("\\t"),
+            c if c < '\u{20}' => {
+                let _ = write!(output, "\\u{:04x}", c as u32);
+            }
+            c => output.push(c),
+        }
+    }
+    output.push('"');
+}
+
+/// Formats a number as ECMAScript's `Number.prototype.toString` would, as mandated by
+/// RFC 8785 section 3.2.2.3.
+pub fn format_number(n: f64) -> Result<String> {
+    if !n.is_finite() {
+        return Err(CanonicalError::NonFiniteNumber(n));
+    }
+    if n == 0.0 {
+        // Covers negative zero as well
+        return Ok("0".to_string());
+    }
+
+    // Rust's `LowerExp` yields the shortest digit string that round-trips, but when several
+    // candidates of that length exist it does not always pick the closest one as ECMAScript
+    // requires. Re-formatting with that exact precision gives the correctly rounded candidate.
+    let shortest = format!("{:e}", n.abs());
+    let exponent_at = shortest
+        .find('e')
+        .expect("LowerExp output always contains an exponent");
+    let precision = shortest[..exponent_at].replace('.', "").len() - 1;
+    let formatted = format!("{:.*e}", precision, n.abs());
+    let (mantissa, exponent) = formatted
+        .split_once('e')
+        .expect("LowerExp output always contains an exponent");
+    let digits: String = mantissa.chars().filter(|c| *c != '.').collect();
+    let exponent: i32 = exponent
+        .parse()
+        .expect("LowerExp exponent is a valid integer");
+
+    let k = digits.len() as i32;
+    let point = exponent + 1;
+
+    let mut output = String::new();
+    if n.is_sign_negative() {
+        output.push('-');
+    }
+
+    if k <= point && point <= 21 {
+        output.push_str(&digits);
+        output.push_str(&"0".repeat((point - k) as usize));
+    } else if 0 < point && point <= 21 {
+        let (integer, fraction) = digits.split_at(point as usize);
+        output.push_str(integer);
+        output.push('.');
+        output.push_str(fraction);
+    } else if -6 < point && point <= 0 {
+        output.push_str("0.");
+        output.push_str(&"0".repeat((-point) as usize));
+        output.push_str(&digits);
+    } else {
+        let (first, rest) = digits.split_at(1);
+        output.push_str(first);
+        if !rest.is_empty() {
+            output.push('.');
+            output.push_str(rest);
+        }
+        let _ = write!(
+            output,
+            "e{}{}",
+            if point > 0 { '+' } else { '-' },
+            (point - 1).abs()
+        );
+    }
+
+    Ok(output)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        canonical::{
+            domain::CanonicalError,
+            format_number,
+            to_canonical_bytes,
+            to_canonical_string,
+        },
+        parser::JsonAST,
+    };
+
+    fn string(s: &str) -> JsonAST {
+        JsonAST::String(s.to_string())
+    }
+
+    #[test]
+    fn format_number_should_match_rfc_8785_appendix_b_vectors() {
+        let vectors: Vec<(u64, &str)> = vec![
+            (0x0000000000000000, "0"),
+            (0x8000000000000000, "0"),
+            (0x0000000000000001, "5e-324"),
+            (0x8000000000000001, "-5e-324"),
+            (0x7fefffffffffffff, "1.7976931348623157e+308"),
+            (0xffefffffffffffff, "-1.7976931348623157e+308"),
+            (0x4340000000000000, "9007199254740992"),
+            (0xc340000000000000, "-9007199254740992"),
+            (0x4430000000000000, "295147905179352830000"),
+            (0x44b52d02c7e14af5, "9.999999999999997e+22"),
+            (0x44b52d02c7e14af6, "1e+23"),
+            (0x44b52d02c7e14af7, "1.0000000000000001e+23"),
+            (0x444b1ae4d6e2ef4e, "999999999999999700000"),
+            (0x444b1ae4d6e2ef4f, "999999999999999900000"),
+            (0x444b1ae4d6e2ef50, "1e+21"),
+            (0x3eb0c6f7a0b5ed8c, "9.999999999999997e-7"),
+            (0x3eb0c6f7a0b5ed8d, "0.000001"),
+            (0x41b3de4355555553, "333333333.3333332"),
+            (0x41b3de4355555554, "333333333.33333325"),
+            (0x41b3de4355555555, "333333333.3333333"),
+            (0x41b3de4355555556, "333333333.3333334"),
+            (0x41b3de4355555557, "333333333.33333343"),
+            (0xbecbf647612f3696, "-0.0000033333333333333333"),
+            (0x43143ff3c1cb0959, "1424953923781206.2"),
+        ];
+
+        for (bits, expected) in vectors {
+            let n = f64::from_bits(bits);
+            assert_eq!(format_number(n).unwrap(), expected, "bits {:016x}", bits);
+        }
+    }
+
+    #[test]
+    fn format_number_should_fail_for_non_finite_values() {
+        let nan = f64::from_bits(0x7fffffffffffffff);
+        assert!(matches!(
+            format_number(nan),
+            Err(CanonicalError::NonFiniteNumber(_))
+        ));
+        assert_eq!(
+            format_number(f64::INFINITY),
+            Err(CanonicalError::NonFiniteNumber(f64::INFINITY))
+        );
+    }
+
+    #[test]
+    fn to_canonical_string_should_match_rfc_8785_section_3_2_2_example() {
+        let ast = JsonAST::Object(vec![
+            (
+                "numbers".to_string(),
+                JsonAST::Array(vec![
+                    JsonAST::Number(333_333_333.333_333_3),
+                    JsonAST::Number(1E30),
+                    JsonAST::Number(4.50),
+                    JsonAST::Number(2e-3),
+                    JsonAST::Number(0.000000000000000000000000001),
+                ]),
+            ),
+            (
+                "string".to_string(),
+                string("\u{20ac}$\u{000F}\u{000a}A'\u{0042}\u{0022}\u{005c}\\\"/"),
+            ),
+            (
+                "literals".to_string(),
+                JsonAST::Array(vec![
+                    JsonAST::Null,
+                    JsonAST::Boolean(true),
+                    JsonAST::Boolean(false),
+                ]),
+            ),
+        ]);
+
+        assert_eq!(
+            to_canonical_string(&ast).unwrap(),
+            "{\"literals\":[null,true,false],\"numbers\":[333333333.3333333,1e+30,4.5,0.002,1e-27],\
+             \"string\":\"€$\\u000f\\nA'B\\\"\\\\\\\\\\\"/\"}"
+        );
+    }
+
+    #[test]
+    fn to_canonical_string_should_sort_keys_by_utf16_code_units() {
+        let ast = JsonAST::Object(vec![
+            ("\u{20ac}".to_string(), string("Euro Sign")),
+            ("\r".to_string(), string("Carriage Return")),
+            (
+                "\u{fb33}".to_string(),
+                string("Hebrew Letter Dalet With Dagesh"),
+            ),
+            ("1".to_string(), string("One")),
+            ("\u{1f600}".to_string(), string("Emoji: Grinning Face")),
+            ("\u{0080}".to_string(), string("Control")),
+            (
+                "\u{00f6}".to_string(),
+                string("Latin Small Letter O With Diaeresis"),
+            ),
+        ]);
+
+        let output = to_canonical_string(&ast).unwrap();
+        let values = [
+            "Carriage Return",
+            "One",
+            "Control",
+            "Latin Small Letter O With Diaeresis",
+            "Euro Sign",
+            "Emoji: Grinning Face",
+            "Hebrew Letter Dalet With Dagesh",
+        ];
+        let positions: Vec<usize> = values.iter().map(|v| output.find(v).unwrap()).collect();
+        assert!(positions.windows(2).all(|pair| pair[0] < pair[1]));
+    }
+
+    #[test]
+    fn to_canonical_string_should_sort_nested_objects() {
+        let ast = JsonAST::Object(vec![
+            (
+                "b".to_string(),
+                JsonAST::Array(vec![JsonAST::Object(vec![
+                    ("z".to_string(), JsonAST::Number(1.0)),
+                    ("y".to_string(), JsonAST::Number(-2.5)),
+                ])]),
+            ),
+            ("a".to_string(), JsonAST::Object(Vec::new())),
+        ]);
+
+        assert_eq!(
+            to_canonical_string(&ast).unwrap(),
+            "{\"a\":{},\"b\":[{\"y\":-2.5,\"z\":1}]}"
+        );
+    }
+
+    #[test]
+    fn to_canonical_string_should_fail_on_duplicated_keys() {
+        let ast = JsonAST::Object(vec![
+            ("a".to_string(), JsonAST::Null),
+            ("a".to_string(), JsonAST::Null),
+        ]);
+
+        assert_eq!(
+            to_canonical_string(&ast),
+            Err(CanonicalError::DuplicatedKey("a".to_string()))
+        );
+    }
+
+    #[test]
+    fn to_canonical_bytes_should_be_utf8_encoded() {
+        let ast = JsonAST::Array(vec![string("\u{20ac}")]);
+        assert_eq!(to_canonical_bytes(&ast).unwrap(), vec![
+            b'[', b'"', 0xe2, 0x82, 0xac, b'"', b']'
+        ]);
+    }
+}
@@ -0,0 +1,375 @@
+//! Converts between arrays of objects and [RFC 4180](https://www.rfc-editor.org/rfc/rfc4180) CSV.
+//!
+//! Nested objects are flattened into dotted columns (`{"a": {"b": 1}}` gives an `a.b` column)
+//! and unflattened again when reading, while arrays are written as json text. The header holds
+//! every column found in any row, in order of appearance, and rows missing a column leave it
+//! empty.
+//!
+//! CSV cells carry no type, so when reading, unquoted cells holding a json number, `true` or
+//! `false` are read as such and empty unquoted cells as `null`. Cells holding a json array or
+//! object are parsed back into it, and quoted cells holding a json string into that string.
+//! Everything else, including any other quoted cell, is a string. Strings that would be read back
+//! as another type are written quoted, or as a json string when even quoted they would be, so
+//! the string `[1]` is written `"""[1]"""`.
+//!
+//! Columns have to be unique within a row, so objects such as `{"a.b": 1, "a": {"b": 2}}` are
+//! rejected rather than losing one of the values.
+
+use crate::{
+    convert::domain::{ConvertError, Result},
+    parser::{JsonAST, parse},
+    writer::{to_json_string, write_number},
+};
+
+pub fn to_csv(ast: &JsonAST) -> Result<String> {
+    let JsonAST::Array(elements) = ast else {
+        return Err(ConvertError::ExpectedArrayOfObjects(to_json_string(ast)));
+    };
+
+    let mut rows = Vec::new();
+    for element in elements {
+        let JsonAST::Object(members) = element else {
+            return Err(ConvertError::ExpectedArrayOfObjects(to_json_string(
+                element,
+            )));
+        };
+        let mut row = Vec::new();
+        flatten("", members, &mut row)?;
+        rows.push(row);
+    }
+
+    let mut header: Vec<String> = Vec::new();
+    for (column, _) in rows.iter().flatten() {
+        if !header.contains(column) {
+            header.push(column.clone());
+        }
+    }
+    if header.is_empty() {
+        return Ok(String::new());
+    }
+
+    let mut output = String::new();
+    write_record(
+        header.iter().map(|column| quote_if_needed(column)),
+        &mut output,
+    );
+    for row in rows {
+        let cells = header.iter().map(|column| {
+            row.iter()
+                .find(|(c, _)| c == column)
+                .map_or(String::new(), |(_, value)| cell(value))
+        });
+        write_record(cells, &mut output);
+    }
+    Ok(output)
+}
+
+pub fn from_csv(input: &str) -> Result<JsonAST> {
+    let mut records = read_records(input)?.into_iter();
+    let Some((_, header)) = records.next() else {
+        return Ok(JsonAST::Array(Vec::new()));
+    };
+
+    let mut rows = Vec::new();
+    for (line, record) in records {
+        if record.len() != header.len() {
+            return Err(ConvertError::RowLengthMismatch(
+                line,
+                header.len(),
+                record.len(),
+            ));
+        }
+        let mut row = Vec::new();
+        for (column, cell) in header.iter().zip(record) {
+            unflatten(&mut row, &column.text, infer(&cell))?;
+        }
+        rows.push(JsonAST::Object(row));
+    }
+    Ok(JsonAST::Array(rows))
+}
+
+fn flatten(
+    prefix: &str,
+    members: &[(String, JsonAST)],
+    row: &mut Vec<(String, JsonAST)>,
+) -> Result<()> {
+    for (key, value) in members {
+        let column = if prefix.is_empty() {
+            key.clone()
+        } else {
+            format!("{}.{}", prefix, key)
+        };
+        match value {
+            JsonAST::Object(members) if !members.is_empty() => flatten(&column, members, row)?,
+            _ if row.iter().any(|(c, _)| *c == column) => {
+                return Err(ConvertError::KeyConflict(column));
+            }
+            value => row.push((column, value.clone())),
+        }
+    }
+    Ok(())
+}
+
+fn unflatten(row: &mut Vec<(String, JsonAST)>, column: &str, value: JsonAST) -> Result<()> {
+    let conflict = || ConvertError::KeyConflict(column.to_string());
+    let mut members = row;
+    let mut keys = column.split('.').peekable();
+    while let Some(key) = keys.next() {
+        let position = members.iter().position(|(k, _)| k == key);
+        if keys.peek().is_none() {
+            if position.is_some() {
+                return Err(conflict());
+            }
+            members.push((key.to_string(), value));
+            return Ok(());
+        }
+        let position = position.unwrap_or_else(|| {
+            members.push((key.to_string(), JsonAST::Object(Vec::new())));
+            members.len() - 1
+        });
+        members = match &mut members[position].1 {
+            JsonAST::Object(nested) => nested,
+            _ => return Err(conflict()),
+        };
+    }
+    Ok(())
+}
+
+fn cell(value: &JsonAST) -> String {
+    match value {
+        JsonAST::Null => String::new(),
+        JsonAST::Boolean(b) => b.to_string(),
+        JsonAST::Number(n) => {
+            let mut output = String::new();
+            write_number(*n, &mut output);
+            output
+        }
+        JsonAST::String(s) => string_cell(s),
+        value => quote_if_needed(&to_json_string(value)),
+    }
+}
+
+/// Writes a string so it's read back as the same string: as it is when possible, quoted when it
+/// would be read as another type, and as a json string when even quoted it would be.
+fn string_cell(s: &str) -> String {
+    let string = JsonAST::String(s.to_string());
+    let read_back = |quoted| {
+        infer(&Cell {
+            text: s.to_string(),
+            quoted,
+        })
+    };
+    if read_back(needs_quotes(s)) == string {
+        quote_if_needed(s)
+    } else if read_back(true) == string {
+        quote(s)
+    } else {
+        quote(&to_json_string(&string))
+    }
+}
+
+/// Whether text holds separators, quotes or line breaks.
+fn needs_quotes(text: &str) -> bool {
+    text.contains([',', '"', '\n', '\r'])
+}
+
+fn quote_if_needed(text: &str) -> String {
+    if needs_quotes(text) {
+        quote(text)
+    } else {
+        text.to_string()
+    }
+}
+
+fn quote(text: &str) -> String {
+    format!("\"{}\"", text.replace('"', "\"\""))
+}
+
+fn write_record(cells: impl Iterator<Item = String>, output: &mut String) {
+    for (i, cell) in cells.enumerate() {
+        if i > 0 {
+            output.push(',');
+        }
+        output.push_str(&cell);
+    }
+    output.push('\n');
+}
+
+#[derive(Debug, PartialEq)]
+struct Cell {
+    text: String,
+    quoted: bool,
+}
+
+impl Cell {
+    fn unquoted(text: &str) -> Self {
+        Cell {
+            text: text.to_string(),
+            quoted: false,
+        }
+    }
+}
+
+fn infer(cell: &Cell) -> JsonAST {
+    let text = cell.text.as_str();
+    let parsed = || parse(text).ok();
+    match parsed() {
+        Some(value @ (JsonAST::Array(_) | JsonAST::Object(_))) => value,
+        Some(value @ JsonAST::String(_)) if cell.quoted => value,
+        _ if cell.quoted => JsonAST::String(cell.text.clone()),
+        _ if text.is_empty() => JsonAST::Null,
+        Some(value @ (JsonAST::Number(_) | JsonAST::Boolean(_))) if text.trim() == text => value,
+        _ => JsonAST::String(cell.text.clone()),
+    }
+}
+
+/// Splits the input into records of cells, each paired with the line it starts on. Blank lines
+/// are skipped.
+fn read_records(input: &str) -> Result<Vec<(usize, Vec<Cell>)>> {
+    let mut chars = input.chars().peekable();
+    let mut line = 1;
+    let mut records = Vec::new();
+
+    while chars.peek().is_some() {
+        let start = line;
+        let mut record = Vec::new();
+        loop {
+            record.push(read_cell(&mut chars, &mut line)?);
+            match chars.next() {
+                Some(',') => continue,
+                Some('\r') => {
+                    chars.next_if_eq(&'\n');
+                    line += 1;
+                }
+                Some('\n') => line += 1,
+                _ => {}
+            }
+            break;
+        }
+        if record != [Cell::unquoted("")] {
+            records.push((start, record));
+        }
+    }
+    Ok(records)
+}
+
+fn read_cell(chars: &mut std::iter::Peekable<std::str::Chars>, line: &mut usize) -> Result<Cell> {
+    let mut text = String::new();
+    if chars.next_if_eq(&'"').is_none() {
+        while let Some(c) = chars.next_if(|c| !matches!(c, ',' | '\r' | '\n')) {
+            text.push(c);
+        }
+        return Ok(Cell::unquoted(&text));
+    }
+
+    let start = *line;
+    loop {
+        match chars.next() {
+            None => return Err(ConvertError::UnterminatedQuote(start)),
+            Some('"') if chars.next_if_eq(&'"').is_some() => text.push('"'),
+            Some('"') => break,
+            Some(c) => {
+                if c == '\n' {
+                    *line += 1;
+                }
+                text.push(c);
+            }
+        }
+    }
+    match chars.peek() {
+        None | Some(',' | '\r' | '\n') => Ok(Cell { text, quoted: true }),
+        Some(c) => Err(ConvertError::UnexpectedCharacterAfterQuote(*c, *line)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        convert::{
+            csv::{from_csv, to_csv},
+            domain::ConvertError,
+        },
+        parser::parse,
+    };
+
+    #[test]
+    fn to_csv_should_infer_the_header_and_flatten_nested_keys() {
+        let ast = parse(
+            r#"[{"id": 1, "user": {"name": "Ada, L.", "langs": ["en", "fr"]}},
+                {"id": 2, "active": false, "note": "said \"hi\""},
+                {"id": 3, "user": {"name": "42"}, "note": null}]"#,
+        )
+        .unwrap();
+
+        assert_eq!(
+            to_csv(&ast).unwrap(),
+            "id,user.name,user.langs,active,note\n\
+             1,\"Ada, L.\",\"[\"\"en\"\",\"\"fr\"\"]\",,\n\
+             2,,,false,\"said \"\"hi\"\"\"\n\
+             3,\"42\",,,\n"
+        );
+    }
+
+    #[test]
+    fn from_csv_should_infer_types_and_unflatten_columns() {
+        let input = "id,user.name,user.langs,active,note\r\n\
+                     1,\"Ada, L.\",\"[\"\"en\"\"]\",,\r\n\
+                     \r\n\
+                     2.5,\"42\",,true,\"multi\nline\"\r\n";
+
+        assert_eq!(
+            from_csv(input).unwrap(),
+            parse(
+                r#"[{"id": 1, "user": {"name": "Ada, L.", "langs": ["en"]}, "active": null, "note": null},
+                    {"id": 2.5, "user": {"name": "42", "langs": null}, "active": true, "note": "multi\nline"}]"#
+            )
+            .unwrap()
+        );
+    }
+
+    #[test]
+    fn csv_should_round_trip_arrays_of_objects() {
+        let ast = parse(
+            r#"[{"a": "true", "b": {"c": "", "d": [1, {"e": null}]}, "f": " x"}, {"a": "007", "b": {"c": "-1", "d": []}, "f": 1e21}]"#,
+        )
+        .unwrap();
+
+        assert_eq!(from_csv(&to_csv(&ast).unwrap()).unwrap(), ast);
+
+        // Strings holding json text
+        let ast =
+            parse(r#"[{"a": "[1]", "b": "{}", "c": "\"x\"", "d": "\"", "e": "[1"}]"#).unwrap();
+        let csv = to_csv(&ast).unwrap();
+        let expected = r#""""[1]""","""{}""","""\""x\""""","""",[1"#;
+        assert_eq!(csv, format!("a,b,c,d,e\n{}\n", expected));
+        assert_eq!(from_csv(&csv).unwrap(), ast);
+    }
+
+    #[test]
+    fn csv_should_report_malformed_input() {
+        assert_eq!(
+            from_csv("a,b\n1\n"),
+            Err(ConvertError::RowLengthMismatch(2, 2, 1))
+        );
+        assert_eq!(
+            from_csv("a\n\"open\n"),
+            Err(ConvertError::UnterminatedQuote(2))
+        );
+        assert_eq!(
+            from_csv("a\n\"x\"y\n"),
+            Err(ConvertError::UnexpectedCharacterAfterQuote('y', 2))
+        );
+        assert_eq!(
+            from_csv("a,a.b\n1,2\n"),
+            Err(ConvertError::KeyConflict("a.b".to_string()))
+        );
+        assert_eq!(
+            to_csv(&parse(r#"[{"a.b": 1, "a": {"b": 2}}]"#).unwrap()),
+            Err(ConvertError::KeyConflict("a.b".to_string()))
+        );
+        assert_eq!(
+            to_csv(&parse("[1]").unwrap()),
+            Err(ConvertError::ExpectedArrayOfObjects("1".to_string()))
+        );
+    }
+}
@@ -0,0 +1,19 @@
+#[derive(Debug, thiserror::Error, PartialEq)]
+pub enum ConvertError {
+    #[error("TOML has no null value, found one at `{0}`")]
+    UnsupportedNull(String),
+    #[error("A TOML document must be a table but got {0}")]
+    ExpectedTable(String),
+    #[error("CSV output needs an array of objects but got {0}")]
+    ExpectedArrayOfObjects(String),
+    #[error("Unterminated quoted field starting on line {0}")]
+    UnterminatedQuote(usize),
+    #[error("Unexpected character {0} after a quoted field on line {1}")]
+    UnexpectedCharacterAfterQuote(char, usize),
+    #[error("Row on line {0} has {2} fields but the header has {1}")]
+    RowLengthMismatch(usize, usize, usize),
+    #[error("Column `{0}` conflicts with another column nesting under the same key")]
+    KeyConflict(String),
+}
+
+pub type Result<T> = std::result::Result<T, ConvertError>;
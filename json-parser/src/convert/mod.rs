@@ -0,0 +1,17 @@
+//! Conversions between [`JsonAST`](crate::parser::JsonAST) and other text formats.
+//!
+//! YAML and TOML are output only. TOML has no null and its documents must be tables, so those
+//! values are reported as errors rather than dropped. CSV goes both ways, see [`csv`].
+
+pub mod csv;
+pub mod domain;
+pub mod toml;
+pub mod yaml;
+
+/// Whether a string can be written as a bare word in YAML or TOML keys.
+fn is_bare_key(key: &str) -> bool {
+    !key.is_empty()
+        && key
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-')
+}
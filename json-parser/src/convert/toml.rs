@@ -0,0 +1,212 @@
+//! Writes a [`JsonAST`] object as a TOML document.
+//!
+//! Nested objects become `[tables]` and arrays holding only objects become `[[arrays of
+//! tables]]`, any other array is written inline. Numbers without a fractional part that fit in an
+//! `i64` are written as TOML integers and the rest as floats.
+
+use std::fmt::Write;
+
+use crate::{
+    convert::{
+        domain::{ConvertError, Result},
+        is_bare_key,
+    },
+    parser::JsonAST,
+    writer::{to_json_string, write_number},
+};
+
+pub fn to_toml(ast: &JsonAST) -> Result<String> {
+    let JsonAST::Object(members) = ast else {
+        return Err(ConvertError::ExpectedTable(to_json_string(ast)));
+    };
+    let mut output = String::new();
+    write_table(&[], members, &mut output)?;
+    Ok(output.trim_start_matches('\n').to_string())
+}
+
+fn is_array_of_tables(value: &JsonAST) -> bool {
+    matches!(value, JsonAST::Array(elements)
+        if !elements.is_empty() && elements.iter().all(|e| matches!(e, JsonAST::Object(_))))
+}
+
+/// Writes the key/values of a table first, as TOML requires, followed by its sub tables.
+fn write_table(path: &[String], members: &[(String, JsonAST)], output: &mut String) -> Result<()> {
+    for (key, value) in members {
+        if matches!(value, JsonAST::Object(_)) || is_array_of_tables(value) {
+            continue;
+        }
+        write_key(key, output);
+        output.push_str(" = ");
+        write_inline(value, &child(path, key), output)?;
+        output.push('\n');
+    }
+
+    for (key, value) in members {
+        let path = child(path, key);
+        match value {
+            JsonAST::Object(members) => {
+                let _ = write!(output, "\n[{}]\n", header(&path));
+                write_table(&path, members, output)?;
+            }
+            JsonAST::Array(elements) if is_array_of_tables(value) => {
+                for element in elements {
+                    let JsonAST::Object(members) = element else {
+                        unreachable!("Arrays of tables only hold objects");
+                    };
+                    let _ = write!(output, "\n[[{}]]\n", header(&path));
+                    write_table(&path, members, output)?;
+                }
+            }
+            _ => {}
+        }
+    }
+    Ok(())
+}
+
+fn child(path: &[String], key: &str) -> Vec<String> {
+    let mut path = path.to_vec();
+    path.push(key.to_string());
+    path
+}
+
+fn header(path: &[String]) -> String {
+    let mut header = String::new();
+    for (i, key) in path.iter().enumerate() {
+        if i > 0 {
+            header.push('.');
+        }
+        write_key(key, &mut header);
+    }
+    header
+}
+
+fn write_key(key: &str, output: &mut String) {
+    if is_bare_key(key) {
+        output.push_str(key);
+    } else {
+        write_toml_string(key, output);
+    }
+}
+
+fn write_inline(value: &JsonAST, path: &[String], output: &mut String) -> Result<()> {
+    match value {
+        JsonAST::Null => return Err(ConvertError::UnsupportedNull(header(path))),
+        JsonAST::Boolean(b) => output.push_str(if *b { "true" } else { "false" }),
+        JsonAST::Number(n) if n.is_nan() => output.push_str("nan"),
+        JsonAST::Number(n) if n.is_infinite() => {
+            output.push_str(if *n > 0.0 { "inf" } else { "-inf" })
+        }
+        JsonAST::Number(n) if n.fract() == 0.0 && n.abs() < i64::MAX as f64 => {
+            let _ = write!(output, "{}", *n as i64);
+        }
+        JsonAST::Number(n) => {
+            let start = output.len();
+            write_number(*n, output);
+            // TOML floats need a fractional part or an exponent
+            if !output[start..].contains(['.', 'e']) {
+                output.push_str(".0");
+            }
+        }
+        JsonAST::String(s) => write_toml_string(s, output),
+        JsonAST::Array(elements) => {
+            output.push('[');
+            for (i, element) in elements.iter().enumerate() {
+                if i > 0 {
+                    output.push_str(", ");
+                }
+                write_inline(element, &child(path, &i.to_string()), output)?;
+            }
+            output.push(']');
+        }
+        JsonAST::Object(members) if members.is_empty() => output.push_str("{}"),
+        JsonAST::Object(members) => {
+            output.push_str("{ ");
+            for (i, (key, value)) in members.iter().enumerate() {
+                if i > 0 {
+                    output.push_str(", ");
+                }
+                write_key(key, output);
+                output.push_str(" = ");
+                write_inline(value, &child(path, key), output)?;
+            }
+            output.push_str(" }");
+        }
+    }
+    Ok(())
+}
+
+fn write_toml_string(s: &str, output: &mut String) {
+    output.push('"');
+    for c in s.chars() {
+        match c {
+            '"' => output.push_str("\\\""),
+            '\\' => output.push_str("\\\\"),
+            '\u{08}' => output.push_str("\\b"),
+            '\t' => output.push_str("\\t"),
+            '\n' => output.push_str("\\n"),
+            '\u{0C}' => output.push_str("\\f"),
+            '\r' => output.push_str("\\r"),
+            c if c < '\u{20}' || c == '\u{7F}' => {
+                let _ = write!(output, "\\u{:04X}", c as u32);
+            }
+            c => output.push(c),
+        }
+    }
+    output.push('"');
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        convert::{domain::ConvertError, toml::to_toml},
+        parser::parse,
+    };
+
+    #[test]
+    fn to_toml_should_write_tables_after_key_values() {
+        let ast = parse(
+            r#"{"title": "demo", "server": {"host": "localhost", "port": 8080, "tls": {"enabled": true}},
+                "ratio": 2.5, "big": 1e300, "tags": ["a", "b"], "point": [{"x": 1}, 2],
+                "products": [{"name": "hammer"}, {"name": "nail", "sku": 284758393}],
+                "odd key": {}}"#,
+        )
+        .unwrap();
+
+        assert_eq!(
+            to_toml(&ast).unwrap(),
+            "title = \"demo\"\n\
+             ratio = 2.5\n\
+             big = 1e+300\n\
+             tags = [\"a\", \"b\"]\n\
+             point = [{ x = 1 }, 2]\n\
+             \n[server]\n\
+             host = \"localhost\"\n\
+             port = 8080\n\
+             \n[server.tls]\n\
+             enabled = true\n\
+             \n[[products]]\n\
+             name = \"hammer\"\n\
+             \n[[products]]\n\
+             name = \"nail\"\n\
+             sku = 284758393\n\
+             \n[\"odd key\"]\n"
+        );
+    }
+
+    #[test]
+    fn to_toml_should_reject_nulls_with_their_path() {
+        let ast = parse(r#"{"a": {"b": [1, null]}}"#).unwrap();
+        assert_eq!(
+            to_toml(&ast),
+            Err(ConvertError::UnsupportedNull("a.b.1".to_string()))
+        );
+    }
+
+    #[test]
+    fn to_toml_should_reject_non_table_documents() {
+        assert_eq!(
+            to_toml(&parse("[1]").unwrap()),
+            Err(ConvertError::ExpectedTable("[1]".to_string()))
+        );
+    }
+}
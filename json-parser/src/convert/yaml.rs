@@ -0,0 +1,165 @@
+//! Writes a [`JsonAST`] as a block style YAML document.
+//!
+//! Strings are left unquoted only when they can't be mistaken for another type or for YAML
+//! syntax, and are double quoted with json escapes otherwise, which YAML accepts as is.
+
+use crate::{canonical::write_string, parser::JsonAST, writer::write_number};
+
+const RESERVED_WORDS: &[&str] = &[
+    "null", "true", "false", "yes", "no", "on", "off", "y", "n", "~",
+];
+
+pub fn to_yaml(ast: &JsonAST) -> String {
+    let mut output = String::new();
+    match ast {
+        JsonAST::Array(elements) if !elements.is_empty() => {
+            write_array(elements, 0, false, &mut output)
+        }
+        JsonAST::Object(members) if !members.is_empty() => {
+            write_object(members, 0, false, &mut output)
+        }
+        scalar => {
+            write_scalar(scalar, &mut output);
+            output.push('\n');
+        }
+    }
+    output
+}
+
+fn indent(level: usize, output: &mut String) {
+    output.push_str(&"  ".repeat(level));
+}
+
+/// Writes the members of an object, one per line. With `inline` the first member continues the
+/// current line, as the first member of an object nested in a sequence does after its `- `.
+fn write_object(members: &[(String, JsonAST)], level: usize, inline: bool, output: &mut String) {
+    for (i, (key, value)) in members.iter().enumerate() {
+        if i > 0 || !inline {
+            indent(level, output);
+        }
+        write_plain_or_quoted(key, output);
+        output.push(':');
+        write_nested(value, level + 1, output);
+    }
+}
+
+fn write_array(elements: &[JsonAST], level: usize, inline: bool, output: &mut String) {
+    for (i, element) in elements.iter().enumerate() {
+        if i > 0 || !inline {
+            indent(level, output);
+        }
+        output.push_str("- ");
+        match element {
+            JsonAST::Object(members) if !members.is_empty() => {
+                write_object(members, level + 1, true, output)
+            }
+            JsonAST::Array(elements) if !elements.is_empty() => {
+                write_array(elements, level + 1, true, output)
+            }
+            scalar => {
+                write_scalar(scalar, output);
+                output.push('\n');
+            }
+        }
+    }
+}
+
+/// Writes the value following a `key:`, on the same line for scalars and empty collections.
+fn write_nested(value: &JsonAST, level: usize, output: &mut String) {
+    match value {
+        JsonAST::Object(members) if !members.is_empty() => {
+            output.push('\n');
+            write_object(members, level, false, output);
+        }
+        JsonAST::Array(elements) if !elements.is_empty() => {
+            output.push('\n');
+            write_array(elements, level, false, output);
+        }
+        scalar => {
+            output.push(' ');
+            write_scalar(scalar, output);
+            output.push('\n');
+        }
+    }
+}
+
+fn write_scalar(value: &JsonAST, output: &mut String) {
+    match value {
+        JsonAST::Null => output.push_str("null"),
+        JsonAST::Boolean(b) => output.push_str(if *b { "true" } else { "false" }),
+        JsonAST::Number(n) if n.is_nan() => output.push_str(".nan"),
+        JsonAST::Number(n) if n.is_infinite() => {
+            output.push_str(if *n > 0.0 { ".inf" } else { "-.inf" })
+        }
+        JsonAST::Number(n) => write_number(*n, output),
+        JsonAST::String(s) => write_plain_or_quoted(s, output),
+        JsonAST::Array(_) => output.push_str("[]"),
+        JsonAST::Object(_) => output.push_str("{}"),
+    }
+}
+
+fn write_plain_or_quoted(s: &str, output: &mut String) {
+    if is_plain(s) {
+        output.push_str(s);
+    } else {
+        write_string(s, output);
+    }
+}
+
+fn is_plain(s: &str) -> bool {
+    let starts_with_letter = s
+        .chars()
+        .next()
+        .is_some_and(|c| c.is_ascii_alphabetic() || c == '_' || c == '/');
+    starts_with_letter
+        && !s.ends_with(' ')
+        && s.chars()
+            .all(|c| c.is_ascii_alphanumeric() || "_-./@ ".contains(c))
+        && !RESERVED_WORDS.contains(&s.to_ascii_lowercase().as_str())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{convert::yaml::to_yaml, parser::parse};
+
+    #[test]
+    fn to_yaml_should_write_block_collections() {
+        let ast = parse(
+            r#"{"name": "api", "replicas": 3, "ratio": 0.5, "env": {"DEBUG": false, "URL": null},
+                "ports": [80, 443], "containers": [{"image": "nginx", "args": ["-g", "daemon off"]}, [1, [2]]],
+                "empty": {}, "none": []}"#,
+        )
+        .unwrap();
+
+        assert_eq!(
+            to_yaml(&ast),
+            "name: api\n\
+             replicas: 3\n\
+             ratio: 0.5\n\
+             env:\n  DEBUG: false\n  URL: null\n\
+             ports:\n  - 80\n  - 443\n\
+             containers:\n  - image: nginx\n    args:\n      - \"-g\"\n      - daemon off\n  - - 1\n    - - 2\n\
+             empty: {}\n\
+             none: []\n"
+        );
+    }
+
+    #[test]
+    fn to_yaml_should_quote_ambiguous_strings() {
+        let ast = parse(
+            r##"["yes", "No", "123", "", " padded", "a: b", "#tag", "line\nbreak", "plain text"]"##,
+        )
+        .unwrap();
+
+        assert_eq!(
+            to_yaml(&ast),
+            "- \"yes\"\n- \"No\"\n- \"123\"\n- \"\"\n- \" padded\"\n- \"a: b\"\n- \"#tag\"\n- \"line\\nbreak\"\n- plain text\n"
+        );
+    }
+
+    #[test]
+    fn to_yaml_should_write_top_level_scalars() {
+        assert_eq!(to_yaml(&parse("1e21").unwrap()), "1e+21\n");
+        assert_eq!(to_yaml(&parse("{}").unwrap()), "{}\n");
+    }
+}
@@ -0,0 +1,140 @@
+use std::fmt::Formatter;
+
+use crate::{canonical::domain::CanonicalError, lexer::TokenizerError};
+
+#[derive(Debug, thiserror::Error, PartialEq)]
+pub enum CstError {
+    #[error("Unexpected character `{0}` at offset {1}")]
+    UnexpectedCharacter(char, usize),
+    #[error("Unexpected end of file")]
+    UnexpectedEOF,
+    #[error("Invalid value `{0}` at offset {1}")]
+    InvalidValue(String, usize),
+    #[error("Nesting is deeper than the maximum of {0} levels")]
+    MaxDepthExceeded(usize),
+    #[error("No value found at `{0}`")]
+    PathNotFound(String),
+    #[error("Value at `{0}` is not an object")]
+    ExpectedObject(String),
+    #[error("Value at `{0}` is not an array")]
+    ExpectedArray(String),
+    #[error("Key `{0}` already exists")]
+    DuplicatedKey(String),
+    #[error("Unable to tokenise the value")]
+    TokenisingError(#[from] TokenizerError),
+    #[error("Unable to write the value")]
+    WritingError(#[from] CanonicalError),
+}
+
+pub type Result<T> = std::result::Result<T, CstError>;
+
+/// A json value together with the exact text it was read from.
+#[derive(Debug, Clone, PartialEq)]
+pub enum CstNode {
+    /// Strings, numbers, booleans and null, kept as their original lexeme.
+    Scalar(String),
+    Array(CstArray),
+    Object(CstObject),
+}
+
+/// Whitespace and, in JSONC mode, comments found around a value is kept as `leading` and
+/// `trailing` trivia, so that `leading value trailing` is the exact text between the separators.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CstElement {
+    pub leading: String,
+    pub value: CstNode,
+    pub trailing: String,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct CstMember {
+    pub leading: String,
+    /// The key as written, including quotes and escapes.
+    pub raw_key: String,
+    /// The decoded key.
+    pub key: String,
+    pub before_colon: String,
+    pub after_colon: String,
+    pub value: CstNode,
+    pub trailing: String,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct CstArray {
+    pub elements: Vec<CstElement>,
+    /// Set when a JSONC trailing comma follows the last element.
+    pub trailing_comma: bool,
+    /// Trivia before `]` not owned by any element, for empty arrays or after a trailing comma.
+    pub inner: String,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct CstObject {
+    pub members: Vec<CstMember>,
+    /// Set when a JSONC trailing comma follows the last member.
+    pub trailing_comma: bool,
+    /// Trivia before `}` not owned by any member, for empty objects or after a trailing comma.
+    pub inner: String,
+}
+
+/// A concrete syntax tree for a whole document, which re-emits the original input unchanged
+/// through its [`Display`](std::fmt::Display) implementation.
+#[derive(Debug, Clone, PartialEq)]
+pub struct JsonCST {
+    pub root: CstElement,
+}
+
+impl std::fmt::Display for CstNode {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            CstNode::Scalar(raw) => write!(f, "{}", raw),
+            CstNode::Array(array) => {
+                write!(f, "[")?;
+                for (i, element) in array.elements.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ",")?;
+                    }
+                    write!(f, "{}", element)?;
+                }
+                if array.trailing_comma {
+                    write!(f, ",")?;
+                }
+                write!(f, "{}]", array.inner)
+            }
+            CstNode::Object(object) => {
+                write!(f, "{{")?;
+                for (i, member) in object.members.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ",")?;
+                    }
+                    write!(
+                        f,
+                        "{}{}{}:{}{}{}",
+                        member.leading,
+                        member.raw_key,
+                        member.before_colon,
+                        member.after_colon,
+                        member.value,
+                        member.trailing
+                    )?;
+                }
+                if object.trailing_comma {
+                    write!(f, ",")?;
+                }
+                write!(f, "{}}}", object.inner)
+            }
+        }
+    }
+}
+
+impl std::fmt::Display for CstElement {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}{}{}", self.leading, self.value, self.trailing)
+    }
+}
+
+impl std::fmt::Display for JsonCST {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.root)
+    }
+}
@@ -0,0 +1,647 @@
+//! Source preserving concrete syntax tree, meant for editing documents in place.
+//!
+//! Unlike [`JsonAST`], a [`JsonCST`] keeps whitespace, comments (when parsed in JSONC mode) and
+//! the original lexeme of every scalar, so printing it back gives the exact input. Edits only
+//! touch the values they target: everything else is re-emitted byte for byte.
+//!
+//! Paths are given as a list of segments, object keys or array indexes, from the root value.
+
+pub use domain::{CstArray, CstElement, CstMember, CstNode, CstObject, JsonCST};
+use domain::{CstError, Result};
+
+use crate::{
+    canonical::{format_number, write_string},
+    lexer::{Token, tokenize},
+    parser::{JsonAST, MAX_DEPTH},
+};
+
+pub mod domain;
+
+/// Parses strict json, as [`parse`](crate::parser::parse) does, keeping all of its formatting.
+pub fn parse_cst(input: &str) -> Result<JsonCST> {
+    CstParser::new(input, false).parse()
+}
+
+/// Parses JSONC, json extended with `//` and `/* */` comments and trailing commas.
+pub fn parse_jsonc_cst(input: &str) -> Result<JsonCST> {
+    CstParser::new(input, true).parse()
+}
+
+struct CstParser {
+    input: Vec<char>,
+    pos: usize,
+    jsonc: bool,
+}
+
+impl CstParser {
+    fn new(input: &str, jsonc: bool) -> Self {
+        CstParser {
+            input: input.chars().collect(),
+            pos: 0,
+            jsonc,
+        }
+    }
+
+    fn parse(mut self) -> Result<JsonCST> {
+        let leading = self.trivia()?;
+        let value = self.value(0)?;
+        let trailing = self.trivia()?;
+        if let Some(c) = self.peek() {
+            return Err(CstError::UnexpectedCharacter(c, self.pos));
+        }
+        Ok(JsonCST {
+            root: CstElement {
+                leading,
+                value,
+                trailing,
+            },
+        })
+    }
+
+    fn peek(&self) -> Option<char> {
+        self.input.get(self.pos).copied()
+    }
+
+    fn peek_at(&self, offset: usize) -> Option<char> {
+        self.input.get(self.pos + offset).copied()
+    }
+
+    fn expect(&mut self, expected: char) -> Result<()> {
+        match self.peek() {
+            Some(c) if c == expected => {
+                self.pos += 1;
+                Ok(())
+            }
+            Some(c) => Err(CstError::UnexpectedCharacter(c, self.pos)),
+            None => Err(CstError::UnexpectedEOF),
+        }
+    }
+
+    fn trivia(&mut self) -> Result<String> {
+        let start = self.pos;
+        loop {
+            match (self.peek(), self.peek_at(1)) {
+                (Some(' ' | '\n' | '\t' | '\r'), _) => self.pos += 1,
+                (Some('/'), Some('/')) if self.jsonc => {
+                    while !matches!(self.peek(), None | Some('\n')) {
+                        self.pos += 1;
+                    }
+                }
+                (Some('/'), Some('*')) if self.jsonc => {
+                    self.pos += 2;
+                    loop {
+                        match (self.peek(), self.peek_at(1)) {
+                            (Some('*'), Some('/')) => {
+                                self.pos += 2;
+                                break;
+                            }
+                            (Some(_), _) => self.pos += 1,
+                            (None, _) => return Err(CstError::UnexpectedEOF),
+                        }
+                    }
+                }
+                _ => break,
+            }
+        }
+        Ok(self.input[start..self.pos].iter().collect())
+    }
+
+    fn value(&mut self, depth: usize) -> Result<CstNode> {
+        match self.peek() {
+            Some('[' | '{') if depth >= MAX_DEPTH => Err(CstError::MaxDepthExceeded(MAX_DEPTH)),
+            Some('[') => self.array(depth + 1),
+            Some('{') => self.object(depth + 1),
+            Some(_) => self.scalar().map(|(raw, _)| CstNode::Scalar(raw)),
+            None => Err(CstError::UnexpectedEOF),
+        }
+    }
+
+    /// Reads the lexeme of a scalar, validating it with the lexer.
+    fn scalar(&mut self) -> Result<(String, Token)> {
+        let start = self.pos;
+        match self.peek() {
+            Some('"') => {
+                self.pos += 1;
+                loop {
+                    match self.peek() {
+                        Some('"') => break,
+                        Some('\\') => self.pos += 2,
+                        Some(_) => self.pos += 1,
+                        None => return Err(CstError::UnexpectedEOF),
+                    }
+                }
+                self.pos += 1;
+            }
+            Some('-' | '0'..='9') => {
+                while let Some('-' | '+' | '.' | 'e' | 'E' | '0'..='9') = self.peek() {
+                    self.pos += 1;
+                }
+            }
+            Some(c) if c.is_ascii_alphabetic() => {
+                while self.peek().is_some_and(|c| c.is_ascii_alphabetic()) {
+                    self.pos += 1;
+                }
+            }
+            Some(c) => return Err(CstError::UnexpectedCharacter(c, start)),
+            None => return Err(CstError::UnexpectedEOF),
+        }
+
+        let raw: String = self.input[start..self.pos.min(self.input.len())]
+            .iter()
+            .collect();
+        match tokenize(&raw).as_deref() {
+            Ok([token]) => Ok((raw, token.clone())),
+            _ => Err(CstError::InvalidValue(raw, start)),
+        }
+    }
+
+    fn array(&mut self, depth: usize) -> Result<CstNode> {
+        self.expect('[')?;
+        let mut array = CstArray {
+            elements: Vec::new(),
+            trailing_comma: false,
+            inner: String::new(),
+        };
+
+        let mut leading = self.trivia()?;
+        if self.peek() == Some(']') {
+            self.pos += 1;
+            array.inner = leading;
+            return Ok(CstNode::Array(array));
+        }
+
+        loop {
+            let value = self.value(depth)?;
+            let trailing = self.trivia()?;
+            array.elements.push(CstElement {
+                leading,
+                value,
+                trailing,
+            });
+
+            match self.peek() {
+                Some(']') => break,
+                Some(',') => {
+                    self.pos += 1;
+                    leading = self.trivia()?;
+                    if self.jsonc && self.peek() == Some(']') {
+                        array.trailing_comma = true;
+                        array.inner = leading;
+                        break;
+                    }
+                }
+                Some(c) => return Err(CstError::UnexpectedCharacter(c, self.pos)),
+                None => return Err(CstError::UnexpectedEOF),
+            }
+        }
+        self.expect(']')?;
+
+        Ok(CstNode::Array(array))
+    }
+
+    fn object(&mut self, depth: usize) -> Result<CstNode> {
+        self.expect('{')?;
+        let mut object = CstObject {
+            members: Vec::new(),
+            trailing_comma: false,
+            inner: String::new(),
+        };
+
+        let mut leading = self.trivia()?;
+        if self.peek() == Some('}') {
+            self.pos += 1;
+            object.inner = leading;
+            return Ok(CstNode::Object(object));
+        }
+
+        loop {
+            let key_start = self.pos;
+            if self.peek() != Some('"') {
+                return match self.peek() {
+                    Some(c) => Err(CstError::UnexpectedCharacter(c, key_start)),
+                    None => Err(CstError::UnexpectedEOF),
+                };
+            }
+            let (raw_key, Token::String(key)) = self.scalar()? else {
+                unreachable!("A lexeme starting with a quote is always a string")
+            };
+            let before_colon = self.trivia()?;
+            self.expect(':')?;
+            let after_colon = self.trivia()?;
+            let value = self.value(depth)?;
+            let trailing = self.trivia()?;
+            object.members.push(CstMember {
+                leading,
+                raw_key,
+                key,
+                before_colon,
+                after_colon,
+                value,
+                trailing,
+            });
+
+            match self.peek() {
+                Some('}') => break,
+                Some(',') => {
+                    self.pos += 1;
+                    leading = self.trivia()?;
+                    if self.jsonc && self.peek() == Some('}') {
+                        object.trailing_comma = true;
+                        object.inner = leading;
+                        break;
+                    }
+                }
+                Some(c) => return Err(CstError::UnexpectedCharacter(c, self.pos)),
+                None => return Err(CstError::UnexpectedEOF),
+            }
+        }
+        self.expect('}')?;
+
+        Ok(CstNode::Object(object))
+    }
+}
+
+fn display_path(path: &[&str]) -> String {
+    format!("/{}", path.join("/"))
+}
+
+/// Builds a compact node, without any trivia, out of a [`JsonAST`].
+pub fn node_from_ast(ast: &JsonAST) -> Result<CstNode> {
+    let node = match ast {
+        JsonAST::Null => CstNode::Scalar("null".to_string()),
+        JsonAST::Boolean(b) => CstNode::Scalar(b.to_string()),
+        JsonAST::Number(n) => CstNode::Scalar(format_number(*n)?),
+        JsonAST::String(s) => {
+            let mut raw = String::new();
+            write_string(s, &mut raw);
+            CstNode::Scalar(raw)
+        }
+        JsonAST::Array(elements) => CstNode::Array(CstArray {
+            elements: elements
+                .iter()
+                .map(|element| {
+                    Ok(CstElement {
+                        leading: String::new(),
+                        value: node_from_ast(element)?,
+                        trailing: String::new(),
+                    })
+                })
+                .collect::<Result<Vec<_>>>()?,
+            trailing_comma: false,
+            inner: String::new(),
+        }),
+        JsonAST::Object(members) => CstNode::Object(CstObject {
+            members: members
+                .iter()
+                .map(|(key, value)| new_member(key, node_from_ast(value)?, None))
+                .collect::<Result<Vec<_>>>()?,
+            trailing_comma: false,
+            inner: String::new(),
+        }),
+    };
+    Ok(node)
+}
+
+/// Builds a member, copying the formatting of `template` when there is one.
+fn new_member(key: &str, value: CstNode, template: Option<&CstMember>) -> Result<CstMember> {
+    let mut raw_key = String::new();
+    write_string(key, &mut raw_key);
+    Ok(CstMember {
+        leading: template.map(|t| t.leading.clone()).unwrap_or_default(),
+        raw_key,
+        key: key.to_string(),
+        before_colon: template.map(|t| t.before_colon.clone()).unwrap_or_default(),
+        after_colon: template.map(|t| t.after_colon.clone()).unwrap_or_default(),
+        value,
+        trailing: String::new(),
+    })
+}
+
+impl CstNode {
+    pub fn to_ast(&self) -> Result<JsonAST> {
+        match self {
+            CstNode::Scalar(raw) => match tokenize(raw)?.pop() {
+                Some(Token::Null) => Ok(JsonAST::Null),
+                Some(Token::True) => Ok(JsonAST::Boolean(true)),
+                Some(Token::False) => Ok(JsonAST::Boolean(false)),
+                Some(Token::Number(n)) => Ok(JsonAST::Number(n)),
+                Some(Token::String(s)) => Ok(JsonAST::String(s)),
+                _ => Err(CstError::InvalidValue(raw.clone(), 0)),
+            },
+            CstNode::Array(array) => array
+                .elements
+                .iter()
+                .map(|element| element.value.to_ast())
+                .collect::<Result<Vec<_>>>()
+                .map(JsonAST::Array),
+            CstNode::Object(object) => object
+                .members
+                .iter()
+                .map(|member| Ok((member.key.clone(), member.value.to_ast()?)))
+                .collect::<Result<Vec<_>>>()
+                .map(JsonAST::Object),
+        }
+    }
+
+    fn child(&self, segment: &str) -> Option<&CstNode> {
+        match self {
+            CstNode::Object(object) => object
+                .members
+                .iter()
+                .find(|member| member.key == segment)
+                .map(|member| &member.value),
+            CstNode::Array(array) => segment
+                .parse::<usize>()
+                .ok()
+                .and_then(|index| array.elements.get(index))
+                .map(|element| &element.value),
+            CstNode::Scalar(_) => None,
+        }
+    }
+
+    fn child_mut(&mut self, segment: &str) -> Option<&mut CstNode> {
+        match self {
+            CstNode::Object(object) => object
+                .members
+                .iter_mut()
+                .find(|member| member.key == segment)
+                .map(|member| &mut member.value),
+            CstNode::Array(array) => segment
+                .parse::<usize>()
+                .ok()
+                .and_then(|index| array.elements.get_mut(index))
+                .map(|element| &mut element.value),
+            CstNode::Scalar(_) => None,
+        }
+    }
+}
+
+impl JsonCST {
+    pub fn to_ast(&self) -> Result<JsonAST> {
+        self.root.value.to_ast()
+    }
+
+    pub fn get(&self, path: &[&str]) -> Option<&CstNode> {
+        path.iter()
+            .try_fold(&self.root.value, |node, segment| node.child(segment))
+    }
+
+    fn get_mut(&mut self, path: &[&str]) -> Result<&mut CstNode> {
+        let mut node = &mut self.root.value;
+        for (i, segment) in path.iter().enumerate() {
+            node = node
+                .child_mut(segment)
+                .ok_or_else(|| CstError::PathNotFound(display_path(&path[..=i])))?;
+        }
+        Ok(node)
+    }
+
+    /// Replaces the value at `path`, keeping the surrounding trivia.
+    pub fn set(&mut self, path: &[&str], value: &JsonAST) -> Result<()> {
+        let node = node_from_ast(value)?;
+        *self.get_mut(path)? = node;
+        Ok(())
+    }
+
+    /// Replaces the value at `path` with a raw lexeme, e.g. to choose how a number is written.
+    pub fn set_raw(&mut self, path: &[&str], raw: &str) -> Result<()> {
+        match tokenize(raw).as_deref() {
+            Ok([_]) => {
+                *self.get_mut(path)? = CstNode::Scalar(raw.to_string());
+                Ok(())
+            }
+            _ => Err(CstError::InvalidValue(raw.to_string(), 0)),
+        }
+    }
+
+    /// Appends a member to the object at `path`, formatted like the current last member.
+    pub fn insert(&mut self, path: &[&str], key: &str, value: &JsonAST) -> Result<()> {
+        let value = node_from_ast(value)?;
+        let CstNode::Object(object) = self.get_mut(path)? else {
+            return Err(CstError::ExpectedObject(display_path(path)));
+        };
+        if object.members.iter().any(|member| member.key == key) {
+            return Err(CstError::DuplicatedKey(key.to_string()));
+        }
+
+        let mut member = new_member(key, value, object.members.last())?;
+        if let Some(last) = object.members.last_mut() {
+            // The trivia closing the object moves along to the new last member
+            member.trailing = std::mem::take(&mut last.trailing);
+        }
+        object.members.push(member);
+        Ok(())
+    }
+
+    /// Appends an element to the array at `path`, formatted like the current last element.
+    pub fn push(&mut self, path: &[&str], value: &JsonAST) -> Result<()> {
+        let value = node_from_ast(value)?;
+        let CstNode::Array(array) = self.get_mut(path)? else {
+            return Err(CstError::ExpectedArray(display_path(path)));
+        };
+
+        let mut element = CstElement {
+            leading: String::new(),
+            value,
+            trailing: String::new(),
+        };
+        if let Some(last) = array.elements.last_mut() {
+            element.leading = last.leading.clone();
+            element.trailing = std::mem::take(&mut last.trailing);
+        }
+        array.elements.push(element);
+        Ok(())
+    }
+
+    /// Removes the member or element at `path`, together with its separator.
+    pub fn remove(&mut self, path: &[&str]) -> Result<()> {
+        let Some((segment, parent)) = path.split_last() else {
+            return Err(CstError::PathNotFound(display_path(path)));
+        };
+        let not_found = || CstError::PathNotFound(display_path(path));
+
+        match self.get_mut(parent)? {
+            CstNode::Object(object) => {
+                let index = object
+                    .members
+                    .iter()
+                    .position(|member| member.key == *segment)
+                    .ok_or_else(not_found)?;
+                let removed = object.members.remove(index);
+                if index == object.members.len() {
+                    match object.members.last_mut() {
+                        Some(last) => last.trailing = removed.trailing,
+                        None if !object.trailing_comma => object.inner = removed.trailing,
+                        None => object.trailing_comma = false,
+                    }
+                }
+            }
+            CstNode::Array(array) => {
+                let index = segment
+                    .parse::<usize>()
+                    .ok()
+                    .filter(|index| *index < array.elements.len())
+                    .ok_or_else(not_found)?;
+                let removed = array.elements.remove(index);
+                if index == array.elements.len() {
+                    match array.elements.last_mut() {
+                        Some(last) => last.trailing = removed.trailing,
+                        None if !array.trailing_comma => array.inner = removed.trailing,
+                        None => array.trailing_comma = false,
+                    }
+                }
+            }
+            CstNode::Scalar(_) => return Err(not_found()),
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        cst::{CstNode, domain::CstError, parse_cst, parse_jsonc_cst},
+        parser::{JsonAST, parse},
+    };
+
+    const CONFIG: &str = "
+{
+    \"name\" :  \"server\",
+    \"port\": 8080,
+    \"ratio\": 1.50e0,
+    \"tags\": [ \"a\",\"b\" ],
+    \"nested\": {}
+}
+";
+
+    #[test]
+    fn parse_cst_should_reemit_the_input_unchanged() {
+        let cst = parse_cst(CONFIG).unwrap();
+        assert_eq!(cst.to_string(), CONFIG);
+    }
+
+    #[test]
+    fn parse_cst_should_keep_original_lexemes() {
+        let cst = parse_cst(CONFIG).unwrap();
+        assert_eq!(
+            cst.get(&["ratio"]),
+            Some(&CstNode::Scalar("1.50e0".to_string()))
+        );
+        assert_eq!(cst.to_ast().unwrap(), parse(CONFIG).unwrap());
+    }
+
+    #[test]
+    fn parse_cst_should_reject_invalid_json() {
+        assert_eq!(
+            parse_cst("[1, 2,]").err().unwrap(),
+            CstError::UnexpectedCharacter(']', 6)
+        );
+        assert_eq!(
+            parse_cst("[01]").err().unwrap(),
+            CstError::InvalidValue("01".to_string(), 1)
+        );
+        assert_eq!(
+            parse_cst("// comment\n{}").err().unwrap(),
+            CstError::UnexpectedCharacter('/', 0)
+        );
+        assert_eq!(
+            parse_cst("{\"a\": ").err().unwrap(),
+            CstError::UnexpectedEOF
+        );
+    }
+
+    #[test]
+    fn parse_jsonc_cst_should_keep_comments_and_trailing_commas() {
+        let input =
+            "// settings\n{\n  /* the port */ \"port\": 80, // http\n  \"hosts\": [\"a\", ],\n}\n";
+        let cst = parse_jsonc_cst(input).unwrap();
+        assert_eq!(cst.to_string(), input);
+        assert_eq!(
+            cst.to_ast().unwrap(),
+            JsonAST::Object(vec![
+                ("port".to_string(), JsonAST::Number(80.0)),
+                (
+                    "hosts".to_string(),
+                    JsonAST::Array(vec![JsonAST::String("a".to_string())])
+                ),
+            ])
+        );
+    }
+
+    #[test]
+    fn set_should_only_change_the_targeted_value() {
+        let mut cst = parse_cst(CONFIG).unwrap();
+        cst.set(&["port"], &JsonAST::Number(9090.0)).unwrap();
+        cst.set(&["tags", "1"], &JsonAST::String("c".to_string()))
+            .unwrap();
+        assert_eq!(
+            cst.to_string(),
+            CONFIG
+                .replace("8080", "9090")
+                .replace("\"a\",\"b\"", "\"a\",\"c\"")
+        );
+    }
+
+    #[test]
+    fn set_raw_should_validate_the_lexeme() {
+        let mut cst = parse_cst(CONFIG).unwrap();
+        cst.set_raw(&["ratio"], "2.00").unwrap();
+        assert!(cst.to_string().contains("\"ratio\": 2.00,"));
+        assert_eq!(
+            cst.set_raw(&["ratio"], "2.00.0"),
+            Err(CstError::InvalidValue("2.00.0".to_string(), 0))
+        );
+    }
+
+    #[test]
+    fn set_should_fail_for_unknown_paths() {
+        let mut cst = parse_cst(CONFIG).unwrap();
+        assert_eq!(
+            cst.set(&["tags", "5"], &JsonAST::Null),
+            Err(CstError::PathNotFound("/tags/5".to_string()))
+        );
+    }
+
+    #[test]
+    fn insert_should_follow_the_formatting_of_the_last_member() {
+        let mut cst = parse_cst(CONFIG).unwrap();
+        cst.insert(&[], "debug", &JsonAST::Boolean(true)).unwrap();
+        cst.insert(&["nested"], "level", &JsonAST::Number(1.0))
+            .unwrap();
+        assert_eq!(
+            cst.to_string(),
+            CONFIG.replace(
+                "\"nested\": {}\n",
+                "\"nested\": {\"level\":1},\n    \"debug\": true\n"
+            )
+        );
+        assert_eq!(
+            cst.insert(&[], "port", &JsonAST::Null),
+            Err(CstError::DuplicatedKey("port".to_string()))
+        );
+        assert_eq!(
+            cst.insert(&["port"], "a", &JsonAST::Null),
+            Err(CstError::ExpectedObject("/port".to_string()))
+        );
+    }
+
+    #[test]
+    fn push_should_follow_the_formatting_of_the_last_element() {
+        let mut cst = parse_cst(CONFIG).unwrap();
+        cst.push(&["tags"], &JsonAST::String("c".to_string()))
+            .unwrap();
+        assert!(cst.to_string().contains("\"tags\": [ \"a\",\"b\",\"c\" ],"));
+    }
+
+    #[test]
+    fn remove_should_drop_the_value_and_its_separator() {
+        let mut cst = parse_cst(CONFIG).unwrap();
+        cst.remove(&["nested"]).unwrap();
+        cst.remove(&["tags", "0"]).unwrap();
+        cst.remove(&["name"]).unwrap();
+        assert_eq!(
+            cst.to_string(),
+            "\n{\n    \"port\": 8080,\n    \"ratio\": 1.50e0,\n    \"tags\": [\"b\" ]\n}\n"
+        );
+    }
+}
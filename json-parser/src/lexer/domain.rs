@@ -49,6 +49,18 @@ pub enum TokenizerError {
     ParseBooleanError(bool, String),
     #[error("Expected char `{0}` but got `{1}`")]
     MismatchTokenExpectation(char, char),
+    #[error("Invalid number `{0}`")]
+    InvalidNumber(String),
+    #[error("Number `{0}` is too large to be represented")]
+    NumberOutOfRange(String),
+    #[error("Invalid escape sequence `\\{0}`")]
+    InvalidEscape(char),
+    #[error("Invalid unicode escape sequence `\\u{0}`")]
+    InvalidUnicodeEscape(String),
+    #[error("Unpaired surrogate `\\u{0:04X}` in string")]
+    LoneSurrogate(u16),
+    #[error("Unescaped control character `{0:?}` in string")]
+    ControlCharacterInString(char),
 }
 
 pub type Result<T> = std::result::Result<T, TokenizerError>;
@@ -1,107 +1,231 @@
+use std::iter::Peekable;
+
 pub(crate) use crate::lexer::domain::*;
 
 pub mod domain;
+pub mod reader;
 
 pub fn tokenize(input: &str) -> Result<Vec<Token>> {
-    let input: Vec<char> = input.trim().chars().collect();
-    let mut tokens = Vec::new();
-
-    let mut i = 0;
-    while i < input.len() {
-        let c = input[i];
-        match c {
-            '{' => tokens.push(Token::LeftBrace),
-            '}' => tokens.push(Token::RightBrace),
-            ':' => tokens.push(Token::Colon),
-            '[' => tokens.push(Token::LeftBracket),
-            ']' => tokens.push(Token::RightBracket),
-            ',' => tokens.push(Token::Comma),
-            '0'..='9' => i = tokenize_number(&input, &mut tokens, i)?,
-            'n' => i = tokenize_null(&input, &mut tokens, i)?,
-            't' => i = tokenize_true(&input, &mut tokens, i)?,
-            'f' => i = tokenize_false(&input, &mut tokens, i)?,
-            ' ' | '\n' | '\t' | '\r' => i = skip_whitespaces(&input, i),
-            '"' => i = tokenize_string(&input, &mut tokens, i)?,
-            _ => return Err(TokenizerError::InvalidCharacter(c)),
-        }
-        i += 1;
-    }
-
-    Ok(tokens)
+    Lexer::new(input.chars()).collect()
 }
 
-fn tokenize_number(input: &[char], tokens: &mut Vec<Token>, i: usize) -> Result<usize> {
-    let mut i = i;
-    let mut tmp = String::new();
-    while input[i].is_ascii_digit() || input[i] == '.' {
-        tmp.push(input[i]);
-        i += 1;
-    }
-    let num = tmp.parse::<f64>()?;
-    tokens.push(Token::Number(num));
-    Ok(i - 1)
+/// Turns a stream of chars into [`Token`]s following the grammar of RFC 8259.
+///
+/// The lexer is driven lazily as an [`Iterator`], so the input never needs to be held in memory
+/// as a whole.
+pub struct Lexer<I: Iterator<Item = char>> {
+    input: Peekable<I>,
+    last: Option<char>,
 }
 
-fn tokenize_null(input: &[char], tokens: &mut Vec<Token>, i: usize) -> Result<usize> {
-    if input[i..i + 4] == ['n', 'u', 'l', 'l'] {
-        tokens.push(Token::Null);
-        Ok(i + 3)
-    } else {
-        Err(TokenizerError::ExpectedNull)
+impl<I: Iterator<Item = char>> Iterator for Lexer<I> {
+    type Item = Result<Token>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.skip_whitespaces();
+        let c = *self.input.peek()?;
+        let token = match c {
+            '{' => self.single(Token::LeftBrace),
+            '}' => self.single(Token::RightBrace),
+            ':' => self.single(Token::Colon),
+            '[' => self.single(Token::LeftBracket),
+            ']' => self.single(Token::RightBracket),
+            ',' => self.single(Token::Comma),
+            '-' | '0'..='9' => self.tokenize_number(),
+            'n' => self.tokenize_null(),
+            't' => self.tokenize_boolean(true),
+            'f' => self.tokenize_boolean(false),
+            '"' => self.tokenize_string(),
+            _ => Err(TokenizerError::InvalidCharacter(c)),
+        };
+        Some(token)
     }
 }
 
-fn tokenize_true(input: &[char], tokens: &mut Vec<Token>, i: usize) -> Result<usize> {
-    if input[i..i + 4] == ['t', 'r', 'u', 'e'] {
-        tokens.push(Token::True);
-        Ok(i + 3)
-    } else {
-        Err(TokenizerError::ParseBooleanError(
-            true,
-            input[i..i + 4].iter().collect::<String>(),
-        ))
+impl<I: Iterator<Item = char>> Lexer<I> {
+    pub fn new(input: I) -> Self {
+        Lexer {
+            input: input.peekable(),
+            last: None,
+        }
     }
-}
 
-fn tokenize_false(input: &[char], tokens: &mut Vec<Token>, i: usize) -> Result<usize> {
-    if input[i..i + 5] == ['f', 'a', 'l', 's', 'e'] {
-        tokens.push(Token::False);
-        Ok(i + 4)
-    } else {
-        Err(TokenizerError::ParseBooleanError(
-            false,
-            input[i..i + 5].iter().collect::<String>(),
-        ))
+    fn advance(&mut self) -> Option<char> {
+        let c = self.input.next();
+        if c.is_some() {
+            self.last = c;
+        }
+        c
     }
-}
 
-fn skip_whitespaces(input: &[char], i: usize) -> usize {
-    let mut i = i;
-    while i < input.len() && input[i] == ' '
-        || input[i] == '\n'
-        || input[i] == '\t'
-        || input[i] == '\r'
-    {
-        i += 1;
+    fn single(&mut self, token: Token) -> Result<Token> {
+        self.advance();
+        Ok(token)
+    }
+
+    fn skip_whitespaces(&mut self) {
+        while let Some(' ' | '\n' | '\t' | '\r') = self.input.peek() {
+            self.advance();
+        }
+    }
+
+    fn take_digits(&mut self, lexeme: &mut String) -> usize {
+        let mut count = 0;
+        while let Some(c) = self.input.peek().copied().filter(char::is_ascii_digit) {
+            lexeme.push(c);
+            self.advance();
+            count += 1;
+        }
+        count
+    }
+
+    fn take_if(&mut self, lexeme: &mut String, accepted: &[char]) -> bool {
+        match self.input.peek() {
+            Some(c) if accepted.contains(c) => {
+                lexeme.push(*c);
+                self.advance();
+                true
+            }
+            _ => false,
+        }
+    }
+
+    fn tokenize_number(&mut self) -> Result<Token> {
+        let mut lexeme = String::new();
+        self.take_if(&mut lexeme, &['-']);
+
+        // Integer part, where a leading zero can't be followed by more digits
+        let integer_digits = self.take_digits(&mut lexeme);
+        if integer_digits == 0
+            || (integer_digits > 1 && lexeme.trim_start_matches('-').starts_with('0'))
+        {
+            return Err(TokenizerError::InvalidNumber(lexeme));
+        }
+
+        if self.take_if(&mut lexeme, &['.']) && self.take_digits(&mut lexeme) == 0 {
+            return Err(TokenizerError::InvalidNumber(lexeme));
+        }
+
+        if self.take_if(&mut lexeme, &['e', 'E']) {
+            self.take_if(&mut lexeme, &['+', '-']);
+            if self.take_digits(&mut lexeme) == 0 {
+                return Err(TokenizerError::InvalidNumber(lexeme));
+            }
+        }
+
+        let num = lexeme.parse::<f64>()?;
+        if num.is_infinite() {
+            return Err(TokenizerError::NumberOutOfRange(lexeme));
+        }
+        Ok(Token::Number(num))
+    }
+
+    /// Consumes chars for as long as they match `word`, returning what was consumed.
+    fn take_word(&mut self, word: &str) -> String {
+        let mut taken = String::new();
+        for expected in word.chars() {
+            match self.input.peek() {
+                Some(c) if *c == expected => {
+                    taken.push(expected);
+                    self.advance();
+                }
+                _ => break,
+            }
+        }
+        taken
+    }
+
+    fn tokenize_null(&mut self) -> Result<Token> {
+        if self.take_word("null") == "null" {
+            Ok(Token::Null)
+        } else {
+            Err(TokenizerError::ExpectedNull)
+        }
+    }
+
+    fn tokenize_boolean(&mut self, value: bool) -> Result<Token> {
+        let word = if value { "true" } else { "false" };
+        let taken = self.take_word(word);
+        if taken == word {
+            Ok(if value { Token::True } else { Token::False })
+        } else {
+            Err(TokenizerError::ParseBooleanError(value, taken))
+        }
+    }
+
+    fn unterminated_string(&self) -> TokenizerError {
+        TokenizerError::MismatchTokenExpectation('"', self.last.unwrap_or('"'))
+    }
+
+    fn tokenize_string(&mut self) -> Result<Token> {
+        // Skip the opening quote
+        self.advance();
+        let mut str = String::new();
+        loop {
+            match self.advance() {
+                None => return Err(self.unterminated_string()),
+                Some('"') => return Ok(Token::String(str)),
+                Some('\\') => str.push(self.tokenize_escape()?),
+                Some(c) if c < '\u{20}' => return Err(TokenizerError::ControlCharacterInString(c)),
+                Some(c) => str.push(c),
+            }
+        }
+    }
+
+    fn tokenize_escape(&mut self) -> Result<char> {
+        match self.advance() {
+            None => Err(self.unterminated_string()),
+            Some('"') => Ok('"'),
+            Some('\\') => Ok('\\'),
+            Some('/') => Ok('/'),
+            Some('b') => Ok('\u{08}'),
+            Some('f') => Ok('\u{0C}'),
+            Some('n') => Ok('\n'),
+            Some('r') => Ok('\r'),
+            Some('t') => Ok('\t'),
+            Some('u') => self.tokenize_unicode_escape(),
+            Some(c) => Err(TokenizerError::InvalidEscape(c)),
+        }
+    }
+
+    fn read_code_unit(&mut self) -> Result<u16> {
+        let mut hex = String::new();
+        for _ in 0..4 {
+            match self.input.peek() {
+                Some(c) if c.is_ascii_hexdigit() => {
+                    hex.push(*c);
+                    self.advance();
+                }
+                Some(c) => {
+                    hex.push(*c);
+                    return Err(TokenizerError::InvalidUnicodeEscape(hex));
+                }
+                None => return Err(self.unterminated_string()),
+            }
+        }
+        Ok(u16::from_str_radix(&hex, 16).expect("Four hex digits always fit in a u16"))
     }
-    i - 1
-}
 
-fn tokenize_string(input: &[char], tokens: &mut Vec<Token>, i: usize) -> Result<usize> {
-    let mut i = i + 1;
-    let mut str = String::new();
-    while i < input.len() && input[i] != '"' {
-        str.push(input[i]);
-        i += 1;
-    }
-    if i < input.len() && input[i] == '"' {
-        tokens.push(Token::String(str));
-        Ok(i)
-    } else {
-        Err(TokenizerError::MismatchTokenExpectation(
-            '"',
-            input[input.len() - 1],
-        ))
+    /// Decodes a `\uXXXX` escape, combining UTF-16 surrogate pairs into a single char.
+    fn tokenize_unicode_escape(&mut self) -> Result<char> {
+        let high = self.read_code_unit()?;
+        match high {
+            0xD800..=0xDBFF => {
+                if self.take_word("\\u") != "\\u" {
+                    return Err(TokenizerError::LoneSurrogate(high));
+                }
+                let low = self.read_code_unit()?;
+                if !(0xDC00..=0xDFFF).contains(&low) {
+                    return Err(TokenizerError::LoneSurrogate(high));
+                }
+                let code = 0x10000 + (((high as u32) - 0xD800) << 10) + ((low as u32) - 0xDC00);
+                Ok(char::from_u32(code).expect("Surrogate pairs always map to a valid char"))
+            }
+            0xDC00..=0xDFFF => Err(TokenizerError::LoneSurrogate(high)),
+            code => {
+                Ok(char::from_u32(code as u32).expect("Non surrogate code units are valid chars"))
+            }
+        }
     }
 }
 
@@ -192,4 +316,67 @@ mod tests {
         let error = tokenize("?").err().unwrap();
         assert_eq!(error, TokenizerError::InvalidCharacter('?'));
     }
+
+    #[test]
+    fn tokenize_should_follow_the_json_number_grammar() {
+        let result = tokenize("-0 12.5e-1 1E+2 -7").expect("should extract tokens");
+        assert_eq!(
+            result,
+            vec!(
+                Token::Number(-0.0),
+                Token::Number(1.25),
+                Token::Number(100.0),
+                Token::Number(-7.0)
+            )
+        );
+
+        for invalid in ["01", "-", "1.", "1e", "-.5"] {
+            assert!(
+                matches!(tokenize(invalid), Err(TokenizerError::InvalidNumber(_))),
+                "{} should be rejected",
+                invalid
+            );
+        }
+        assert_eq!(
+            tokenize("1e999").err().unwrap(),
+            TokenizerError::NumberOutOfRange("1e999".to_string())
+        );
+    }
+
+    #[test]
+    fn tokenize_should_decode_escape_sequences() {
+        let result =
+            tokenize(r#""a\"\\\/\b\f\n\r\t\u00e9\uD834\uDD1E""#).expect("should extract tokens");
+        assert_eq!(
+            result,
+            vec!(Token::String(
+                "a\"\\/\u{08}\u{0C}\n\r\té\u{1D11E}".to_string()
+            ))
+        );
+    }
+
+    #[test]
+    fn tokenize_should_fail_on_invalid_strings() {
+        assert_eq!(
+            tokenize(r#""\x""#).err().unwrap(),
+            TokenizerError::InvalidEscape('x')
+        );
+        assert_eq!(
+            tokenize(r#""\uD800""#).err().unwrap(),
+            TokenizerError::LoneSurrogate(0xD800)
+        );
+        assert_eq!(
+            tokenize("\"a\nb\"").err().unwrap(),
+            TokenizerError::ControlCharacterInString('\n')
+        );
+    }
+
+    #[test]
+    fn tokenize_should_fail_on_incomplete_literals() {
+        assert_eq!(tokenize("nul").err().unwrap(), TokenizerError::ExpectedNull);
+        assert_eq!(
+            tokenize("tru").err().unwrap(),
+            TokenizerError::ParseBooleanError(true, "tru".to_string())
+        );
+    }
 }
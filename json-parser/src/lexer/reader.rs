@@ -0,0 +1,105 @@
+use std::io::{BufRead, ErrorKind};
+
+/// Decodes UTF-8 chars out of a [`BufRead`], one at a time, so they can be fed to the
+/// [`Lexer`](crate::lexer::Lexer) without reading the whole input upfront.
+///
+/// Iteration stops at the first I/O or decoding error, which is kept in [`ReadChars::error`]
+/// for the caller to report.
+pub struct ReadChars<R: BufRead> {
+    reader: R,
+    pub error: Option<std::io::Error>,
+}
+
+impl<R: BufRead> ReadChars<R> {
+    pub fn new(reader: R) -> Self {
+        ReadChars {
+            reader,
+            error: None,
+        }
+    }
+
+    fn next_byte(&mut self) -> std::io::Result<Option<u8>> {
+        let byte = loop {
+            match self.reader.fill_buf() {
+                Ok(buffer) => break buffer.first().copied(),
+                Err(e) if e.kind() == ErrorKind::Interrupted => continue,
+                Err(e) => return Err(e),
+            }
+        };
+        if byte.is_some() {
+            self.reader.consume(1);
+        }
+        Ok(byte)
+    }
+
+    fn next_char(&mut self) -> std::io::Result<Option<char>> {
+        let Some(first) = self.next_byte()? else {
+            return Ok(None);
+        };
+        let width = match first {
+            0x00..=0x7F => return Ok(Some(first as char)),
+            0xC0..=0xDF => 2,
+            0xE0..=0xEF => 3,
+            0xF0..=0xF7 => 4,
+            _ => 0,
+        };
+
+        let mut bytes = [first, 0, 0, 0];
+        for byte in bytes.iter_mut().take(width).skip(1) {
+            *byte = self.next_byte()?.ok_or(ErrorKind::UnexpectedEof)?;
+        }
+        std::str::from_utf8(&bytes[..width])
+            .ok()
+            .and_then(|s| s.chars().next())
+            .map(Some)
+            .ok_or_else(|| std::io::Error::new(ErrorKind::InvalidData, "Input is not valid UTF-8"))
+    }
+}
+
+impl<R: BufRead> Iterator for ReadChars<R> {
+    type Item = char;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.error.is_some() {
+            return None;
+        }
+        match self.next_char() {
+            Ok(c) => c,
+            Err(e) => {
+                self.error = Some(e);
+                None
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::{Cursor, ErrorKind};
+
+    use crate::lexer::reader::ReadChars;
+
+    #[test]
+    fn read_chars_should_decode_multi_byte_chars() {
+        let mut chars = ReadChars::new(Cursor::new("a€𝄞é".as_bytes()));
+        assert_eq!(chars.by_ref().collect::<String>(), "a€𝄞é");
+        assert!(chars.error.is_none());
+    }
+
+    #[test]
+    fn read_chars_should_stop_on_invalid_utf8() {
+        let mut chars = ReadChars::new(Cursor::new(vec![b'a', 0xFF, b'b']));
+        assert_eq!(chars.by_ref().collect::<String>(), "a");
+        assert_eq!(chars.error.map(|e| e.kind()), Some(ErrorKind::InvalidData));
+    }
+
+    #[test]
+    fn read_chars_should_stop_on_truncated_sequences() {
+        let mut chars = ReadChars::new(Cursor::new(vec![b'a', 0xE2, 0x82]));
+        assert_eq!(chars.by_ref().collect::<String>(), "a");
+        assert_eq!(
+            chars.error.map(|e| e.kind()),
+            Some(ErrorKind::UnexpectedEof)
+        );
+    }
+}
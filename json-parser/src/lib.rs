@@ -1,2 +1,10 @@
+#[cfg(any(feature = "cbor", feature = "msgpack"))] pub mod binary;
+pub mod canonical;
+pub mod convert;
+pub mod cst;
 pub mod lexer;
 pub mod parser;
+pub mod query;
+pub mod repl;
+pub mod schema;
+pub mod writer;
@@ -1,32 +1,212 @@
-use std::{io, io::Write};
+use std::{
+    fs::File,
+    io,
+    io::{BufReader, Read, Write},
+};
 
-use anyhow::{Context, Result};
-use json_parser::parser::parse;
+use anyhow::{Context, Result, bail};
+use json_parser::{
+    convert::{
+        csv::{from_csv, to_csv},
+        toml::to_toml,
+        yaml::to_yaml,
+    },
+    parser::{JsonAST, parse_reader},
+    query::compile,
+    repl,
+    writer::{to_json_string, to_json_string_pretty},
+};
 
-fn read_input() -> Result<String> {
-    let mut raw_input = String::new();
+const USAGE: &str = "\
+Usage:
+    json-parser                                   Start the interactive REPL
+    json-parser query [OPTIONS] <FILTER> [FILE]   Run a jq-style filter over FILE, or stdin
+    json-parser to-yaml [FILE]                    Convert json to YAML
+    json-parser to-toml [FILE]                    Convert a json object to TOML
+    json-parser to-csv [FILE]                     Convert a json array of objects to CSV
+    json-parser from-csv [OPTIONS] [FILE]         Convert CSV to a json array of objects
 
-    io::stdin()
-        .read_line(&mut raw_input)
-        .context("Failed to read line")?;
+Options:
+    -c, --compact-output   Print every result on a single line
+    -r, --raw-output       Print string results without quotes
+    -h, --help             Print this message
+    --                     Stop reading options, so a filter may start with -";
 
-    Ok(raw_input)
+enum Command {
+    Repl,
+    Help,
+    Query {
+        filter: String,
+        file: Option<String>,
+        compact: bool,
+        raw: bool,
+    },
+    ToYaml(Option<String>),
+    ToToml(Option<String>),
+    ToCsv(Option<String>),
+    FromCsv {
+        file: Option<String>,
+        compact: bool,
+    },
 }
 
-pub fn repl() {
-    loop {
-        print!("json-parser> ");
-        io::stdout().flush().unwrap();
+#[derive(Default)]
+struct Options {
+    compact: bool,
+    raw: bool,
+    help: bool,
+    positional: Vec<String>,
+}
+
+/// Splits the flags from the other arguments. Anything but a known flag is positional, as
+/// filters such as `-1` or `-.a` start with `-` too, and so is everything after `--`.
+fn parse_options(mut args: impl Iterator<Item = String>) -> Options {
+    let mut options = Options::default();
+    for arg in args.by_ref() {
+        match arg.as_str() {
+            "--" => break,
+            "-c" | "--compact-output" => options.compact = true,
+            "-r" | "--raw-output" => options.raw = true,
+            "-h" | "--help" => options.help = true,
+            _ => options.positional.push(arg),
+        }
+    }
+    options.positional.extend(args);
+    options
+}
+
+/// Takes the optional input file, where `-` stands for stdin, failing on any extra argument.
+fn input_file(positional: &mut impl Iterator<Item = String>) -> Result<Option<String>> {
+    let file = positional.next().filter(|file| file != "-");
+    if let Some(extra) = positional.next() {
+        bail!("Unexpected argument {}\n\n{}", extra, USAGE);
+    }
+    Ok(file)
+}
+
+fn parse_args(mut args: impl Iterator<Item = String>) -> Result<Command> {
+    let Some(command) = args.next() else {
+        return Ok(Command::Repl);
+    };
+    let options = parse_options(args);
+    if options.help || command == "-h" || command == "--help" {
+        return Ok(Command::Help);
+    }
+
+    // Conversions print a single document in their own format
+    let converts = matches!(
+        command.as_str(),
+        "to-yaml" | "to-toml" | "to-csv" | "from-csv"
+    );
+    if converts && options.raw {
+        bail!("{} does not take the -r option\n\n{}", command, USAGE);
+    }
+    if converts && options.compact && command != "from-csv" {
+        bail!("{} does not take the -c option\n\n{}", command, USAGE);
+    }
+
+    let mut positional = options.positional.into_iter();
+    match command.as_str() {
+        "query" => {
+            let Some(filter) = positional.next() else {
+                bail!("Missing the filter argument\n\n{}", USAGE);
+            };
+            Ok(Command::Query {
+                filter,
+                file: input_file(&mut positional)?,
+                compact: options.compact,
+                raw: options.raw,
+            })
+        }
+        "to-yaml" => Ok(Command::ToYaml(input_file(&mut positional)?)),
+        "to-toml" => Ok(Command::ToToml(input_file(&mut positional)?)),
+        "to-csv" => Ok(Command::ToCsv(input_file(&mut positional)?)),
+        "from-csv" => Ok(Command::FromCsv {
+            file: input_file(&mut positional)?,
+            compact: options.compact,
+        }),
+        command => bail!("Unknown command {}\n\n{}", command, USAGE),
+    }
+}
+
+fn open_input(file: Option<&str>) -> Result<Box<dyn Read>> {
+    Ok(match file {
+        Some(path) => {
+            Box::new(File::open(path).with_context(|| format!("Failed to open {}", path))?)
+        }
+        None => Box::new(io::stdin().lock()),
+    })
+}
+
+fn read_document(file: Option<&str>) -> Result<JsonAST> {
+    parse_reader(BufReader::new(open_input(file)?)).context("Failed to parse the input document")
+}
+
+fn print_json(value: &JsonAST, compact: bool, output: &mut impl Write) -> io::Result<()> {
+    if compact {
+        writeln!(output, "{}", to_json_string(value))
+    } else {
+        writeln!(output, "{}", to_json_string_pretty(value))
+    }
+}
+
+fn run_query(filter: &str, file: Option<&str>, compact: bool, raw: bool) -> Result<()> {
+    let filter = compile(filter).context("Failed to compile the filter")?;
+    let document = read_document(file)?;
+
+    let mut stdout = io::stdout().lock();
+    for value in filter.apply(&document)? {
+        match value {
+            JsonAST::String(s) if raw => writeln!(stdout, "{}", s)?,
+            value => print_json(&value, compact, &mut stdout)?,
+        }
+    }
+    Ok(())
+}
+
+fn run_from_csv(file: Option<&str>, compact: bool) -> Result<()> {
+    let mut input = String::new();
+    open_input(file)?
+        .read_to_string(&mut input)
+        .context("Failed to read the input")?;
+    print_json(&from_csv(&input)?, compact, &mut io::stdout().lock())?;
+    Ok(())
+}
 
-        let expr = read_input().unwrap();
+fn write_output(output: &str) -> io::Result<()> {
+    io::stdout().lock().write_all(output.as_bytes())
+}
 
-        match parse(expr.as_ref()) {
-            Ok(val) => println!(" ==> {}", val),
-            Err(error) => eprintln!("==> Error: {}", error),
-        };
+fn main() -> Result<()> {
+    match run() {
+        // The reader went away, such as `head` once it has its lines, so there's nothing to do
+        Err(error) if is_broken_pipe(&error) => Ok(()),
+        result => result,
     }
 }
 
-fn main() {
-    repl();
+fn is_broken_pipe(error: &anyhow::Error) -> bool {
+    error.chain().any(|cause| {
+        cause
+            .downcast_ref::<io::Error>()
+            .is_some_and(|error| error.kind() == io::ErrorKind::BrokenPipe)
+    })
+}
+
+fn run() -> Result<()> {
+    match parse_args(std::env::args().skip(1))? {
+        Command::Repl => repl::run()?,
+        Command::Help => println!("{}", USAGE),
+        Command::Query {
+            filter,
+            file,
+            compact,
+            raw,
+        } => run_query(&filter, file.as_deref(), compact, raw)?,
+        Command::ToYaml(file) => write_output(&to_yaml(&read_document(file.as_deref())?))?,
+        Command::ToToml(file) => write_output(&to_toml(&read_document(file.as_deref())?)?)?,
+        Command::ToCsv(file) => write_output(&to_csv(&read_document(file.as_deref())?)?)?,
+        Command::FromCsv { file, compact } => run_from_csv(file.as_deref(), compact)?,
+    }
+    Ok(())
 }
@@ -2,7 +2,10 @@ use std::fmt::Formatter;
 
 use tracing::error;
 
-use crate::lexer::{Token, TokenizerError};
+use crate::{
+    lexer::{Token, TokenizerError},
+    writer::{to_json_string, to_json_string_pretty},
+};
 
 #[derive(Debug, Clone, PartialEq)]
 pub enum JsonAST {
@@ -14,22 +17,49 @@ pub enum JsonAST {
     Null,
 }
 
+impl JsonAST {
+    /// Looks up a value by [RFC 6901](https://www.rfc-editor.org/rfc/rfc6901) JSON Pointer, e.g.
+    /// `/users/0/name`, where `~1` stands for `/` and `~0` for `~` in keys.
+    pub fn pointer(&self, pointer: &str) -> Option<&JsonAST> {
+        if pointer.is_empty() {
+            return Some(self);
+        }
+        let tokens = pointer.strip_prefix('/')?;
+        tokens.split('/').try_fold(self, |value, token| {
+            let token = token.replace("~1", "/").replace("~0", "~");
+            match value {
+                JsonAST::Object(members) => members
+                    .iter()
+                    .rev()
+                    .find(|(key, _)| *key == token)
+                    .map(|(_, value)| value),
+                JsonAST::Array(elements) => {
+                    let leading_zero = token.len() > 1 && token.starts_with('0');
+                    if leading_zero || !token.bytes().all(|b| b.is_ascii_digit()) {
+                        return None;
+                    }
+                    elements.get(token.parse::<usize>().ok()?)
+                }
+                _ => None,
+            }
+        })
+    }
+}
+
+/// Writes the value as compact json, or indented with the alternate flag, `{:#}`.
 impl std::fmt::Display for JsonAST {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
-        match self {
-            JsonAST::Object(elements) => write!(f, "{{ {:?} }}", elements),
-            JsonAST::Array(_) => write!(f, "[]"),
-            JsonAST::String(s) => write!(f, "\"{}\"", s),
-            JsonAST::Boolean(b) => write!(f, "{}", if *b { "true" } else { "false" }),
-            JsonAST::Number(n) => write!(f, "{}", n),
-            JsonAST::Null => write!(f, "null"),
+        if f.alternate() {
+            write!(f, "{}", to_json_string_pretty(self))
+        } else {
+            write!(f, "{}", to_json_string(self))
         }
     }
 }
 
 #[derive(Debug, thiserror::Error, PartialEq)]
 pub enum ParserError {
-    #[error("Expected token {0} but go {1} instead")]
+    #[error("Expected token {0} but got {1} instead")]
     ExpectedTokenMismatch(Token, Token),
     #[error("Unexpected token {0}")]
     UnexpectedToken(Token),
@@ -39,8 +69,18 @@ pub enum ParserError {
     UnexpectedEOF,
     #[error("Unexpected token {0} after end of file")]
     UnexpectedTokenAfterEOF(Token),
+    #[error("Nesting is deeper than the maximum of {0} levels")]
+    MaxDepthExceeded(usize),
     #[error("Unable to tokenise the string")]
     TokenisingError(#[from] TokenizerError),
+    #[error("Unable to read the input: {1}")]
+    IoError(std::io::ErrorKind, String),
+}
+
+impl From<std::io::Error> for ParserError {
+    fn from(error: std::io::Error) -> Self {
+        ParserError::IoError(error.kind(), error.to_string())
+    }
 }
 
 pub(crate) type Result<T> = std::result::Result<T, ParserError>;
@@ -1,123 +1,150 @@
-use domain::{ParserError, Result};
+use std::io::{BufReader, Read};
 
-use crate::{
-    lexer::{Token, tokenize},
-    parser::domain::JsonAST,
-};
+use domain::Result;
+pub use domain::{JsonAST, ParserError};
+#[cfg(feature = "tokio")] use tokio::io::AsyncRead;
+
+use crate::lexer::{Lexer, Token, reader::ReadChars};
 
 mod domain;
+#[cfg(feature = "parallel")] pub mod parallel;
+
+/// Maximum nesting of arrays and objects accepted, so deeply nested input is rejected rather
+/// than overflowing the stack.
+pub const MAX_DEPTH: usize = 512;
+
+/// Anything yielding tokens, usually a [`Lexer`] reading its input lazily.
+trait Tokens: Iterator<Item = crate::lexer::Result<Token>> {}
+
+impl<T: Iterator<Item = crate::lexer::Result<Token>>> Tokens for T {}
+
+fn next_token(tokens: &mut impl Tokens) -> Result<Token> {
+    Ok(tokens.next().ok_or(ParserError::UnexpectedEOF)??)
+}
 
-fn parse_array(tokens: &mut Vec<Token>) -> Result<JsonAST> {
+fn parse_array(tokens: &mut impl Tokens, depth: usize) -> Result<JsonAST> {
     let mut list: Vec<JsonAST> = Vec::new();
 
+    let mut token = next_token(tokens)?;
+    if token == Token::RightBracket {
+        return Ok(JsonAST::Array(list));
+    }
+
     loop {
-        let token = tokens.pop().expect("Unexpected end for JsonArray");
-        if token == Token::RightBracket {
-            break;
-        }
-        let value = parse_json_value(tokens, token)?;
+        list.push(parse_json_value(tokens, token, depth)?);
 
         // Should be comma or end
-        let token = tokens.pop().expect("Unexpected end for JsonArray");
-
-        list.push(value);
-
-        if token == Token::RightBracket {
-            break;
-        }
-        if token == Token::Comma {
-            continue;
+        match next_token(tokens)? {
+            Token::RightBracket => break,
+            Token::Comma => token = next_token(tokens)?,
+            t => return Err(ParserError::ExpectedTokenMismatch(Token::RightBracket, t)),
         }
     }
 
     Ok(JsonAST::Array(list))
 }
 
-fn parse_json_object(tokens: &mut Vec<Token>) -> Result<JsonAST> {
+fn parse_json_object(tokens: &mut impl Tokens, depth: usize) -> Result<JsonAST> {
     let mut obj: Vec<(String, JsonAST)> = Vec::new();
 
+    let mut token = next_token(tokens)?;
+    if token == Token::RightBrace {
+        return Ok(JsonAST::Object(obj));
+    }
+
     loop {
-        let token = tokens.pop().ok_or(ParserError::UnexpectedEOF)?;
-        if token == Token::RightBrace {
-            break;
-        }
-        let item_key: String;
         // We need to find key values here, so first thing is a String followed by colon
-        if let Token::String(key) = token {
-            item_key = key;
-        } else {
+        let Token::String(item_key) = token else {
             return Err(ParserError::ExpectedKey(token));
-        }
+        };
         // Next should be the `:`
-        let token = tokens.pop().ok_or(ParserError::UnexpectedEOF)?;
-        if token != Token::Colon {
-            return Err(ParserError::ExpectedTokenMismatch(token, Token::Colon));
+        let token_after_key = next_token(tokens)?;
+        if token_after_key != Token::Colon {
+            return Err(ParserError::ExpectedTokenMismatch(
+                Token::Colon,
+                token_after_key,
+            ));
         }
         // Next should be a JsonAST
-        let token = tokens.pop().ok_or(ParserError::UnexpectedEOF)?;
-        let value = parse_json_value(tokens, token)?;
+        let value_token = next_token(tokens)?;
+        let value = parse_json_value(tokens, value_token, depth)?;
         obj.push((item_key, value));
 
         // Now we should check for a comma or end of jsonObject
-        let token = tokens.pop().ok_or(ParserError::UnexpectedEOF)?;
-        if token == Token::RightBrace {
-            break;
-        }
-        if token == Token::Comma {
-            continue;
+        match next_token(tokens)? {
+            Token::RightBrace => break,
+            Token::Comma => token = next_token(tokens)?,
+            t => return Err(ParserError::ExpectedTokenMismatch(Token::RightBrace, t)),
         }
     }
 
     Ok(JsonAST::Object(obj))
 }
 
-fn parse_json_value(tokens: &mut Vec<Token>, token: Token) -> Result<JsonAST> {
+fn parse_json_value(tokens: &mut impl Tokens, token: Token, depth: usize) -> Result<JsonAST> {
     match token {
         Token::Null => Ok(JsonAST::Null),
         Token::True => Ok(JsonAST::Boolean(true)),
         Token::False => Ok(JsonAST::Boolean(false)),
         Token::Number(n) => Ok(JsonAST::Number(n)),
         Token::String(s) => Ok(JsonAST::String(s)),
-        Token::LeftBrace => parse_json_object(tokens),
-        Token::LeftBracket => parse_array(tokens),
+        Token::LeftBrace | Token::LeftBracket if depth >= MAX_DEPTH => {
+            Err(ParserError::MaxDepthExceeded(MAX_DEPTH))
+        }
+        Token::LeftBrace => parse_json_object(tokens, depth + 1),
+        Token::LeftBracket => parse_array(tokens, depth + 1),
         t => Err(ParserError::UnexpectedToken(t)),
     }
 }
 
-fn parse_token_list(tokens: &mut Vec<Token>) -> Result<JsonAST> {
-    let token = tokens.pop();
-
-    if token == Some(Token::LeftBrace) {
-        parse_json_object(tokens)
-    } else if token == Some(Token::LeftBracket) {
-        parse_array(tokens)
-    } else if let Some(t) = token {
-        Err(ParserError::UnexpectedToken(t))
-    } else {
-        Err(ParserError::UnexpectedEOF)
+/// Parses a single value nested `depth` levels deep, failing on anything following it.
+fn parse_token_list(tokens: &mut impl Tokens, depth: usize) -> Result<JsonAST> {
+    let token = next_token(tokens)?;
+    let result = parse_json_value(tokens, token, depth)?;
+
+    // If there are other tokens we should fail as it is a malformed json
+    match tokens.next() {
+        Some(t) => Err(ParserError::UnexpectedTokenAfterEOF(t?)),
+        None => Ok(result),
     }
 }
 
 pub fn parse(input: &str) -> Result<JsonAST> {
-    let token_result = tokenize(input)?;
-
-    let mut tokens = token_result.into_iter().rev().collect();
+    parse_token_list(&mut Lexer::new(input.chars()), 0)
+}
 
-    let result = parse_token_list(&mut tokens);
+/// Parses the json read from `reader`, decoding and tokenising it as the bytes come in.
+pub fn parse_reader(reader: impl Read) -> Result<JsonAST> {
+    let mut chars = ReadChars::new(BufReader::new(reader));
+    let result = parse_token_list(&mut Lexer::new(&mut chars), 0);
 
-    // If there are other tokens we should fail as it is a malformed json
-    if let Some(t) = tokens.pop() {
-        Err(ParserError::UnexpectedTokenAfterEOF(t))
-    } else {
-        result
+    // A failed read looks like the end of the input to the lexer, so it takes precedence
+    match chars.error.take() {
+        Some(error) => Err(error.into()),
+        None => result,
     }
 }
 
+/// Async counterpart of [`parse_reader`], which streams `reader` into the parser on tokio's
+/// blocking thread pool. Must be called from within a tokio runtime.
+#[cfg(feature = "tokio")]
+pub async fn parse_async<R>(reader: R) -> Result<JsonAST>
+where
+    R: AsyncRead + Unpin + Send + 'static,
+{
+    let reader = tokio_util::io::SyncIoBridge::new(reader);
+    tokio::task::spawn_blocking(move || parse_reader(reader))
+        .await
+        .map_err(|e| ParserError::IoError(std::io::ErrorKind::Other, e.to_string()))?
+}
+
 #[cfg(test)]
 mod tests {
+    use std::io::{Cursor, ErrorKind, Read};
+
     use crate::{
         lexer::Token,
-        parser::{JsonAST, domain::ParserError, parse},
+        parser::{JsonAST, MAX_DEPTH, domain::ParserError, parse, parse_reader},
     };
 
     #[test]
@@ -277,4 +304,158 @@ mod tests {
         .unwrap();
         assert_eq!(result, ParserError::UnexpectedEOF)
     }
+
+    #[test]
+    fn scalar_at_top_level_should_succeed() {
+        assert_eq!(parse(" 42 ").unwrap(), JsonAST::Number(42.0));
+        assert_eq!(
+            parse("\"aloha\"").unwrap(),
+            JsonAST::String("aloha".to_string())
+        );
+        assert_eq!(parse("null").unwrap(), JsonAST::Null);
+    }
+
+    #[test]
+    fn trailing_comma_should_fail() {
+        assert_eq!(
+            parse("[1,]").err().unwrap(),
+            ParserError::UnexpectedToken(Token::RightBracket)
+        );
+        assert_eq!(
+            parse("{\"a\": 1,}").err().unwrap(),
+            ParserError::ExpectedKey(Token::RightBrace)
+        );
+    }
+
+    #[test]
+    fn missing_comma_should_fail() {
+        assert_eq!(
+            parse("[1 2]").err().unwrap(),
+            ParserError::ExpectedTokenMismatch(Token::RightBracket, Token::Number(2.0))
+        );
+    }
+
+    #[test]
+    fn nesting_deeper_than_max_depth_should_fail() {
+        let within = format!("{}{}", "[".repeat(MAX_DEPTH), "]".repeat(MAX_DEPTH));
+        assert!(parse(&within).is_ok());
+
+        let beyond = "[".repeat(MAX_DEPTH + 1);
+        assert_eq!(
+            parse(&beyond).err().unwrap(),
+            ParserError::MaxDepthExceeded(MAX_DEPTH)
+        );
+    }
+
+    struct FailingReader {
+        content: &'static [u8],
+    }
+
+    impl Read for FailingReader {
+        fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+            if self.content.is_empty() {
+                return Err(std::io::Error::new(ErrorKind::ConnectionReset, "reset"));
+            }
+            let n = self.content.len().min(buf.len()).min(4);
+            buf[..n].copy_from_slice(&self.content[..n]);
+            self.content = &self.content[n..];
+            Ok(n)
+        }
+    }
+
+    #[test]
+    fn parse_reader_should_match_parse() {
+        let input = "{\"foo\": [1, 2.5, \"b\\u00e9\"], \"bar\": {\"baz\": null}}";
+        let result = parse_reader(Cursor::new(input.as_bytes())).unwrap();
+        assert_eq!(result, parse(input).unwrap());
+    }
+
+    #[test]
+    fn parse_reader_should_report_io_errors() {
+        let reader = FailingReader {
+            content: b"{\"foo\": [1, 2",
+        };
+        assert_eq!(
+            parse_reader(reader).err().unwrap(),
+            ParserError::IoError(ErrorKind::ConnectionReset, "reset".to_string())
+        );
+    }
+
+    #[test]
+    fn parse_reader_should_report_invalid_utf8() {
+        let result = parse_reader(Cursor::new(b"[\"\xff\"]".to_vec()));
+        assert!(matches!(
+            result,
+            Err(ParserError::IoError(ErrorKind::InvalidData, _))
+        ));
+    }
+
+    #[test]
+    fn display_should_write_json() {
+        let ast = parse("{\"a\": [1, \"x\"], \"b\": {}}").unwrap();
+        assert_eq!(ast.to_string(), "{\"a\":[1,\"x\"],\"b\":{}}");
+        assert_eq!(
+            format!("{:#}", ast),
+            "{\n  \"a\": [\n    1,\n    \"x\"\n  ],\n  \"b\": {}\n}"
+        );
+    }
+
+    #[test]
+    fn pointer_should_follow_rfc_6901_examples() {
+        let ast =
+            parse(r#"{"foo": ["bar", "baz"], "": 0, "a/b": 1, "m~n": 8, "nested": {"k": [true]}}"#)
+                .unwrap();
+
+        assert_eq!(ast.pointer(""), Some(&ast));
+        assert_eq!(
+            ast.pointer("/foo/0"),
+            Some(&JsonAST::String("bar".to_string()))
+        );
+        assert_eq!(ast.pointer("/"), Some(&JsonAST::Number(0.0)));
+        assert_eq!(ast.pointer("/a~1b"), Some(&JsonAST::Number(1.0)));
+        assert_eq!(ast.pointer("/m~0n"), Some(&JsonAST::Number(8.0)));
+        assert_eq!(ast.pointer("/nested/k/0"), Some(&JsonAST::Boolean(true)));
+        assert_eq!(ast.pointer("/foo/01"), None);
+        assert_eq!(ast.pointer("/foo/-"), None);
+        assert_eq!(ast.pointer("/foo/2"), None);
+        assert_eq!(ast.pointer("foo"), None);
+    }
+
+    #[cfg(feature = "tokio")]
+    #[tokio::test]
+    async fn parse_async_should_match_parse() {
+        let input = "[true, false, null, {\"a\": -1e3}]";
+        let result = crate::parser::parse_async(Cursor::new(input.as_bytes()))
+            .await
+            .unwrap();
+        assert_eq!(result, parse(input).unwrap());
+    }
+
+    #[cfg(feature = "tokio")]
+    #[tokio::test]
+    async fn parse_async_should_report_io_errors() {
+        let reader =
+            tokio::io::AsyncReadExt::chain(Cursor::new(b"[1, 2".to_vec()), FailingAsyncReader);
+        assert_eq!(
+            crate::parser::parse_async(reader).await.err().unwrap(),
+            ParserError::IoError(ErrorKind::ConnectionReset, "reset".to_string())
+        );
+    }
+
+    #[cfg(feature = "tokio")]
+    struct FailingAsyncReader;
+
+    #[cfg(feature = "tokio")]
+    impl tokio::io::AsyncRead for FailingAsyncReader {
+        fn poll_read(
+            self: std::pin::Pin<&mut Self>,
+            _: &mut std::task::Context<'_>,
+            _: &mut tokio::io::ReadBuf<'_>,
+        ) -> std::task::Poll<std::io::Result<()>> {
+            std::task::Poll::Ready(Err(std::io::Error::new(
+                ErrorKind::ConnectionReset,
+                "reset",
+            )))
+        }
+    }
 }
@@ -0,0 +1,200 @@
+//! Parses large top-level arrays on rayon's thread pool.
+//!
+//! A structural pre-scan walks the input bytes once, tracking only string and nesting state, to
+//! find the commas separating the top-level elements. Batches of consecutive elements are then
+//! parsed in parallel and joined back in order.
+//!
+//! Anything the pre-scan or an element parse rejects is handed to the serial [`parse`], so the
+//! result, errors included, is always the same as the serial path's.
+
+use std::ops::Range;
+
+use rayon::prelude::*;
+
+use crate::{
+    lexer::Lexer,
+    parser::{JsonAST, domain::Result, parse, parse_token_list},
+};
+
+/// Inputs smaller than this are parsed serially, as splitting them isn't worth the overhead.
+pub const MIN_PARALLEL_BYTES: usize = 1 << 20;
+/// Approximate size of the batches of consecutive elements parsed by each task.
+const BATCH_BYTES: usize = 256 * 1024;
+
+/// Same as [`parse`], parsing the elements of a top-level array in parallel when the input is
+/// at least [`MIN_PARALLEL_BYTES`] long and the current rayon pool has more than one thread.
+pub fn parse_parallel(input: &str) -> Result<JsonAST> {
+    if input.len() < MIN_PARALLEL_BYTES || rayon::current_num_threads() < 2 {
+        return parse(input);
+    }
+    parse_in_batches(input, BATCH_BYTES)
+}
+
+fn parse_in_batches(input: &str, batch_bytes: usize) -> Result<JsonAST> {
+    let Some(elements) = split_elements(input) else {
+        return parse(input);
+    };
+
+    let mut batches: Vec<&[Range<usize>]> = Vec::new();
+    let mut rest = elements.as_slice();
+    while !rest.is_empty() {
+        let first = rest[0].start;
+        let size = rest
+            .iter()
+            .position(|range| range.end - first >= batch_bytes)
+            .map_or(rest.len(), |i| i + 1);
+        let (batch, remaining) = rest.split_at(size);
+        batches.push(batch);
+        rest = remaining;
+    }
+
+    let parsed: Result<Vec<Vec<JsonAST>>> = batches
+        .par_iter()
+        .map(|batch| {
+            batch
+                .iter()
+                .map(|range| parse_token_list(&mut Lexer::new(input[range.clone()].chars()), 1))
+                .collect()
+        })
+        .collect();
+
+    match parsed {
+        Ok(batches) => Ok(JsonAST::Array(batches.into_iter().flatten().collect())),
+        // Let the serial parser find the first error, as it would report it
+        Err(_) => parse(input),
+    }
+}
+
+fn is_whitespace(byte: &u8) -> bool {
+    matches!(byte, b' ' | b'\t' | b'\n' | b'\r')
+}
+
+/// Finds the byte range of every element of a top-level array, or `None` when the input isn't a
+/// well delimited array. Multi-byte UTF-8 sequences never contain ASCII bytes, so scanning bytes
+/// is safe.
+fn split_elements(input: &str) -> Option<Vec<Range<usize>>> {
+    let bytes = input.as_bytes();
+    let open = bytes.iter().position(|b| !is_whitespace(b))?;
+    if bytes[open] != b'[' {
+        return None;
+    }
+
+    let mut elements = Vec::new();
+    let mut element_start = open + 1;
+    let mut depth = 0usize;
+    let mut in_string = false;
+    let mut escaped = false;
+
+    for (i, byte) in bytes.iter().enumerate().skip(open + 1) {
+        if in_string {
+            match byte {
+                _ if escaped => escaped = false,
+                b'\\' => escaped = true,
+                b'"' => in_string = false,
+                _ => {}
+            }
+            continue;
+        }
+        match byte {
+            b'"' => in_string = true,
+            b'[' | b'{' => depth += 1,
+            b']' | b'}' if depth > 0 => depth -= 1,
+            b',' if depth == 0 => {
+                elements.push(element_start..i);
+                element_start = i + 1;
+            }
+            b']' => {
+                elements.push(element_start..i);
+                if !bytes[i + 1..].iter().all(is_whitespace) {
+                    return None;
+                }
+                let blank = |range: &Range<usize>| bytes[range.clone()].iter().all(is_whitespace);
+                return match elements.as_slice() {
+                    [only] if blank(only) => Some(Vec::new()),
+                    elements if elements.iter().any(blank) => None,
+                    _ => Some(elements),
+                };
+            }
+            b'}' => return None,
+            _ => {}
+        }
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::parser::{
+        MAX_DEPTH,
+        parallel::{MIN_PARALLEL_BYTES, parse_in_batches, parse_parallel, split_elements},
+        parse,
+    };
+
+    fn sample(elements: usize) -> String {
+        let element = |i: usize| {
+            format!(
+                r#"{{"id": {i}, "name": "item, [{i}] \"quoted\" \\", "tags": ["a", {{"b": [{i}, null]}}], "ok": true}}"#
+            )
+        };
+        let elements: Vec<String> = (0..elements).map(element).collect();
+        format!(" [\n{}\n] ", elements.join(",\n"))
+    }
+
+    #[test]
+    fn split_elements_should_ignore_structure_inside_strings() {
+        let input = r#"[1, "a,]\"}", [2, 3], {"b": ","}]"#;
+        let elements: Vec<&str> = split_elements(input)
+            .unwrap()
+            .into_iter()
+            .map(|range| input[range].trim())
+            .collect();
+        assert_eq!(elements, vec![
+            "1",
+            r#""a,]\"}""#,
+            "[2, 3]",
+            r#"{"b": ","}"#
+        ]);
+        assert_eq!(split_elements(" [ ] "), Some(Vec::new()));
+        assert_eq!(split_elements("{\"a\": 1}"), None);
+        assert_eq!(split_elements("[1,,2]"), None);
+    }
+
+    #[test]
+    fn parse_in_batches_should_match_the_serial_parser() {
+        let input = sample(500);
+        for batch_bytes in [1, 100, 10_000, usize::MAX] {
+            assert_eq!(parse_in_batches(&input, batch_bytes), parse(&input));
+        }
+
+        let large = sample(15_000);
+        assert!(large.len() >= MIN_PARALLEL_BYTES);
+        assert_eq!(parse_parallel(&large), parse(&large));
+    }
+
+    #[test]
+    fn parse_in_batches_should_report_the_serial_errors() {
+        let too_deep = format!("[1, {}{}]", "[".repeat(MAX_DEPTH), "]".repeat(MAX_DEPTH));
+        let inputs = [
+            "[1, 2,]",
+            "[1,, 2]",
+            "[1, 2 3]",
+            "[1, {\"a\": ]}",
+            "[1, 2] 3",
+            "[1, \"open]",
+            "[1, 2",
+            "[1, tru]",
+            too_deep.as_str(),
+        ];
+        for input in inputs {
+            assert!(parse(input).is_err(), "{} should fail", input);
+            assert_eq!(parse_in_batches(input, 1), parse(input), "{}", input);
+        }
+    }
+
+    #[test]
+    fn parse_in_batches_should_parse_non_array_documents() {
+        for input in ["{\"a\": [1, 2]}", "3", "[]", "  [ 1 ]  "] {
+            assert_eq!(parse_in_batches(input, 1), parse(input));
+        }
+    }
+}
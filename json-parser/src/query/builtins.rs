@@ -0,0 +1,602 @@
+use crate::{
+    parser::{JsonAST, MAX_DEPTH, parse},
+    query::{
+        domain::{Filter, QueryError, Result},
+        evaluator::{
+            add,
+            compare,
+            describe,
+            evaluate,
+            index_value,
+            insert,
+            is_truthy,
+            iterate,
+            recurse,
+            run,
+            type_name,
+        },
+    },
+    writer::to_json_string,
+};
+
+/// Calls the builtin `name`. Arguments are filters run against `input` wherever the builtin needs
+/// their values, so `map(.a)` or `has("a", "b")` behave as in jq.
+pub(crate) fn call(
+    name: &str,
+    arguments: &[Filter],
+    input: &JsonAST,
+    output: &mut Vec<JsonAST>,
+) -> Result<()> {
+    match (name, arguments) {
+        ("empty", []) => {}
+        ("not", []) => output.push(JsonAST::Boolean(!is_truthy(input))),
+        ("error", []) => return Err(user_error(input)),
+        ("error", [message]) => {
+            if let Some(message) = evaluate(message, input)?.first() {
+                return Err(user_error(message));
+            }
+        }
+        ("type", []) => output.push(string(type_name(input))),
+        ("length", []) => output.push(length(input)?),
+        ("utf8bytelength", []) => output.push(JsonAST::Number(as_str(input)?.len() as f64)),
+        ("keys", []) | ("keys_unsorted", []) => {
+            let mut keys: Vec<JsonAST> = match input {
+                JsonAST::Object(members) => members.iter().map(|(k, _)| string(k)).collect(),
+                JsonAST::Array(elements) => (0..elements.len())
+                    .map(|i| JsonAST::Number(i as f64))
+                    .collect(),
+                value => return Err(has_no(value, "keys")),
+            };
+            if name == "keys" {
+                keys.sort_by(compare);
+            }
+            output.push(JsonAST::Array(keys));
+        }
+        ("has", [key]) => {
+            for key in evaluate(key, input)? {
+                let has = match (input, &key) {
+                    (JsonAST::Object(members), JsonAST::String(key)) => {
+                        members.iter().any(|(k, _)| k == key)
+                    }
+                    (JsonAST::Array(elements), JsonAST::Number(i)) => {
+                        *i >= 0.0 && (*i as usize) < elements.len()
+                    }
+                    (value, key) => {
+                        return Err(QueryError::CannotIndex(
+                            type_name(value),
+                            to_json_string(key),
+                        ));
+                    }
+                };
+                output.push(JsonAST::Boolean(has));
+            }
+        }
+        ("map", [f]) => {
+            let mut mapped = Vec::new();
+            for value in iterate(input)? {
+                run(f, &value, &mut mapped)?;
+            }
+            output.push(JsonAST::Array(mapped));
+        }
+        ("map_values", [f]) => {
+            let first = |value: &JsonAST| -> Result<Option<JsonAST>> {
+                Ok(evaluate(f, value)?.into_iter().next())
+            };
+            output.push(match input {
+                JsonAST::Array(elements) => {
+                    let mut mapped = Vec::new();
+                    for value in elements {
+                        mapped.extend(first(value)?);
+                    }
+                    JsonAST::Array(mapped)
+                }
+                JsonAST::Object(members) => {
+                    let mut mapped = Vec::new();
+                    for (key, value) in members {
+                        if let Some(value) = first(value)? {
+                            mapped.push((key.clone(), value));
+                        }
+                    }
+                    JsonAST::Object(mapped)
+                }
+                value => return Err(QueryError::CannotIterate(type_name(value))),
+            });
+        }
+        ("select", [f]) => {
+            for condition in evaluate(f, input)? {
+                if is_truthy(&condition) {
+                    output.push(input.clone());
+                }
+            }
+        }
+        ("recurse", []) => recurse(input, 0, output)?,
+        ("recurse", [f]) => recurse_with(f, input, 0, output)?,
+        ("values", []) => select_types(input, output, &[
+            "boolean", "number", "string", "array", "object",
+        ]),
+        ("nulls", []) => select_types(input, output, &["null"]),
+        ("booleans", []) => select_types(input, output, &["boolean"]),
+        ("numbers", []) => select_types(input, output, &["number"]),
+        ("strings", []) => select_types(input, output, &["string"]),
+        ("arrays", []) => select_types(input, output, &["array"]),
+        ("objects", []) => select_types(input, output, &["object"]),
+        ("iterables", []) => select_types(input, output, &["array", "object"]),
+        ("scalars", []) => select_types(input, output, &["null", "boolean", "number", "string"]),
+        ("add", []) => {
+            let mut sum = JsonAST::Null;
+            for value in iterate(input)? {
+                sum = add(sum, value)?;
+            }
+            output.push(sum);
+        }
+        ("any", []) => output.push(JsonAST::Boolean(iterate(input)?.iter().any(is_truthy))),
+        ("all", []) => output.push(JsonAST::Boolean(iterate(input)?.iter().all(is_truthy))),
+        ("any", [f]) | ("all", [f]) => {
+            let mut results = Vec::new();
+            for value in iterate(input)? {
+                results.push(evaluate(f, &value)?.iter().any(is_truthy));
+            }
+            output.push(JsonAST::Boolean(if name == "any" {
+                results.into_iter().any(|r| r)
+            } else {
+                results.into_iter().all(|r| r)
+            }));
+        }
+        ("flatten", []) => output.push(flatten(input, f64::INFINITY)?),
+        ("flatten", [depth]) => {
+            for depth in evaluate(depth, input)? {
+                match depth {
+                    JsonAST::Number(depth) if depth >= 0.0 => output.push(flatten(input, depth)?),
+                    _ => {
+                        return Err(QueryError::TypeError(
+                            "flatten depth must not be negative".to_string(),
+                        ));
+                    }
+                }
+            }
+        }
+        ("range", [to]) => {
+            for to in evaluate(to, input)? {
+                range(&JsonAST::Number(0.0), &to, output)?;
+            }
+        }
+        ("range", [from, to]) => {
+            for from in evaluate(from, input)? {
+                for to in evaluate(to, input)? {
+                    range(&from, &to, output)?;
+                }
+            }
+        }
+        ("floor", []) => output.push(JsonAST::Number(as_number(input)?.floor())),
+        ("ceil", []) => output.push(JsonAST::Number(as_number(input)?.ceil())),
+        ("round", []) => output.push(JsonAST::Number(as_number(input)?.round())),
+        ("sqrt", []) => output.push(JsonAST::Number(as_number(input)?.sqrt())),
+        ("tostring", []) => output.push(match input {
+            JsonAST::String(_) => input.clone(),
+            value => JsonAST::String(to_json_string(value)),
+        }),
+        ("tonumber", []) => output.push(match input {
+            JsonAST::Number(_) => input.clone(),
+            JsonAST::String(s) => match parse(s) {
+                Ok(number @ JsonAST::Number(_)) => number,
+                _ => {
+                    return Err(QueryError::TypeError(format!(
+                        "Cannot parse '{}' as a number",
+                        s
+                    )));
+                }
+            },
+            value => {
+                return Err(QueryError::TypeError(format!(
+                    "{} cannot be parsed as a number",
+                    describe(value)
+                )));
+            }
+        }),
+        ("tojson", []) => output.push(JsonAST::String(to_json_string(input))),
+        ("fromjson", []) => {
+            let s = as_str(input)?;
+            output.push(parse(s).map_err(|error| {
+                QueryError::TypeError(format!("{} (while parsing '{}')", error, s))
+            })?);
+        }
+        ("ascii_downcase", []) => output.push(string(&as_str(input)?.to_ascii_lowercase())),
+        ("ascii_upcase", []) => output.push(string(&as_str(input)?.to_ascii_uppercase())),
+        ("startswith", [prefix]) | ("endswith", [prefix]) => {
+            for prefix in evaluate(prefix, input)? {
+                let (s, prefix) = match (input, &prefix) {
+                    (JsonAST::String(s), JsonAST::String(prefix)) => (s, prefix),
+                    _ => {
+                        return Err(QueryError::TypeError(format!(
+                            "{}() requires string inputs",
+                            name
+                        )));
+                    }
+                };
+                output.push(JsonAST::Boolean(if name == "startswith" {
+                    s.starts_with(prefix.as_str())
+                } else {
+                    s.ends_with(prefix.as_str())
+                }));
+            }
+        }
+        ("ltrimstr", [affix]) | ("rtrimstr", [affix]) => {
+            for affix in evaluate(affix, input)? {
+                let trimmed = match (input, &affix) {
+                    (JsonAST::String(s), JsonAST::String(affix)) if name == "ltrimstr" => {
+                        s.strip_prefix(affix.as_str()).map(string)
+                    }
+                    (JsonAST::String(s), JsonAST::String(affix)) => {
+                        s.strip_suffix(affix.as_str()).map(string)
+                    }
+                    _ => None,
+                };
+                output.push(trimmed.unwrap_or_else(|| input.clone()));
+            }
+        }
+        ("split", [separator]) => {
+            for separator in evaluate(separator, input)? {
+                let (s, separator) = match (input, &separator) {
+                    (JsonAST::String(s), JsonAST::String(separator)) => (s, separator),
+                    _ => {
+                        return Err(QueryError::TypeError(
+                            "split input and separator must be strings".to_string(),
+                        ));
+                    }
+                };
+                let parts = if s.is_empty() {
+                    Vec::new()
+                } else {
+                    s.split(separator.as_str()).map(string).collect()
+                };
+                output.push(JsonAST::Array(parts));
+            }
+        }
+        ("join", [separator]) => {
+            for separator in evaluate(separator, input)? {
+                let separator = as_str(&separator)?;
+                let mut parts = Vec::new();
+                for value in iterate(input)? {
+                    parts.push(match value {
+                        JsonAST::Null => String::new(),
+                        JsonAST::String(s) => s,
+                        value @ (JsonAST::Number(_) | JsonAST::Boolean(_)) => {
+                            to_json_string(&value)
+                        }
+                        value => {
+                            return Err(QueryError::TypeError(format!(
+                                "Cannot join with {}",
+                                describe(&value)
+                            )));
+                        }
+                    });
+                }
+                output.push(JsonAST::String(parts.join(separator)));
+            }
+        }
+        ("contains", [element]) => {
+            for element in evaluate(element, input)? {
+                output.push(JsonAST::Boolean(contains(input, &element)?));
+            }
+        }
+        ("reverse", []) => output.push(match input {
+            JsonAST::Null => JsonAST::Array(Vec::new()),
+            JsonAST::String(s) => JsonAST::String(s.chars().rev().collect()),
+            JsonAST::Array(elements) => JsonAST::Array(elements.iter().rev().cloned().collect()),
+            value => {
+                return Err(QueryError::TypeError(format!(
+                    "Cannot reverse {}",
+                    describe(value)
+                )));
+            }
+        }),
+        ("sort", []) => {
+            let mut elements = as_array(input, "sorted")?.to_vec();
+            elements.sort_by(compare);
+            output.push(JsonAST::Array(elements));
+        }
+        ("unique", []) => {
+            let mut elements = as_array(input, "sorted")?.to_vec();
+            elements.sort_by(compare);
+            elements.dedup_by(|a, b| compare(a, b).is_eq());
+            output.push(JsonAST::Array(elements));
+        }
+        ("min", []) | ("max", []) => {
+            let elements = as_array(input, "sorted")?;
+            let extreme = if name == "min" {
+                elements.iter().min_by(|a, b| compare(a, b))
+            } else {
+                elements.iter().max_by(|a, b| compare(a, b))
+            };
+            output.push(extreme.cloned().unwrap_or(JsonAST::Null));
+        }
+        ("sort_by", [f]) => {
+            let sorted = sort_by_key(input, f)?;
+            output.push(JsonAST::Array(sorted.into_iter().map(|(_, v)| v).collect()));
+        }
+        ("group_by", [f]) | ("unique_by", [f]) => {
+            let mut groups: Vec<(JsonAST, Vec<JsonAST>)> = Vec::new();
+            for (key, value) in sort_by_key(input, f)? {
+                match groups.last_mut() {
+                    Some((last, group)) if compare(last, &key).is_eq() => group.push(value),
+                    _ => groups.push((key, vec![value])),
+                }
+            }
+            output.push(JsonAST::Array(
+                groups
+                    .into_iter()
+                    .map(|(_, group)| {
+                        if name == "group_by" {
+                            JsonAST::Array(group)
+                        } else {
+                            group.into_iter().next().unwrap_or(JsonAST::Null)
+                        }
+                    })
+                    .collect(),
+            ));
+        }
+        ("min_by", [f]) | ("max_by", [f]) => {
+            let sorted = sort_by_key(input, f)?;
+            let extreme = if name == "min_by" {
+                sorted.into_iter().next()
+            } else {
+                sorted.into_iter().next_back()
+            };
+            output.push(extreme.map_or(JsonAST::Null, |(_, v)| v));
+        }
+        ("to_entries", []) => output.push(to_entries(input)?),
+        ("from_entries", []) => output.push(from_entries(input)?),
+        ("with_entries", [f]) => {
+            let mut mapped = Vec::new();
+            for entry in iterate(&to_entries(input)?)? {
+                run(f, &entry, &mut mapped)?;
+            }
+            output.push(from_entries(&JsonAST::Array(mapped))?);
+        }
+        ("first", []) => output.push(index_value(input, &JsonAST::Number(0.0))?),
+        ("last", []) => output.push(index_value(input, &JsonAST::Number(-1.0))?),
+        ("first", [f]) => output.extend(evaluate(f, input)?.into_iter().next()),
+        ("last", [f]) => output.extend(evaluate(f, input)?.into_iter().next_back()),
+        ("limit", [n, f]) => {
+            for n in evaluate(n, input)? {
+                let n = as_number(&n)?.max(0.0) as usize;
+                if n > 0 {
+                    output.extend(evaluate(f, input)?.into_iter().take(n));
+                }
+            }
+        }
+        (name, arguments) => {
+            return Err(QueryError::UnknownFunction(
+                name.to_string(),
+                arguments.len(),
+            ));
+        }
+    }
+    Ok(())
+}
+
+fn string(s: &str) -> JsonAST {
+    JsonAST::String(s.to_string())
+}
+
+fn user_error(message: &JsonAST) -> QueryError {
+    match message {
+        JsonAST::String(message) => QueryError::UserError(message.clone()),
+        message => QueryError::UserError(format!("{} (not a string)", to_json_string(message))),
+    }
+}
+
+fn has_no(value: &JsonAST, what: &str) -> QueryError {
+    QueryError::TypeError(format!("{} has no {}", describe(value), what))
+}
+
+fn as_str(value: &JsonAST) -> Result<&str> {
+    match value {
+        JsonAST::String(s) => Ok(s),
+        value => Err(QueryError::TypeError(format!(
+            "{} is not a string",
+            describe(value)
+        ))),
+    }
+}
+
+fn as_number(value: &JsonAST) -> Result<f64> {
+    match value {
+        JsonAST::Number(n) => Ok(*n),
+        value => Err(QueryError::TypeError(format!(
+            "{} is not a number",
+            describe(value)
+        ))),
+    }
+}
+
+fn as_array<'a>(value: &'a JsonAST, action: &str) -> Result<&'a [JsonAST]> {
+    match value {
+        JsonAST::Array(elements) => Ok(elements),
+        value => Err(QueryError::TypeError(format!(
+            "{} cannot be {}, as it is not an array",
+            describe(value),
+            action
+        ))),
+    }
+}
+
+fn length(value: &JsonAST) -> Result<JsonAST> {
+    let length = match value {
+        JsonAST::Null => 0.0,
+        JsonAST::Number(n) => n.abs(),
+        JsonAST::String(s) => s.chars().count() as f64,
+        JsonAST::Array(elements) => elements.len() as f64,
+        JsonAST::Object(members) => members.len() as f64,
+        value @ JsonAST::Boolean(_) => return Err(has_no(value, "length")),
+    };
+    Ok(JsonAST::Number(length))
+}
+
+fn select_types(input: &JsonAST, output: &mut Vec<JsonAST>, types: &[&str]) {
+    if types.contains(&type_name(input)) {
+        output.push(input.clone());
+    }
+}
+
+/// Produces `input` and, recursively, whatever `f` produces from it, until `f` produces nothing.
+/// Bounded like `..`, as a filter such as `recurse(.)` would otherwise recurse forever.
+fn recurse_with(
+    f: &Filter,
+    input: &JsonAST,
+    depth: usize,
+    output: &mut Vec<JsonAST>,
+) -> Result<()> {
+    if depth > MAX_DEPTH {
+        return Err(QueryError::MaxDepthExceeded(MAX_DEPTH));
+    }
+    output.push(input.clone());
+    for value in evaluate(f, input)? {
+        recurse_with(f, &value, depth + 1, output)?;
+    }
+    Ok(())
+}
+
+fn flatten(value: &JsonAST, depth: f64) -> Result<JsonAST> {
+    fn flatten_into(elements: &[JsonAST], depth: f64, output: &mut Vec<JsonAST>) {
+        for element in elements {
+            match element {
+                JsonAST::Array(inner) if depth > 0.0 => flatten_into(inner, depth - 1.0, output),
+                element => output.push(element.clone()),
+            }
+        }
+    }
+
+    let mut flattened = Vec::new();
+    flatten_into(as_array(value, "flattened")?, depth, &mut flattened);
+    Ok(JsonAST::Array(flattened))
+}
+
+/// Most values a single `range` may produce. Filters produce every value before the next one
+/// runs, so `limit(1; range(1e18))` would otherwise never finish.
+const MAX_RANGE_LENGTH: f64 = 10_000_000.0;
+
+fn range(from: &JsonAST, to: &JsonAST, output: &mut Vec<JsonAST>) -> Result<()> {
+    let (from, to) = (as_number(from)?, as_number(to)?);
+    let length = (to - from).ceil();
+    if length > MAX_RANGE_LENGTH {
+        return Err(QueryError::TypeError(format!(
+            "range from {} to {} is too long, at most {} values can be produced",
+            from, to, MAX_RANGE_LENGTH
+        )));
+    }
+    // Counting rather than adding 1 until `to`, which stalls once numbers are too large to
+    // change by 1
+    for i in 0..length.max(0.0) as u64 {
+        output.push(JsonAST::Number(from + i as f64));
+    }
+    Ok(())
+}
+
+fn contains(a: &JsonAST, b: &JsonAST) -> Result<bool> {
+    match (a, b) {
+        (JsonAST::String(a), JsonAST::String(b)) => Ok(a.contains(b.as_str())),
+        (JsonAST::Array(a), JsonAST::Array(b)) => {
+            for b in b {
+                let mut found = false;
+                for a in a {
+                    if type_name(a) == type_name(b) && contains(a, b)? {
+                        found = true;
+                        break;
+                    }
+                }
+                if !found {
+                    return Ok(false);
+                }
+            }
+            Ok(true)
+        }
+        (JsonAST::Object(a), JsonAST::Object(b)) => {
+            for (key, b) in b {
+                match a.iter().rev().find(|(k, _)| k == key) {
+                    Some((_, a)) if type_name(a) == type_name(b) && contains(a, b)? => {}
+                    _ => return Ok(false),
+                }
+            }
+            Ok(true)
+        }
+        (a, b) if type_name(a) == type_name(b) => Ok(compare(a, b).is_eq()),
+        (a, b) => Err(QueryError::TypeError(format!(
+            "{} and {} cannot have their containment checked",
+            describe(a),
+            describe(b)
+        ))),
+    }
+}
+
+/// Pairs every element of an array with the array of values `f` produces for it, sorted by
+/// those keys. The sort is stable, as jq's is.
+fn sort_by_key(input: &JsonAST, f: &Filter) -> Result<Vec<(JsonAST, JsonAST)>> {
+    let mut keyed = Vec::new();
+    for value in as_array(input, "sorted")? {
+        keyed.push((JsonAST::Array(evaluate(f, value)?), value.clone()));
+    }
+    keyed.sort_by(|a, b| compare(&a.0, &b.0));
+    Ok(keyed)
+}
+
+fn to_entries(value: &JsonAST) -> Result<JsonAST> {
+    match value {
+        JsonAST::Object(members) => Ok(JsonAST::Array(
+            members
+                .iter()
+                .map(|(key, value)| {
+                    JsonAST::Object(vec![
+                        ("key".to_string(), string(key)),
+                        ("value".to_string(), value.clone()),
+                    ])
+                })
+                .collect(),
+        )),
+        value => Err(has_no(value, "keys")),
+    }
+}
+
+/// Builds an object from `{"key": k, "value": v}` entries, also accepting the `k`, `name`, `v`
+/// and capitalised spellings jq understands.
+fn from_entries(value: &JsonAST) -> Result<JsonAST> {
+    let field = |entry: &JsonAST, names: &[&str]| -> JsonAST {
+        names
+            .iter()
+            .map(|name| index_value(entry, &string(name)).unwrap_or(JsonAST::Null))
+            .find(is_truthy)
+            .unwrap_or(JsonAST::Null)
+    };
+
+    let mut members = Vec::new();
+    for entry in iterate(value)? {
+        if !matches!(entry, JsonAST::Object(_)) {
+            return Err(QueryError::CannotIndex(
+                type_name(&entry),
+                "\"key\"".to_string(),
+            ));
+        }
+        let key = match field(&entry, &["key", "k", "name", "Name", "Key", "K"]) {
+            JsonAST::String(key) => key,
+            key @ (JsonAST::Number(_) | JsonAST::Boolean(_)) => to_json_string(&key),
+            key => {
+                return Err(QueryError::TypeError(format!(
+                    "Cannot use {} as object key",
+                    describe(&key)
+                )));
+            }
+        };
+        let value = ["value", "v", "Value", "V"]
+            .iter()
+            .find_map(|name| match &entry {
+                JsonAST::Object(members) => members
+                    .iter()
+                    .find(|(k, _)| k == name)
+                    .map(|(_, v)| v.clone()),
+                _ => None,
+            })
+            .unwrap_or(JsonAST::Null);
+        insert(&mut members, key, value);
+    }
+    Ok(JsonAST::Object(members))
+}
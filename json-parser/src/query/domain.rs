@@ -0,0 +1,141 @@
+use std::fmt::{Display, Formatter};
+
+use crate::parser::JsonAST;
+
+#[derive(Debug, thiserror::Error, PartialEq)]
+pub enum QueryError {
+    #[error("Unexpected character {0} at position {1}")]
+    UnexpectedCharacter(char, usize),
+    #[error("Unterminated string literal")]
+    UnterminatedString,
+    #[error("Invalid escape sequence \\{0} in string literal")]
+    InvalidEscape(char),
+    #[error("Invalid number {0}")]
+    InvalidNumber(String),
+    #[error("Unexpected token {0}")]
+    UnexpectedToken(QueryToken),
+    #[error("Unexpected end of filter")]
+    UnexpectedEnd,
+    #[error("{0}/{1} is not defined")]
+    UnknownFunction(String, usize),
+    #[error("Cannot index {0} with {1}")]
+    CannotIndex(&'static str, String),
+    #[error("Cannot iterate over {0}")]
+    CannotIterate(&'static str),
+    #[error("{0}")]
+    TypeError(String),
+    #[error("Recursion deeper than {0} levels")]
+    MaxDepthExceeded(usize),
+    #[error("{0}")]
+    UserError(String),
+}
+
+pub type Result<T> = std::result::Result<T, QueryError>;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum QueryToken {
+    Dot,
+    RecurseDefault,
+    Field(String),
+    Identifier(String),
+    String(String),
+    Number(f64),
+    LeftBracket,
+    RightBracket,
+    LeftBrace,
+    RightBrace,
+    LeftParen,
+    RightParen,
+    Pipe,
+    Comma,
+    Colon,
+    Semicolon,
+    Question,
+    Operator(Operator),
+    Alternative,
+}
+
+impl Display for QueryToken {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            QueryToken::Dot => write!(f, "."),
+            QueryToken::RecurseDefault => write!(f, ".."),
+            QueryToken::Field(name) => write!(f, ".{}", name),
+            QueryToken::Identifier(name) => write!(f, "{}", name),
+            QueryToken::String(s) => write!(f, "{:?}", s),
+            QueryToken::Number(n) => write!(f, "{}", n),
+            QueryToken::LeftBracket => write!(f, "["),
+            QueryToken::RightBracket => write!(f, "]"),
+            QueryToken::LeftBrace => write!(f, "{{"),
+            QueryToken::RightBrace => write!(f, "}}"),
+            QueryToken::LeftParen => write!(f, "("),
+            QueryToken::RightParen => write!(f, ")"),
+            QueryToken::Pipe => write!(f, "|"),
+            QueryToken::Comma => write!(f, ","),
+            QueryToken::Colon => write!(f, ":"),
+            QueryToken::Semicolon => write!(f, ";"),
+            QueryToken::Question => write!(f, "?"),
+            QueryToken::Operator(operator) => write!(f, "{}", operator),
+            QueryToken::Alternative => write!(f, "//"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Operator {
+    Add,
+    Subtract,
+    Multiply,
+    Divide,
+    Modulo,
+    Equal,
+    NotEqual,
+    Less,
+    LessOrEqual,
+    Greater,
+    GreaterOrEqual,
+    And,
+    Or,
+}
+
+impl Display for Operator {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let symbol = match self {
+            Operator::Add => "+",
+            Operator::Subtract => "-",
+            Operator::Multiply => "*",
+            Operator::Divide => "/",
+            Operator::Modulo => "%",
+            Operator::Equal => "==",
+            Operator::NotEqual => "!=",
+            Operator::Less => "<",
+            Operator::LessOrEqual => "<=",
+            Operator::Greater => ">",
+            Operator::GreaterOrEqual => ">=",
+            Operator::And => "and",
+            Operator::Or => "or",
+        };
+        write!(f, "{}", symbol)
+    }
+}
+
+/// A compiled filter, produced by [`compile`](crate::query::compile).
+#[derive(Debug, Clone, PartialEq)]
+pub enum Filter {
+    Identity,
+    RecurseDefault,
+    Literal(JsonAST),
+    Index(Box<Filter>, Box<Filter>),
+    Slice(Box<Filter>, Option<Box<Filter>>, Option<Box<Filter>>),
+    Iterate(Box<Filter>),
+    Optional(Box<Filter>),
+    Pipe(Box<Filter>, Box<Filter>),
+    Comma(Box<Filter>, Box<Filter>),
+    Negate(Box<Filter>),
+    Binary(Operator, Box<Filter>, Box<Filter>),
+    Alternative(Box<Filter>, Box<Filter>),
+    ArrayConstruction(Option<Box<Filter>>),
+    ObjectConstruction(Vec<(Filter, Filter)>),
+    IfThenElse(Box<Filter>, Box<Filter>, Box<Filter>),
+    Call(String, Vec<Filter>),
+}
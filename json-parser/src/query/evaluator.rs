@@ -0,0 +1,443 @@
+use std::cmp::Ordering;
+
+use crate::{
+    parser::{JsonAST, MAX_DEPTH},
+    query::{
+        builtins::call,
+        domain::{Filter, Operator, QueryError, Result},
+    },
+    writer::to_json_string,
+};
+
+/// Runs `filter` against `input`, collecting every value it produces.
+pub fn evaluate(filter: &Filter, input: &JsonAST) -> Result<Vec<JsonAST>> {
+    let mut output = Vec::new();
+    run(filter, input, &mut output)?;
+    Ok(output)
+}
+
+/// Runs `filter` against `input`, pushing the produced values into `output` as they come, so a
+/// failing filter keeps whatever it produced before the error.
+pub(crate) fn run(filter: &Filter, input: &JsonAST, output: &mut Vec<JsonAST>) -> Result<()> {
+    match filter {
+        Filter::Identity => output.push(input.clone()),
+        Filter::RecurseDefault => recurse(input, 0, output)?,
+        Filter::Literal(value) => output.push(value.clone()),
+        Filter::Index(term, index) => {
+            for target in evaluate(term, input)? {
+                for index in evaluate(index, input)? {
+                    output.push(index_value(&target, &index)?);
+                }
+            }
+        }
+        Filter::Slice(term, from, to) => {
+            let bounds = |bound: &Option<Box<Filter>>| match bound {
+                Some(bound) => evaluate(bound, input),
+                None => Ok(vec![JsonAST::Null]),
+            };
+            for target in evaluate(term, input)? {
+                for to in bounds(to)? {
+                    for from in bounds(from)? {
+                        output.push(slice_value(&target, &from, &to)?);
+                    }
+                }
+            }
+        }
+        Filter::Iterate(term) => {
+            for target in evaluate(term, input)? {
+                output.extend(iterate(&target)?);
+            }
+        }
+        Filter::Optional(term) => {
+            let _ = run(term, input, output);
+        }
+        Filter::Pipe(lhs, rhs) => {
+            for value in evaluate(lhs, input)? {
+                run(rhs, &value, output)?;
+            }
+        }
+        Filter::Comma(lhs, rhs) => {
+            run(lhs, input, output)?;
+            run(rhs, input, output)?;
+        }
+        Filter::Negate(term) => {
+            for value in evaluate(term, input)? {
+                match value {
+                    JsonAST::Number(n) => output.push(JsonAST::Number(-n)),
+                    value => {
+                        return Err(QueryError::TypeError(format!(
+                            "{} cannot be negated",
+                            describe(&value)
+                        )));
+                    }
+                }
+            }
+        }
+        Filter::Binary(operator @ (Operator::And | Operator::Or), lhs, rhs) => {
+            for l in evaluate(lhs, input)? {
+                match (operator, is_truthy(&l)) {
+                    (Operator::And, false) => output.push(JsonAST::Boolean(false)),
+                    (Operator::Or, true) => output.push(JsonAST::Boolean(true)),
+                    _ => {
+                        for r in evaluate(rhs, input)? {
+                            output.push(JsonAST::Boolean(is_truthy(&r)));
+                        }
+                    }
+                }
+            }
+        }
+        Filter::Binary(operator, lhs, rhs) => {
+            for r in evaluate(rhs, input)? {
+                for l in evaluate(lhs, input)? {
+                    output.push(apply_operator(*operator, l, r.clone())?);
+                }
+            }
+        }
+        Filter::Alternative(lhs, rhs) => {
+            let mut values = Vec::new();
+            let _ = run(lhs, input, &mut values);
+            let truthy: Vec<JsonAST> = values.into_iter().filter(is_truthy).collect();
+            if truthy.is_empty() {
+                run(rhs, input, output)?;
+            } else {
+                output.extend(truthy);
+            }
+        }
+        Filter::ArrayConstruction(None) => output.push(JsonAST::Array(Vec::new())),
+        Filter::ArrayConstruction(Some(elements)) => {
+            output.push(JsonAST::Array(evaluate(elements, input)?))
+        }
+        Filter::ObjectConstruction(entries) => {
+            let mut objects = vec![Vec::new()];
+            for (key, value) in entries {
+                let keys = evaluate(key, input)?;
+                let values = evaluate(value, input)?;
+                let mut extended = Vec::new();
+                for object in &objects {
+                    for key in &keys {
+                        let JsonAST::String(key) = key else {
+                            return Err(QueryError::TypeError(format!(
+                                "Object keys must be strings, got {}",
+                                describe(key)
+                            )));
+                        };
+                        for value in &values {
+                            let mut object: Vec<(String, JsonAST)> = object.clone();
+                            insert(&mut object, key.clone(), value.clone());
+                            extended.push(object);
+                        }
+                    }
+                }
+                objects = extended;
+            }
+            output.extend(objects.into_iter().map(JsonAST::Object));
+        }
+        Filter::IfThenElse(condition, then, otherwise) => {
+            for condition in evaluate(condition, input)? {
+                if is_truthy(&condition) {
+                    run(then, input, output)?;
+                } else {
+                    run(otherwise, input, output)?;
+                }
+            }
+        }
+        Filter::Call(name, arguments) => call(name, arguments, input, output)?,
+    }
+    Ok(())
+}
+
+pub(crate) fn is_truthy(value: &JsonAST) -> bool {
+    !matches!(value, JsonAST::Null | JsonAST::Boolean(false))
+}
+
+pub(crate) fn type_name(value: &JsonAST) -> &'static str {
+    match value {
+        JsonAST::Null => "null",
+        JsonAST::Boolean(_) => "boolean",
+        JsonAST::Number(_) => "number",
+        JsonAST::String(_) => "string",
+        JsonAST::Array(_) => "array",
+        JsonAST::Object(_) => "object",
+    }
+}
+
+/// Describes a value in error messages the way jq does, e.g. `number (42)`.
+pub(crate) fn describe(value: &JsonAST) -> String {
+    format!("{} ({})", type_name(value), to_json_string(value))
+}
+
+/// Sets `key` in an object, replacing the previous value if the key is already present.
+pub(crate) fn insert(members: &mut Vec<(String, JsonAST)>, key: String, value: JsonAST) {
+    match members.iter_mut().find(|(k, _)| *k == key) {
+        Some((_, v)) => *v = value,
+        None => members.push((key, value)),
+    }
+}
+
+/// Produces `value` and every value nested in it, `depth` being how deep `value` itself is.
+/// Filters may build values nested deeper than parsed ones can be, so the depth is bounded the
+/// same way to keep the stack from overflowing.
+pub(crate) fn recurse(value: &JsonAST, depth: usize, output: &mut Vec<JsonAST>) -> Result<()> {
+    if depth > MAX_DEPTH {
+        return Err(QueryError::MaxDepthExceeded(MAX_DEPTH));
+    }
+    output.push(value.clone());
+    match value {
+        JsonAST::Array(elements) => {
+            for v in elements {
+                recurse(v, depth + 1, output)?;
+            }
+        }
+        JsonAST::Object(members) => {
+            for (_, v) in members {
+                recurse(v, depth + 1, output)?;
+            }
+        }
+        _ => {}
+    }
+    Ok(())
+}
+
+pub(crate) fn iterate(value: &JsonAST) -> Result<Vec<JsonAST>> {
+    match value {
+        JsonAST::Array(elements) => Ok(elements.clone()),
+        JsonAST::Object(members) => Ok(members.iter().map(|(_, v)| v.clone()).collect()),
+        value => Err(QueryError::CannotIterate(type_name(value))),
+    }
+}
+
+pub(crate) fn index_value(target: &JsonAST, index: &JsonAST) -> Result<JsonAST> {
+    match (target, index) {
+        (JsonAST::Null, JsonAST::String(_) | JsonAST::Number(_) | JsonAST::Null) => {
+            Ok(JsonAST::Null)
+        }
+        (JsonAST::Object(members), JsonAST::String(key)) => Ok(members
+            .iter()
+            .rev()
+            .find(|(k, _)| k == key)
+            .map_or(JsonAST::Null, |(_, v)| v.clone())),
+        (JsonAST::Array(elements), JsonAST::Number(n)) => {
+            let i = n.floor() as i64;
+            let i = if i < 0 { i + elements.len() as i64 } else { i };
+            Ok(usize::try_from(i)
+                .ok()
+                .and_then(|i| elements.get(i))
+                .cloned()
+                .unwrap_or(JsonAST::Null))
+        }
+        (target, index) => Err(QueryError::CannotIndex(
+            type_name(target),
+            to_json_string(index),
+        )),
+    }
+}
+
+fn slice_value(target: &JsonAST, from: &JsonAST, to: &JsonAST) -> Result<JsonAST> {
+    let bounds = |length: usize| -> Result<(usize, usize)> {
+        let bound = |value: &JsonAST, default: usize, round: fn(f64) -> f64| match value {
+            JsonAST::Null => Ok(default),
+            JsonAST::Number(n) => {
+                let n = round(*n);
+                let n = if n < 0.0 { n + length as f64 } else { n };
+                Ok(n.clamp(0.0, length as f64) as usize)
+            }
+            value => Err(QueryError::TypeError(format!(
+                "Start and end indices of a slice must be numbers, got {}",
+                describe(value)
+            ))),
+        };
+        let start = bound(from, 0, f64::floor)?;
+        let end = bound(to, length, f64::ceil)?;
+        Ok((start, end.max(start)))
+    };
+
+    match target {
+        JsonAST::Null => Ok(JsonAST::Null),
+        JsonAST::Array(elements) => {
+            let (start, end) = bounds(elements.len())?;
+            Ok(JsonAST::Array(elements[start..end].to_vec()))
+        }
+        JsonAST::String(s) => {
+            let (start, end) = bounds(s.chars().count())?;
+            Ok(JsonAST::String(
+                s.chars().skip(start).take(end - start).collect(),
+            ))
+        }
+        target => Err(QueryError::CannotIndex(
+            type_name(target),
+            "object".to_string(),
+        )),
+    }
+}
+
+/// Orders values as jq does: null < false < true < numbers < strings < arrays < objects. Objects
+/// are compared by their sorted keys first and then by the values under those keys.
+pub(crate) fn compare(a: &JsonAST, b: &JsonAST) -> Ordering {
+    fn rank(value: &JsonAST) -> u8 {
+        match value {
+            JsonAST::Null => 0,
+            JsonAST::Boolean(false) => 1,
+            JsonAST::Boolean(true) => 2,
+            JsonAST::Number(_) => 3,
+            JsonAST::String(_) => 4,
+            JsonAST::Array(_) => 5,
+            JsonAST::Object(_) => 6,
+        }
+    }
+
+    match (a, b) {
+        (JsonAST::Number(a), JsonAST::Number(b)) => a.partial_cmp(b).unwrap_or(Ordering::Equal),
+        (JsonAST::String(a), JsonAST::String(b)) => a.cmp(b),
+        (JsonAST::Array(a), JsonAST::Array(b)) => a
+            .iter()
+            .zip(b)
+            .map(|(a, b)| compare(a, b))
+            .find(|ordering| ordering.is_ne())
+            .unwrap_or_else(|| a.len().cmp(&b.len())),
+        (JsonAST::Object(a), JsonAST::Object(b)) => {
+            let keys = |members: &[(String, JsonAST)]| {
+                let mut keys: Vec<String> = members.iter().map(|(k, _)| k.clone()).collect();
+                keys.sort();
+                keys.dedup();
+                keys
+            };
+            let (a_keys, b_keys) = (keys(a), keys(b));
+            a_keys.cmp(&b_keys).then_with(|| {
+                a_keys
+                    .iter()
+                    .map(|key| {
+                        let key = JsonAST::String(key.clone());
+                        let a =
+                            index_value(&JsonAST::Object(a.clone()), &key).unwrap_or(JsonAST::Null);
+                        let b =
+                            index_value(&JsonAST::Object(b.clone()), &key).unwrap_or(JsonAST::Null);
+                        compare(&a, &b)
+                    })
+                    .find(|ordering| ordering.is_ne())
+                    .unwrap_or(Ordering::Equal)
+            })
+        }
+        (a, b) => rank(a).cmp(&rank(b)),
+    }
+}
+
+/// Longest string, in bytes, that multiplying a string by a number may produce.
+const MAX_STRING_LENGTH: usize = 1 << 30;
+
+fn apply_operator(operator: Operator, lhs: JsonAST, rhs: JsonAST) -> Result<JsonAST> {
+    let comparison =
+        |expected: fn(Ordering) -> bool| Ok(JsonAST::Boolean(expected(compare(&lhs, &rhs))));
+    match operator {
+        Operator::Equal => comparison(Ordering::is_eq),
+        Operator::NotEqual => comparison(Ordering::is_ne),
+        Operator::Less => comparison(Ordering::is_lt),
+        Operator::LessOrEqual => comparison(Ordering::is_le),
+        Operator::Greater => comparison(Ordering::is_gt),
+        Operator::GreaterOrEqual => comparison(Ordering::is_ge),
+        Operator::Add => add(lhs, rhs),
+        Operator::Subtract => match (lhs, rhs) {
+            (JsonAST::Number(a), JsonAST::Number(b)) => Ok(JsonAST::Number(a - b)),
+            (JsonAST::Array(a), JsonAST::Array(b)) => Ok(JsonAST::Array(
+                a.into_iter()
+                    .filter(|v| !b.iter().any(|r| compare(v, r).is_eq()))
+                    .collect(),
+            )),
+            (a, b) => Err(cannot(&a, &b, "subtracted")),
+        },
+        Operator::Multiply => match (lhs, rhs) {
+            (JsonAST::Number(a), JsonAST::Number(b)) => Ok(JsonAST::Number(a * b)),
+            (JsonAST::String(s), JsonAST::Number(n)) | (JsonAST::Number(n), JsonAST::String(s)) => {
+                if n <= 0.0 {
+                    Ok(JsonAST::Null)
+                } else if n.ceil() * s.len() as f64 > MAX_STRING_LENGTH as f64 {
+                    Err(QueryError::TypeError(format!(
+                        "{} cannot be repeated {} times because the result is too long",
+                        describe(&JsonAST::String(s)),
+                        n.ceil()
+                    )))
+                } else {
+                    Ok(JsonAST::String(s.repeat(n.ceil() as usize)))
+                }
+            }
+            (JsonAST::Object(a), JsonAST::Object(b)) => Ok(JsonAST::Object(deep_merge(a, b))),
+            (a, b) => Err(cannot(&a, &b, "multiplied")),
+        },
+        Operator::Divide => match (lhs, rhs) {
+            (JsonAST::Number(a), JsonAST::Number(b)) if b == 0.0 => {
+                Err(QueryError::TypeError(format!(
+                    "{} and {} cannot be divided because the divisor is zero",
+                    describe(&JsonAST::Number(a)),
+                    describe(&JsonAST::Number(b))
+                )))
+            }
+            (JsonAST::Number(a), JsonAST::Number(b)) => Ok(JsonAST::Number(a / b)),
+            (JsonAST::String(a), JsonAST::String(b)) => Ok(JsonAST::Array(
+                a.split(b.as_str())
+                    .map(|s| JsonAST::String(s.to_string()))
+                    .collect(),
+            )),
+            (a, b) => Err(cannot(&a, &b, "divided")),
+        },
+        Operator::Modulo => match (lhs, rhs) {
+            (JsonAST::Number(a), JsonAST::Number(b)) if b.trunc() == 0.0 => {
+                Err(QueryError::TypeError(format!(
+                    "{} and {} cannot be divided because the divisor is zero",
+                    describe(&JsonAST::Number(a)),
+                    describe(&JsonAST::Number(b))
+                )))
+            }
+            // `i64::MIN % -1` overflows, though anything modulo -1 is 0
+            (JsonAST::Number(a), JsonAST::Number(b)) => Ok(JsonAST::Number(
+                (a.trunc() as i64)
+                    .checked_rem(b.trunc() as i64)
+                    .unwrap_or(0) as f64,
+            )),
+            (a, b) => Err(cannot(&a, &b, "divided")),
+        },
+        Operator::And | Operator::Or => unreachable!("Boolean operators short circuit in run"),
+    }
+}
+
+pub(crate) fn add(lhs: JsonAST, rhs: JsonAST) -> Result<JsonAST> {
+    match (lhs, rhs) {
+        (JsonAST::Null, value) | (value, JsonAST::Null) => Ok(value),
+        (JsonAST::Number(a), JsonAST::Number(b)) => Ok(JsonAST::Number(a + b)),
+        (JsonAST::String(a), JsonAST::String(b)) => Ok(JsonAST::String(a + &b)),
+        (JsonAST::Array(mut a), JsonAST::Array(b)) => {
+            a.extend(b);
+            Ok(JsonAST::Array(a))
+        }
+        (JsonAST::Object(mut a), JsonAST::Object(b)) => {
+            for (key, value) in b {
+                insert(&mut a, key, value);
+            }
+            Ok(JsonAST::Object(a))
+        }
+        (a, b) => Err(cannot(&a, &b, "added")),
+    }
+}
+
+fn deep_merge(
+    mut lhs: Vec<(String, JsonAST)>,
+    rhs: Vec<(String, JsonAST)>,
+) -> Vec<(String, JsonAST)> {
+    for (key, value) in rhs {
+        let merged = match (lhs.iter().find(|(k, _)| *k == key), value) {
+            (Some((_, JsonAST::Object(a))), JsonAST::Object(b)) => {
+                JsonAST::Object(deep_merge(a.clone(), b))
+            }
+            (_, value) => value,
+        };
+        insert(&mut lhs, key, merged);
+    }
+    lhs
+}
+
+fn cannot(lhs: &JsonAST, rhs: &JsonAST, action: &str) -> QueryError {
+    QueryError::TypeError(format!(
+        "{} and {} cannot be {}",
+        describe(lhs),
+        describe(rhs),
+        action
+    ))
+}
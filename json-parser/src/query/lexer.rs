@@ -0,0 +1,169 @@
+use std::{iter::Peekable, str::CharIndices};
+
+use crate::query::domain::{Operator, QueryError, QueryToken, Result};
+
+pub fn tokenize(input: &str) -> Result<Vec<QueryToken>> {
+    let mut chars = input.char_indices().peekable();
+    let mut tokens = Vec::new();
+
+    while let Some((position, c)) = chars.next() {
+        let token = match c {
+            c if c.is_whitespace() => continue,
+            '.' => match chars.peek() {
+                Some((_, '.')) => {
+                    chars.next();
+                    QueryToken::RecurseDefault
+                }
+                Some((_, c)) if is_identifier_start(*c) => {
+                    QueryToken::Field(take_identifier(&mut chars))
+                }
+                _ => QueryToken::Dot,
+            },
+            '[' => QueryToken::LeftBracket,
+            ']' => QueryToken::RightBracket,
+            '{' => QueryToken::LeftBrace,
+            '}' => QueryToken::RightBrace,
+            '(' => QueryToken::LeftParen,
+            ')' => QueryToken::RightParen,
+            '|' => QueryToken::Pipe,
+            ',' => QueryToken::Comma,
+            ':' => QueryToken::Colon,
+            ';' => QueryToken::Semicolon,
+            '?' => QueryToken::Question,
+            '+' => QueryToken::Operator(Operator::Add),
+            '-' => QueryToken::Operator(Operator::Subtract),
+            '*' => QueryToken::Operator(Operator::Multiply),
+            '%' => QueryToken::Operator(Operator::Modulo),
+            '/' if next_is(&mut chars, '/') => QueryToken::Alternative,
+            '/' => QueryToken::Operator(Operator::Divide),
+            '=' if next_is(&mut chars, '=') => QueryToken::Operator(Operator::Equal),
+            '!' if next_is(&mut chars, '=') => QueryToken::Operator(Operator::NotEqual),
+            '<' if next_is(&mut chars, '=') => QueryToken::Operator(Operator::LessOrEqual),
+            '<' => QueryToken::Operator(Operator::Less),
+            '>' if next_is(&mut chars, '=') => QueryToken::Operator(Operator::GreaterOrEqual),
+            '>' => QueryToken::Operator(Operator::Greater),
+            '"' => QueryToken::String(take_string(&mut chars)?),
+            c if c.is_ascii_digit() => QueryToken::Number(take_number(c, &mut chars)?),
+            c if is_identifier_start(c) => {
+                let mut identifier = c.to_string();
+                identifier.push_str(&take_identifier(&mut chars));
+                match identifier.as_str() {
+                    "and" => QueryToken::Operator(Operator::And),
+                    "or" => QueryToken::Operator(Operator::Or),
+                    _ => QueryToken::Identifier(identifier),
+                }
+            }
+            c => return Err(QueryError::UnexpectedCharacter(c, position)),
+        };
+        tokens.push(token);
+    }
+
+    Ok(tokens)
+}
+
+fn is_identifier_start(c: char) -> bool {
+    c.is_ascii_alphabetic() || c == '_'
+}
+
+fn next_is(chars: &mut Peekable<CharIndices>, expected: char) -> bool {
+    chars.next_if(|(_, c)| *c == expected).is_some()
+}
+
+fn take_identifier(chars: &mut Peekable<CharIndices>) -> String {
+    let mut identifier = String::new();
+    while let Some((_, c)) = chars.next_if(|(_, c)| c.is_ascii_alphanumeric() || *c == '_') {
+        identifier.push(c);
+    }
+    identifier
+}
+
+fn take_number(first: char, chars: &mut Peekable<CharIndices>) -> Result<f64> {
+    let mut number = first.to_string();
+    while let Some((_, c)) = chars.next_if(|(_, c)| c.is_ascii_digit() || *c == '.') {
+        number.push(c);
+    }
+    if let Some((_, e)) = chars.next_if(|(_, c)| *c == 'e' || *c == 'E') {
+        number.push(e);
+        if let Some((_, sign)) = chars.next_if(|(_, c)| *c == '+' || *c == '-') {
+            number.push(sign);
+        }
+        while let Some((_, c)) = chars.next_if(|(_, c)| c.is_ascii_digit()) {
+            number.push(c);
+        }
+    }
+    number
+        .parse::<f64>()
+        .map_err(|_| QueryError::InvalidNumber(number))
+}
+
+fn take_string(chars: &mut Peekable<CharIndices>) -> Result<String> {
+    let mut string = String::new();
+    loop {
+        match chars.next() {
+            None => return Err(QueryError::UnterminatedString),
+            Some((_, '"')) => return Ok(string),
+            Some((_, '\\')) => match chars.next() {
+                None => return Err(QueryError::UnterminatedString),
+                Some((_, '"')) => string.push('"'),
+                Some((_, '\\')) => string.push('\\'),
+                Some((_, '/')) => string.push('/'),
+                Some((_, 'b')) => string.push('\u{08}'),
+                Some((_, 'f')) => string.push('\u{0C}'),
+                Some((_, 'n')) => string.push('\n'),
+                Some((_, 'r')) => string.push('\r'),
+                Some((_, 't')) => string.push('\t'),
+                Some((_, 'u')) => {
+                    let hex: String = (0..4)
+                        .filter_map(|_| chars.next())
+                        .map(|(_, c)| c)
+                        .collect();
+                    let c = u32::from_str_radix(&hex, 16)
+                        .ok()
+                        .and_then(char::from_u32)
+                        .ok_or(QueryError::InvalidEscape('u'))?;
+                    string.push(c);
+                }
+                Some((_, c)) => return Err(QueryError::InvalidEscape(c)),
+            },
+            Some((_, c)) => string.push(c),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::query::{
+        domain::{Operator, QueryError, QueryToken},
+        lexer::tokenize,
+    };
+
+    #[test]
+    fn tokenize_should_split_fields_and_operators() {
+        assert_eq!(
+            tokenize(".foo[0] | .. // \"a\\n\" >= 1.5e1 and not").unwrap(),
+            vec![
+                QueryToken::Field("foo".to_string()),
+                QueryToken::LeftBracket,
+                QueryToken::Number(0.0),
+                QueryToken::RightBracket,
+                QueryToken::Pipe,
+                QueryToken::RecurseDefault,
+                QueryToken::Alternative,
+                QueryToken::String("a\n".to_string()),
+                QueryToken::Operator(Operator::GreaterOrEqual),
+                QueryToken::Number(15.0),
+                QueryToken::Operator(Operator::And),
+                QueryToken::Identifier("not".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn tokenize_should_fail_on_unknown_characters() {
+        assert_eq!(
+            tokenize(". & 1"),
+            Err(QueryError::UnexpectedCharacter('&', 2))
+        );
+        assert_eq!(tokenize("\"abc"), Err(QueryError::UnterminatedString));
+    }
+}
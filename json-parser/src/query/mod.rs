@@ -0,0 +1,243 @@
+//! A subset of the [jq](https://jqlang.github.io/jq/manual/) filter language over [`JsonAST`].
+//!
+//! Supported are paths (`.foo`, `."foo"`, `.[0]`, `.[1:3]`, `.[]`, `..`), the optional
+//! operator `?`, pipes, commas, arithmetic and comparisons, `and`/`or`, the alternative operator
+//! `//`, `if`/`elif`/`else`, array and object construction, and the common builtins such as
+//! `map`, `select`, `keys`, `has`, `length`, `sort_by`, `group_by` or `to_entries`. Variables,
+//! `reduce`, `def`, string interpolation and regular expressions are not supported.
+
+use domain::{Filter, Result};
+
+use crate::parser::JsonAST;
+
+mod builtins;
+pub mod domain;
+mod evaluator;
+mod lexer;
+mod parser;
+
+pub fn compile(filter: &str) -> Result<Filter> {
+    parser::parse_filter(lexer::tokenize(filter)?)
+}
+
+/// Compiles `filter` and runs it against `input`, returning every value it produces.
+pub fn query(filter: &str, input: &JsonAST) -> Result<Vec<JsonAST>> {
+    compile(filter)?.apply(input)
+}
+
+impl Filter {
+    pub fn apply(&self, input: &JsonAST) -> Result<Vec<JsonAST>> {
+        evaluator::evaluate(self, input)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        parser::{JsonAST, MAX_DEPTH, parse},
+        query::{domain::QueryError, query},
+        writer::to_json_string,
+    };
+
+    fn run(filter: &str, input: &str) -> Result<Vec<String>, QueryError> {
+        let input = parse(input).unwrap();
+        query(filter, &input).map(|values| values.iter().map(to_json_string).collect())
+    }
+
+    fn run_ok(filter: &str, input: &str) -> Vec<String> {
+        run(filter, input).unwrap_or_else(|error| panic!("{} failed: {}", filter, error))
+    }
+
+    #[test]
+    fn query_should_follow_paths() {
+        let input = r#"{"a": {"b": [10, 20, 30]}, "c d": true}"#;
+        assert_eq!(run_ok(".", "1"), vec!["1"]);
+        assert_eq!(run_ok(".a.b[1]", input), vec!["20"]);
+        assert_eq!(run_ok(".a.b[-1]", input), vec!["30"]);
+        assert_eq!(run_ok(".a.b[5]", input), vec!["null"]);
+        assert_eq!(run_ok(".a.b[1:]", input), vec!["[20,30]"]);
+        assert_eq!(run_ok(".[\"c d\"], .\"c d\"", input), vec!["true", "true"]);
+        assert_eq!(run_ok(".a.b[]", input), vec!["10", "20", "30"]);
+        assert_eq!(run_ok(".missing.deeper", input), vec!["null"]);
+        assert_eq!(run_ok("[..] | length", input), vec!["7"]);
+    }
+
+    #[test]
+    fn query_should_report_type_errors() {
+        assert_eq!(
+            run(".a", "[1]"),
+            Err(QueryError::CannotIndex("array", "\"a\"".to_string()))
+        );
+        assert_eq!(run(".[]", "1"), Err(QueryError::CannotIterate("number")));
+        assert_eq!(
+            run("foo(1)", "1"),
+            Err(QueryError::UnknownFunction("foo".to_string(), 1))
+        );
+        assert_eq!(
+            run(".a + 1", "{\"a\": \"x\"}"),
+            Err(QueryError::TypeError(
+                "string (\"x\") and number (1) cannot be added".to_string()
+            ))
+        );
+    }
+
+    #[test]
+    fn query_should_reject_results_too_large_to_build() {
+        assert!(matches!(
+            run("\"a\" * 1e20", "null"),
+            Err(QueryError::TypeError(_))
+        ));
+        assert!(matches!(
+            run("limit(1; range(1e18))", "null"),
+            Err(QueryError::TypeError(_))
+        ));
+        assert_eq!(run_ok("\"ab\" * 2", "null"), vec!["\"abab\""]);
+        assert_eq!(run_ok("(0 - 1e19) % -1, 7 % -2", "null"), vec!["0", "1"]);
+        assert_eq!(run_ok("[range(1e16; 1e16 + 4)] | length", "null"), vec![
+            "4"
+        ]);
+    }
+
+    #[test]
+    fn recursion_deeper_than_max_depth_should_fail() {
+        assert_eq!(
+            run("recurse(.)", "1"),
+            Err(QueryError::MaxDepthExceeded(MAX_DEPTH))
+        );
+        assert_eq!(
+            run("[recurse([.])] | length", "1"),
+            Err(QueryError::MaxDepthExceeded(MAX_DEPTH))
+        );
+        assert_eq!(
+            run_ok("[recurse(if . < 3 then . + 1 else empty end)]", "0"),
+            vec!["[0,1,2,3]"]
+        );
+
+        let deepest = format!("{}{}", "[".repeat(MAX_DEPTH), "]".repeat(MAX_DEPTH));
+        assert_eq!(run_ok("[..] | length", &deepest), vec![
+            MAX_DEPTH.to_string()
+        ]);
+    }
+
+    #[test]
+    fn optional_should_suppress_errors() {
+        assert_eq!(run_ok(".[]?", "1"), Vec::<String>::new());
+        assert_eq!(run_ok("[.[] | .a?]", "[{\"a\": 1}, 2]"), vec!["[1]"]);
+        assert_eq!(run_ok(".a // \"default\"", "{\"a\": null}"), vec![
+            "\"default\""
+        ]);
+        assert_eq!(run_ok("(.a | error(\"boom\")) // 2", "{}"), vec!["2"]);
+    }
+
+    #[test]
+    fn query_should_reshape_documents() {
+        let input = r#"[
+            {"name": "ada", "age": 36, "langs": ["en"]},
+            {"name": "bob", "age": 17, "langs": []},
+            {"name": "cy", "age": 51, "langs": ["en", "fr"]}
+        ]"#;
+
+        assert_eq!(
+            run_ok(
+                "map(select(.age >= 18) | {name, polyglot: (.langs | length > 1)})",
+                input
+            ),
+            vec!["[{\"name\":\"ada\",\"polyglot\":false},{\"name\":\"cy\",\"polyglot\":true}]"]
+        );
+        assert_eq!(run_ok("[.[] | .name] | join(\", \")", input), vec![
+            "\"ada, bob, cy\""
+        ]);
+        assert_eq!(run_ok("sort_by(-.age) | map(.name)", input), vec![
+            "[\"cy\",\"ada\",\"bob\"]"
+        ]);
+        assert_eq!(
+            run_ok("group_by(.langs | length) | map(length)", input),
+            vec!["[1,1,1]"]
+        );
+        assert_eq!(run_ok("{(.[0].name): .[0].age}", input), vec![
+            "{\"ada\":36}"
+        ]);
+        assert_eq!(run_ok("map(.age) | add / length | floor", input), vec![
+            "34"
+        ]);
+    }
+
+    #[test]
+    fn generators_should_produce_cartesian_products() {
+        assert_eq!(run_ok("(1, 2) + (10, 20)", "null"), vec![
+            "11", "12", "21", "22"
+        ]);
+        assert_eq!(run_ok("{a: (1, 2), b: 3}", "null"), vec![
+            "{\"a\":1,\"b\":3}",
+            "{\"a\":2,\"b\":3}"
+        ]);
+        assert_eq!(run_ok("[range(3)]", "null"), vec!["[0,1,2]"]);
+        assert_eq!(run_ok("[limit(2; .[])]", "[5, 6, 7]"), vec!["[5,6]"]);
+    }
+
+    #[test]
+    fn conditionals_should_use_jq_truthiness() {
+        let filter = "map(if . == 0 then \"zero\" elif . then \"truthy\" else \"falsy\" end)";
+        assert_eq!(run_ok(filter, "[0, 1, null, false, \"\"]"), vec![
+            "[\"zero\",\"truthy\",\"falsy\",\"falsy\",\"truthy\"]"
+        ]);
+        assert_eq!(run_ok("if . then 1 end", "false"), vec!["false"]);
+        assert_eq!(run_ok("[.[] | not]", "[null, 0]"), vec!["[true,false]"]);
+        assert_eq!(run_ok("true and (1, null)", "null"), vec!["true", "false"]);
+    }
+
+    #[test]
+    fn comparisons_should_order_values_like_jq() {
+        assert_eq!(
+            run_ok("sort", "[{}, [], \"a\", 2, true, false, null]"),
+            vec!["[null,false,true,2,\"a\",[],{}]"]
+        );
+        assert_eq!(
+            run_ok(". == {\"b\": 2, \"a\": 1}", "{\"a\": 1, \"b\": 2}"),
+            vec!["true"]
+        );
+        assert_eq!(run_ok("[1, 2] < [1, 3]", "null"), vec!["true"]);
+    }
+
+    #[test]
+    fn builtins_should_transform_objects() {
+        let input = r#"{"b": 1, "a": {"x": 1}}"#;
+        assert_eq!(run_ok("keys", input), vec!["[\"a\",\"b\"]"]);
+        assert_eq!(run_ok("keys_unsorted", input), vec!["[\"b\",\"a\"]"]);
+        assert_eq!(run_ok("has(\"a\"), has(\"z\")", input), vec![
+            "true", "false"
+        ]);
+        assert_eq!(run_ok("to_entries[0]", input), vec![
+            "{\"key\":\"b\",\"value\":1}"
+        ]);
+        assert_eq!(
+            run_ok("with_entries(select(.value | type == \"number\"))", input),
+            vec!["{\"b\":1}"]
+        );
+        assert_eq!(run_ok(". * {\"a\": {\"y\": 2}}", input), vec![
+            "{\"b\":1,\"a\":{\"x\":1,\"y\":2}}"
+        ]);
+        assert_eq!(run_ok(".a | tojson", input), vec!["\"{\\\"x\\\":1}\""]);
+    }
+
+    #[test]
+    fn builtins_should_transform_strings_and_arrays() {
+        assert_eq!(run_ok("split(\",\") | map(ascii_upcase)", "\"a,b\""), vec![
+            "[\"A\",\"B\"]"
+        ]);
+        assert_eq!(run_ok("ltrimstr(\"foo\")", "\"foobar\""), vec!["\"bar\""]);
+        assert_eq!(run_ok("[.[] | tonumber]", "[\"1.5\", 2]"), vec!["[1.5,2]"]);
+        assert_eq!(run_ok("flatten", "[1, [2, [3]]]"), vec!["[1,2,3]"]);
+        assert_eq!(run_ok("unique", "[3, 1, 3]"), vec!["[1,3]"]);
+        assert_eq!(run_ok(". - [1]", "[1, 2, 1]"), vec!["[2]"]);
+        assert_eq!(run_ok("contains([\"b\"])", "[\"abc\"]"), vec!["true"]);
+        assert_eq!(run_ok("min, max", "[]"), vec!["null", "null"]);
+    }
+
+    #[test]
+    fn parsed_values_should_round_trip_through_fromjson() {
+        let input = JsonAST::String("{\"a\":[1,2]}".to_string());
+        let values = query("fromjson | .a[1]", &input).unwrap();
+        assert_eq!(values, vec![JsonAST::Number(2.0)]);
+    }
+}
@@ -0,0 +1,398 @@
+use std::{iter::Peekable, vec::IntoIter};
+
+use crate::{
+    parser::JsonAST,
+    query::domain::{Filter, Operator, QueryError, QueryToken, Result},
+};
+
+type Tokens = Peekable<IntoIter<QueryToken>>;
+
+pub fn parse_filter(tokens: Vec<QueryToken>) -> Result<Filter> {
+    let mut tokens = tokens.into_iter().peekable();
+    let filter = parse_pipe(&mut tokens, false)?;
+    match tokens.next() {
+        None => Ok(filter),
+        Some(token) => Err(QueryError::UnexpectedToken(token)),
+    }
+}
+
+fn next_token(tokens: &mut Tokens) -> Result<QueryToken> {
+    tokens.next().ok_or(QueryError::UnexpectedEnd)
+}
+
+fn expect(tokens: &mut Tokens, expected: QueryToken) -> Result<()> {
+    match next_token(tokens)? {
+        token if token == expected => Ok(()),
+        token => Err(QueryError::UnexpectedToken(token)),
+    }
+}
+
+fn expect_keyword(tokens: &mut Tokens, keyword: &str) -> Result<()> {
+    expect(tokens, QueryToken::Identifier(keyword.to_string()))
+}
+
+fn next_is(tokens: &mut Tokens, expected: &QueryToken) -> bool {
+    tokens.next_if(|token| token == expected).is_some()
+}
+
+fn next_operator(tokens: &mut Tokens, operators: &[Operator]) -> Option<Operator> {
+    match tokens.peek() {
+        Some(QueryToken::Operator(operator)) if operators.contains(operator) => {
+            let operator = *operator;
+            tokens.next();
+            Some(operator)
+        }
+        _ => None,
+    }
+}
+
+fn binary(operator: Operator, lhs: Filter, rhs: Filter) -> Filter {
+    Filter::Binary(operator, Box::new(lhs), Box::new(rhs))
+}
+
+fn key(name: String) -> Filter {
+    Filter::Literal(JsonAST::String(name))
+}
+
+/// Pipes bind loosest and associate to the right. Object values can't contain a bare comma, as
+/// it separates the object entries, hence `no_comma`.
+fn parse_pipe(tokens: &mut Tokens, no_comma: bool) -> Result<Filter> {
+    let lhs = parse_comma(tokens, no_comma)?;
+    if next_is(tokens, &QueryToken::Pipe) {
+        let rhs = parse_pipe(tokens, no_comma)?;
+        return Ok(Filter::Pipe(Box::new(lhs), Box::new(rhs)));
+    }
+    Ok(lhs)
+}
+
+fn parse_comma(tokens: &mut Tokens, no_comma: bool) -> Result<Filter> {
+    let mut lhs = parse_alternative(tokens)?;
+    while !no_comma && next_is(tokens, &QueryToken::Comma) {
+        let rhs = parse_alternative(tokens)?;
+        lhs = Filter::Comma(Box::new(lhs), Box::new(rhs));
+    }
+    Ok(lhs)
+}
+
+fn parse_alternative(tokens: &mut Tokens) -> Result<Filter> {
+    let lhs = parse_or(tokens)?;
+    if next_is(tokens, &QueryToken::Alternative) {
+        let rhs = parse_alternative(tokens)?;
+        return Ok(Filter::Alternative(Box::new(lhs), Box::new(rhs)));
+    }
+    Ok(lhs)
+}
+
+fn parse_or(tokens: &mut Tokens) -> Result<Filter> {
+    let mut lhs = parse_and(tokens)?;
+    while let Some(operator) = next_operator(tokens, &[Operator::Or]) {
+        lhs = binary(operator, lhs, parse_and(tokens)?);
+    }
+    Ok(lhs)
+}
+
+fn parse_and(tokens: &mut Tokens) -> Result<Filter> {
+    let mut lhs = parse_comparison(tokens)?;
+    while let Some(operator) = next_operator(tokens, &[Operator::And]) {
+        lhs = binary(operator, lhs, parse_comparison(tokens)?);
+    }
+    Ok(lhs)
+}
+
+/// Comparisons don't associate, `1 < 2 < 3` is a syntax error as it is in jq.
+fn parse_comparison(tokens: &mut Tokens) -> Result<Filter> {
+    const COMPARISONS: [Operator; 6] = [
+        Operator::Equal,
+        Operator::NotEqual,
+        Operator::Less,
+        Operator::LessOrEqual,
+        Operator::Greater,
+        Operator::GreaterOrEqual,
+    ];
+    let lhs = parse_additive(tokens)?;
+    if let Some(operator) = next_operator(tokens, &COMPARISONS) {
+        return Ok(binary(operator, lhs, parse_additive(tokens)?));
+    }
+    Ok(lhs)
+}
+
+fn parse_additive(tokens: &mut Tokens) -> Result<Filter> {
+    let mut lhs = parse_multiplicative(tokens)?;
+    while let Some(operator) = next_operator(tokens, &[Operator::Add, Operator::Subtract]) {
+        lhs = binary(operator, lhs, parse_multiplicative(tokens)?);
+    }
+    Ok(lhs)
+}
+
+fn parse_multiplicative(tokens: &mut Tokens) -> Result<Filter> {
+    const OPERATORS: [Operator; 3] = [Operator::Multiply, Operator::Divide, Operator::Modulo];
+    let mut lhs = parse_unary(tokens)?;
+    while let Some(operator) = next_operator(tokens, &OPERATORS) {
+        lhs = binary(operator, lhs, parse_unary(tokens)?);
+    }
+    Ok(lhs)
+}
+
+fn parse_unary(tokens: &mut Tokens) -> Result<Filter> {
+    if next_operator(tokens, &[Operator::Subtract]).is_some() {
+        return Ok(Filter::Negate(Box::new(parse_unary(tokens)?)));
+    }
+    parse_postfix(tokens)
+}
+
+fn parse_postfix(tokens: &mut Tokens) -> Result<Filter> {
+    let mut term = parse_primary(tokens)?;
+    loop {
+        term = match tokens.peek() {
+            Some(QueryToken::Field(_)) => match tokens.next() {
+                Some(QueryToken::Field(name)) => Filter::Index(Box::new(term), Box::new(key(name))),
+                _ => unreachable!("Peeked a field token"),
+            },
+            Some(QueryToken::Dot) => {
+                tokens.next();
+                match next_token(tokens)? {
+                    QueryToken::String(name) => Filter::Index(Box::new(term), Box::new(key(name))),
+                    QueryToken::LeftBracket => parse_brackets(tokens, term)?,
+                    token => return Err(QueryError::UnexpectedToken(token)),
+                }
+            }
+            Some(QueryToken::LeftBracket) => {
+                tokens.next();
+                parse_brackets(tokens, term)?
+            }
+            Some(QueryToken::Question) => {
+                tokens.next();
+                Filter::Optional(Box::new(term))
+            }
+            _ => return Ok(term),
+        };
+    }
+}
+
+/// Parses what follows `term[`: an iteration, an index or a slice.
+fn parse_brackets(tokens: &mut Tokens, term: Filter) -> Result<Filter> {
+    let term = Box::new(term);
+    if next_is(tokens, &QueryToken::RightBracket) {
+        return Ok(Filter::Iterate(term));
+    }
+    if next_is(tokens, &QueryToken::Colon) {
+        let to = parse_pipe(tokens, false)?;
+        expect(tokens, QueryToken::RightBracket)?;
+        return Ok(Filter::Slice(term, None, Some(Box::new(to))));
+    }
+
+    let index = parse_pipe(tokens, false)?;
+    if next_is(tokens, &QueryToken::Colon) {
+        let to = match tokens.peek() {
+            Some(QueryToken::RightBracket) => None,
+            _ => Some(Box::new(parse_pipe(tokens, false)?)),
+        };
+        expect(tokens, QueryToken::RightBracket)?;
+        return Ok(Filter::Slice(term, Some(Box::new(index)), to));
+    }
+    expect(tokens, QueryToken::RightBracket)?;
+    Ok(Filter::Index(term, Box::new(index)))
+}
+
+fn parse_primary(tokens: &mut Tokens) -> Result<Filter> {
+    match next_token(tokens)? {
+        QueryToken::Dot => match tokens.peek() {
+            Some(QueryToken::String(_)) => match tokens.next() {
+                Some(QueryToken::String(name)) => Ok(Filter::Index(
+                    Box::new(Filter::Identity),
+                    Box::new(key(name)),
+                )),
+                _ => unreachable!("Peeked a string token"),
+            },
+            _ => Ok(Filter::Identity),
+        },
+        QueryToken::Field(name) => Ok(Filter::Index(
+            Box::new(Filter::Identity),
+            Box::new(key(name)),
+        )),
+        QueryToken::RecurseDefault => Ok(Filter::RecurseDefault),
+        QueryToken::Number(n) => Ok(Filter::Literal(JsonAST::Number(n))),
+        QueryToken::String(s) => Ok(Filter::Literal(JsonAST::String(s))),
+        QueryToken::LeftParen => {
+            let filter = parse_pipe(tokens, false)?;
+            expect(tokens, QueryToken::RightParen)?;
+            Ok(filter)
+        }
+        QueryToken::LeftBracket => {
+            if next_is(tokens, &QueryToken::RightBracket) {
+                return Ok(Filter::ArrayConstruction(None));
+            }
+            let filter = parse_pipe(tokens, false)?;
+            expect(tokens, QueryToken::RightBracket)?;
+            Ok(Filter::ArrayConstruction(Some(Box::new(filter))))
+        }
+        QueryToken::LeftBrace => parse_object(tokens),
+        QueryToken::Identifier(name) => match name.as_str() {
+            "true" => Ok(Filter::Literal(JsonAST::Boolean(true))),
+            "false" => Ok(Filter::Literal(JsonAST::Boolean(false))),
+            "null" => Ok(Filter::Literal(JsonAST::Null)),
+            "if" => parse_if(tokens),
+            "then" | "elif" | "else" | "end" => {
+                Err(QueryError::UnexpectedToken(QueryToken::Identifier(name)))
+            }
+            _ => parse_call(tokens, name),
+        },
+        token => Err(QueryError::UnexpectedToken(token)),
+    }
+}
+
+fn parse_call(tokens: &mut Tokens, name: String) -> Result<Filter> {
+    let mut arguments = Vec::new();
+    if next_is(tokens, &QueryToken::LeftParen) {
+        loop {
+            arguments.push(parse_pipe(tokens, false)?);
+            match next_token(tokens)? {
+                QueryToken::Semicolon => continue,
+                QueryToken::RightParen => break,
+                token => return Err(QueryError::UnexpectedToken(token)),
+            }
+        }
+    }
+    Ok(Filter::Call(name, arguments))
+}
+
+/// Parses what follows `if`. A missing `else` branch behaves as `else . end`.
+fn parse_if(tokens: &mut Tokens) -> Result<Filter> {
+    let condition = parse_pipe(tokens, false)?;
+    expect_keyword(tokens, "then")?;
+    let then = parse_pipe(tokens, false)?;
+    let otherwise = match next_token(tokens)? {
+        QueryToken::Identifier(keyword) if keyword == "elif" => parse_if(tokens)?,
+        QueryToken::Identifier(keyword) if keyword == "else" => {
+            let otherwise = parse_pipe(tokens, false)?;
+            expect_keyword(tokens, "end")?;
+            otherwise
+        }
+        QueryToken::Identifier(keyword) if keyword == "end" => Filter::Identity,
+        token => return Err(QueryError::UnexpectedToken(token)),
+    };
+    Ok(Filter::IfThenElse(
+        Box::new(condition),
+        Box::new(then),
+        Box::new(otherwise),
+    ))
+}
+
+/// Parses what follows `{`. Entries are `key: value`, `"key": value`, `(filter): value` or the
+/// `key` shorthand for `key: .key`.
+fn parse_object(tokens: &mut Tokens) -> Result<Filter> {
+    let mut entries = Vec::new();
+    if next_is(tokens, &QueryToken::RightBrace) {
+        return Ok(Filter::ObjectConstruction(entries));
+    }
+
+    loop {
+        let (name, shorthand) = match next_token(tokens)? {
+            QueryToken::Identifier(name) | QueryToken::String(name) => {
+                (key(name.clone()), Some(name))
+            }
+            QueryToken::Operator(operator @ (Operator::And | Operator::Or)) => {
+                (key(operator.to_string()), Some(operator.to_string()))
+            }
+            QueryToken::LeftParen => {
+                let name = parse_pipe(tokens, false)?;
+                expect(tokens, QueryToken::RightParen)?;
+                (name, None)
+            }
+            token => return Err(QueryError::UnexpectedToken(token)),
+        };
+
+        let value = match (next_is(tokens, &QueryToken::Colon), shorthand) {
+            (true, _) => parse_pipe(tokens, true)?,
+            (false, Some(shorthand)) => {
+                Filter::Index(Box::new(Filter::Identity), Box::new(key(shorthand)))
+            }
+            (false, None) => {
+                return Err(tokens
+                    .next()
+                    .map_or(QueryError::UnexpectedEnd, QueryError::UnexpectedToken));
+            }
+        };
+        entries.push((name, value));
+
+        match next_token(tokens)? {
+            QueryToken::Comma => continue,
+            QueryToken::RightBrace => return Ok(Filter::ObjectConstruction(entries)),
+            token => return Err(QueryError::UnexpectedToken(token)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        parser::JsonAST,
+        query::{
+            domain::{Filter, Operator, QueryError, QueryToken},
+            lexer::tokenize,
+            parser::parse_filter,
+        },
+    };
+
+    fn compile(input: &str) -> Result<Filter, QueryError> {
+        parse_filter(tokenize(input).unwrap())
+    }
+
+    fn field(name: &str) -> Filter {
+        Filter::Index(
+            Box::new(Filter::Identity),
+            Box::new(Filter::Literal(JsonAST::String(name.to_string()))),
+        )
+    }
+
+    #[test]
+    fn parse_filter_should_bind_pipe_looser_than_comma() {
+        assert_eq!(
+            compile(".a, .b | .c").unwrap(),
+            Filter::Pipe(
+                Box::new(Filter::Comma(Box::new(field("a")), Box::new(field("b")))),
+                Box::new(field("c"))
+            )
+        );
+    }
+
+    #[test]
+    fn parse_filter_should_respect_arithmetic_precedence() {
+        let one = || Box::new(Filter::Literal(JsonAST::Number(1.0)));
+        assert_eq!(
+            compile("1 + 1 * 1").unwrap(),
+            Filter::Binary(
+                Operator::Add,
+                one(),
+                Box::new(Filter::Binary(Operator::Multiply, one(), one()))
+            )
+        );
+    }
+
+    #[test]
+    fn parse_filter_should_expand_object_shorthand() {
+        assert_eq!(
+            compile("{a, \"b\": 1}").unwrap(),
+            Filter::ObjectConstruction(vec![
+                (
+                    Filter::Literal(JsonAST::String("a".to_string())),
+                    field("a")
+                ),
+                (
+                    Filter::Literal(JsonAST::String("b".to_string())),
+                    Filter::Literal(JsonAST::Number(1.0))
+                ),
+            ])
+        );
+    }
+
+    #[test]
+    fn parse_filter_should_fail_on_dangling_tokens() {
+        assert_eq!(
+            compile(".a )"),
+            Err(QueryError::UnexpectedToken(QueryToken::RightParen))
+        );
+        assert_eq!(compile(".a |"), Err(QueryError::UnexpectedEnd));
+        assert_eq!(compile("if . then 1"), Err(QueryError::UnexpectedEnd));
+    }
+}
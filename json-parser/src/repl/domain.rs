@@ -0,0 +1,23 @@
+use crate::{parser::ParserError, query::domain::QueryError};
+
+#[derive(Debug, thiserror::Error, PartialEq)]
+pub enum ReplError {
+    #[error("Unknown command {0}, try :help")]
+    UnknownCommand(String),
+    #[error("Missing argument, usage: {0}")]
+    MissingArgument(&'static str),
+    #[error("No document yet, enter some json or :load a file")]
+    NoDocument,
+    #[error("Nothing found at pointer `{0}`")]
+    PointerNotFound(String),
+    #[error("Expected a depth or `off` but got {0}")]
+    InvalidCollapseDepth(String),
+    #[error("Unable to read {0}: {1}")]
+    IoError(String, String),
+    #[error(transparent)]
+    ParsingError(#[from] ParserError),
+    #[error(transparent)]
+    QueryError(#[from] QueryError),
+}
+
+pub type Result<T> = std::result::Result<T, ReplError>;
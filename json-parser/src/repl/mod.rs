@@ -0,0 +1,257 @@
+//! The interactive `json-parser` session.
+//!
+//! Any line not starting with `:` is parsed as json, becomes the current document and is printed
+//! back highlighted. Commands then inspect the current document, see [`HELP`]. History is kept in
+//! the file named by `JSON_PARSER_HISTORY`, or `~/.json_parser_history` by default.
+
+use std::{
+    env,
+    fs::File,
+    io::{IsTerminal, stdout},
+    path::PathBuf,
+};
+
+use domain::{ReplError, Result};
+use rustyline::{DefaultEditor, error::ReadlineError};
+use stats::Stats;
+
+use crate::{
+    parser::{JsonAST, parse, parse_reader},
+    query::query,
+    writer::{PrettyOptions, to_json_string_with},
+};
+
+pub mod domain;
+pub mod stats;
+
+pub const HELP: &str = "\
+<json>              Parse json and make it the current document
+:load <file>        Parse a file and make it the current document
+:pointer <pointer>  Show the value at an RFC 6901 JSON Pointer, e.g. /users/0/name
+:path <filter>      Show the values a jq-style path selects, e.g. .users[0].name
+:stats              Show the depth, key counts and sizes of the current document
+:collapse <n|off>   Collapse arrays and objects nested n levels deep
+:help               Show this message
+:quit               Leave the session";
+
+const PROMPT: &str = "json-parser> ";
+
+pub struct Repl {
+    document: Option<JsonAST>,
+    options: PrettyOptions,
+}
+
+impl Repl {
+    pub fn new(colors: bool) -> Self {
+        Repl {
+            document: None,
+            options: PrettyOptions {
+                colors,
+                collapse_depth: None,
+            },
+        }
+    }
+
+    /// Evaluates a line of input, returning what should be printed.
+    pub fn eval(&mut self, line: &str) -> Result<String> {
+        let line = line.trim();
+        let Some(command) = line.strip_prefix(':') else {
+            let document = parse(line)?;
+            let output = self.render(&document);
+            self.document = Some(document);
+            return Ok(output);
+        };
+
+        let (command, argument) = match command.split_once(char::is_whitespace) {
+            Some((command, argument)) => (command, argument.trim()),
+            None => (command, ""),
+        };
+        match command {
+            "help" => Ok(HELP.to_string()),
+            "load" => {
+                let path = required(argument, ":load <file>")?;
+                let file = File::open(path)
+                    .map_err(|error| ReplError::IoError(path.to_string(), error.to_string()))?;
+                let document = parse_reader(file)?;
+                let output = self.render(&document);
+                self.document = Some(document);
+                Ok(output)
+            }
+            "pointer" => {
+                let pointer = required(argument, ":pointer <pointer>")?;
+                let value = self
+                    .document()?
+                    .pointer(pointer)
+                    .ok_or_else(|| ReplError::PointerNotFound(pointer.to_string()))?;
+                Ok(self.render(value))
+            }
+            "path" => {
+                let filter = required(argument, ":path <filter>")?;
+                let values = query(filter, self.document()?)?;
+                Ok(values
+                    .iter()
+                    .map(|value| self.render(value))
+                    .collect::<Vec<_>>()
+                    .join("\n"))
+            }
+            "stats" => Ok(Stats::of(self.document()?).to_string()),
+            "collapse" => {
+                self.options.collapse_depth = match required(argument, ":collapse <n|off>")? {
+                    "off" => None,
+                    depth => Some(
+                        depth
+                            .parse()
+                            .map_err(|_| ReplError::InvalidCollapseDepth(depth.to_string()))?,
+                    ),
+                };
+                match &self.document {
+                    Some(document) => Ok(self.render(document)),
+                    None => Ok(String::new()),
+                }
+            }
+            command => Err(ReplError::UnknownCommand(format!(":{}", command))),
+        }
+    }
+
+    fn document(&self) -> Result<&JsonAST> {
+        self.document.as_ref().ok_or(ReplError::NoDocument)
+    }
+
+    fn render(&self, value: &JsonAST) -> String {
+        to_json_string_with(value, &self.options)
+    }
+}
+
+fn required<'a>(argument: &'a str, usage: &'static str) -> Result<&'a str> {
+    if argument.is_empty() {
+        Err(ReplError::MissingArgument(usage))
+    } else {
+        Ok(argument)
+    }
+}
+
+fn history_file() -> Option<PathBuf> {
+    env::var_os("JSON_PARSER_HISTORY")
+        .map(PathBuf::from)
+        .or_else(|| env::home_dir().map(|home| home.join(".json_parser_history")))
+}
+
+/// Runs the session until `:quit` or the end of the input. Colors are used when stdout is a
+/// terminal and `NO_COLOR` isn't set.
+pub fn run() -> anyhow::Result<()> {
+    let colors = stdout().is_terminal() && env::var_os("NO_COLOR").is_none();
+    let mut repl = Repl::new(colors);
+    let mut editor = DefaultEditor::new()?;
+    let history = history_file();
+    if let Some(history) = &history {
+        // There is no history to load on the first session
+        let _ = editor.load_history(history);
+    }
+
+    loop {
+        let line = match editor.readline(PROMPT) {
+            Ok(line) => line,
+            Err(ReadlineError::Interrupted) => continue,
+            Err(ReadlineError::Eof) => break,
+            Err(error) => return Err(error.into()),
+        };
+        let line = line.trim();
+        if line.is_empty() {
+            continue;
+        }
+        editor.add_history_entry(line)?;
+        if line == ":quit" || line == ":q" {
+            break;
+        }
+
+        match repl.eval(line) {
+            Ok(output) if output.is_empty() => {}
+            Ok(output) => println!("{}", output),
+            Err(error) => eprintln!("==> Error: {}", error),
+        }
+    }
+
+    if let Some(history) = &history {
+        editor.save_history(history)?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{env, fs};
+
+    use crate::repl::{Repl, domain::ReplError};
+
+    fn eval(repl: &mut Repl, line: &str) -> String {
+        repl.eval(line)
+            .unwrap_or_else(|error| panic!("{} failed: {}", line, error))
+    }
+
+    #[test]
+    fn eval_should_pretty_print_parsed_json() {
+        let mut repl = Repl::new(false);
+        assert_eq!(
+            eval(&mut repl, " [1, {\"a\": null}] "),
+            "[\n  1,\n  {\n    \"a\": null\n  }\n]"
+        );
+        assert!(matches!(repl.eval("[1,"), Err(ReplError::ParsingError(_))));
+    }
+
+    #[test]
+    fn commands_should_inspect_the_current_document() {
+        let mut repl = Repl::new(false);
+        assert_eq!(repl.eval(":stats"), Err(ReplError::NoDocument));
+
+        eval(
+            &mut repl,
+            r#"{"users": [{"name": "ada", "tags": ["x"]}, {"name": "bob"}]}"#,
+        );
+        assert_eq!(eval(&mut repl, ":pointer /users/1/name"), "\"bob\"");
+        assert_eq!(
+            repl.eval(":pointer /users/2"),
+            Err(ReplError::PointerNotFound("/users/2".to_string()))
+        );
+        assert_eq!(eval(&mut repl, ":path .users[].name"), "\"ada\"\n\"bob\"");
+        assert!(eval(&mut repl, ":stats").starts_with("depth:          4\n"));
+        assert_eq!(
+            eval(&mut repl, ":collapse 2"),
+            "{\n  \"users\": [\n    {…2 keys},\n    {…1 key}\n  ]\n}"
+        );
+        assert_eq!(
+            repl.eval(":collapse deep"),
+            Err(ReplError::InvalidCollapseDepth("deep".to_string()))
+        );
+    }
+
+    #[test]
+    fn commands_should_report_misuse() {
+        let mut repl = Repl::new(false);
+        assert_eq!(
+            repl.eval(":frobnicate"),
+            Err(ReplError::UnknownCommand(":frobnicate".to_string()))
+        );
+        assert_eq!(
+            repl.eval(":pointer"),
+            Err(ReplError::MissingArgument(":pointer <pointer>"))
+        );
+        assert!(matches!(
+            repl.eval(":load /nonexistent/file.json"),
+            Err(ReplError::IoError(..))
+        ));
+    }
+
+    #[test]
+    fn load_should_replace_the_current_document() {
+        let path = env::temp_dir().join(format!("json-parser-repl-{}.json", std::process::id()));
+        fs::write(&path, "{\"loaded\": true}").unwrap();
+
+        let mut repl = Repl::new(false);
+        eval(&mut repl, "[1]");
+        let output = eval(&mut repl, &format!(":load {}", path.display()));
+        fs::remove_file(&path).unwrap();
+
+        assert_eq!(output, "{\n  \"loaded\": true\n}");
+        assert_eq!(eval(&mut repl, ":pointer /loaded"), "true");
+    }
+}
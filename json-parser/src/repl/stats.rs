@@ -0,0 +1,106 @@
+use std::fmt::{Display, Formatter};
+
+use crate::{parser::JsonAST, writer::to_json_string};
+
+/// Structural figures about a document, as shown by `:stats`.
+#[derive(Debug, Default, PartialEq)]
+pub struct Stats {
+    /// Levels of nested arrays and objects, 0 for a scalar.
+    pub depth: usize,
+    pub objects: usize,
+    pub arrays: usize,
+    pub strings: usize,
+    pub numbers: usize,
+    pub booleans: usize,
+    pub nulls: usize,
+    /// Keys across every object.
+    pub keys: usize,
+    /// Keys in the object with the most of them.
+    pub largest_object: usize,
+    /// Elements in the array with the most of them.
+    pub largest_array: usize,
+    /// Size of the document written as compact json.
+    pub bytes: usize,
+}
+
+impl Stats {
+    pub fn of(ast: &JsonAST) -> Stats {
+        let mut stats = Stats {
+            bytes: to_json_string(ast).len(),
+            ..Stats::default()
+        };
+        stats.depth = stats.visit(ast);
+        stats
+    }
+
+    /// Counts `ast` and its descendants, returning its depth.
+    fn visit(&mut self, ast: &JsonAST) -> usize {
+        match ast {
+            JsonAST::Null => self.nulls += 1,
+            JsonAST::Boolean(_) => self.booleans += 1,
+            JsonAST::Number(_) => self.numbers += 1,
+            JsonAST::String(_) => self.strings += 1,
+            JsonAST::Array(elements) => {
+                self.arrays += 1;
+                self.largest_array = self.largest_array.max(elements.len());
+                return 1 + elements.iter().map(|e| self.visit(e)).max().unwrap_or(0);
+            }
+            JsonAST::Object(members) => {
+                self.objects += 1;
+                self.keys += members.len();
+                self.largest_object = self.largest_object.max(members.len());
+                return 1 + members
+                    .iter()
+                    .map(|(_, v)| self.visit(v))
+                    .max()
+                    .unwrap_or(0);
+            }
+        }
+        0
+    }
+}
+
+impl Display for Stats {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "depth:          {}", self.depth)?;
+        writeln!(f, "size:           {} bytes", self.bytes)?;
+        writeln!(
+            f,
+            "objects:        {} ({} keys, largest has {})",
+            self.objects, self.keys, self.largest_object
+        )?;
+        writeln!(
+            f,
+            "arrays:         {} (largest has {} elements)",
+            self.arrays, self.largest_array
+        )?;
+        writeln!(f, "strings:        {}", self.strings)?;
+        writeln!(f, "numbers:        {}", self.numbers)?;
+        writeln!(f, "booleans:       {}", self.booleans)?;
+        write!(f, "nulls:          {}", self.nulls)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{parser::parse, repl::stats::Stats};
+
+    #[test]
+    fn stats_should_count_every_value() {
+        let ast = parse(r#"{"a": [1, 2, {"b": null, "c": "x"}], "d": true, "e": []}"#).unwrap();
+        assert_eq!(Stats::of(&ast), Stats {
+            depth: 3,
+            objects: 2,
+            arrays: 2,
+            strings: 1,
+            numbers: 2,
+            booleans: 1,
+            nulls: 1,
+            keys: 5,
+            largest_object: 3,
+            largest_array: 3,
+            bytes: 46,
+        });
+        assert_eq!(Stats::of(&parse("1").unwrap()).depth, 0);
+    }
+}
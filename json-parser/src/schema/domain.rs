@@ -0,0 +1,36 @@
+/// The type shape inferred from one or more json samples.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Shape {
+    /// Nothing is known about the value, e.g. the elements of an array that was always empty.
+    Unknown,
+    Null,
+    Boolean,
+    /// A number without a fractional part that fits in an `i64`.
+    Integer,
+    Number,
+    String,
+    Array(Box<Shape>),
+    Object(Vec<Field>),
+    /// A value that was `null` in some samples and had the inner shape in the others.
+    Nullable(Box<Shape>),
+    /// A value seen with several incompatible shapes, never holding `Null` or `Nullable`.
+    Union(Vec<Shape>),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Field {
+    pub name: String,
+    pub shape: Shape,
+    /// The field was missing from some of the samples.
+    pub optional: bool,
+}
+
+impl Field {
+    pub fn new(name: &str, shape: Shape, optional: bool) -> Self {
+        Field {
+            name: name.to_string(),
+            shape,
+            optional,
+        }
+    }
+}
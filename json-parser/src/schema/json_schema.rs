@@ -0,0 +1,116 @@
+//! Exports a [`Shape`] as a [JSON Schema](https://json-schema.org/draft/2020-12) document.
+
+use crate::{
+    parser::JsonAST,
+    schema::domain::{Field, Shape},
+};
+
+pub const DRAFT: &str = "https://json-schema.org/draft/2020-12/schema";
+
+pub fn to_json_schema(shape: &Shape) -> JsonAST {
+    let mut schema = vec![("$schema".to_string(), string(DRAFT))];
+    match schema_for(shape) {
+        JsonAST::Object(members) => schema.extend(members),
+        _ => unreachable!("Schemas are always objects"),
+    }
+    JsonAST::Object(schema)
+}
+
+fn string(s: &str) -> JsonAST {
+    JsonAST::String(s.to_string())
+}
+
+fn typed(name: &str) -> JsonAST {
+    JsonAST::Object(vec![("type".to_string(), string(name))])
+}
+
+fn schema_for(shape: &Shape) -> JsonAST {
+    match shape {
+        Shape::Unknown => JsonAST::Object(Vec::new()),
+        Shape::Null => typed("null"),
+        Shape::Boolean => typed("boolean"),
+        Shape::Integer => typed("integer"),
+        Shape::Number => typed("number"),
+        Shape::String => typed("string"),
+        Shape::Array(elements) => {
+            let mut schema = vec![("type".to_string(), string("array"))];
+            if **elements != Shape::Unknown {
+                schema.push(("items".to_string(), schema_for(elements)));
+            }
+            JsonAST::Object(schema)
+        }
+        Shape::Object(fields) => object_schema(fields),
+        Shape::Nullable(inner) => match schema_for(inner) {
+            // A plain `{"type": "x"}` becomes `{"type": ["x", "null"]}`, anything else an `anyOf`
+            JsonAST::Object(members) if members.len() == 1 && members[0].0 == "type" => {
+                JsonAST::Object(vec![(
+                    "type".to_string(),
+                    JsonAST::Array(vec![members[0].1.clone(), string("null")]),
+                )])
+            }
+            schema => any_of(vec![schema, typed("null")]),
+        },
+        Shape::Union(members) => any_of(members.iter().map(schema_for).collect()),
+    }
+}
+
+fn any_of(schemas: Vec<JsonAST>) -> JsonAST {
+    JsonAST::Object(vec![("anyOf".to_string(), JsonAST::Array(schemas))])
+}
+
+fn object_schema(fields: &[Field]) -> JsonAST {
+    let properties = fields
+        .iter()
+        .map(|field| (field.name.clone(), schema_for(&field.shape)))
+        .collect();
+    let required: Vec<JsonAST> = fields
+        .iter()
+        .filter(|field| !field.optional)
+        .map(|field| string(&field.name))
+        .collect();
+
+    let mut schema = vec![
+        ("type".to_string(), string("object")),
+        ("properties".to_string(), JsonAST::Object(properties)),
+    ];
+    if !required.is_empty() {
+        schema.push(("required".to_string(), JsonAST::Array(required)));
+    }
+    JsonAST::Object(schema)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        parser::parse,
+        schema::{infer, json_schema::to_json_schema},
+        writer::to_json_string,
+    };
+
+    #[test]
+    fn to_json_schema_should_describe_the_inferred_shape() {
+        let samples = vec![
+            parse("{\"id\": 1, \"tags\": [\"a\"], \"score\": null, \"meta\": {\"v\": 1}}").unwrap(),
+            parse("{\"id\": 2, \"tags\": [], \"score\": 1.5, \"extra\": [1, \"x\"]}").unwrap(),
+        ];
+
+        assert_eq!(
+            to_json_string(&to_json_schema(&infer(&samples))),
+            "{\"$schema\":\"https://json-schema.org/draft/2020-12/schema\",\"type\":\"object\",\
+             \"properties\":{\"id\":{\"type\":\"integer\"},\
+             \"tags\":{\"type\":\"array\",\"items\":{\"type\":\"string\"}},\
+             \"score\":{\"type\":[\"number\",\"null\"]},\
+             \"meta\":{\"type\":\"object\",\"properties\":{\"v\":{\"type\":\"integer\"}},\"required\":[\"v\"]},\
+             \"extra\":{\"type\":\"array\",\"items\":{\"anyOf\":[{\"type\":\"integer\"},{\"type\":\"string\"}]}}},\
+             \"required\":[\"id\",\"tags\",\"score\"]}"
+        );
+    }
+
+    #[test]
+    fn to_json_schema_should_accept_anything_for_unknown_shapes() {
+        assert_eq!(
+            to_json_string(&to_json_schema(&infer(&[]))),
+            "{\"$schema\":\"https://json-schema.org/draft/2020-12/schema\"}"
+        );
+    }
+}
@@ -0,0 +1,193 @@
+//! Infers a unified [`Shape`] from json samples, to generate Rust types or a JSON Schema from it.
+//!
+//! Samples are merged pairwise: objects keep every field seen, flagging as optional the ones
+//! missing from some samples, `null` makes a shape nullable, integers widen to numbers and any
+//! other mismatch becomes a union.
+
+use domain::{Field, Shape};
+
+use crate::parser::JsonAST;
+
+pub mod domain;
+pub mod json_schema;
+pub mod rust;
+
+/// Infers the shape covering every sample. No samples yields [`Shape::Unknown`].
+pub fn infer(samples: &[JsonAST]) -> Shape {
+    samples
+        .iter()
+        .map(Shape::from_sample)
+        .fold(Shape::Unknown, Shape::merge)
+}
+
+impl Shape {
+    pub fn from_sample(sample: &JsonAST) -> Shape {
+        match sample {
+            JsonAST::Null => Shape::Null,
+            JsonAST::Boolean(_) => Shape::Boolean,
+            JsonAST::Number(n) if is_integer(*n) => Shape::Integer,
+            JsonAST::Number(_) => Shape::Number,
+            JsonAST::String(_) => Shape::String,
+            JsonAST::Array(elements) => Shape::Array(Box::new(infer(elements))),
+            JsonAST::Object(members) => {
+                let mut fields: Vec<Field> = Vec::new();
+                for (name, value) in members {
+                    let shape = Shape::from_sample(value);
+                    match fields.iter_mut().find(|field| field.name == *name) {
+                        Some(field) => {
+                            field.shape =
+                                std::mem::replace(&mut field.shape, Shape::Unknown).merge(shape)
+                        }
+                        None => fields.push(Field::new(name, shape, false)),
+                    }
+                }
+                Shape::Object(fields)
+            }
+        }
+    }
+
+    /// Combines two shapes into the narrowest shape covering both.
+    pub fn merge(self, other: Shape) -> Shape {
+        match (self, other) {
+            (Shape::Unknown, shape) | (shape, Shape::Unknown) => shape,
+            (Shape::Null, Shape::Null) => Shape::Null,
+            (Shape::Null, shape) | (shape, Shape::Null) => shape.into_nullable(),
+            (Shape::Nullable(a), b) | (b, Shape::Nullable(a)) => a.merge(b).into_nullable(),
+            (Shape::Boolean, Shape::Boolean) => Shape::Boolean,
+            (Shape::Integer, Shape::Integer) => Shape::Integer,
+            (Shape::Integer | Shape::Number, Shape::Integer | Shape::Number) => Shape::Number,
+            (Shape::String, Shape::String) => Shape::String,
+            (Shape::Array(a), Shape::Array(b)) => Shape::Array(Box::new(a.merge(*b))),
+            (Shape::Object(a), Shape::Object(b)) => Shape::Object(merge_fields(a, b)),
+            (Shape::Union(members), Shape::Union(others)) => {
+                others.into_iter().fold(Shape::Union(members), Shape::merge)
+            }
+            (Shape::Union(mut members), shape) | (shape, Shape::Union(mut members)) => {
+                match members
+                    .iter()
+                    .position(|member| member.kind() == shape.kind())
+                {
+                    Some(i) => {
+                        let member = std::mem::replace(&mut members[i], Shape::Unknown);
+                        members[i] = member.merge(shape);
+                    }
+                    None => members.push(shape),
+                }
+                Shape::Union(members)
+            }
+            (a, b) => Shape::Union(vec![a, b]),
+        }
+    }
+
+    fn into_nullable(self) -> Shape {
+        match self {
+            Shape::Null | Shape::Nullable(_) => self,
+            shape => Shape::Nullable(Box::new(shape)),
+        }
+    }
+
+    /// Shapes of the same kind merge together rather than forming a union.
+    fn kind(&self) -> &'static str {
+        match self {
+            Shape::Unknown => "unknown",
+            Shape::Null | Shape::Nullable(_) => "null",
+            Shape::Boolean => "boolean",
+            Shape::Integer | Shape::Number => "number",
+            Shape::String => "string",
+            Shape::Array(_) => "array",
+            Shape::Object(_) => "object",
+            Shape::Union(_) => "union",
+        }
+    }
+}
+
+fn is_integer(n: f64) -> bool {
+    n.fract() == 0.0 && n >= i64::MIN as f64 && n < i64::MAX as f64
+}
+
+fn merge_fields(mut fields: Vec<Field>, others: Vec<Field>) -> Vec<Field> {
+    for field in fields.iter_mut() {
+        if !others.iter().any(|other| other.name == field.name) {
+            field.optional = true;
+        }
+    }
+    for other in others {
+        match fields.iter_mut().find(|field| field.name == other.name) {
+            Some(field) => {
+                field.shape =
+                    std::mem::replace(&mut field.shape, Shape::Unknown).merge(other.shape);
+                field.optional |= other.optional;
+            }
+            None => fields.push(Field {
+                optional: true,
+                ..other
+            }),
+        }
+    }
+    fields
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        parser::parse,
+        schema::{
+            domain::{Field, Shape},
+            infer,
+        },
+    };
+
+    fn samples(inputs: &[&str]) -> Shape {
+        let samples: Vec<_> = inputs.iter().map(|input| parse(input).unwrap()).collect();
+        infer(&samples)
+    }
+
+    #[test]
+    fn infer_should_flag_fields_missing_from_some_samples_as_optional() {
+        assert_eq!(
+            samples(&["{\"id\": 1, \"name\": \"a\"}", "{\"id\": 2, \"tag\": true}"]),
+            Shape::Object(vec![
+                Field::new("id", Shape::Integer, false),
+                Field::new("name", Shape::String, true),
+                Field::new("tag", Shape::Boolean, true),
+            ])
+        );
+    }
+
+    #[test]
+    fn infer_should_widen_numbers_and_wrap_nulls() {
+        assert_eq!(samples(&["1", "2.5"]), Shape::Number);
+        assert_eq!(
+            samples(&["null", "1", "null"]),
+            Shape::Nullable(Box::new(Shape::Integer))
+        );
+        assert_eq!(samples(&[]), Shape::Unknown);
+        assert_eq!(samples(&["[]"]), Shape::Array(Box::new(Shape::Unknown)));
+    }
+
+    #[test]
+    fn infer_should_build_unions_of_incompatible_shapes() {
+        assert_eq!(
+            samples(&["[1, \"a\", 2.5, null, \"b\"]"]),
+            Shape::Array(Box::new(Shape::Nullable(Box::new(Shape::Union(vec![
+                Shape::Number,
+                Shape::String,
+            ])))))
+        );
+    }
+
+    #[test]
+    fn infer_should_merge_arrays_of_objects() {
+        assert_eq!(
+            samples(&["{\"items\": [{\"a\": 1}, {\"a\": null, \"b\": [\"x\"]}]}"]),
+            Shape::Object(vec![Field::new(
+                "items",
+                Shape::Array(Box::new(Shape::Object(vec![
+                    Field::new("a", Shape::Nullable(Box::new(Shape::Integer)), false),
+                    Field::new("b", Shape::Array(Box::new(Shape::String)), true),
+                ]))),
+                false
+            )])
+        );
+    }
+}
@@ -0,0 +1,418 @@
+//! Generates Rust type definitions with serde attributes for a [`Shape`].
+//!
+//! Objects become structs, unions become `#[serde(untagged)]` enums and values nothing is known
+//! about become `serde_json::Value`, so the generated code depends on `serde` and `serde_json`.
+
+use std::{collections::HashSet, fmt::Write};
+
+use crate::schema::domain::{Field, Shape};
+
+const DERIVES: &str = "#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]";
+
+const KEYWORDS: &[&str] = &[
+    "abstract", "as", "async", "await", "become", "box", "break", "const", "continue", "do", "dyn",
+    "else", "enum", "extern", "false", "final", "fn", "for", "gen", "if", "impl", "in", "let",
+    "loop", "macro", "match", "mod", "move", "mut", "override", "priv", "pub", "ref", "return",
+    "static", "struct", "trait", "true", "try", "type", "typeof", "unsafe", "unsized", "use",
+    "virtual", "where", "while", "yield",
+];
+
+/// Keywords that can't be used as raw identifiers either.
+const RESERVED: &[&str] = &["crate", "self", "super", "Self"];
+
+/// Names the generated code refers to, or that are in scope through the prelude, which a type
+/// named after a field must not shadow.
+const TYPE_NAMES: &[&str] = &[
+    "AsMut",
+    "AsRef",
+    "Box",
+    "Clone",
+    "Copy",
+    "Debug",
+    "Default",
+    "Deserialize",
+    "Drop",
+    "Eq",
+    "Err",
+    "Extend",
+    "Fn",
+    "FnMut",
+    "FnOnce",
+    "From",
+    "Hash",
+    "Into",
+    "IntoIterator",
+    "Iterator",
+    "None",
+    "Ok",
+    "Option",
+    "Ord",
+    "PartialEq",
+    "PartialOrd",
+    "Result",
+    "Self",
+    "Send",
+    "Serialize",
+    "Sized",
+    "Some",
+    "String",
+    "Sync",
+    "ToOwned",
+    "ToString",
+    "Unpin",
+    "Value",
+    "Vec",
+];
+
+/// Generates the definitions for `shape`, naming the root type `root`. Nested types are named
+/// after the fields holding them.
+pub fn to_rust_types(shape: &Shape, root: &str) -> String {
+    let mut generator = Generator::default();
+    let root_name = generator.reserve(root);
+    match shape {
+        Shape::Object(fields) => generator.define_struct(root_name, fields),
+        Shape::Union(members) => generator.define_enum(root_name, members),
+        shape => {
+            let slot = generator.slot();
+            let rust_type = generator.rust_type(shape, root);
+            generator.definitions[slot] = format!("pub type {} = {};\n", root_name, rust_type);
+        }
+    }
+
+    let mut output = String::from("use serde::{Deserialize, Serialize};\n");
+    for definition in generator.definitions {
+        output.push('\n');
+        output.push_str(&definition);
+    }
+    output
+}
+
+struct Generator {
+    definitions: Vec<String>,
+    names: HashSet<String>,
+}
+
+impl Default for Generator {
+    fn default() -> Self {
+        Generator {
+            definitions: Vec::new(),
+            names: TYPE_NAMES.iter().map(|name| name.to_string()).collect(),
+        }
+    }
+}
+
+impl Generator {
+    /// Claims a unique type name derived from `hint`.
+    fn reserve(&mut self, hint: &str) -> String {
+        let base = pascal_case(hint);
+        let mut name = base.clone();
+        let mut suffix = 2;
+        while !self.names.insert(name.clone()) {
+            name = format!("{}{}", base, suffix);
+            suffix += 1;
+        }
+        name
+    }
+
+    /// Reserves the position of a definition, so parents are emitted before the types they use.
+    fn slot(&mut self) -> usize {
+        self.definitions.push(String::new());
+        self.definitions.len() - 1
+    }
+
+    fn rust_type(&mut self, shape: &Shape, hint: &str) -> String {
+        match shape {
+            Shape::Unknown | Shape::Null => "serde_json::Value".to_string(),
+            Shape::Boolean => "bool".to_string(),
+            Shape::Integer => "i64".to_string(),
+            Shape::Number => "f64".to_string(),
+            Shape::String => "String".to_string(),
+            Shape::Array(elements) => format!("Vec<{}>", self.rust_type(elements, &singular(hint))),
+            Shape::Nullable(inner) => format!("Option<{}>", self.rust_type(inner, hint)),
+            Shape::Object(fields) => {
+                let name = self.reserve(hint);
+                self.define_struct(name.clone(), fields);
+                name
+            }
+            Shape::Union(members) => {
+                let name = self.reserve(hint);
+                self.define_enum(name.clone(), members);
+                name
+            }
+        }
+    }
+
+    fn define_struct(&mut self, name: String, fields: &[Field]) {
+        let slot = self.slot();
+        let mut identifiers = HashSet::new();
+        let mut definition = format!("{}\npub struct {} {{\n", DERIVES, name);
+
+        for field in fields {
+            let identifier = unique_identifier(&field.name, &mut identifiers);
+            let mut attributes = Vec::new();
+            if identifier.trim_start_matches("r#") != field.name {
+                attributes.push(format!("rename = {:?}", field.name));
+            }
+            let mut rust_type = self.rust_type(&field.shape, &field.name);
+            if field.optional {
+                if !matches!(field.shape, Shape::Nullable(_)) {
+                    rust_type = format!("Option<{}>", rust_type);
+                }
+                attributes.push("default, skip_serializing_if = \"Option::is_none\"".to_string());
+            }
+            if !attributes.is_empty() {
+                let _ = writeln!(definition, "    #[serde({})]", attributes.join(", "));
+            }
+            let _ = writeln!(definition, "    pub {}: {},", identifier, rust_type);
+        }
+
+        definition.push_str("}\n");
+        self.definitions[slot] = definition;
+    }
+
+    fn define_enum(&mut self, name: String, members: &[Shape]) {
+        let slot = self.slot();
+        let mut definition = format!("{}\n#[serde(untagged)]\npub enum {} {{\n", DERIVES, name);
+
+        for member in members {
+            let variant = match member {
+                Shape::Boolean => "Boolean",
+                Shape::Integer => "Integer",
+                Shape::Number => "Number",
+                Shape::String => "String",
+                Shape::Array(_) => "Array",
+                Shape::Object(_) => "Object",
+                _ => "Value",
+            };
+            let rust_type = self.rust_type(member, &format!("{}{}", name, variant));
+            let _ = writeln!(definition, "    {}({}),", variant, rust_type);
+        }
+
+        definition.push_str("}\n");
+        self.definitions[slot] = definition;
+    }
+}
+
+/// Splits a json key into words on separators and lower to upper case boundaries.
+fn words(name: &str) -> Vec<String> {
+    let mut words = Vec::new();
+    let mut current = String::new();
+    let mut previous: Option<char> = None;
+    for c in name.chars() {
+        if !c.is_alphanumeric() {
+            if !current.is_empty() {
+                words.push(std::mem::take(&mut current));
+            }
+        } else {
+            if c.is_uppercase()
+                && previous.is_some_and(|p| p.is_lowercase() || p.is_ascii_digit())
+                && !current.is_empty()
+            {
+                words.push(std::mem::take(&mut current));
+            }
+            current.push(c);
+        }
+        previous = Some(c);
+    }
+    if !current.is_empty() {
+        words.push(current);
+    }
+    words
+}
+
+fn pascal_case(name: &str) -> String {
+    let name: String = words(name)
+        .iter()
+        .map(|word| {
+            let mut chars = word.chars();
+            chars
+                .next()
+                .map(|first| first.to_uppercase().chain(chars).collect::<String>())
+                .unwrap_or_default()
+        })
+        .collect();
+    match name.chars().next() {
+        Some(first) if first.is_alphabetic() => name,
+        _ => format!("Type{}", name),
+    }
+}
+
+fn snake_case(name: &str) -> String {
+    let name = words(name)
+        .iter()
+        .map(|word| word.to_lowercase())
+        .collect::<Vec<_>>()
+        .join("_");
+    match name.chars().next() {
+        Some(first) if first.is_alphabetic() => name,
+        _ => format!("field_{}", name).trim_end_matches('_').to_string(),
+    }
+}
+
+fn unique_identifier(name: &str, used: &mut HashSet<String>) -> String {
+    let base = snake_case(name);
+    let base = if RESERVED.contains(&base.as_str()) {
+        format!("{}_", base)
+    } else {
+        base
+    };
+    let mut identifier = base.clone();
+    let mut suffix = 2;
+    while !used.insert(identifier.clone()) {
+        identifier = format!("{}_{}", base, suffix);
+        suffix += 1;
+    }
+    if KEYWORDS.contains(&identifier.as_str()) {
+        format!("r#{}", identifier)
+    } else {
+        identifier
+    }
+}
+
+/// Names the element type of an array after its field, e.g. `categories` holds `Category`.
+fn singular(name: &str) -> String {
+    if let Some(stem) = name.strip_suffix("ies") {
+        format!("{}y", stem)
+    } else if name.ends_with("ss") || name.len() < 2 {
+        format!("{}Item", name)
+    } else if let Some(stem) = name.strip_suffix('s') {
+        stem.to_string()
+    } else {
+        format!("{}Item", name)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        parser::parse,
+        schema::{infer, rust::to_rust_types},
+    };
+
+    #[test]
+    fn to_rust_types_should_generate_serde_structs() {
+        let samples = vec![
+            parse(r#"{"id": 1, "userName": "a", "type": "x", "tags": [{"label": "t"}], "score": null}"#)
+                .unwrap(),
+            parse(r#"{"id": 2, "userName": "b", "type": "y", "tags": [], "score": 2.5, "is-admin": true}"#)
+                .unwrap(),
+        ];
+
+        assert_eq!(
+            to_rust_types(&infer(&samples), "user"),
+            r#"use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct User {
+    pub id: i64,
+    #[serde(rename = "userName")]
+    pub user_name: String,
+    pub r#type: String,
+    pub tags: Vec<Tag>,
+    pub score: Option<f64>,
+    #[serde(rename = "is-admin", default, skip_serializing_if = "Option::is_none")]
+    pub is_admin: Option<bool>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Tag {
+    pub label: String,
+}
+"#
+        );
+    }
+
+    #[test]
+    fn to_rust_types_should_generate_untagged_enums_for_unions() {
+        let samples = vec![
+            parse(r#"{"value": 1}"#).unwrap(),
+            parse(r#"{"value": {"a": [true]}}"#).unwrap(),
+        ];
+
+        assert_eq!(
+            to_rust_types(&infer(&samples), "Root"),
+            r#"use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Root {
+    pub value: Value2,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum Value2 {
+    Integer(i64),
+    Object(Value2Object),
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Value2Object {
+    pub a: Vec<bool>,
+}
+"#
+        );
+    }
+
+    #[test]
+    fn to_rust_types_should_alias_non_object_roots() {
+        let samples =
+            vec![parse(r#"[{"0day": 1, "0Day": 2, "self": 3, "a_b": 4, "a-b": 5}]"#).unwrap()];
+
+        assert_eq!(
+            to_rust_types(&infer(&samples), "events"),
+            r#"use serde::{Deserialize, Serialize};
+
+pub type Events = Vec<Event>;
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Event {
+    #[serde(rename = "0day")]
+    pub field_0day: i64,
+    #[serde(rename = "0Day")]
+    pub field_0_day: i64,
+    #[serde(rename = "self")]
+    pub self_: i64,
+    pub a_b: i64,
+    #[serde(rename = "a-b")]
+    pub a_b_2: i64,
+}
+"#
+        );
+    }
+
+    #[test]
+    fn to_rust_types_should_not_shadow_std_or_serde_names() {
+        let samples = vec![
+            parse(r#"{"option": {"a": 1}, "serialize": {"b": 2}, "strings": [{"c": "x"}]}"#)
+                .unwrap(),
+        ];
+
+        assert_eq!(
+            to_rust_types(&infer(&samples), "vec"),
+            r#"use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Vec2 {
+    pub option: Option2,
+    pub serialize: Serialize2,
+    pub strings: Vec<String2>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Option2 {
+    pub a: i64,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Serialize2 {
+    pub b: i64,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct String2 {
+    pub c: String,
+}
+"#
+        );
+    }
+}
@@ -0,0 +1,205 @@
+//! Serialises a [`JsonAST`] back into json text, either compact or indented.
+//!
+//! Numbers use the same shortest round-trip formatting as the [canonical](crate::canonical)
+//! output. NaN and infinities have no json representation and are written as `null`, as
+//! `JSON.stringify` does.
+//!
+//! Indented output can also be highlighted with ANSI colors and have deeply nested arrays and
+//! objects collapsed into a summary, see [`PrettyOptions`].
+
+use crate::{
+    canonical::{format_number, write_string},
+    parser::JsonAST,
+};
+
+const INDENT: &str = "  ";
+
+const KEY_COLOR: &str = "\x1b[1;34m";
+const STRING_COLOR: &str = "\x1b[32m";
+const NUMBER_COLOR: &str = "\x1b[36m";
+const BOOLEAN_COLOR: &str = "\x1b[33m";
+const NULL_COLOR: &str = "\x1b[90m";
+const COLLAPSED_COLOR: &str = "\x1b[2m";
+const RESET: &str = "\x1b[0m";
+
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct PrettyOptions {
+    /// Highlights keys, strings, numbers, booleans and nulls with ANSI colors.
+    pub colors: bool,
+    /// Arrays and objects nested this deep are replaced by a summary like `[…3 items]`.
+    pub collapse_depth: Option<usize>,
+}
+
+pub fn to_json_string(ast: &JsonAST) -> String {
+    let mut output = String::new();
+    Writer {
+        indent: false,
+        options: PrettyOptions::default(),
+    }
+    .write_value(ast, &mut output, 0);
+    output
+}
+
+pub fn to_json_string_pretty(ast: &JsonAST) -> String {
+    to_json_string_with(ast, &PrettyOptions::default())
+}
+
+pub fn to_json_string_with(ast: &JsonAST, options: &PrettyOptions) -> String {
+    let mut output = String::new();
+    Writer {
+        indent: true,
+        options: *options,
+    }
+    .write_value(ast, &mut output, 0);
+    output
+}
+
+pub(crate) fn write_number(n: f64, output: &mut String) {
+    output.push_str(&format_number(n).unwrap_or_else(|_| "null".to_string()));
+}
+
+struct Writer {
+    indent: bool,
+    options: PrettyOptions,
+}
+
+impl Writer {
+    fn colored(&self, color: &str, output: &mut String, write: impl FnOnce(&mut String)) {
+        if self.options.colors {
+            output.push_str(color);
+            write(output);
+            output.push_str(RESET);
+        } else {
+            write(output);
+        }
+    }
+
+    fn newline(&self, output: &mut String, level: usize) {
+        if self.indent {
+            output.push('\n');
+            output.push_str(&INDENT.repeat(level));
+        }
+    }
+
+    fn collapses(&self, level: usize) -> bool {
+        self.options
+            .collapse_depth
+            .is_some_and(|depth| level >= depth)
+    }
+
+    fn write_value(&self, ast: &JsonAST, output: &mut String, level: usize) {
+        match ast {
+            JsonAST::Null => self.colored(NULL_COLOR, output, |o| o.push_str("null")),
+            JsonAST::Boolean(b) => self.colored(BOOLEAN_COLOR, output, |o| {
+                o.push_str(if *b { "true" } else { "false" })
+            }),
+            JsonAST::Number(n) => self.colored(NUMBER_COLOR, output, |o| write_number(*n, o)),
+            JsonAST::String(s) => self.colored(STRING_COLOR, output, |o| write_string(s, o)),
+            JsonAST::Array(elements) if elements.is_empty() => output.push_str("[]"),
+            JsonAST::Array(elements) if self.collapses(level) => {
+                let summary = format!("[…{} {}]", elements.len(), plural(elements.len(), "item"));
+                self.colored(COLLAPSED_COLOR, output, |o| o.push_str(&summary));
+            }
+            JsonAST::Array(elements) => {
+                output.push('[');
+                for (i, element) in elements.iter().enumerate() {
+                    if i > 0 {
+                        output.push(',');
+                    }
+                    self.newline(output, level + 1);
+                    self.write_value(element, output, level + 1);
+                }
+                self.newline(output, level);
+                output.push(']');
+            }
+            JsonAST::Object(members) if members.is_empty() => output.push_str("{}"),
+            JsonAST::Object(members) if self.collapses(level) => {
+                let summary = format!("{{…{} {}}}", members.len(), plural(members.len(), "key"));
+                self.colored(COLLAPSED_COLOR, output, |o| o.push_str(&summary));
+            }
+            JsonAST::Object(members) => {
+                output.push('{');
+                for (i, (key, value)) in members.iter().enumerate() {
+                    if i > 0 {
+                        output.push(',');
+                    }
+                    self.newline(output, level + 1);
+                    self.colored(KEY_COLOR, output, |o| write_string(key, o));
+                    output.push(':');
+                    if self.indent {
+                        output.push(' ');
+                    }
+                    self.write_value(value, output, level + 1);
+                }
+                self.newline(output, level);
+                output.push('}');
+            }
+        }
+    }
+}
+
+fn plural(count: usize, word: &str) -> String {
+    if count == 1 {
+        word.to_string()
+    } else {
+        format!("{}s", word)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        parser::{JsonAST, parse},
+        writer::{PrettyOptions, to_json_string, to_json_string_pretty, to_json_string_with},
+    };
+
+    #[test]
+    fn to_json_string_should_round_trip_through_parse() {
+        let input =
+            "{\"b\":[1,2.5,-3e-7,\"a\\n\\\"\"],\"a\":{\"c\":null,\"d\":true},\"e\":[],\"f\":{}}";
+        let ast = parse(input).unwrap();
+        assert_eq!(to_json_string(&ast), input);
+    }
+
+    #[test]
+    fn to_json_string_pretty_should_indent_nested_values() {
+        let ast = parse("{\"a\": [1, {\"b\": false}], \"c\": []}").unwrap();
+        assert_eq!(
+            to_json_string_pretty(&ast),
+            "{\n  \"a\": [\n    1,\n    {\n      \"b\": false\n    }\n  ],\n  \"c\": []\n}"
+        );
+    }
+
+    #[test]
+    fn to_json_string_should_write_non_finite_numbers_as_null() {
+        let ast = JsonAST::Array(vec![JsonAST::Number(f64::NAN)]);
+        assert_eq!(to_json_string(&ast), "[null]");
+    }
+
+    #[test]
+    fn to_json_string_with_should_highlight_tokens() {
+        let ast = parse("{\"k\": [\"s\", 1, true, null]}").unwrap();
+        let options = PrettyOptions {
+            colors: true,
+            collapse_depth: None,
+        };
+        assert_eq!(
+            to_json_string_with(&ast, &options),
+            "{\n  \x1b[1;34m\"k\"\x1b[0m: [\n    \x1b[32m\"s\"\x1b[0m,\n    \x1b[36m1\x1b[0m,\n    \
+             \x1b[33mtrue\x1b[0m,\n    \x1b[90mnull\x1b[0m\n  ]\n}"
+        );
+    }
+
+    #[test]
+    fn to_json_string_with_should_collapse_deep_containers() {
+        let ast = parse("{\"a\": {\"b\": [1, 2, 3], \"c\": {\"d\": 1}, \"e\": []}}").unwrap();
+        let options = PrettyOptions {
+            colors: false,
+            collapse_depth: Some(2),
+        };
+        assert_eq!(
+            to_json_string_with(&ast, &options),
+            "{\n  \"a\": {\n    \"b\": […3 items],\n    \"c\": {…1 key},\n    \"e\": []\n  }\n}"
+        );
+    }
+}
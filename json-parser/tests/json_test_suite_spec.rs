@@ -0,0 +1,193 @@
+//! Runs the `test_parsing` corpus from [JSONTestSuite](https://github.com/nst/JSONTestSuite)
+//! against [`parse`].
+//!
+//! Files prefixed with `y_` must be accepted and files prefixed with `n_` must be rejected. Files
+//! prefixed with `i_` are implementation defined, and the outcome we chose for every one of them
+//! is listed in [`IMPLEMENTATION_DEFINED`].
+
+use std::{collections::HashMap, fs, panic, path::PathBuf};
+
+use json_parser::parser::parse;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Outcome {
+    Accepted,
+    Rejected,
+    Crashed,
+}
+
+const ACCEPT: Outcome = Outcome::Accepted;
+const REJECT: Outcome = Outcome::Rejected;
+
+/// Input that is not valid UTF-8 never reaches the parser, since `parse` takes a `&str`.
+const NOT_UTF8: &str = "input is not valid UTF-8";
+/// Numbers are held as `f64`, so anything overflowing to infinity can't be represented.
+const OVERFLOW: &str = "number overflows an f64";
+/// Numbers are held as `f64`, so precision loss and underflow to zero are accepted.
+const ROUNDED: &str = "number is rounded to the nearest f64";
+/// Rust strings can only hold Unicode scalar values, so unpaired surrogates can't be decoded.
+const SURROGATE: &str = "escape holds an unpaired UTF-16 surrogate";
+
+const IMPLEMENTATION_DEFINED: &[(&str, Outcome, &str)] = &[
+    ("i_number_double_huge_neg_exp.json", ACCEPT, ROUNDED),
+    ("i_number_huge_exp.json", REJECT, OVERFLOW),
+    ("i_number_neg_int_huge_exp.json", REJECT, OVERFLOW),
+    ("i_number_pos_double_huge_exp.json", REJECT, OVERFLOW),
+    ("i_number_real_neg_overflow.json", REJECT, OVERFLOW),
+    ("i_number_real_pos_overflow.json", REJECT, OVERFLOW),
+    ("i_number_real_underflow.json", ACCEPT, ROUNDED),
+    ("i_number_too_big_neg_int.json", ACCEPT, ROUNDED),
+    ("i_number_too_big_pos_int.json", ACCEPT, ROUNDED),
+    ("i_number_very_big_negative_int.json", ACCEPT, ROUNDED),
+    ("i_object_key_lone_2nd_surrogate.json", REJECT, SURROGATE),
+    (
+        "i_string_1st_surrogate_but_2nd_missing.json",
+        REJECT,
+        SURROGATE,
+    ),
+    (
+        "i_string_1st_valid_surrogate_2nd_invalid.json",
+        REJECT,
+        SURROGATE,
+    ),
+    ("i_string_UTF-16LE_with_BOM.json", REJECT, NOT_UTF8),
+    ("i_string_UTF-8_invalid_sequence.json", REJECT, NOT_UTF8),
+    ("i_string_UTF8_surrogate_U+D800.json", REJECT, NOT_UTF8),
+    (
+        "i_string_incomplete_surrogate_and_escape_valid.json",
+        REJECT,
+        SURROGATE,
+    ),
+    ("i_string_incomplete_surrogate_pair.json", REJECT, SURROGATE),
+    (
+        "i_string_incomplete_surrogates_escape_valid.json",
+        REJECT,
+        SURROGATE,
+    ),
+    ("i_string_invalid_lonely_surrogate.json", REJECT, SURROGATE),
+    ("i_string_invalid_surrogate.json", REJECT, SURROGATE),
+    ("i_string_invalid_utf-8.json", REJECT, NOT_UTF8),
+    (
+        "i_string_inverted_surrogates_U+1D11E.json",
+        REJECT,
+        SURROGATE,
+    ),
+    ("i_string_iso_latin_1.json", REJECT, NOT_UTF8),
+    ("i_string_lone_second_surrogate.json", REJECT, SURROGATE),
+    (
+        "i_string_lone_utf8_continuation_byte.json",
+        REJECT,
+        NOT_UTF8,
+    ),
+    ("i_string_not_in_unicode_range.json", REJECT, NOT_UTF8),
+    ("i_string_overlong_sequence_2_bytes.json", REJECT, NOT_UTF8),
+    ("i_string_overlong_sequence_6_bytes.json", REJECT, NOT_UTF8),
+    (
+        "i_string_overlong_sequence_6_bytes_null.json",
+        REJECT,
+        NOT_UTF8,
+    ),
+    ("i_string_truncated-utf-8.json", REJECT, NOT_UTF8),
+    ("i_string_utf16BE_no_BOM.json", REJECT, NOT_UTF8),
+    ("i_string_utf16LE_no_BOM.json", REJECT, NOT_UTF8),
+    (
+        "i_structure_500_nested_arrays.json",
+        ACCEPT,
+        "nesting is within parser::MAX_DEPTH",
+    ),
+    (
+        "i_structure_UTF-8_BOM_empty_object.json",
+        REJECT,
+        "a byte order mark is not json whitespace",
+    ),
+];
+
+fn corpus() -> Vec<(String, Vec<u8>)> {
+    let directory = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("tests/data/test_parsing");
+    let mut files: Vec<(String, Vec<u8>)> = fs::read_dir(directory)
+        .expect("Failed to read the test_parsing corpus")
+        .map(|entry| {
+            let path = entry.expect("Failed to read corpus entry").path();
+            let name = path
+                .file_name()
+                .and_then(|name| name.to_str())
+                .expect("Corpus file names are valid UTF-8")
+                .to_string();
+            let content = fs::read(&path).expect("Failed to read corpus file");
+            (name, content)
+        })
+        .filter(|(name, _)| name.ends_with(".json"))
+        .collect();
+    files.sort();
+    files
+}
+
+fn run(content: &[u8]) -> Outcome {
+    let Ok(input) = std::str::from_utf8(content) else {
+        return Outcome::Rejected;
+    };
+    match panic::catch_unwind(|| parse(input)) {
+        Ok(Ok(_)) => Outcome::Accepted,
+        Ok(Err(_)) => Outcome::Rejected,
+        Err(_) => Outcome::Crashed,
+    }
+}
+
+#[test]
+fn parse_should_conform_to_json_test_suite() {
+    let policies: HashMap<&str, Outcome> = IMPLEMENTATION_DEFINED
+        .iter()
+        .map(|(name, outcome, _)| (*name, *outcome))
+        .collect();
+
+    let mut report: HashMap<char, (usize, usize)> = HashMap::new();
+    let mut failures = Vec::new();
+
+    for (name, content) in corpus() {
+        let kind = name
+            .chars()
+            .next()
+            .expect("Corpus file names are not empty");
+        let expected = match kind {
+            'y' => Outcome::Accepted,
+            'n' => Outcome::Rejected,
+            'i' => *policies
+                .get(name.as_str())
+                .unwrap_or_else(|| panic!("No policy documented for {}", name)),
+            _ => continue,
+        };
+
+        let outcome = run(&content);
+        let counts = report.entry(kind).or_default();
+        if outcome == expected {
+            counts.0 += 1;
+        } else {
+            counts.1 += 1;
+            failures.push(format!(
+                "{}: expected {:?} got {:?}",
+                name, expected, outcome
+            ));
+        }
+    }
+
+    for kind in ['y', 'n', 'i'] {
+        let (passed, failed) = report.get(&kind).copied().unwrap_or_default();
+        println!("{}_ files: {} passed, {} failed", kind, passed, failed);
+    }
+    assert!(failures.is_empty(), "Failures:\n{}", failures.join("\n"));
+}
+
+#[test]
+fn implementation_defined_policy_should_cover_the_corpus_exactly() {
+    let corpus: Vec<String> = corpus()
+        .into_iter()
+        .map(|(name, _)| name)
+        .filter(|name| name.starts_with("i_"))
+        .collect();
+    let documented: Vec<&str> = IMPLEMENTATION_DEFINED
+        .iter()
+        .map(|(name, ..)| *name)
+        .collect();
+
+    assert_eq!(corpus, documented);
+}
@@ -0,0 +1,856 @@
+use std::{
+    collections::BTreeMap,
+    net::SocketAddr,
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
+use clap::Parser;
+use serde::Deserialize;
+use thiserror::Error;
+
+use crate::{middleware::SecurityHeaders, startup::ServerSettings, tls::CertificateFiles};
+
+pub const DEFAULT_LISTEN_ADDRESS: &str = "127.0.0.1:8080";
+
+#[derive(Debug, Error)]
+pub enum ConfigError {
+    #[error("Unable to read config file {path}: {source}")]
+    Read {
+        path: PathBuf,
+        source: std::io::Error,
+    },
+    #[error("Invalid config file {path}: {message}")]
+    Parse { path: PathBuf, message: String },
+    #[error("Invalid configuration:\n  {}", .0.join("\n  "))]
+    Invalid(Vec<String>),
+}
+
+/// Command line flags, which take precedence over the environment variables of the same name,
+/// which in turn take precedence over the config file.
+#[derive(Debug, Default, Parser)]
+#[command(name = "web-server", about = "Serves static files over HTTP/1.1")]
+pub struct Cli {
+    /// TOML file to read the configuration from.
+    #[arg(short, long, env = "WEB_SERVER_CONFIG")]
+    pub config: Option<PathBuf>,
+    /// Addresses to listen on, such as 127.0.0.1:8080, separated by commas.
+    #[arg(short, long, env = "WEB_SERVER_LISTEN", value_delimiter = ',')]
+    pub listen: Vec<String>,
+    /// Directory files are served from.
+    #[arg(short = 'r', long, env = "WEB_SERVER_DOCUMENT_ROOT")]
+    pub document_root: Option<PathBuf>,
+    /// Files sent for a directory, separated by commas, the first one found winning.
+    #[arg(long, env = "WEB_SERVER_INDEX_FILES", value_delimiter = ',')]
+    pub index_files: Vec<String>,
+    /// List directories without an index file.
+    #[arg(long, env = "WEB_SERVER_AUTOINDEX")]
+    pub autoindex: Option<bool>,
+    /// Say how long each response took in a `Server-Timing` header.
+    #[arg(long, env = "WEB_SERVER_TIMING")]
+    pub timing: Option<bool>,
+    /// Seconds a connection may stay idle between requests.
+    #[arg(long, env = "WEB_SERVER_IDLE_TIMEOUT_SECS")]
+    pub idle_timeout_secs: Option<u64>,
+    /// Largest request body accepted, in bytes.
+    #[arg(long, env = "WEB_SERVER_MAX_BODY")]
+    pub max_body: Option<usize>,
+    /// Addresses to listen on with TLS, such as 127.0.0.1:8443, separated by commas.
+    #[arg(long, env = "WEB_SERVER_TLS_LISTEN", value_delimiter = ',')]
+    pub tls_listen: Vec<String>,
+    /// PEM file with the certificate chain sent over TLS.
+    #[arg(long, env = "WEB_SERVER_TLS_CERTIFICATE")]
+    pub tls_certificate: Option<PathBuf>,
+    /// PEM file with the private key of the certificate.
+    #[arg(long, env = "WEB_SERVER_TLS_PRIVATE_KEY")]
+    pub tls_private_key: Option<PathBuf>,
+}
+
+/// The config file. Every key is optional, and relative paths are relative to the file.
+///
+/// ```toml
+/// listen = ["127.0.0.1:8080", "[::1]:8080"]
+/// document_root = "www"
+/// index_files = ["index.html", "index.htm"]
+/// autoindex = false
+/// timing = false
+/// idle_timeout_secs = 5
+/// max_requests_per_connection = 100
+///
+/// [limits]
+/// max_body = 1048576
+///
+/// [mime_types]
+/// webmanifest = "application/manifest+json"
+///
+/// # An empty value leaves the header out
+/// [security_headers]
+/// enabled = true
+/// content_type_options = true
+/// content_security_policy = "default-src 'self'"
+/// strict_transport_security = "max-age=31536000"
+///
+/// [tls]
+/// listen = ["127.0.0.1:8443"]
+/// certificate = "certs/localhost.pem"
+/// private_key = "certs/localhost.key"
+/// reload_interval_secs = 60
+/// redirect = true
+///
+/// [[virtual_hosts]]
+/// server_names = ["example.com", "www.example.com"]
+/// document_root = "sites/example"
+/// certificate = "certs/example.pem"
+/// private_key = "certs/example.key"
+/// ```
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct FileConfig {
+    listen: Option<Vec<String>>,
+    document_root: Option<PathBuf>,
+    index_files: Option<Vec<String>>,
+    autoindex: Option<bool>,
+    timing: Option<bool>,
+    idle_timeout_secs: Option<u64>,
+    max_requests_per_connection: Option<usize>,
+    limits: FileLimits,
+    mime_types: BTreeMap<String, String>,
+    security_headers: FileSecurityHeaders,
+    tls: FileTls,
+    virtual_hosts: Vec<FileVirtualHost>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct FileLimits {
+    max_request_line: Option<usize>,
+    max_header_bytes: Option<usize>,
+    max_headers: Option<usize>,
+    max_body: Option<usize>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct FileSecurityHeaders {
+    enabled: Option<bool>,
+    content_type_options: Option<bool>,
+    content_security_policy: Option<String>,
+    strict_transport_security: Option<String>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct FileTls {
+    listen: Option<Vec<String>>,
+    certificate: Option<PathBuf>,
+    private_key: Option<PathBuf>,
+    reload_interval_secs: Option<u64>,
+    redirect: Option<bool>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct FileVirtualHost {
+    server_names: Vec<String>,
+    document_root: PathBuf,
+    index_files: Option<Vec<String>>,
+    autoindex: Option<bool>,
+    certificate: Option<PathBuf>,
+    private_key: Option<PathBuf>,
+}
+
+/// A site served for the requests whose `Host` is one of its names.
+#[derive(Debug, Clone, PartialEq)]
+pub struct VirtualHost {
+    /// Host names, lowercase and without a port.
+    pub server_names: Vec<String>,
+    pub document_root: PathBuf,
+    /// The server's own unless the site sets them.
+    pub index_files: Vec<String>,
+    pub autoindex: bool,
+    /// Sent to the clients asking for one of the names over TLS, rather than the default one.
+    pub certificate: Option<CertificateFiles>,
+}
+
+impl VirtualHost {
+    /// The settings of the site, which are `server`'s apart from those the site sets.
+    pub fn settings(&self, server: &ServerSettings) -> ServerSettings {
+        let mut settings = server.clone();
+        settings.document_root = self.document_root.clone();
+        settings.index_files = self.index_files.clone();
+        settings.autoindex.enabled = self.autoindex;
+        settings
+    }
+}
+
+/// How the sites are served over TLS.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TlsConfig {
+    pub listen: Vec<SocketAddr>,
+    /// Sent to clients asking for a name without a certificate of its own, or for none.
+    pub certificate: CertificateFiles,
+    /// How often the certificate files are checked for changes.
+    pub reload_interval: Duration,
+    /// Whether the plain HTTP listeners redirect every request to HTTPS.
+    pub redirect: bool,
+}
+
+/// Everything the server is configured with, validated.
+#[derive(Debug, Clone)]
+pub struct Config {
+    pub listen: Vec<SocketAddr>,
+    pub settings: ServerSettings,
+    /// `None` unless some address to listen on with TLS is set.
+    pub tls: Option<TlsConfig>,
+    pub virtual_hosts: Vec<VirtualHost>,
+}
+
+impl Config {
+    /// Reads the config file `cli` points to, if any, applies the flags on top of it and
+    /// validates the result.
+    pub fn load(cli: &Cli) -> Result<Self, ConfigError> {
+        let (file, base) = match &cli.config {
+            Some(path) => {
+                let content =
+                    std::fs::read_to_string(path).map_err(|source| ConfigError::Read {
+                        path: path.clone(),
+                        source,
+                    })?;
+                let base = path.parent().unwrap_or(Path::new("")).to_path_buf();
+                (parse(path, &content)?, base)
+            }
+            None => (FileConfig::default(), PathBuf::new()),
+        };
+        build(file, &base, cli)
+    }
+}
+
+fn parse(path: &Path, content: &str) -> Result<FileConfig, ConfigError> {
+    toml::from_str(content).map_err(|error| ConfigError::Parse {
+        path: path.to_path_buf(),
+        message: error.to_string(),
+    })
+}
+
+/// Merges the file and the flags over the defaults, collecting every problem found rather than
+/// stopping at the first one.
+fn build(file: FileConfig, base: &Path, cli: &Cli) -> Result<Config, ConfigError> {
+    let mut errors = Vec::new();
+    let mut settings = ServerSettings::default();
+
+    let listen = if !cli.listen.is_empty() {
+        cli.listen.clone()
+    } else {
+        file.listen
+            .unwrap_or_else(|| vec![DEFAULT_LISTEN_ADDRESS.to_string()])
+    };
+    if listen.is_empty() {
+        errors.push("listen: at least one address is needed".to_string());
+    }
+    let listen = parse_addresses("listen", &listen, &mut errors);
+
+    settings.document_root = match (&cli.document_root, file.document_root) {
+        (Some(document_root), _) => document_root.clone(),
+        (None, Some(document_root)) => base.join(document_root),
+        (None, None) => settings.document_root,
+    };
+    check_directory("document_root", &settings.document_root, &mut errors);
+
+    if !cli.index_files.is_empty() {
+        settings.index_files = cli.index_files.clone();
+    } else if let Some(index_files) = file.index_files {
+        settings.index_files = index_files;
+    }
+    check_index_files("index_files", &settings.index_files, &mut errors);
+
+    if let Some(autoindex) = cli.autoindex.or(file.autoindex) {
+        settings.autoindex.enabled = autoindex;
+    }
+
+    if let Some(idle_timeout) = cli.idle_timeout_secs.or(file.idle_timeout_secs) {
+        if idle_timeout == 0 {
+            errors.push("idle_timeout_secs: has to be at least 1".to_string());
+        }
+        settings.idle_timeout = Duration::from_secs(idle_timeout);
+    }
+    if let Some(max_requests) = file.max_requests_per_connection {
+        if max_requests == 0 {
+            errors.push("max_requests_per_connection: has to be at least 1".to_string());
+        }
+        settings.max_requests_per_connection = max_requests;
+    }
+
+    let limits = &mut settings.limits;
+    for (name, value, limit) in [
+        (
+            "limits.max_request_line",
+            file.limits.max_request_line,
+            &mut limits.max_request_line,
+        ),
+        (
+            "limits.max_header_bytes",
+            file.limits.max_header_bytes,
+            &mut limits.max_header_bytes,
+        ),
+        (
+            "limits.max_headers",
+            file.limits.max_headers,
+            &mut limits.max_headers,
+        ),
+    ] {
+        if let Some(value) = value {
+            if value == 0 {
+                errors.push(format!("{}: has to be at least 1", name));
+            }
+            *limit = value;
+        }
+    }
+    // A limit of 0 is fine for bodies, it rejects any request sending one
+    if let Some(max_body) = cli.max_body.or(file.limits.max_body) {
+        limits.max_body = max_body;
+    }
+
+    for (extension, mime) in &file.mime_types {
+        if extension.is_empty() || extension.contains('.') || extension.contains('/') {
+            errors.push(format!(
+                "mime_types: {:?} has to be an extension without the dot, such as \"html\"",
+                extension
+            ));
+        } else if !mime.contains('/') || mime.chars().any(|char| char.is_control()) {
+            errors.push(format!(
+                "mime_types.{}: {:?} isn't a media type such as \"text/plain\"",
+                extension, mime
+            ));
+        } else {
+            settings.mime_types.insert(extension, mime);
+        }
+    }
+
+    if let Some(timing) = cli.timing.or(file.timing) {
+        settings.timing = timing;
+    }
+    settings.security_headers = build_security_headers(file.security_headers, &mut errors);
+
+    let tls = build_tls(file.tls, base, cli, &mut errors);
+
+    let mut virtual_hosts: Vec<VirtualHost> = Vec::new();
+    for (i, host) in file.virtual_hosts.into_iter().enumerate() {
+        let name = format!("virtual_hosts[{}]", i);
+        if host.server_names.is_empty() {
+            errors.push(format!(
+                "{}.server_names: at least one name is needed",
+                name
+            ));
+        }
+        let server_names: Vec<String> = host
+            .server_names
+            .iter()
+            .map(|server_name| server_name.trim().to_ascii_lowercase())
+            .collect();
+        for server_name in &server_names {
+            if server_name.is_empty() || server_name.contains([':', '/', ' ']) {
+                errors.push(format!(
+                    "{}.server_names: {:?} has to be a host name without a port",
+                    name, server_name
+                ));
+            } else if virtual_hosts
+                .iter()
+                .any(|other| other.server_names.contains(server_name))
+            {
+                errors.push(format!(
+                    "{}.server_names: {:?} belongs to an earlier virtual host already",
+                    name, server_name
+                ));
+            }
+        }
+        let document_root = base.join(host.document_root);
+        check_directory(
+            &format!("{}.document_root", name),
+            &document_root,
+            &mut errors,
+        );
+        // Inherited index files were checked already
+        if let Some(index_files) = &host.index_files {
+            check_index_files(&format!("{}.index_files", name), index_files, &mut errors);
+        }
+        let index_files = host
+            .index_files
+            .unwrap_or_else(|| settings.index_files.clone());
+        let certificate = certificate_files(
+            &name,
+            host.certificate.map(|path| base.join(path)),
+            host.private_key.map(|path| base.join(path)),
+            &mut errors,
+        );
+        if certificate.is_some() && tls.is_none() {
+            errors.push(format!(
+                "{}.certificate: is only used when listening with TLS",
+                name
+            ));
+        }
+        virtual_hosts.push(VirtualHost {
+            server_names,
+            document_root,
+            index_files,
+            autoindex: host.autoindex.unwrap_or(settings.autoindex.enabled),
+            certificate,
+        });
+    }
+
+    if errors.is_empty() {
+        Ok(Config {
+            listen,
+            settings,
+            tls,
+            virtual_hosts,
+        })
+    } else {
+        Err(ConfigError::Invalid(errors))
+    }
+}
+
+/// The security headers sent, over the defaults, or `None` when they're disabled.
+fn build_security_headers(
+    file: FileSecurityHeaders,
+    errors: &mut Vec<String>,
+) -> Option<SecurityHeaders> {
+    let mut headers = SecurityHeaders::default();
+    if let Some(content_type_options) = file.content_type_options {
+        headers.content_type_options = content_type_options;
+    }
+    for (name, value, header) in [
+        (
+            "security_headers.content_security_policy",
+            file.content_security_policy,
+            &mut headers.content_security_policy,
+        ),
+        (
+            "security_headers.strict_transport_security",
+            file.strict_transport_security,
+            &mut headers.strict_transport_security,
+        ),
+    ] {
+        match value {
+            Some(value) if value.chars().any(|char| char.is_control()) => {
+                errors.push(format!(
+                    "{}: {:?} can't hold control characters",
+                    name, value
+                ));
+            }
+            Some(value) if value.trim().is_empty() => *header = None,
+            Some(value) => *header = Some(value.trim().to_string()),
+            None => {}
+        }
+    }
+    file.enabled.unwrap_or(true).then_some(headers)
+}
+
+/// The TLS settings, when some address to listen on with TLS is set.
+fn build_tls(file: FileTls, base: &Path, cli: &Cli, errors: &mut Vec<String>) -> Option<TlsConfig> {
+    let listen = if !cli.tls_listen.is_empty() {
+        cli.tls_listen.clone()
+    } else {
+        file.listen.unwrap_or_default()
+    };
+    let listen = parse_addresses("tls.listen", &listen, errors);
+    let certificate = cli
+        .tls_certificate
+        .clone()
+        .or(file.certificate.map(|path| base.join(path)));
+    let private_key = cli
+        .tls_private_key
+        .clone()
+        .or(file.private_key.map(|path| base.join(path)));
+    let missing = certificate.is_none() && private_key.is_none();
+    let certificate = certificate_files("tls", certificate, private_key, errors);
+    let reload_interval = file.reload_interval_secs.unwrap_or(60);
+    if reload_interval == 0 {
+        errors.push("tls.reload_interval_secs: has to be at least 1".to_string());
+    }
+
+    match (listen.is_empty(), certificate) {
+        (true, None) => None,
+        (true, Some(_)) => {
+            errors.push("tls.listen: at least one address is needed for the certificate".into());
+            None
+        }
+        (false, None) => {
+            if missing {
+                errors.push("tls.certificate: needed to listen with TLS".to_string());
+            }
+            None
+        }
+        (false, Some(certificate)) => Some(TlsConfig {
+            listen,
+            certificate,
+            reload_interval: Duration::from_secs(reload_interval),
+            redirect: file.redirect.unwrap_or(false),
+        }),
+    }
+}
+
+fn parse_addresses(name: &str, addresses: &[String], errors: &mut Vec<String>) -> Vec<SocketAddr> {
+    addresses
+        .iter()
+        .filter_map(|address| match address.trim().parse() {
+            Ok(address) => Some(address),
+            Err(_) => {
+                errors.push(format!(
+                    "{}: {:?} isn't an address such as {}",
+                    name, address, DEFAULT_LISTEN_ADDRESS
+                ));
+                None
+            }
+        })
+        .collect()
+}
+
+/// The certificate and key of `name`, which have to be set together.
+fn certificate_files(
+    name: &str,
+    certificate: Option<PathBuf>,
+    private_key: Option<PathBuf>,
+    errors: &mut Vec<String>,
+) -> Option<CertificateFiles> {
+    match (certificate, private_key) {
+        (Some(certificate), Some(private_key)) => {
+            for (key, path) in [("certificate", &certificate), ("private_key", &private_key)] {
+                if !path.is_file() {
+                    errors.push(format!("{}.{}: {} isn't a file", name, key, path.display()));
+                }
+            }
+            Some(CertificateFiles::new(certificate, private_key))
+        }
+        (Some(_), None) => {
+            errors.push(format!("{}.private_key: needed with the certificate", name));
+            None
+        }
+        (None, Some(_)) => {
+            errors.push(format!("{}.certificate: needed with the private key", name));
+            None
+        }
+        (None, None) => None,
+    }
+}
+
+fn check_directory(name: &str, path: &Path, errors: &mut Vec<String>) {
+    if !path.is_dir() {
+        errors.push(format!("{}: {} isn't a directory", name, path.display()));
+    }
+}
+
+fn check_index_files(name: &str, index_files: &[String], errors: &mut Vec<String>) {
+    for index_file in index_files {
+        if index_file.is_empty() || index_file.contains('/') || index_file == ".." {
+            errors.push(format!(
+                "{}: {:?} has to be a file name, such as \"index.html\"",
+                name, index_file
+            ));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{path::Path, time::Duration};
+
+    use clap::Parser;
+
+    use super::{Cli, ConfigError, build, parse};
+    use crate::tls::Certificates;
+
+    fn load(content: &str, args: &[&str]) -> Result<super::Config, ConfigError> {
+        let file = parse(Path::new("web-server.toml"), content)?;
+        let cli = Cli::parse_from([&["web-server"], args].concat());
+        build(file, Path::new(env!("CARGO_MANIFEST_DIR")), &cli)
+    }
+
+    fn errors(result: Result<super::Config, ConfigError>) -> Vec<String> {
+        match result {
+            Err(ConfigError::Invalid(errors)) => errors,
+            other => panic!("Expected validation errors, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn build_should_default_without_a_file() {
+        let config = load("", &[]).unwrap();
+
+        assert_eq!(config.listen, vec!["127.0.0.1:8080".parse().unwrap()]);
+        assert!(config.settings.document_root.ends_with("www"));
+        assert_eq!(config.settings.index_files, vec!["index.html"]);
+        assert!(config.virtual_hosts.is_empty());
+    }
+
+    #[test]
+    fn build_should_read_the_file() {
+        let config = load(
+            r#"
+            listen = ["0.0.0.0:80", "[::1]:8080"]
+            document_root = "www/foobar"
+            index_files = ["aloha.html"]
+            autoindex = true
+            idle_timeout_secs = 30
+            max_requests_per_connection = 10
+
+            [limits]
+            max_headers = 20
+            max_body = 0
+
+            [mime_types]
+            webmanifest = "application/manifest+json"
+
+            [[virtual_hosts]]
+            server_names = ["Example.com", "www.example.com"]
+            document_root = "www"
+            "#,
+            &[],
+        )
+        .unwrap();
+        let settings = &config.settings;
+
+        assert_eq!(config.listen.len(), 2);
+        assert!(settings.document_root.ends_with("www/foobar"));
+        assert_eq!(settings.index_files, vec!["aloha.html"]);
+        assert!(settings.autoindex.enabled);
+        assert_eq!(settings.idle_timeout, Duration::from_secs(30));
+        assert_eq!(settings.max_requests_per_connection, 10);
+        assert_eq!(settings.limits.max_headers, 20);
+        assert_eq!(settings.limits.max_body, 0);
+        assert_eq!(
+            settings.mime_types.get("webmanifest"),
+            Some("application/manifest+json")
+        );
+        assert_eq!(config.virtual_hosts[0].server_names, vec![
+            "example.com",
+            "www.example.com"
+        ]);
+        let host_settings = config.virtual_hosts[0].settings(settings);
+        assert!(host_settings.document_root.ends_with("web-server/www"));
+        assert_eq!(host_settings.index_files, vec!["aloha.html"]);
+        assert!(host_settings.autoindex.enabled);
+        assert_eq!(host_settings.limits.max_headers, 20);
+    }
+
+    #[test]
+    fn build_should_read_the_middleware_settings() {
+        let settings = load("", &[]).unwrap().settings;
+        assert!(!settings.timing);
+        let headers = settings.security_headers.unwrap();
+        assert!(headers.content_type_options);
+        assert_eq!(
+            headers.content_security_policy.as_deref(),
+            Some("default-src 'self'")
+        );
+
+        let config = load(
+            r#"
+            timing = false
+
+            [security_headers]
+            content_type_options = false
+            content_security_policy = ""
+            strict_transport_security = "max-age=31536000"
+            "#,
+            &["--timing", "true"],
+        )
+        .unwrap();
+        assert!(config.settings.timing);
+        let headers = config.settings.security_headers.unwrap();
+        assert!(!headers.content_type_options);
+        assert_eq!(headers.content_security_policy, None);
+        assert_eq!(
+            headers.strict_transport_security.as_deref(),
+            Some("max-age=31536000")
+        );
+
+        let config = load("[security_headers]\nenabled = false", &[]).unwrap();
+        assert!(config.settings.security_headers.is_none());
+
+        let errors = errors(load(
+            "[security_headers]\ncontent_security_policy = \"default-src\\r\\nX: y\"",
+            &[],
+        ));
+        assert_eq!(errors.len(), 1, "{:#?}", errors);
+        assert!(errors[0].starts_with("security_headers.content_security_policy: "));
+    }
+
+    #[test]
+    fn flags_should_override_the_file() {
+        let config = load(
+            "listen = [\"0.0.0.0:80\"]\nindex_files = [\"a.html\"]\nidle_timeout_secs = 30",
+            &[
+                "--listen",
+                "127.0.0.1:3000,127.0.0.1:3001",
+                "--index-files",
+                "b.html",
+                "--idle-timeout-secs",
+                "2",
+                "--max-body",
+                "10",
+                "--autoindex",
+                "true",
+            ],
+        )
+        .unwrap();
+
+        assert_eq!(config.listen.len(), 2);
+        assert_eq!(config.listen[1].port(), 3001);
+        assert_eq!(config.settings.index_files, vec!["b.html"]);
+        assert_eq!(config.settings.idle_timeout, Duration::from_secs(2));
+        assert_eq!(config.settings.limits.max_body, 10);
+        assert!(config.settings.autoindex.enabled);
+    }
+
+    #[test]
+    fn build_should_report_every_invalid_value() {
+        let errors = errors(load(
+            r#"
+            listen = ["localhost"]
+            document_root = "missing"
+            index_files = ["../index.html"]
+            idle_timeout_secs = 0
+
+            [limits]
+            max_headers = 0
+
+            [mime_types]
+            ".txt" = "text/plain"
+            md = "markdown"
+
+            [[virtual_hosts]]
+            server_names = ["example.com:80"]
+            document_root = "www"
+
+            [[virtual_hosts]]
+            server_names = []
+            document_root = "www"
+            "#,
+            &[],
+        ));
+
+        let expected = [
+            "listen: \"localhost\"",
+            "document_root: ",
+            "index_files: \"../index.html\"",
+            "idle_timeout_secs: ",
+            "limits.max_headers: ",
+            "mime_types: \".txt\"",
+            "mime_types.md: \"markdown\"",
+            "virtual_hosts[0].server_names: \"example.com:80\"",
+            "virtual_hosts[1].server_names: at least one",
+        ];
+        assert_eq!(errors.len(), expected.len(), "{:#?}", errors);
+        for (error, expected) in errors.iter().zip(expected) {
+            assert!(error.starts_with(expected), "{} {}", error, expected);
+        }
+    }
+
+    #[test]
+    fn build_should_reject_duplicate_server_names() {
+        let errors = errors(load(
+            r#"
+            [[virtual_hosts]]
+            server_names = ["example.com"]
+            document_root = "www"
+
+            [[virtual_hosts]]
+            server_names = ["EXAMPLE.com"]
+            document_root = "www"
+            "#,
+            &[],
+        ));
+
+        assert_eq!(errors, vec![
+            "virtual_hosts[1].server_names: \"example.com\" belongs to an earlier virtual \
+                 host already"
+        ]);
+    }
+
+    #[test]
+    fn build_should_read_the_tls_settings() {
+        let config = load(
+            r#"
+            [tls]
+            listen = ["127.0.0.1:8443"]
+            certificate = "tests/fixtures/localhost.pem"
+            private_key = "tests/fixtures/example.com.key"
+            redirect = true
+
+            [[virtual_hosts]]
+            server_names = ["example.com"]
+            document_root = "www"
+            certificate = "tests/fixtures/example.com.pem"
+            private_key = "tests/fixtures/example.com.key"
+            "#,
+            &[
+                "--tls-listen",
+                "127.0.0.1:9443",
+                "--tls-private-key",
+                "tests/fixtures/localhost.key",
+            ],
+        );
+        let config = config.unwrap();
+        let tls = config.tls.unwrap();
+
+        assert_eq!(tls.listen, vec!["127.0.0.1:9443".parse().unwrap()]);
+        assert!(
+            tls.certificate
+                .certificate
+                .ends_with("web-server/tests/fixtures/localhost.pem")
+        );
+        assert_eq!(
+            tls.certificate.private_key,
+            Path::new("tests/fixtures/localhost.key")
+        );
+        assert_eq!(tls.reload_interval, Duration::from_secs(60));
+        assert!(tls.redirect);
+        let certificate = config.virtual_hosts[0].certificate.as_ref().unwrap();
+        assert!(
+            certificate
+                .certificate
+                .ends_with("web-server/tests/fixtures/example.com.pem")
+        );
+
+        // The files given make a usable certificate for every site
+        Certificates::new(tls.certificate)
+            .and_then(|certificates| certificates.host(&["example.com"], certificate.clone()))
+            .expect("Failed to load the certificates");
+    }
+
+    #[test]
+    fn build_should_reject_incomplete_tls_settings() {
+        let errors = errors(load(
+            r#"
+            [tls]
+            listen = ["127.0.0.1:8443"]
+            reload_interval_secs = 0
+
+            [[virtual_hosts]]
+            server_names = ["example.com"]
+            document_root = "www"
+            certificate = "missing.pem"
+            "#,
+            &[],
+        ));
+
+        assert_eq!(errors, vec![
+            "tls.reload_interval_secs: has to be at least 1",
+            "tls.certificate: needed to listen with TLS",
+            "virtual_hosts[0].private_key: needed with the certificate",
+        ]);
+    }
+
+    #[test]
+    fn parse_should_reject_unknown_keys() {
+        let error = parse(Path::new("web-server.toml"), "document_rot = \"www\"").unwrap_err();
+
+        let message = error.to_string();
+        assert!(message.starts_with("Invalid config file web-server.toml: "));
+        assert!(
+            message.contains("unknown field `document_rot`"),
+            "{}",
+            message
+        );
+    }
+}
@@ -0,0 +1,284 @@
+use std::{
+    path::Path,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+/// Directory listings, sent for directories without an index file.
+#[derive(Debug, Clone, Default)]
+pub struct Autoindex {
+    /// Listings are opt-in, as they can reveal files nobody links to.
+    pub enabled: bool,
+    /// Whether entries starting with a `.` are listed.
+    pub show_hidden: bool,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct DirectoryEntry {
+    pub name: String,
+    pub is_directory: bool,
+    pub size: u64,
+    pub modified: Option<SystemTime>,
+}
+
+/// The entries of the directory at `path`, directories first and then by name. Entries whose
+/// name isn't valid UTF-8 or whose metadata can't be read, such as broken symlinks, are left
+/// out.
+pub async fn read_directory(
+    path: &Path,
+    show_hidden: bool,
+) -> std::io::Result<Vec<DirectoryEntry>> {
+    let mut entries = Vec::new();
+    let mut directory = tokio::fs::read_dir(path).await?;
+    while let Some(entry) = directory.next_entry().await? {
+        let Ok(name) = entry.file_name().into_string() else {
+            continue;
+        };
+        if name.starts_with('.') && !show_hidden {
+            continue;
+        }
+        let Ok(metadata) = tokio::fs::metadata(entry.path()).await else {
+            continue;
+        };
+        entries.push(DirectoryEntry {
+            name,
+            is_directory: metadata.is_dir(),
+            size: if metadata.is_dir() { 0 } else { metadata.len() },
+            modified: metadata.modified().ok(),
+        });
+    }
+    entries.sort_by(|a, b| {
+        b.is_directory
+            .cmp(&a.is_directory)
+            .then_with(|| a.name.cmp(&b.name))
+    });
+    Ok(entries)
+}
+
+/// Whether the `Accept` header prefers JSON to HTML, comparing the weights of
+/// `application/json` and of the ranges matching `text/html`.
+pub fn prefers_json(accept: Option<&str>) -> bool {
+    let Some(accept) = accept else {
+        return false;
+    };
+    let mut json = 0.0;
+    let mut html: f32 = 0.0;
+    for item in accept.split(',') {
+        let mut params = item.split(';');
+        let media_range = params
+            .next()
+            .unwrap_or_default()
+            .trim()
+            .to_ascii_lowercase();
+        let quality = params
+            .filter_map(|param| param.trim().split_once('='))
+            .find(|(name, _)| name.trim().eq_ignore_ascii_case("q"))
+            .map_or(1.0, |(_, value)| value.trim().parse::<f32>().unwrap_or(0.0));
+        match media_range.as_str() {
+            "application/json" => json = quality,
+            "text/html" | "text/*" | "*/*" => html = html.max(quality),
+            _ => {}
+        }
+    }
+    json > html
+}
+
+/// An HTML page listing `entries` of `directory`, its decoded path from the document root such
+/// as `/files`, with a link to the parent directory unless it's the root.
+pub fn render_html(directory: &str, entries: &[DirectoryEntry]) -> String {
+    let base = encode_path(directory.trim_end_matches('/'));
+    let title = escape_html(&format!("Index of {}/", directory.trim_end_matches('/')));
+    let mut rows = String::new();
+    if !base.is_empty() {
+        let parent = base.rsplit_once('/').map_or("", |(parent, _)| parent);
+        rows.push_str(&format!(
+            "<tr><td><a href=\"{}/\">../</a></td><td></td><td></td></tr>\n",
+            escape_html(parent)
+        ));
+    }
+    for entry in entries {
+        let suffix = if entry.is_directory { "/" } else { "" };
+        let size = if entry.is_directory {
+            "-".to_string()
+        } else {
+            entry.size.to_string()
+        };
+        let modified = entry
+            .modified
+            .map(httpdate::fmt_http_date)
+            .unwrap_or_default();
+        rows.push_str(&format!(
+            "<tr><td><a href=\"{}/{}{}\">{}{}</a></td><td>{}</td><td>{}</td></tr>\n",
+            escape_html(&base),
+            escape_html(&percent_encode(&entry.name)),
+            suffix,
+            escape_html(&entry.name),
+            suffix,
+            size,
+            modified
+        ));
+    }
+    format!(
+        "<!DOCTYPE html>\n<html>\n<head><meta charset=\"utf-8\"><title>{title}</title></head>\n\
+         <body>\n<h1>{title}</h1>\n<table>\n\
+         <tr><th>Name</th><th>Size</th><th>Last modified</th></tr>\n{rows}</table>\n\
+         </body>\n</html>\n"
+    )
+}
+
+/// A JSON array with an object per entry, with its modification time in seconds since the
+/// Unix epoch.
+pub fn render_json(entries: &[DirectoryEntry]) -> String {
+    let objects: Vec<String> = entries
+        .iter()
+        .map(|entry| {
+            let modified = entry
+                .modified
+                .and_then(|modified| modified.duration_since(UNIX_EPOCH).ok())
+                .map_or("null".to_string(), |modified| {
+                    modified.as_secs().to_string()
+                });
+            format!(
+                "{{\"name\":{},\"type\":\"{}\",\"size\":{},\"modified\":{}}}",
+                escape_json(&entry.name),
+                if entry.is_directory {
+                    "directory"
+                } else {
+                    "file"
+                },
+                entry.size,
+                modified
+            )
+        })
+        .collect();
+    format!("[{}]", objects.join(","))
+}
+
+fn encode_path(path: &str) -> String {
+    path.split('/')
+        .filter(|segment| !segment.is_empty())
+        .map(|segment| format!("/{}", percent_encode(segment)))
+        .collect()
+}
+
+fn escape_html(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for char in text.chars() {
+        match char {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&#39;"),
+            char => escaped.push(char),
+        }
+    }
+    escaped
+}
+
+fn escape_json(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len() + 2);
+    escaped.push('"');
+    for char in text.chars() {
+        match char {
+            '"' => escaped.push_str("\\\""),
+            '\\' => escaped.push_str("\\\\"),
+            '\n' => escaped.push_str("\\n"),
+            '\r' => escaped.push_str("\\r"),
+            '\t' => escaped.push_str("\\t"),
+            char if (char as u32) < 0x20 => escaped.push_str(&format!("\\u{:04x}", char as u32)),
+            char => escaped.push(char),
+        }
+    }
+    escaped.push('"');
+    escaped
+}
+
+/// Percent-encodes a file name for use as a path segment.
+fn percent_encode(segment: &str) -> String {
+    let mut encoded = String::with_capacity(segment.len());
+    for byte in segment.bytes() {
+        if byte.is_ascii_alphanumeric() || b"-._~!$&'()*+,;=:@".contains(&byte) {
+            encoded.push(byte as char);
+        } else {
+            encoded.push_str(&format!("%{:02X}", byte));
+        }
+    }
+    encoded
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, UNIX_EPOCH};
+
+    use super::{DirectoryEntry, prefers_json, read_directory, render_html, render_json};
+
+    fn entry(name: &str, is_directory: bool, size: u64) -> DirectoryEntry {
+        DirectoryEntry {
+            name: name.to_string(),
+            is_directory,
+            size,
+            modified: Some(UNIX_EPOCH + Duration::from_secs(784111777)),
+        }
+    }
+
+    #[tokio::test]
+    async fn read_directory_should_sort_and_hide_dot_files() {
+        let directory = tempfile::tempdir().unwrap();
+        std::fs::write(directory.path().join("b.txt"), "bb").unwrap();
+        std::fs::write(directory.path().join("a.txt"), "a").unwrap();
+        std::fs::write(directory.path().join(".secret"), "").unwrap();
+        std::fs::create_dir(directory.path().join("zeta")).unwrap();
+
+        let names = |entries: Vec<DirectoryEntry>| -> Vec<String> {
+            entries.into_iter().map(|entry| entry.name).collect()
+        };
+        let entries = read_directory(directory.path(), false).await.unwrap();
+        assert_eq!(entries[1].size, 1);
+        assert_eq!(names(entries), vec!["zeta", "a.txt", "b.txt"]);
+
+        let entries = read_directory(directory.path(), true).await.unwrap();
+        assert_eq!(names(entries), vec!["zeta", ".secret", "a.txt", "b.txt"]);
+    }
+
+    #[test]
+    fn render_html_should_link_and_escape_entries() {
+        let entries = vec![entry("docs", true, 0), entry("a <b>&c.txt", false, 12)];
+        let html = render_html("/my files", &entries);
+
+        assert!(html.contains("<title>Index of /my files/</title>"));
+        assert!(html.contains("<a href=\"/\">../</a>"));
+        assert!(html.contains("<a href=\"/my%20files/docs/\">docs/</a></td><td>-</td>"));
+        assert!(html.contains(
+            "<a href=\"/my%20files/a%20%3Cb%3E&amp;c.txt\">a &lt;b&gt;&amp;c.txt</a></td>\
+             <td>12</td><td>Sun, 06 Nov 1994 08:49:37 GMT</td>"
+        ));
+        assert!(!render_html("", &entries).contains("../"));
+    }
+
+    #[test]
+    fn render_json_should_list_entries() {
+        let entries = vec![entry("docs", true, 0), entry("say \"hi\".txt", false, 3)];
+        assert_eq!(
+            render_json(&entries),
+            "[{\"name\":\"docs\",\"type\":\"directory\",\"size\":0,\"modified\":784111777},\
+             {\"name\":\"say \\\"hi\\\".txt\",\"type\":\"file\",\"size\":3,\"modified\":784111777}]"
+        );
+        assert_eq!(render_json(&[]), "[]");
+    }
+
+    #[test]
+    fn prefers_json_should_compare_weights() {
+        let cases = vec![
+            (None, false),
+            (Some("application/json"), true),
+            (Some("text/html,application/xhtml+xml,*/*;q=0.8"), false),
+            (Some("application/json, */*;q=0.5"), true),
+            (Some("text/html;q=0.5, application/json;q=0.9"), true),
+            (Some("application/json;q=0, */*"), false),
+        ];
+
+        for (accept, expected) in cases {
+            assert_eq!(prefers_json(accept), expected, "{:?}", accept);
+        }
+    }
+}
@@ -0,0 +1,299 @@
+use std::{
+    fs::Metadata,
+    path::Path,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use tokio::io::AsyncReadExt;
+
+use crate::domain::headers::Headers;
+
+/// How the `ETag` of a file is computed.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum EtagStrategy {
+    /// From the modification time and size, as nginx does. Cheap, but changes when a file is
+    /// touched without its content changing.
+    Metadata,
+    /// From a hash of the content, so identical files share an `ETag` across servers. The whole
+    /// file is read on each request.
+    Content,
+    None,
+}
+
+/// The validators sent with a file, which conditional requests are checked against.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Validators {
+    pub etag: Option<String>,
+    pub last_modified: Option<SystemTime>,
+}
+
+impl Validators {
+    pub async fn for_file(
+        path: &Path,
+        metadata: &Metadata,
+        strategy: EtagStrategy,
+    ) -> std::io::Result<Self> {
+        // HTTP dates have a resolution of a second, so anything finer would never match
+        let last_modified = metadata.modified().ok().map(truncate_to_seconds);
+        let etag = match strategy {
+            EtagStrategy::Metadata => {
+                let modified = metadata
+                    .modified()
+                    .ok()
+                    .and_then(|modified| modified.duration_since(UNIX_EPOCH).ok())
+                    .unwrap_or_default();
+                Some(format!(
+                    "\"{:x}.{:x}-{:x}\"",
+                    modified.as_secs(),
+                    modified.subsec_nanos(),
+                    metadata.len()
+                ))
+            }
+            EtagStrategy::Content => Some(format!("\"{:016x}\"", hash_file(path).await?)),
+            EtagStrategy::None => None,
+        };
+        Ok(Validators {
+            etag,
+            last_modified,
+        })
+    }
+
+    /// Whether the client's cached copy is still fresh, so `304 Not Modified` can be sent.
+    ///
+    /// Follows RFC 9110 section 13.2.2: `If-Modified-Since` is only looked at when there's no
+    /// `If-None-Match`, and an unparsable date is ignored.
+    pub fn not_modified(&self, request_headers: &Headers) -> bool {
+        if request_headers.contains("If-None-Match") {
+            let Some(etag) = &self.etag else {
+                return false;
+            };
+            return request_headers
+                .get_all("If-None-Match")
+                .flat_map(|value| value.split(','))
+                .map(str::trim)
+                .any(|tag| tag == "*" || weak_eq(tag, etag));
+        }
+
+        match (self.last_modified, request_headers.get("If-Modified-Since")) {
+            (Some(last_modified), Some(since)) => {
+                httpdate::parse_http_date(since).is_ok_and(|since| last_modified <= since)
+            }
+            _ => false,
+        }
+    }
+
+    /// The `ETag` and `Last-Modified` headers to send.
+    pub fn headers(&self) -> Vec<(&'static str, String)> {
+        let mut headers = Vec::new();
+        if let Some(etag) = &self.etag {
+            headers.push(("ETag", etag.clone()));
+        }
+        if let Some(last_modified) = self.last_modified {
+            headers.push(("Last-Modified", httpdate::fmt_http_date(last_modified)));
+        }
+        headers
+    }
+}
+
+/// Weak comparison of two entity tags, which ignores the `W/` prefix.
+fn weak_eq(a: &str, b: &str) -> bool {
+    a.trim_start_matches("W/") == b.trim_start_matches("W/")
+}
+
+fn truncate_to_seconds(time: SystemTime) -> SystemTime {
+    match time.duration_since(UNIX_EPOCH) {
+        Ok(since_epoch) => UNIX_EPOCH + Duration::from_secs(since_epoch.as_secs()),
+        Err(_) => time,
+    }
+}
+
+/// 64-bit FNV-1a of the file content, which is stable across builds unlike the std hashers.
+async fn hash_file(path: &Path) -> std::io::Result<u64> {
+    let mut file = tokio::fs::File::open(path).await?;
+    let mut buffer = vec![0; 64 * 1024];
+    let mut hash: u64 = 0xcbf29ce484222325;
+    loop {
+        let read = file.read(&mut buffer).await?;
+        if read == 0 {
+            return Ok(hash);
+        }
+        for byte in &buffer[..read] {
+            hash ^= *byte as u64;
+            hash = hash.wrapping_mul(0x100000001b3);
+        }
+    }
+}
+
+/// `Cache-Control` values to send for the files matching a path pattern.
+///
+/// Patterns are matched against the path of the served file from the document root, such as
+/// `/assets/app.css`. A `*` matches within a single path segment, `?` matches one character and
+/// `**` matches across segments, so `/assets/**` and `**/*.css` both match it. The first rule
+/// that matches wins.
+#[derive(Debug, Clone, Default)]
+pub struct CacheControlRules(Vec<(String, String)>);
+
+impl CacheControlRules {
+    pub fn new() -> Self {
+        CacheControlRules(Vec::new())
+    }
+
+    pub fn add(&mut self, pattern: &str, cache_control: &str) {
+        self.0
+            .push((pattern.to_string(), cache_control.to_string()));
+    }
+
+    pub fn for_path(&self, path: &str) -> Option<&str> {
+        self.0
+            .iter()
+            .find(|(pattern, _)| glob_match(pattern.as_bytes(), path.as_bytes()))
+            .map(|(_, cache_control)| cache_control.as_str())
+    }
+}
+
+fn glob_match(pattern: &[u8], path: &[u8]) -> bool {
+    match pattern {
+        [] => path.is_empty(),
+        [b'*', b'*', rest @ ..] => {
+            // `**/` also matches no directory at all, so `**/*.css` matches `a.css`
+            let (rest, whole_segments) = match rest {
+                [b'/', rest @ ..] => (rest, true),
+                rest => (rest, false),
+            };
+            (0..=path.len())
+                .filter(|&i| !whole_segments || i == 0 || path[i - 1] == b'/')
+                .any(|i| glob_match(rest, &path[i..]))
+        }
+        [b'*', rest @ ..] => (0..=path.len())
+            .take_while(|&i| i == 0 || path[i - 1] != b'/')
+            .any(|i| glob_match(rest, &path[i..])),
+        [b'?', rest @ ..] => {
+            matches!(path, [first, path @ ..] if *first != b'/' && glob_match(rest, path))
+        }
+        [expected, rest @ ..] => {
+            matches!(path, [first, path @ ..] if first == expected && glob_match(rest, path))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+    use super::{CacheControlRules, Validators, glob_match};
+    use crate::domain::headers::Headers;
+
+    fn validators() -> Validators {
+        Validators {
+            etag: Some("\"abc\"".to_string()),
+            // Sun, 06 Nov 1994 08:49:37 GMT
+            last_modified: Some(UNIX_EPOCH + Duration::from_secs(784111777)),
+        }
+    }
+
+    fn headers(pairs: &[(&str, &str)]) -> Headers {
+        let mut headers = Headers::new();
+        for (name, value) in pairs {
+            headers.append(name, value);
+        }
+        headers
+    }
+
+    #[test]
+    fn not_modified_should_match_entity_tags() {
+        let validators = validators();
+        let cases = vec![
+            ("\"abc\"", true),
+            ("W/\"abc\"", true),
+            ("\"xyz\", \"abc\"", true),
+            ("*", true),
+            ("\"xyz\"", false),
+            ("abc", false),
+        ];
+
+        for (if_none_match, expected) in cases {
+            let headers = headers(&[("If-None-Match", if_none_match)]);
+            assert_eq!(
+                validators.not_modified(&headers),
+                expected,
+                "{}",
+                if_none_match
+            );
+        }
+    }
+
+    #[test]
+    fn not_modified_should_compare_dates() {
+        let validators = validators();
+        let cases = vec![
+            ("Sun, 06 Nov 1994 08:49:37 GMT", true),
+            ("Mon, 07 Nov 1994 08:49:37 GMT", true),
+            ("Sun, 06 Nov 1994 08:49:36 GMT", false),
+            ("yesterday", false),
+        ];
+
+        for (since, expected) in cases {
+            let headers = headers(&[("If-Modified-Since", since)]);
+            assert_eq!(validators.not_modified(&headers), expected, "{}", since);
+        }
+
+        // If-None-Match takes precedence, even when the date would match
+        let headers = headers(&[
+            ("If-None-Match", "\"xyz\""),
+            ("If-Modified-Since", "Mon, 07 Nov 1994 08:49:37 GMT"),
+        ]);
+        assert!(!validators.not_modified(&headers));
+        assert!(!validators.not_modified(&Headers::new()));
+    }
+
+    #[test]
+    fn not_modified_should_need_an_etag_for_if_none_match() {
+        let validators = Validators {
+            etag: None,
+            last_modified: Some(SystemTime::now()),
+        };
+        assert!(!validators.not_modified(&headers(&[("If-None-Match", "*")])));
+    }
+
+    #[test]
+    fn glob_match_should_handle_wildcards() {
+        let cases = vec![
+            ("/assets/**", "/assets/css/app.css", true),
+            ("/assets/**", "/other/app.css", false),
+            ("**/*.css", "/assets/app.css", true),
+            ("**/*.css", "/app.css", true),
+            ("**/*.css", "/app.css.map", false),
+            ("/*.html", "/index.html", true),
+            ("/*.html", "/foobar/index.html", false),
+            ("/foobar/?.png", "/foobar/a.png", true),
+            ("/foobar/?.png", "/foobar/ab.png", false),
+            ("/exact.txt", "/exact.txt", true),
+        ];
+
+        for (pattern, path, expected) in cases {
+            assert_eq!(
+                glob_match(pattern.as_bytes(), path.as_bytes()),
+                expected,
+                "{} {}",
+                pattern,
+                path
+            );
+        }
+    }
+
+    #[test]
+    fn cache_control_rules_should_use_the_first_match() {
+        let mut rules = CacheControlRules::new();
+        rules.add("/assets/**", "public, max-age=31536000, immutable");
+        rules.add("**/*.html", "no-cache");
+        rules.add("**", "max-age=60");
+
+        assert_eq!(
+            rules.for_path("/assets/index.html"),
+            Some("public, max-age=31536000, immutable")
+        );
+        assert_eq!(rules.for_path("/foobar/index.html"), Some("no-cache"));
+        assert_eq!(rules.for_path("/foobar/pixel.png"), Some("max-age=60"));
+        assert_eq!(CacheControlRules::new().for_path("/index.html"), None);
+    }
+}
@@ -0,0 +1,224 @@
+use std::io::Write;
+
+use flate2::{Compression, write::GzEncoder};
+
+/// The content codings the server can produce, in order of preference when a client accepts
+/// several of them equally.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Encoding {
+    Brotli,
+    Gzip,
+}
+
+impl Encoding {
+    pub const ALL: [Encoding; 2] = [Encoding::Brotli, Encoding::Gzip];
+
+    /// The token used in `Accept-Encoding` and `Content-Encoding`.
+    pub fn token(&self) -> &'static str {
+        match self {
+            Encoding::Brotli => "br",
+            Encoding::Gzip => "gzip",
+        }
+    }
+
+    /// The extension of precompressed siblings, as `app.js.br`.
+    pub fn extension(&self) -> &'static str {
+        match self {
+            Encoding::Brotli => "br",
+            Encoding::Gzip => "gz",
+        }
+    }
+
+    pub fn compress(&self, content: &[u8]) -> std::io::Result<Vec<u8>> {
+        match self {
+            Encoding::Brotli => {
+                let mut compressed = Vec::new();
+                {
+                    // Quality 5 compresses about as well as gzip's best at a fraction of the
+                    // time brotli's highest qualities take, which only suit precompression
+                    let mut writer = brotli::CompressorWriter::new(&mut compressed, 4096, 5, 22);
+                    writer.write_all(content)?;
+                }
+                Ok(compressed)
+            }
+            Encoding::Gzip => {
+                let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+                encoder.write_all(content)?;
+                encoder.finish()
+            }
+        }
+    }
+}
+
+/// Picks the encoding to send among `available`, following the `Accept-Encoding` header as
+/// RFC 9110 section 12.5.3 describes: the highest `q` wins, a `q` of 0 rules an encoding out and
+/// `*` stands for every encoding not listed. `None` means the content is sent as it is.
+pub fn negotiate(accept_encoding: Option<&str>, available: &[Encoding]) -> Option<Encoding> {
+    let accept_encoding = accept_encoding?;
+    let mut preferences = Vec::new();
+    for item in accept_encoding.split(',') {
+        let mut params = item.split(';');
+        let token = params
+            .next()
+            .unwrap_or_default()
+            .trim()
+            .to_ascii_lowercase();
+        if token.is_empty() {
+            continue;
+        }
+        let quality = params
+            .filter_map(|param| param.trim().split_once('='))
+            .find(|(name, _)| name.trim().eq_ignore_ascii_case("q"))
+            .map_or(Some(1.0), |(_, value)| value.trim().parse::<f32>().ok());
+        // An invalid weight makes the whole item unusable
+        let Some(quality) = quality else {
+            continue;
+        };
+        preferences.push((token, quality));
+    }
+
+    let quality_of = |encoding: &Encoding| {
+        let listed = |token: &str| {
+            preferences
+                .iter()
+                .find(|(t, _)| t == token)
+                .map(|(_, quality)| *quality)
+        };
+        // `x-gzip` is an alias of `gzip` kept for compatibility
+        let own = match encoding {
+            Encoding::Gzip => listed("gzip").or_else(|| listed("x-gzip")),
+            Encoding::Brotli => listed("br"),
+        };
+        own.or_else(|| listed("*")).unwrap_or(0.0)
+    };
+
+    let mut best: Option<(Encoding, f32)> = None;
+    for encoding in Encoding::ALL {
+        if !available.contains(&encoding) {
+            continue;
+        }
+        let quality = quality_of(&encoding);
+        if quality > 0.0 && best.is_none_or(|(_, best)| quality > best) {
+            best = Some((encoding, quality));
+        }
+    }
+    best.map(|(encoding, _)| encoding)
+}
+
+/// When responses are compressed.
+#[derive(Debug, Clone)]
+pub struct CompressionSettings {
+    /// Whether responses may be compressed as they are sent.
+    pub on_the_fly: bool,
+    /// Whether `file.br` and `file.gz` siblings are sent in place of `file` when they exist.
+    pub precompressed: bool,
+    /// Smaller files are sent as they are, as compressing them saves less than it costs.
+    pub min_size: u64,
+    /// Larger files are sent as they are when there's no precompressed sibling, as compressing
+    /// them on the fly means holding them in memory.
+    pub max_size: u64,
+    /// `Content-Type` prefixes worth compressing. Images, video and fonts are compressed
+    /// already.
+    pub compressible_types: Vec<String>,
+}
+
+impl Default for CompressionSettings {
+    fn default() -> Self {
+        CompressionSettings {
+            on_the_fly: true,
+            precompressed: true,
+            min_size: 1024,
+            max_size: 8 * 1024 * 1024,
+            compressible_types: [
+                "text/",
+                "application/json",
+                "application/javascript",
+                "application/xml",
+                "application/wasm",
+                "image/svg+xml",
+            ]
+            .iter()
+            .map(|prefix| prefix.to_string())
+            .collect(),
+        }
+    }
+}
+
+impl CompressionSettings {
+    pub fn is_compressible(&self, content_type: &str) -> bool {
+        self.compressible_types
+            .iter()
+            .any(|prefix| content_type.starts_with(prefix.as_str()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Read;
+
+    use super::{CompressionSettings, Encoding, negotiate};
+
+    #[test]
+    fn negotiate_should_follow_quality_values() {
+        let both = &Encoding::ALL;
+        let cases = vec![
+            (None, None),
+            (Some(""), None),
+            (Some("identity"), None),
+            (Some("gzip"), Some(Encoding::Gzip)),
+            (Some("x-gzip"), Some(Encoding::Gzip)),
+            (Some("gzip, deflate, br"), Some(Encoding::Brotli)),
+            (Some("gzip;q=1.0, br;q=0.5"), Some(Encoding::Gzip)),
+            (Some("br;q=0, gzip;q=0.1"), Some(Encoding::Gzip)),
+            (Some("*"), Some(Encoding::Brotli)),
+            (Some("*;q=0.5, br;q=0"), Some(Encoding::Gzip)),
+            (Some("gzip;q=0, br;q=0"), None),
+            (Some("GZIP ; Q=0.8"), Some(Encoding::Gzip)),
+            (Some("br;q=high, gzip"), Some(Encoding::Gzip)),
+        ];
+
+        for (accept_encoding, expected) in cases {
+            assert_eq!(
+                negotiate(accept_encoding, both),
+                expected,
+                "{:?}",
+                accept_encoding
+            );
+        }
+        assert_eq!(
+            negotiate(Some("gzip, br"), &[Encoding::Gzip]),
+            Some(Encoding::Gzip)
+        );
+        assert_eq!(negotiate(Some("br"), &[Encoding::Gzip]), None);
+    }
+
+    #[test]
+    fn compress_should_round_trip() {
+        let content = "All work and no play makes Jack a dull boy. ".repeat(100);
+
+        let gzip = Encoding::Gzip.compress(content.as_bytes()).unwrap();
+        let mut decompressed = String::new();
+        flate2::read::GzDecoder::new(gzip.as_slice())
+            .read_to_string(&mut decompressed)
+            .unwrap();
+        assert_eq!(decompressed, content);
+        assert!(gzip.len() < content.len() / 10);
+
+        let brotli = Encoding::Brotli.compress(content.as_bytes()).unwrap();
+        let mut decompressed = String::new();
+        brotli::Decompressor::new(brotli.as_slice(), 4096)
+            .read_to_string(&mut decompressed)
+            .unwrap();
+        assert_eq!(decompressed, content);
+    }
+
+    #[test]
+    fn is_compressible_should_match_type_prefixes() {
+        let settings = CompressionSettings::default();
+        assert!(settings.is_compressible("text/html; charset=utf-8"));
+        assert!(settings.is_compressible("application/json"));
+        assert!(settings.is_compressible("image/svg+xml"));
+        assert!(!settings.is_compressible("image/png"));
+        assert!(!settings.is_compressible("application/octet-stream"));
+    }
+}
@@ -1,28 +1,474 @@
-use std::str::FromStr;
+use std::{net::SocketAddr, str::FromStr};
 
-/// On this implementation we only care about this two
-/// parameters of the request, which come on the very first line
+use tokio::io::{AsyncBufRead, AsyncBufReadExt, AsyncReadExt};
+
+use crate::domain::{headers::Headers, http_response::StatusCodes};
+
+/// Limits applied while reading a request, so a client can't make the server buffer unbounded
+/// amounts of data.
+#[derive(Debug, Clone)]
+pub struct RequestLimits {
+    /// Longest request line accepted, in bytes.
+    pub max_request_line: usize,
+    /// Largest header section accepted, in bytes, counting every header line.
+    pub max_header_bytes: usize,
+    pub max_headers: usize,
+    /// Largest body accepted, whether sent with `Content-Length` or chunked.
+    pub max_body: usize,
+}
+
+impl Default for RequestLimits {
+    fn default() -> Self {
+        RequestLimits {
+            max_request_line: 8 * 1024,
+            max_header_bytes: 16 * 1024,
+            max_headers: 100,
+            max_body: 1024 * 1024,
+        }
+    }
+}
+
+#[derive(Debug, Clone, thiserror::Error, PartialEq)]
+pub enum RequestError {
+    #[error("Connection closed before the request was complete")]
+    UnexpectedEof,
+    #[error("Unable to read the request: {0}")]
+    IoError(String),
+    #[error("Malformed request line `{0}`")]
+    MalformedRequestLine(String),
+    #[error("Unsupported method `{0}`")]
+    UnknownMethod(String),
+    #[error("Unsupported HTTP version `{0}`")]
+    UnsupportedVersion(String),
+    #[error("Malformed header line `{0}`")]
+    MalformedHeader(String),
+    #[error("Request line is longer than {0} bytes")]
+    RequestLineTooLong(usize),
+    #[error("Request headers are larger than {0} bytes")]
+    HeadersTooLarge(usize),
+    #[error("Request has more than {0} headers")]
+    TooManyHeaders(usize),
+    #[error("Invalid Content-Length `{0}`")]
+    InvalidContentLength(String),
+    #[error("Request has both a Content-Length and a Transfer-Encoding")]
+    ConflictingBodyLength,
+    #[error("Unsupported Transfer-Encoding `{0}`")]
+    UnsupportedTransferEncoding(String),
+    #[error("Malformed chunked body: {0}")]
+    MalformedChunk(String),
+    #[error("Request body is larger than {0} bytes")]
+    BodyTooLarge(usize),
+}
+
+impl RequestError {
+    /// The status to answer a request failing with this error.
+    pub fn status_code(&self) -> StatusCodes {
+        match self {
+            RequestError::RequestLineTooLong(_) => StatusCodes::URITooLong,
+            RequestError::HeadersTooLarge(_) | RequestError::TooManyHeaders(_) => {
+                StatusCodes::RequestHeaderFieldsTooLarge
+            }
+            RequestError::BodyTooLarge(_) => StatusCodes::PayloadTooLarge,
+            RequestError::UnknownMethod(_) | RequestError::UnsupportedTransferEncoding(_) => {
+                StatusCodes::NotImplemented
+            }
+            RequestError::UnsupportedVersion(_) => StatusCodes::HTTPVersionNotSupported,
+            _ => StatusCodes::BadRequest,
+        }
+    }
+}
+
+impl From<std::io::Error> for RequestError {
+    fn from(error: std::io::Error) -> Self {
+        RequestError::IoError(error.to_string())
+    }
+}
+
+/// A full HTTP/1.1 request: request line, headers and body.
 #[derive(Debug, PartialEq)]
 pub struct HttpRequest {
     pub method: Method,
+    /// Target path, still percent-encoded and without the query string.
     pub path: String,
+    /// Query parameters, percent-decoded, in the order they were sent.
+    pub query: Vec<(String, String)>,
+    /// Query string as sent, without the `?`.
+    pub raw_query: Option<String>,
+    /// Parameters captured by the route that matched, such as `id` in `/users/:id`. Empty until
+    /// the request is routed.
+    pub params: Vec<(String, String)>,
+    pub version: String,
+    /// Address of the client, once the server fills it in.
+    pub remote_address: Option<SocketAddr>,
+    pub headers: Headers,
+    pub body: Vec<u8>,
 }
 
-impl FromStr for HttpRequest {
-    type Err = std::io::Error;
+impl HttpRequest {
+    /// Reads the next request from `reader`, consuming its body so the connection is left at
+    /// the start of the following request. Returns `None` when the connection is closed before
+    /// a new request starts.
+    pub async fn read_from<R>(
+        reader: &mut R,
+        limits: &RequestLimits,
+    ) -> Result<Option<Self>, RequestError>
+    where
+        R: AsyncBufRead + Unpin,
+    {
+        // Empty lines ahead of a request line are ignored, as RFC 9112 section 2.2 allows
+        let request_line = loop {
+            match read_line(reader, limits.max_request_line).await {
+                Ok(None) => return Ok(None),
+                Ok(Some(line)) if line.is_empty() => continue,
+                Ok(Some(line)) => break line,
+                Err(LineError::TooLong) => {
+                    return Err(RequestError::RequestLineTooLong(limits.max_request_line));
+                }
+                Err(LineError::Request(error)) => return Err(error),
+            }
+        };
+        let (method, target, version) = parse_request_line(&request_line)?;
+        let headers = read_headers(reader, limits).await?;
+        let body = read_body(reader, &headers, limits).await?;
 
-    fn from_str(s: &str) -> Result<Self, Self::Err> {
-        let bits = s.split(' ').collect::<Vec<&str>>();
-        let method = bits[0].parse::<Method>()?;
-        let path = bits[1];
-        Ok(HttpRequest {
+        let (path, raw_query) = match target.split_once('?') {
+            Some((path, query)) => (path.to_string(), Some(query.to_string())),
+            None => (target, None),
+        };
+        let query = raw_query.as_deref().map_or_else(Vec::new, parse_query);
+        Ok(Some(HttpRequest {
             method,
-            path: path.to_string(),
-        })
+            path,
+            query,
+            raw_query,
+            params: Vec::new(),
+            version,
+            remote_address: None,
+            headers,
+            body,
+        }))
+    }
+
+    pub fn query_param(&self, name: &str) -> Option<&str> {
+        self.query
+            .iter()
+            .find(|(key, _)| key == name)
+            .map(|(_, value)| value.as_str())
+    }
+
+    pub fn param(&self, name: &str) -> Option<&str> {
+        self.params
+            .iter()
+            .find(|(key, _)| key == name)
+            .map(|(_, value)| value.as_str())
     }
 }
 
-#[derive(Debug, PartialEq)]
+enum LineError {
+    TooLong,
+    Request(RequestError),
+}
+
+/// Reads a line ending in CRLF, or a bare LF, without its line ending. `None` means the input
+/// ended before the line started.
+async fn read_line<R>(reader: &mut R, limit: usize) -> Result<Option<String>, LineError>
+where
+    R: AsyncBufRead + Unpin,
+{
+    let mut line = Vec::new();
+    let read = (&mut *reader)
+        .take(limit as u64 + 2)
+        .read_until(b'\n', &mut line)
+        .await
+        .map_err(|error| LineError::Request(error.into()))?;
+
+    if read == 0 {
+        return Ok(None);
+    }
+    if line.last() != Some(&b'\n') {
+        return Err(if read > limit {
+            LineError::TooLong
+        } else {
+            LineError::Request(RequestError::UnexpectedEof)
+        });
+    }
+    line.pop();
+    if line.last() == Some(&b'\r') {
+        line.pop();
+    }
+    if line.len() > limit {
+        return Err(LineError::TooLong);
+    }
+    String::from_utf8(line).map(Some).map_err(|error| {
+        let line = String::from_utf8_lossy(error.as_bytes()).to_string();
+        LineError::Request(RequestError::MalformedHeader(line))
+    })
+}
+
+fn parse_request_line(line: &str) -> Result<(Method, String, String), RequestError> {
+    let malformed = || RequestError::MalformedRequestLine(line.to_string());
+    let mut parts = line.split(' ');
+    let (Some(method), Some(target), Some(version), None) =
+        (parts.next(), parts.next(), parts.next(), parts.next())
+    else {
+        return Err(malformed());
+    };
+
+    if !version.starts_with("HTTP/") {
+        return Err(malformed());
+    }
+    if version != "HTTP/1.1" && version != "HTTP/1.0" {
+        return Err(RequestError::UnsupportedVersion(version.to_string()));
+    }
+    if method.is_empty() || !method.bytes().all(is_token) {
+        return Err(malformed());
+    }
+    let method = method
+        .parse::<Method>()
+        .map_err(|_| RequestError::UnknownMethod(method.to_string()))?;
+
+    // Absolute-form targets, sent to proxies, are reduced to their path. Only a target not
+    // starting with `/` can be one, as origin-form queries may hold URLs too.
+    let is_scheme = |scheme: &str| {
+        scheme.starts_with(|c: char| c.is_ascii_alphabetic())
+            && scheme
+                .bytes()
+                .all(|byte| byte.is_ascii_alphanumeric() || b"+-.".contains(&byte))
+    };
+    let target = match target.split_once("://") {
+        _ if target.starts_with('/') || target == "*" => target.to_string(),
+        Some((scheme, rest)) if is_scheme(scheme) => match rest.find('/') {
+            Some(start) => rest[start..].to_string(),
+            None => "/".to_string(),
+        },
+        _ => return Err(malformed()),
+    };
+    Ok((method, target, version.to_string()))
+}
+
+/// Whether a byte may appear in a method or header name, the `tchar` of RFC 9110.
+fn is_token(byte: u8) -> bool {
+    byte.is_ascii_alphanumeric() || b"!#$%&'*+-.^_`|~".contains(&byte)
+}
+
+/// Whether a byte may appear in a header value: anything but control characters, tabs aside,
+/// so a bare CR or a NUL can't reach handlers or be echoed into a response.
+fn is_field_value(byte: u8) -> bool {
+    byte == b'\t' || (byte >= 0x20 && byte != 0x7f)
+}
+
+async fn read_headers<R>(reader: &mut R, limits: &RequestLimits) -> Result<Headers, RequestError>
+where
+    R: AsyncBufRead + Unpin,
+{
+    let mut headers = Headers::new();
+    let mut remaining = limits.max_header_bytes;
+    loop {
+        let line = match read_line(reader, remaining).await {
+            Ok(Some(line)) => line,
+            Ok(None) => return Err(RequestError::UnexpectedEof),
+            Err(LineError::TooLong) => {
+                return Err(RequestError::HeadersTooLarge(limits.max_header_bytes));
+            }
+            Err(LineError::Request(error)) => return Err(error),
+        };
+        if line.is_empty() {
+            return Ok(headers);
+        }
+        remaining = remaining.saturating_sub(line.len() + 2);
+        if headers.len() == limits.max_headers {
+            return Err(RequestError::TooManyHeaders(limits.max_headers));
+        }
+
+        // Neither whitespace before the colon nor obsolete line folding are allowed
+        let Some((name, value)) = line.split_once(':') else {
+            return Err(RequestError::MalformedHeader(line));
+        };
+        if name.is_empty() || !name.bytes().all(is_token) {
+            return Err(RequestError::MalformedHeader(line));
+        }
+        if !value.bytes().all(is_field_value) {
+            return Err(RequestError::MalformedHeader(line));
+        }
+        headers.append(name, value.trim_matches([' ', '\t']));
+    }
+}
+
+async fn read_body<R>(
+    reader: &mut R,
+    headers: &Headers,
+    limits: &RequestLimits,
+) -> Result<Vec<u8>, RequestError>
+where
+    R: AsyncBufRead + Unpin,
+{
+    let content_length = content_length(headers)?;
+
+    if headers.contains("Transfer-Encoding") {
+        if content_length.is_some() {
+            return Err(RequestError::ConflictingBodyLength);
+        }
+        let codings: Vec<&str> = headers
+            .get_all("Transfer-Encoding")
+            .flat_map(|value| value.split(','))
+            .map(str::trim)
+            .collect();
+        return match codings.as_slice() {
+            [coding] if coding.eq_ignore_ascii_case("chunked") => {
+                read_chunked(reader, limits).await
+            }
+            _ => Err(RequestError::UnsupportedTransferEncoding(
+                codings.join(", "),
+            )),
+        };
+    }
+
+    let Some(length) = content_length else {
+        return Ok(Vec::new());
+    };
+    if length > limits.max_body as u64 {
+        return Err(RequestError::BodyTooLarge(limits.max_body));
+    }
+    let mut body = vec![0; length as usize];
+    reader
+        .read_exact(&mut body)
+        .await
+        .map_err(|error| match error.kind() {
+            std::io::ErrorKind::UnexpectedEof => RequestError::UnexpectedEof,
+            _ => error.into(),
+        })?;
+    Ok(body)
+}
+
+/// The `Content-Length`, which may be repeated as long as every value is the same.
+fn content_length(headers: &Headers) -> Result<Option<u64>, RequestError> {
+    let mut length = None;
+    for value in headers.get_all("Content-Length").flat_map(|v| v.split(',')) {
+        let value = value.trim();
+        let invalid = || RequestError::InvalidContentLength(value.to_string());
+        if value.is_empty() || !value.bytes().all(|b| b.is_ascii_digit()) {
+            return Err(invalid());
+        }
+        let parsed: u64 = value.parse().map_err(|_| invalid())?;
+        if length.is_some_and(|length| length != parsed) {
+            return Err(invalid());
+        }
+        length = Some(parsed);
+    }
+    Ok(length)
+}
+
+async fn read_chunked<R>(reader: &mut R, limits: &RequestLimits) -> Result<Vec<u8>, RequestError>
+where
+    R: AsyncBufRead + Unpin,
+{
+    let chunk_line = |line: Result<Option<String>, LineError>| match line {
+        Ok(Some(line)) => Ok(line),
+        Ok(None) => Err(RequestError::UnexpectedEof),
+        Err(LineError::TooLong) => Err(RequestError::MalformedChunk(
+            "chunk line is too long".to_string(),
+        )),
+        Err(LineError::Request(error)) => Err(error),
+    };
+
+    let mut body = Vec::new();
+    loop {
+        let line = chunk_line(read_line(reader, limits.max_request_line).await)?;
+        // Chunk extensions, after a `;`, are ignored
+        let size = line.split(';').next().unwrap_or_default().trim();
+        let size = u64::from_str_radix(size, 16)
+            .map_err(|_| RequestError::MalformedChunk(format!("invalid chunk size `{}`", size)))?;
+        if size == 0 {
+            break;
+        }
+        // Compared to what's left, as adding `size` to the length may overflow
+        if size > limits.max_body.saturating_sub(body.len()) as u64 {
+            return Err(RequestError::BodyTooLarge(limits.max_body));
+        }
+
+        let start = body.len();
+        body.resize(start + size as usize, 0);
+        reader
+            .read_exact(&mut body[start..])
+            .await
+            .map_err(|error| match error.kind() {
+                std::io::ErrorKind::UnexpectedEof => RequestError::UnexpectedEof,
+                _ => error.into(),
+            })?;
+        // Only the line ending may follow the chunk data
+        match read_line(reader, 0).await {
+            Ok(Some(_)) => {}
+            Ok(None) => return Err(RequestError::UnexpectedEof),
+            Err(LineError::TooLong) => {
+                return Err(RequestError::MalformedChunk(
+                    "chunk data is longer than its size".to_string(),
+                ));
+            }
+            Err(LineError::Request(error)) => return Err(error),
+        }
+    }
+
+    // Trailer fields are read to leave the connection at the next request, but not kept
+    let mut remaining = limits.max_header_bytes;
+    loop {
+        match read_line(reader, remaining).await {
+            Ok(Some(line)) if line.is_empty() => return Ok(body),
+            Ok(Some(line)) => remaining = remaining.saturating_sub(line.len() + 2),
+            Ok(None) => return Err(RequestError::UnexpectedEof),
+            Err(LineError::TooLong) => {
+                return Err(RequestError::HeadersTooLarge(limits.max_header_bytes));
+            }
+            Err(LineError::Request(error)) => return Err(error),
+        }
+    }
+}
+
+/// Decodes `%XX` escapes, and `+` as a space when `plus_as_space` is set, as in query strings.
+/// Invalid escapes are kept as they are.
+pub fn percent_decode(input: &str, plus_as_space: bool) -> String {
+    String::from_utf8_lossy(&percent_decode_bytes(input, plus_as_space)).to_string()
+}
+
+/// Like [`percent_decode`], but without replacing bytes that aren't valid UTF-8.
+pub fn percent_decode_bytes(input: &str, plus_as_space: bool) -> Vec<u8> {
+    let bytes = input.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        match bytes[i] {
+            b'%' if i + 2 < bytes.len() => {
+                match std::str::from_utf8(&bytes[i + 1..i + 3])
+                    .ok()
+                    .and_then(|hex| u8::from_str_radix(hex, 16).ok())
+                {
+                    Some(byte) => {
+                        decoded.push(byte);
+                        i += 3;
+                        continue;
+                    }
+                    None => decoded.push(b'%'),
+                }
+            }
+            b'+' if plus_as_space => decoded.push(b' '),
+            byte => decoded.push(byte),
+        }
+        i += 1;
+    }
+    decoded
+}
+
+fn parse_query(query: &str) -> Vec<(String, String)> {
+    query
+        .split('&')
+        .filter(|pair| !pair.is_empty())
+        .map(|pair| {
+            let (key, value) = pair.split_once('=').unwrap_or((pair, ""));
+            (percent_decode(key, true), percent_decode(value, true))
+        })
+        .collect()
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
 pub enum Method {
     CONNECT,
     GET,
@@ -35,10 +481,26 @@ pub enum Method {
     TRACE,
 }
 
+impl Method {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Method::CONNECT => "CONNECT",
+            Method::GET => "GET",
+            Method::HEAD => "HEAD",
+            Method::DELETE => "DELETE",
+            Method::OPTIONS => "OPTIONS",
+            Method::PATCH => "PATCH",
+            Method::POST => "POST",
+            Method::PUT => "PUT",
+            Method::TRACE => "TRACE",
+        }
+    }
+}
+
 impl FromStr for Method {
-    type Err = std::io::Error;
+    type Err = RequestError;
 
-    fn from_str(s: &str) -> std::io::Result<Self> {
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
         match s {
             "CONNECT" => Ok(Method::CONNECT),
             "GET" => Ok(Method::GET),
@@ -49,7 +511,7 @@ impl FromStr for Method {
             "POST" => Ok(Method::POST),
             "PUT" => Ok(Method::PUT),
             "TRACE" => Ok(Method::TRACE),
-            _ => Err(std::io::Error::from(std::io::ErrorKind::InvalidData)),
+            _ => Err(RequestError::UnknownMethod(s.to_string())),
         }
     }
 }
@@ -58,6 +520,18 @@ impl FromStr for Method {
 mod test {
     use super::*;
 
+    async fn read(raw: &str) -> Result<Option<HttpRequest>, RequestError> {
+        HttpRequest::read_from(&mut raw.as_bytes(), &RequestLimits::default()).await
+    }
+
+    fn headers(pairs: &[(&str, &str)]) -> Headers {
+        let mut headers = Headers::new();
+        for (name, value) in pairs {
+            headers.append(name, value);
+        }
+        headers
+    }
+
     #[test]
     fn method_creation_succeeds_from_valid_string() {
         let valid_strings = vec![
@@ -85,16 +559,226 @@ mod test {
         assert!("INVALID".parse::<Method>().is_err());
     }
 
-    #[test]
-    fn http_requests_succeeds_from_valid_string() {
-        let http_request_line = "GET /aloha HTTP/1.1";
-        let http_request = http_request_line
-            .parse::<HttpRequest>()
-            .expect("Failed to extract the request info.");
+    #[tokio::test]
+    async fn http_requests_succeeds_from_valid_string() {
+        let http_request = read("GET /aloha HTTP/1.1\r\n\r\n")
+            .await
+            .expect("Failed to extract the request info.")
+            .expect("Missing request");
         let expected = HttpRequest {
             method: Method::GET,
             path: "/aloha".to_string(),
+            query: Vec::new(),
+            raw_query: None,
+            params: Vec::new(),
+            version: "HTTP/1.1".to_string(),
+            remote_address: None,
+            headers: Headers::new(),
+            body: Vec::new(),
         };
         assert_eq!(http_request, expected);
     }
+
+    #[tokio::test]
+    async fn http_request_should_parse_headers_query_and_body() {
+        let raw = "\r\nPOST /search?q=rust+lang&page=2&flag&name=a%26b HTTP/1.1\r\n\
+                   Host: localhost\r\n\
+                   Content-Type:text/plain \r\n\
+                   Content-Length: 5\r\n\r\nhello";
+        let request = read(raw).await.unwrap().unwrap();
+
+        assert_eq!(request.method, Method::POST);
+        assert_eq!(request.path, "/search");
+        assert_eq!(request.query_param("q"), Some("rust lang"));
+        assert_eq!(request.query_param("flag"), Some(""));
+        assert_eq!(request.query_param("name"), Some("a&b"));
+        assert_eq!(
+            request.raw_query.as_deref(),
+            Some("q=rust+lang&page=2&flag&name=a%26b")
+        );
+        assert_eq!(
+            request.headers,
+            headers(&[
+                ("Host", "localhost"),
+                ("Content-Type", "text/plain"),
+                ("Content-Length", "5")
+            ])
+        );
+        assert_eq!(request.body, b"hello");
+    }
+
+    #[tokio::test]
+    async fn http_request_should_only_reduce_absolute_form_targets() {
+        let cases = vec![
+            ("http://example.com/a/b?c=d", "/a/b", Some("c=d")),
+            ("HTTPS://example.com", "/", None),
+            (
+                "/index.html?next=https://x/foobar/aloha.html",
+                "/index.html",
+                Some("next=https://x/foobar/aloha.html"),
+            ),
+        ];
+
+        for (target, path, query) in cases {
+            let raw = format!("GET {} HTTP/1.1\r\n\r\n", target);
+            let request = read(&raw).await.unwrap().unwrap();
+            assert_eq!(request.path, path, "{}", target);
+            assert_eq!(request.raw_query.as_deref(), query, "{}", target);
+        }
+    }
+
+    #[tokio::test]
+    async fn http_request_should_decode_chunked_bodies_and_leave_the_next_request() {
+        let raw = "POST /upload HTTP/1.1\r\nTransfer-Encoding: chunked\r\n\r\n\
+                   5;ext=1\r\nhello\r\n7\r\n, world\r\n0\r\nTrailer: x\r\n\r\n\
+                   GET / HTTP/1.1\n\n";
+        let mut reader = raw.as_bytes();
+        let limits = RequestLimits::default();
+
+        let request = HttpRequest::read_from(&mut reader, &limits)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(request.body, b"hello, world");
+
+        let next = HttpRequest::read_from(&mut reader, &limits)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!((next.method, next.path.as_str()), (Method::GET, "/"));
+        assert_eq!(HttpRequest::read_from(&mut reader, &limits).await, Ok(None));
+    }
+
+    #[tokio::test]
+    async fn http_request_should_reject_malformed_input() {
+        let cases = vec![
+            (
+                "GET /\r\n\r\n",
+                RequestError::MalformedRequestLine("GET /".to_string()),
+            ),
+            (
+                "GET  / HTTP/1.1\r\n\r\n",
+                RequestError::MalformedRequestLine("GET  / HTTP/1.1".to_string()),
+            ),
+            (
+                "BREW / HTTP/1.1\r\n\r\n",
+                RequestError::UnknownMethod("BREW".to_string()),
+            ),
+            (
+                "GET / HTTP/2.0\r\n\r\n",
+                RequestError::UnsupportedVersion("HTTP/2.0".to_string()),
+            ),
+            (
+                "GET relative HTTP/1.1\r\n\r\n",
+                RequestError::MalformedRequestLine("GET relative HTTP/1.1".to_string()),
+            ),
+            (
+                "GET ://x/ HTTP/1.1\r\n\r\n",
+                RequestError::MalformedRequestLine("GET ://x/ HTTP/1.1".to_string()),
+            ),
+            (
+                "GET / HTTP/1.1\r\nHost : x\r\n\r\n",
+                RequestError::MalformedHeader("Host : x".to_string()),
+            ),
+            (
+                "GET / HTTP/1.1\r\nHost: x\r\n folded\r\n\r\n",
+                RequestError::MalformedHeader(" folded".to_string()),
+            ),
+            (
+                "GET / HTTP/1.1\r\nHost: evil.com\rSet-Cookie: x\r\n\r\n",
+                RequestError::MalformedHeader("Host: evil.com\rSet-Cookie: x".to_string()),
+            ),
+            (
+                "GET / HTTP/1.1\r\nX-Name: a\0b\r\n\r\n",
+                RequestError::MalformedHeader("X-Name: a\0b".to_string()),
+            ),
+            (
+                "GET / HTTP/1.1\r\nX-Name: a\x7fb\r\n\r\n",
+                RequestError::MalformedHeader("X-Name: a\x7fb".to_string()),
+            ),
+            ("GET / HTTP/1.1\r\nHost: x\r\n", RequestError::UnexpectedEof),
+            (
+                "POST / HTTP/1.1\r\nContent-Length: -1\r\n\r\n",
+                RequestError::InvalidContentLength("-1".to_string()),
+            ),
+            (
+                "POST / HTTP/1.1\r\nContent-Length: 1\r\nContent-Length: 2\r\n\r\nab",
+                RequestError::InvalidContentLength("2".to_string()),
+            ),
+            (
+                "POST / HTTP/1.1\r\nContent-Length: 10\r\n\r\nshort",
+                RequestError::UnexpectedEof,
+            ),
+            (
+                "POST / HTTP/1.1\r\nContent-Length: 1\r\nTransfer-Encoding: chunked\r\n\r\n",
+                RequestError::ConflictingBodyLength,
+            ),
+            (
+                "POST / HTTP/1.1\r\nTransfer-Encoding: gzip, chunked\r\n\r\n",
+                RequestError::UnsupportedTransferEncoding("gzip, chunked".to_string()),
+            ),
+            (
+                "POST / HTTP/1.1\r\nTransfer-Encoding: chunked\r\n\r\nzz\r\n",
+                RequestError::MalformedChunk("invalid chunk size `zz`".to_string()),
+            ),
+            (
+                "POST / HTTP/1.1\r\nTransfer-Encoding: chunked\r\n\r\n2\r\nabc\r\n0\r\n\r\n",
+                RequestError::MalformedChunk("chunk data is longer than its size".to_string()),
+            ),
+        ];
+
+        for (raw, expected) in cases {
+            assert_eq!(read(raw).await, Err(expected), "{:?}", raw);
+        }
+    }
+
+    #[tokio::test]
+    async fn http_request_should_enforce_limits() {
+        let limits = RequestLimits {
+            max_request_line: 16,
+            max_header_bytes: 32,
+            max_headers: 2,
+            max_body: 4,
+        };
+        let cases = vec![
+            (
+                "GET /a-very-long-path HTTP/1.1\r\n\r\n",
+                RequestError::RequestLineTooLong(16),
+            ),
+            (
+                "GET / HTTP/1.1\r\nX-Long: 0123456789012345678901234567890\r\n\r\n",
+                RequestError::HeadersTooLarge(32),
+            ),
+            (
+                "GET / HTTP/1.1\r\nA: 1\r\nB: 2\r\nC: 3\r\n\r\n",
+                RequestError::TooManyHeaders(2),
+            ),
+            (
+                "POST / HTTP/1.1\r\nContent-Length: 5\r\n\r\nhello",
+                RequestError::BodyTooLarge(4),
+            ),
+            (
+                "POST / HTTP/1.1\r\nTransfer-Encoding: chunked\r\n\r\n3\r\nabc\r\n2\r\nde\r\n0\r\n\r\n",
+                RequestError::BodyTooLarge(4),
+            ),
+            (
+                "POST / HTTP/1.1\r\nTransfer-Encoding: chunked\r\n\r\n1\r\na\r\nffffffffffffffff\r\n",
+                RequestError::BodyTooLarge(4),
+            ),
+        ];
+
+        for (raw, expected) in cases {
+            let result = HttpRequest::read_from(&mut raw.as_bytes(), &limits).await;
+            assert_eq!(result, Err(expected.clone()), "{:?}", raw);
+            assert_ne!(expected.status_code().to_string(), "400 Bad Request");
+        }
+    }
+
+    #[test]
+    fn percent_decode_should_keep_invalid_escapes() {
+        assert_eq!(percent_decode("a%20b%2Fc+d", false), "a b/c+d");
+        assert_eq!(percent_decode("a+b", true), "a b");
+        assert_eq!(percent_decode("100%", false), "100%");
+        assert_eq!(percent_decode("%zz%4", false), "%zz%4");
+    }
 }
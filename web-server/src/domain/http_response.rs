@@ -1,55 +1,414 @@
-use std::fmt::Display;
+use std::{fmt::Display, io::SeekFrom, time::SystemTime};
+
+use tokio::{
+    fs::File,
+    io::{AsyncBufReadExt, AsyncReadExt, AsyncSeekExt, AsyncWrite, AsyncWriteExt, BufReader},
+};
+
+use crate::domain::headers::Headers;
+
+/// Size of the chunks a file body is read from disk and written to the client in.
+const FILE_CHUNK_SIZE: usize = 64 * 1024;
+
+#[derive(Debug)]
+pub enum Body {
+    Empty,
+    Bytes(Vec<u8>),
+    /// `length` bytes of a file from `start`, streamed from disk as the response is written.
+    File {
+        file: File,
+        start: u64,
+        length: u64,
+    },
+    /// Ranges of a file, each preceded by its own bytes, such as the part headers of a
+    /// `multipart/byteranges` body, and followed by `epilogue`.
+    FileParts {
+        file: File,
+        parts: Vec<FilePart>,
+        epilogue: Vec<u8>,
+    },
+    /// A body left out of the answer to a `HEAD` request because its length isn't known without
+    /// producing it, such as content compressed on the fly. It's sent without `Content-Length`.
+    Omitted,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct FilePart {
+    pub head: Vec<u8>,
+    pub start: u64,
+    pub length: u64,
+}
+
+impl Body {
+    pub fn len(&self) -> u64 {
+        match self {
+            Body::Empty | Body::Omitted => 0,
+            Body::Bytes(bytes) => bytes.len() as u64,
+            Body::File { length, .. } => *length,
+            Body::FileParts {
+                parts, epilogue, ..
+            } => {
+                let parts: u64 = parts
+                    .iter()
+                    .map(|part| part.head.len() as u64 + part.length)
+                    .sum();
+                parts + epilogue.len() as u64
+            }
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+/// Identifies this server in the `Server` header.
+pub const SERVER_NAME: &str = concat!(env!("CARGO_PKG_NAME"), "/", env!("CARGO_PKG_VERSION"));
+
+#[derive(Debug, thiserror::Error, PartialEq)]
+pub enum ResponseError {
+    #[error("Invalid header name `{0}`")]
+    InvalidHeaderName(String),
+    #[error("Invalid value for header `{0}`")]
+    InvalidHeaderValue(String),
+    #[error("Header `{0}` is set from the body and can't be set directly")]
+    ReservedHeader(String),
+}
 
 #[derive(Debug)]
 pub struct HttpResponse {
     status_code: StatusCodes,
-    content: String,
+    headers: Headers,
+    body: Body,
 }
 
 impl HttpResponse {
     pub fn new(status_code: StatusCodes, content: Option<String>) -> Self {
         HttpResponse {
             status_code,
-            content: content.unwrap_or("".to_string()),
+            headers: Headers::new(),
+            body: content.map_or(Body::Empty, |content| Body::Bytes(content.into_bytes())),
+        }
+    }
+
+    pub fn builder(status_code: StatusCodes) -> HttpResponseBuilder {
+        HttpResponseBuilder {
+            response: HttpResponse::new(status_code, None),
+            error: None,
+        }
+    }
+
+    pub fn status_code(&self) -> StatusCodes {
+        self.status_code
+    }
+
+    pub fn headers(&self) -> &Headers {
+        &self.headers
+    }
+
+    pub fn body(&self) -> &Body {
+        &self.body
+    }
+
+    /// Adds a header, written after `Content-Length`. Unlike [`HttpResponseBuilder::header`] this
+    /// doesn't validate it, so it's meant for names and values known to be valid.
+    pub fn with_header(mut self, name: &str, value: &str) -> Self {
+        debug_assert!(
+            validate_header(name, value).is_ok(),
+            "invalid header {}",
+            name
+        );
+        self.headers.append(name, value);
+        self
+    }
+
+    pub fn with_body(mut self, body: Body) -> Self {
+        self.body = body;
+        self
+    }
+
+    /// Adds the `Date` and `Server` headers every response carries, unless already set.
+    pub fn with_server_headers(mut self, now: SystemTime) -> Self {
+        if !self.headers.contains("Date") {
+            self.headers.append("Date", &httpdate::fmt_http_date(now));
+        }
+        if !self.headers.contains("Server") {
+            self.headers.append("Server", SERVER_NAME);
         }
+        self
     }
 
+    /// The status line and headers, up to and including the empty line before the body.
+    ///
+    /// Informational, `204 No Content` and `304 Not Modified` responses can't have a body, so
+    /// they are sent without `Content-Length` and their body is dropped.
+    pub fn response_head(&self) -> String {
+        let mut head = format!("HTTP/1.1 {}\r\n", self.status_code);
+        if self.status_code.allows_body() && !matches!(self.body, Body::Omitted) {
+            head.push_str(&format!("Content-Length: {}\r\n", self.body.len()));
+        }
+        for (name, value) in self.headers.iter() {
+            head.push_str(&format!("{}: {}\r\n", name, value));
+        }
+        head.push_str("\r\n");
+        head
+    }
+
+    /// The whole response as text. A file body is not read, so only its head is included.
     pub fn response_string(&self) -> String {
-        let content_length_line = format!("Content-Length: {}", self.content.len());
-        format!(
-            "HTTP/1.1 {}\r\n{}\r\n\r\n{}\r\n",
-            self.status_code, content_length_line, self.content
-        )
+        // Nothing may follow the body, or a client reusing the connection would read it as the
+        // start of the next response
+        match &self.body {
+            Body::Bytes(bytes) if self.status_code.allows_body() => {
+                self.response_head() + &String::from_utf8_lossy(bytes)
+            }
+            _ => self.response_head(),
+        }
+    }
+
+    /// Writes the status line and headers only, as the answer to a `HEAD` request. Its
+    /// `Content-Length` is still the length of the body a `GET` would have received, unless
+    /// the body is [`Body::Omitted`].
+    pub async fn write_head_to<W>(&self, writer: &mut W) -> std::io::Result<()>
+    where
+        W: AsyncWrite + Unpin,
+    {
+        writer.write_all(self.response_head().as_bytes()).await?;
+        writer.flush().await
+    }
+
+    /// Writes the response, streaming a file body in chunks rather than loading it in memory.
+    pub async fn write_to<W>(self, writer: &mut W) -> std::io::Result<()>
+    where
+        W: AsyncWrite + Unpin,
+    {
+        writer.write_all(self.response_head().as_bytes()).await?;
+        if !self.status_code.allows_body() {
+            return writer.flush().await;
+        }
+        match self.body {
+            Body::Empty | Body::Omitted => {}
+            Body::Bytes(bytes) => writer.write_all(&bytes).await?,
+            Body::File {
+                mut file,
+                start,
+                length,
+            } => copy_file_range(&mut file, start, length, writer).await?,
+            Body::FileParts {
+                mut file,
+                parts,
+                epilogue,
+            } => {
+                for part in parts {
+                    writer.write_all(&part.head).await?;
+                    copy_file_range(&mut file, part.start, part.length, writer).await?;
+                }
+                writer.write_all(&epilogue).await?;
+            }
+        }
+        writer.flush().await
+    }
+}
+
+/// Streams `length` bytes of `file` from `start` in chunks.
+async fn copy_file_range<W>(
+    file: &mut File,
+    start: u64,
+    length: u64,
+    writer: &mut W,
+) -> std::io::Result<()>
+where
+    W: AsyncWrite + Unpin,
+{
+    file.seek(SeekFrom::Start(start)).await?;
+    let mut reader = BufReader::with_capacity(FILE_CHUNK_SIZE, file.take(length));
+    loop {
+        let chunk = reader.fill_buf().await?;
+        if chunk.is_empty() {
+            break;
+        }
+        writer.write_all(chunk).await?;
+        let written = chunk.len();
+        reader.consume(written);
+    }
+    if reader.into_inner().limit() > 0 {
+        return Err(std::io::Error::new(
+            std::io::ErrorKind::UnexpectedEof,
+            "file is shorter than its Content-Length",
+        ));
     }
+    Ok(())
 }
 
+/// Builds an [`HttpResponse`], checking every header so the response can be serialized safely.
+/// The first invalid header is reported by [`HttpResponseBuilder::build`].
 #[derive(Debug)]
-pub enum StatusCodes {
-    OK,
-    Created,
-    Accepted,
-    NoContent,
-    NotFound,
-    InternalServerError,
+pub struct HttpResponseBuilder {
+    response: HttpResponse,
+    error: Option<ResponseError>,
+}
+
+impl HttpResponseBuilder {
+    /// Adds a header, keeping any previous value of the same header.
+    pub fn header(mut self, name: &str, value: &str) -> Self {
+        if self.error.is_none() {
+            match validate_header(name, value) {
+                Ok(()) => self.response.headers.append(name, value),
+                Err(error) => self.error = Some(error),
+            }
+        }
+        self
+    }
+
+    pub fn body(mut self, body: impl Into<Vec<u8>>) -> Self {
+        self.response.body = Body::Bytes(body.into());
+        self
+    }
+
+    /// Streams the first `length` bytes of `file` as the body.
+    pub fn file(self, file: File, length: u64) -> Self {
+        self.body_from(Body::File {
+            file,
+            start: 0,
+            length,
+        })
+    }
+
+    pub fn body_from(mut self, body: Body) -> Self {
+        self.response.body = body;
+        self
+    }
+
+    pub fn build(self) -> Result<HttpResponse, ResponseError> {
+        match self.error {
+            Some(error) => Err(error),
+            None => Ok(self.response),
+        }
+    }
+}
+
+/// Checks a header name is a token and its value has no control characters, so neither can
+/// inject extra lines into the response.
+fn validate_header(name: &str, value: &str) -> Result<(), ResponseError> {
+    let is_token = |byte: u8| byte.is_ascii_alphanumeric() || b"!#$%&'*+-.^_`|~".contains(&byte);
+    if name.is_empty() || !name.bytes().all(is_token) {
+        return Err(ResponseError::InvalidHeaderName(name.to_string()));
+    }
+    if name.eq_ignore_ascii_case("Content-Length") || name.eq_ignore_ascii_case("Transfer-Encoding")
+    {
+        return Err(ResponseError::ReservedHeader(name.to_string()));
+    }
+    if value
+        .bytes()
+        .any(|byte| (byte < 0x20 && byte != b'\t') || byte == 0x7f)
+    {
+        return Err(ResponseError::InvalidHeaderValue(name.to_string()));
+    }
+    Ok(())
+}
+
+macro_rules! status_codes {
+    ($($variant:ident = $code:literal $reason:literal,)*) => {
+        /// Every status code defined by RFC 9110, plus the ones from later RFCs in common use.
+        #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+        pub enum StatusCodes {
+            $($variant,)*
+        }
+
+        impl StatusCodes {
+            pub fn code(&self) -> u16 {
+                match self {
+                    $(StatusCodes::$variant => $code,)*
+                }
+            }
+
+            pub fn reason(&self) -> &'static str {
+                match self {
+                    $(StatusCodes::$variant => $reason,)*
+                }
+            }
+
+            pub fn from_code(code: u16) -> Option<Self> {
+                match code {
+                    $($code => Some(StatusCodes::$variant),)*
+                    _ => None,
+                }
+            }
+        }
+    };
+}
+
+status_codes! {
+    Continue = 100 "Continue",
+    SwitchingProtocols = 101 "Switching Protocols",
+    EarlyHints = 103 "Early Hints",
+    OK = 200 "OK",
+    Created = 201 "Created",
+    Accepted = 202 "Accepted",
+    NonAuthoritativeInformation = 203 "Non-Authoritative Information",
+    NoContent = 204 "No Content",
+    ResetContent = 205 "Reset Content",
+    PartialContent = 206 "Partial Content",
+    MultipleChoices = 300 "Multiple Choices",
+    MovedPermanently = 301 "Moved Permanently",
+    Found = 302 "Found",
+    SeeOther = 303 "See Other",
+    NotModified = 304 "Not Modified",
+    TemporaryRedirect = 307 "Temporary Redirect",
+    PermanentRedirect = 308 "Permanent Redirect",
+    BadRequest = 400 "Bad Request",
+    Unauthorized = 401 "Unauthorized",
+    PaymentRequired = 402 "Payment Required",
+    Forbidden = 403 "Forbidden",
+    NotFound = 404 "Not Found",
+    MethodNotAllowed = 405 "Method Not Allowed",
+    NotAcceptable = 406 "Not Acceptable",
+    ProxyAuthenticationRequired = 407 "Proxy Authentication Required",
+    RequestTimeout = 408 "Request Timeout",
+    Conflict = 409 "Conflict",
+    Gone = 410 "Gone",
+    LengthRequired = 411 "Length Required",
+    PreconditionFailed = 412 "Precondition Failed",
+    PayloadTooLarge = 413 "Payload Too Large",
+    URITooLong = 414 "URI Too Long",
+    UnsupportedMediaType = 415 "Unsupported Media Type",
+    RangeNotSatisfiable = 416 "Range Not Satisfiable",
+    ExpectationFailed = 417 "Expectation Failed",
+    MisdirectedRequest = 421 "Misdirected Request",
+    UnprocessableContent = 422 "Unprocessable Content",
+    UpgradeRequired = 426 "Upgrade Required",
+    PreconditionRequired = 428 "Precondition Required",
+    TooManyRequests = 429 "Too Many Requests",
+    RequestHeaderFieldsTooLarge = 431 "Request Header Fields Too Large",
+    UnavailableForLegalReasons = 451 "Unavailable For Legal Reasons",
+    InternalServerError = 500 "Internal Server Error",
+    NotImplemented = 501 "Not Implemented",
+    BadGateway = 502 "Bad Gateway",
+    ServiceUnavailable = 503 "Service Unavailable",
+    GatewayTimeout = 504 "Gateway Timeout",
+    HTTPVersionNotSupported = 505 "HTTP Version Not Supported",
+    NetworkAuthenticationRequired = 511 "Network Authentication Required",
+}
+
+impl StatusCodes {
+    /// Whether a response with this status may carry content, per RFC 9110 section 6.4.1.
+    pub fn allows_body(&self) -> bool {
+        let code = self.code();
+        code >= 200 && code != 204 && code != 304
+    }
 }
 
 impl Display for StatusCodes {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        let str = match self {
-            StatusCodes::OK => "200 OK",
-            StatusCodes::Created => "201 Created",
-            StatusCodes::Accepted => "202 Accepted",
-            StatusCodes::NoContent => "204 No Content",
-            StatusCodes::NotFound => "404 Not Found",
-            StatusCodes::InternalServerError => "500 Internal Server Error",
-        };
-        write!(f, "{}", str)
+        write!(f, "{} {}", self.code(), self.reason())
     }
 }
 
 #[cfg(test)]
 mod tests {
-    use super::{HttpResponse, StatusCodes};
+    use std::time::{Duration, SystemTime};
+
+    use super::{Body, FilePart, HttpResponse, ResponseError, SERVER_NAME, StatusCodes};
 
     #[test]
     fn http_response_builds_ok_string() {
@@ -57,7 +416,7 @@ mod tests {
             .response_string();
         assert_eq!(
             str,
-            "HTTP/1.1 200 OK\r\nContent-Length: 23\r\n\r\nRequested path: /foobar\r\n"
+            "HTTP/1.1 200 OK\r\nContent-Length: 23\r\n\r\nRequested path: /foobar"
         )
     }
 
@@ -70,7 +429,7 @@ mod tests {
         .response_string();
         assert_eq!(
             str,
-            "HTTP/1.1 201 Created\r\nContent-Length: 23\r\n\r\nRequested path: /foobar\r\n"
+            "HTTP/1.1 201 Created\r\nContent-Length: 23\r\n\r\nRequested path: /foobar"
         )
     }
 
@@ -83,26 +442,20 @@ mod tests {
         .response_string();
         assert_eq!(
             str,
-            "HTTP/1.1 202 Accepted\r\nContent-Length: 23\r\n\r\nRequested path: /foobar\r\n"
+            "HTTP/1.1 202 Accepted\r\nContent-Length: 23\r\n\r\nRequested path: /foobar"
         )
     }
 
     #[test]
     fn http_response_builds_no_content_string() {
         let str = HttpResponse::new(StatusCodes::NoContent, None).response_string();
-        assert_eq!(
-            str,
-            "HTTP/1.1 204 No Content\r\nContent-Length: 0\r\n\r\n\r\n"
-        )
+        assert_eq!(str, "HTTP/1.1 204 No Content\r\n\r\n")
     }
 
     #[test]
     fn http_response_builds_not_found_string() {
         let str = HttpResponse::new(StatusCodes::NotFound, None).response_string();
-        assert_eq!(
-            str,
-            "HTTP/1.1 404 Not Found\r\nContent-Length: 0\r\n\r\n\r\n"
-        )
+        assert_eq!(str, "HTTP/1.1 404 Not Found\r\nContent-Length: 0\r\n\r\n")
     }
 
     #[test]
@@ -110,7 +463,181 @@ mod tests {
         let str = HttpResponse::new(StatusCodes::InternalServerError, None).response_string();
         assert_eq!(
             str,
-            "HTTP/1.1 500 Internal Server Error\r\nContent-Length: 0\r\n\r\n\r\n"
+            "HTTP/1.1 500 Internal Server Error\r\nContent-Length: 0\r\n\r\n"
         )
     }
+
+    #[test]
+    fn http_response_builds_string_with_headers() {
+        let str = HttpResponse::new(StatusCodes::NoContent, None)
+            .with_header("Connection", "close")
+            .response_string();
+        assert_eq!(str, "HTTP/1.1 204 No Content\r\nConnection: close\r\n\r\n")
+    }
+
+    #[test]
+    fn http_response_leaves_out_the_length_of_omitted_bodies() {
+        let str = HttpResponse::new(StatusCodes::OK, None)
+            .with_body(Body::Omitted)
+            .with_header("Content-Encoding", "gzip")
+            .response_string();
+        assert_eq!(str, "HTTP/1.1 200 OK\r\nContent-Encoding: gzip\r\n\r\n")
+    }
+
+    #[tokio::test]
+    async fn http_response_streams_file_bodies() {
+        let content: Vec<u8> = (0..=255).cycle().take(200_000).collect();
+        let path = tempfile::NamedTempFile::new().unwrap().into_temp_path();
+        std::fs::write(&path, &content).unwrap();
+
+        let file = tokio::fs::File::open(&path).await.unwrap();
+        let response = HttpResponse::new(StatusCodes::OK, None).with_body(Body::File {
+            file,
+            start: 1_000,
+            length: 150_000,
+        });
+        let mut written = Vec::new();
+        response.write_to(&mut written).await.unwrap();
+
+        let head = b"HTTP/1.1 200 OK\r\nContent-Length: 150000\r\n\r\n";
+        assert_eq!(&written[..head.len()], head);
+        assert_eq!(&written[head.len()..], &content[1_000..151_000]);
+    }
+
+    #[tokio::test]
+    async fn http_response_streams_file_parts() {
+        let path = tempfile::NamedTempFile::new().unwrap().into_temp_path();
+        std::fs::write(&path, "0123456789").unwrap();
+
+        let file = tokio::fs::File::open(&path).await.unwrap();
+        let part = |head: &str, start, length| FilePart {
+            head: head.as_bytes().to_vec(),
+            start,
+            length,
+        };
+        let body = Body::FileParts {
+            file,
+            parts: vec![part("<a>", 7, 3), part("<b>", 0, 2)],
+            epilogue: b"<end>".to_vec(),
+        };
+        assert_eq!(body.len(), 16);
+
+        let mut written = Vec::new();
+        HttpResponse::new(StatusCodes::PartialContent, None)
+            .with_body(body)
+            .write_to(&mut written)
+            .await
+            .unwrap();
+        assert_eq!(
+            String::from_utf8(written).unwrap(),
+            "HTTP/1.1 206 Partial Content\r\nContent-Length: 16\r\n\r\n<a>789<b>01<end>"
+        );
+    }
+
+    #[test]
+    fn http_response_builder_sets_headers_and_byte_bodies() {
+        let response = HttpResponse::builder(StatusCodes::Created)
+            .header("Content-Type", "application/octet-stream")
+            .header("Location", "/files/1")
+            .body(vec![0xff, 0x00, 0xfe])
+            .build()
+            .unwrap();
+
+        assert_eq!(
+            response.response_head(),
+            "HTTP/1.1 201 Created\r\nContent-Length: 3\r\n\
+             Content-Type: application/octet-stream\r\nLocation: /files/1\r\n\r\n"
+        );
+        assert!(matches!(response.body(), Body::Bytes(bytes) if bytes == &[0xff, 0x00, 0xfe]));
+    }
+
+    #[test]
+    fn http_response_builder_rejects_invalid_headers() {
+        let cases = vec![
+            (
+                "X-Injected",
+                "a\r\nSet-Cookie: b",
+                ResponseError::InvalidHeaderValue("X-Injected".to_string()),
+            ),
+            (
+                "Bad Name",
+                "a",
+                ResponseError::InvalidHeaderName("Bad Name".to_string()),
+            ),
+            ("", "a", ResponseError::InvalidHeaderName("".to_string())),
+            (
+                "content-length",
+                "1",
+                ResponseError::ReservedHeader("content-length".to_string()),
+            ),
+        ];
+
+        for (name, value, expected) in cases {
+            let result = HttpResponse::builder(StatusCodes::OK)
+                .header(name, value)
+                .header("Valid", "value")
+                .build();
+            assert_eq!(result.unwrap_err(), expected);
+        }
+    }
+
+    #[test]
+    fn http_response_adds_date_and_server_headers() {
+        let now = SystemTime::UNIX_EPOCH + Duration::from_secs(784111777);
+        let response = HttpResponse::new(StatusCodes::OK, None).with_server_headers(now);
+        assert_eq!(
+            response.response_string(),
+            format!(
+                "HTTP/1.1 200 OK\r\nContent-Length: 0\r\n\
+                 Date: Sun, 06 Nov 1994 08:49:37 GMT\r\nServer: {}\r\n\r\n",
+                SERVER_NAME
+            )
+        );
+
+        let response = HttpResponse::new(StatusCodes::OK, None)
+            .with_header("Server", "custom")
+            .with_server_headers(now);
+        assert_eq!(
+            response.headers().get_all("Server").collect::<Vec<_>>(),
+            vec!["custom"]
+        );
+    }
+
+    #[test]
+    fn http_response_omits_bodies_where_not_allowed() {
+        for status_code in [
+            StatusCodes::Continue,
+            StatusCodes::NoContent,
+            StatusCodes::NotModified,
+        ] {
+            let str = HttpResponse::new(status_code, Some("ignored".to_string())).response_string();
+            assert_eq!(str, format!("HTTP/1.1 {}\r\n\r\n", status_code));
+        }
+    }
+
+    #[test]
+    fn status_codes_round_trip_through_their_code() {
+        for code in 100..600 {
+            if let Some(status_code) = StatusCodes::from_code(code) {
+                assert_eq!(status_code.code(), code);
+                assert_eq!(
+                    status_code.to_string(),
+                    format!("{} {}", code, status_code.reason())
+                );
+            }
+        }
+        assert_eq!(
+            StatusCodes::from_code(416),
+            Some(StatusCodes::RangeNotSatisfiable)
+        );
+        assert_eq!(StatusCodes::from_code(299), None);
+    }
+
+    #[tokio::test]
+    async fn http_response_writes_only_the_head_for_head_requests() {
+        let response = HttpResponse::new(StatusCodes::OK, Some("Hello".to_string()));
+        let mut written = Vec::new();
+        response.write_head_to(&mut written).await.unwrap();
+        assert_eq!(written, b"HTTP/1.1 200 OK\r\nContent-Length: 5\r\n\r\n");
+    }
 }
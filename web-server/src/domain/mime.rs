@@ -0,0 +1,133 @@
+use std::{collections::HashMap, path::Path};
+
+/// Sent for files whose extension isn't mapped to a type.
+pub const DEFAULT_MIME_TYPE: &str = "application/octet-stream";
+
+const DEFAULT_MIME_TYPES: &[(&str, &str)] = &[
+    ("html", "text/html; charset=utf-8"),
+    ("htm", "text/html; charset=utf-8"),
+    ("css", "text/css; charset=utf-8"),
+    ("js", "text/javascript; charset=utf-8"),
+    ("mjs", "text/javascript; charset=utf-8"),
+    ("json", "application/json"),
+    ("map", "application/json"),
+    ("xml", "application/xml"),
+    ("txt", "text/plain; charset=utf-8"),
+    ("md", "text/markdown; charset=utf-8"),
+    ("csv", "text/csv; charset=utf-8"),
+    ("wasm", "application/wasm"),
+    ("pdf", "application/pdf"),
+    ("zip", "application/zip"),
+    ("gz", "application/gzip"),
+    ("png", "image/png"),
+    ("jpg", "image/jpeg"),
+    ("jpeg", "image/jpeg"),
+    ("gif", "image/gif"),
+    ("webp", "image/webp"),
+    ("avif", "image/avif"),
+    ("svg", "image/svg+xml"),
+    ("ico", "image/x-icon"),
+    ("woff", "font/woff"),
+    ("woff2", "font/woff2"),
+    ("ttf", "font/ttf"),
+    ("otf", "font/otf"),
+    ("mp3", "audio/mpeg"),
+    ("ogg", "audio/ogg"),
+    ("wav", "audio/wav"),
+    ("mp4", "video/mp4"),
+    ("webm", "video/webm"),
+];
+
+/// Maps file extensions to the `Content-Type` they are served with.
+///
+/// Starts with the common web types, which can be overridden or extended with [`MimeTypes::insert`].
+#[derive(Debug, Clone)]
+pub struct MimeTypes(HashMap<String, String>);
+
+impl Default for MimeTypes {
+    fn default() -> Self {
+        MimeTypes(
+            DEFAULT_MIME_TYPES
+                .iter()
+                .map(|(extension, mime)| (extension.to_string(), mime.to_string()))
+                .collect(),
+        )
+    }
+}
+
+impl MimeTypes {
+    /// A map without any extension, so every file is served as [`DEFAULT_MIME_TYPE`].
+    pub fn empty() -> Self {
+        MimeTypes(HashMap::new())
+    }
+
+    /// Maps `extension`, given without the leading dot, to `mime`. Extensions are matched
+    /// case-insensitively.
+    pub fn insert(&mut self, extension: &str, mime: &str) {
+        self.0
+            .insert(extension.to_ascii_lowercase(), mime.to_string());
+    }
+
+    pub fn get(&self, extension: &str) -> Option<&str> {
+        self.0
+            .get(&extension.to_ascii_lowercase())
+            .map(String::as_str)
+    }
+
+    /// The type to serve the file at `path` with.
+    pub fn content_type(&self, path: &Path) -> &str {
+        path.extension()
+            .and_then(|extension| extension.to_str())
+            .and_then(|extension| self.get(extension))
+            .unwrap_or(DEFAULT_MIME_TYPE)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use super::{DEFAULT_MIME_TYPE, MimeTypes};
+
+    #[test]
+    fn content_type_should_match_extensions_case_insensitively() {
+        let mime_types = MimeTypes::default();
+        assert_eq!(
+            mime_types.content_type(Path::new("www/index.HTML")),
+            "text/html; charset=utf-8"
+        );
+        assert_eq!(mime_types.content_type(Path::new("logo.png")), "image/png");
+        assert_eq!(
+            mime_types.content_type(Path::new("archive.tar.gz")),
+            "application/gzip"
+        );
+        assert_eq!(
+            mime_types.content_type(Path::new("README")),
+            DEFAULT_MIME_TYPE
+        );
+        assert_eq!(
+            mime_types.content_type(Path::new("data.unknown")),
+            DEFAULT_MIME_TYPE
+        );
+    }
+
+    #[test]
+    fn insert_should_override_defaults() {
+        let mut mime_types = MimeTypes::default();
+        mime_types.insert("TXT", "text/plain; charset=latin1");
+        mime_types.insert("webmanifest", "application/manifest+json");
+
+        assert_eq!(
+            mime_types.content_type(Path::new("notes.txt")),
+            "text/plain; charset=latin1"
+        );
+        assert_eq!(
+            mime_types.content_type(Path::new("site.webmanifest")),
+            "application/manifest+json"
+        );
+        assert_eq!(
+            MimeTypes::empty().content_type(Path::new("a.html")),
+            DEFAULT_MIME_TYPE
+        );
+    }
+}
@@ -1,2 +1,9 @@
+pub mod autoindex;
+pub mod caching;
+pub mod compression;
+pub mod headers;
 pub mod http_request;
 pub mod http_response;
+pub mod mime;
+pub mod range;
+pub mod request_path;
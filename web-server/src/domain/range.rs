@@ -0,0 +1,302 @@
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use tokio::fs::File;
+
+use crate::domain::{
+    caching::Validators,
+    http_response::{Body, FilePart},
+};
+
+/// Requests asking for more ranges than this are answered in full, as a large number of small
+/// ranges costs the server far more than sending the whole file.
+pub const MAX_RANGES: usize = 32;
+
+/// A range of bytes, both ends included, as in `Content-Range`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ByteRange {
+    pub first: u64,
+    pub last: u64,
+}
+
+impl ByteRange {
+    pub fn length(&self) -> u64 {
+        self.last - self.first + 1
+    }
+
+    pub fn content_range(&self, complete_length: u64) -> String {
+        format!("bytes {}-{}/{}", self.first, self.last, complete_length)
+    }
+}
+
+/// What a `Range` header asks for, once checked against the length of the file.
+#[derive(Debug, PartialEq)]
+pub enum RangeRequest {
+    /// The header is missing, invalid or uses another unit, so the whole file is sent.
+    Full,
+    Partial(Vec<ByteRange>),
+    /// None of the ranges overlaps the file, which is answered with `416`.
+    Unsatisfiable,
+}
+
+/// Parses a `Range` header as RFC 9110 section 14.2 describes, against a file of `length` bytes.
+///
+/// A syntactically invalid header is ignored rather than rejected, as the RFC requires, and so
+/// is one with more than [`MAX_RANGES`] ranges. Ranges past the end of the file are dropped and
+/// the rest are clamped to it. Overlapping or adjacent ranges are coalesced, in ascending order,
+/// so no byte is sent twice however many times it is asked for.
+pub fn parse_range(header: Option<&str>, length: u64) -> RangeRequest {
+    let Some(header) = header else {
+        return RangeRequest::Full;
+    };
+    let Some((unit, specs)) = header.split_once('=') else {
+        return RangeRequest::Full;
+    };
+    if !unit.trim().eq_ignore_ascii_case("bytes") {
+        return RangeRequest::Full;
+    }
+
+    let specs: Vec<&str> = specs
+        .split(',')
+        .map(str::trim)
+        .filter(|spec| !spec.is_empty())
+        .collect();
+    if specs.is_empty() || specs.len() > MAX_RANGES {
+        return RangeRequest::Full;
+    }
+
+    let mut ranges = Vec::new();
+    for spec in specs {
+        let Some((first, last)) = spec.split_once('-') else {
+            return RangeRequest::Full;
+        };
+        let (Ok(first), Ok(last)) = (parse_position(first), parse_position(last)) else {
+            return RangeRequest::Full;
+        };
+        let range = match (first, last) {
+            (Some(first), Some(last)) if first > last => return RangeRequest::Full,
+            (Some(first), last) if first < length => ByteRange {
+                first,
+                last: last.unwrap_or(u64::MAX).min(length - 1),
+            },
+            (Some(_), _) => continue,
+            // A suffix range asks for the last `suffix` bytes
+            (None, Some(suffix)) if suffix > 0 && length > 0 => ByteRange {
+                first: length.saturating_sub(suffix),
+                last: length - 1,
+            },
+            (None, Some(_)) => continue,
+            (None, None) => return RangeRequest::Full,
+        };
+        ranges.push(range);
+    }
+
+    if ranges.is_empty() {
+        RangeRequest::Unsatisfiable
+    } else {
+        RangeRequest::Partial(coalesce(ranges))
+    }
+}
+
+fn coalesce(mut ranges: Vec<ByteRange>) -> Vec<ByteRange> {
+    ranges.sort_by_key(|range| range.first);
+    let mut coalesced: Vec<ByteRange> = Vec::with_capacity(ranges.len());
+    for range in ranges {
+        match coalesced.last_mut() {
+            Some(previous) if range.first <= previous.last.saturating_add(1) => {
+                previous.last = previous.last.max(range.last);
+            }
+            _ => coalesced.push(range),
+        }
+    }
+    coalesced
+}
+
+/// An empty position is `None`, anything else has to be digits only.
+fn parse_position(position: &str) -> Result<Option<u64>, ()> {
+    let position = position.trim();
+    if position.is_empty() {
+        return Ok(None);
+    }
+    if !position.bytes().all(|byte| byte.is_ascii_digit()) {
+        return Err(());
+    }
+    position.parse().map(Some).map_err(|_| ())
+}
+
+/// Whether the `If-Range` precondition holds, so a `Range` header should be honoured.
+///
+/// An entity tag only matches with strong comparison, so weak ones never do, and a date only
+/// matches the exact `Last-Modified` (RFC 9110 section 13.1.5).
+pub fn if_range_matches(if_range: Option<&str>, validators: &Validators) -> bool {
+    let Some(if_range) = if_range.map(str::trim) else {
+        return true;
+    };
+    if if_range.starts_with('"') || if_range.starts_with("W/") {
+        return !if_range.starts_with("W/") && validators.etag.as_deref() == Some(if_range);
+    }
+    match (
+        httpdate::parse_http_date(if_range),
+        validators.last_modified,
+    ) {
+        (Ok(date), Some(last_modified)) => date == last_modified,
+        _ => false,
+    }
+}
+
+/// A `multipart/byteranges` body with a part per range, and its `Content-Type`.
+pub fn multipart_body(
+    file: File,
+    ranges: &[ByteRange],
+    content_type: &str,
+    complete_length: u64,
+) -> (Body, String) {
+    let boundary = boundary();
+    let parts = ranges
+        .iter()
+        .map(|range| FilePart {
+            head: format!(
+                "\r\n--{}\r\nContent-Type: {}\r\nContent-Range: {}\r\n\r\n",
+                boundary,
+                content_type,
+                range.content_range(complete_length)
+            )
+            .into_bytes(),
+            start: range.first,
+            length: range.length(),
+        })
+        .collect();
+    let body = Body::FileParts {
+        file,
+        parts,
+        epilogue: format!("\r\n--{}--\r\n", boundary).into_bytes(),
+    };
+    (body, format!("multipart/byteranges; boundary={}", boundary))
+}
+
+/// A boundary unlikely to appear within the file. It doesn't need to be unpredictable, only
+/// different from the content.
+fn boundary() -> String {
+    let nanos = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_nanos() as u64;
+    let mixed =
+        (nanos ^ (std::process::id() as u64).rotate_left(32)).wrapping_mul(0x9e3779b97f4a7c15);
+    format!("web-server-{:016x}", mixed)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, UNIX_EPOCH};
+
+    use super::{ByteRange, MAX_RANGES, RangeRequest, if_range_matches, parse_range};
+    use crate::domain::caching::Validators;
+
+    fn partial(ranges: &[(u64, u64)]) -> RangeRequest {
+        RangeRequest::Partial(
+            ranges
+                .iter()
+                .map(|&(first, last)| ByteRange { first, last })
+                .collect(),
+        )
+    }
+
+    #[test]
+    fn parse_range_should_read_byte_ranges() {
+        let cases = vec![
+            ("bytes=0-499", partial(&[(0, 499)])),
+            ("bytes=500-", partial(&[(500, 999)])),
+            ("bytes=-200", partial(&[(800, 999)])),
+            ("bytes=-5000", partial(&[(0, 999)])),
+            ("bytes=900-5000", partial(&[(900, 999)])),
+            (
+                "Bytes = 0-0, 10-19 ,-1",
+                partial(&[(0, 0), (10, 19), (999, 999)]),
+            ),
+            ("bytes=0-9,2000-3000", partial(&[(0, 9)])),
+        ];
+
+        for (header, expected) in cases {
+            assert_eq!(parse_range(Some(header), 1000), expected, "{}", header);
+        }
+    }
+
+    #[test]
+    fn parse_range_should_coalesce_overlapping_ranges() {
+        let repeated = format!("bytes={}", vec!["0-"; MAX_RANGES].join(","));
+        let cases = vec![
+            (repeated.as_str(), partial(&[(0, 999)])),
+            ("bytes=500-599,0-9,550-700", partial(&[(0, 9), (500, 700)])),
+            ("bytes=10-19,20-29,-1", partial(&[(10, 29), (999, 999)])),
+            ("bytes=0-99,-950", partial(&[(0, 999)])),
+        ];
+
+        for (header, expected) in cases {
+            assert_eq!(parse_range(Some(header), 1000), expected, "{}", header);
+        }
+    }
+
+    #[test]
+    fn parse_range_should_detect_unsatisfiable_ranges() {
+        for header in ["bytes=1000-", "bytes=2000-3000, 1500-", "bytes=-0"] {
+            assert_eq!(
+                parse_range(Some(header), 1000),
+                RangeRequest::Unsatisfiable,
+                "{}",
+                header
+            );
+        }
+        assert_eq!(
+            parse_range(Some("bytes=0-"), 0),
+            RangeRequest::Unsatisfiable
+        );
+    }
+
+    #[test]
+    fn parse_range_should_ignore_invalid_headers() {
+        let too_many = format!("bytes={}", vec!["0-0"; MAX_RANGES + 1].join(","));
+        let headers = [
+            "items=0-1",
+            "bytes",
+            "bytes=",
+            "bytes=5-1",
+            "bytes=a-b",
+            "bytes=-",
+            "bytes=+1-2",
+            "bytes=0-1;x",
+            too_many.as_str(),
+        ];
+
+        for header in headers {
+            assert_eq!(
+                parse_range(Some(header), 1000),
+                RangeRequest::Full,
+                "{}",
+                header
+            );
+        }
+        assert_eq!(parse_range(None, 1000), RangeRequest::Full);
+    }
+
+    #[test]
+    fn if_range_should_use_strong_comparison() {
+        let validators = Validators {
+            etag: Some("\"abc\"".to_string()),
+            last_modified: Some(UNIX_EPOCH + Duration::from_secs(784111777)),
+        };
+
+        assert!(if_range_matches(None, &validators));
+        assert!(if_range_matches(Some("\"abc\""), &validators));
+        assert!(!if_range_matches(Some("W/\"abc\""), &validators));
+        assert!(!if_range_matches(Some("\"xyz\""), &validators));
+        assert!(if_range_matches(
+            Some("Sun, 06 Nov 1994 08:49:37 GMT"),
+            &validators
+        ));
+        assert!(!if_range_matches(
+            Some("Mon, 07 Nov 1994 08:49:37 GMT"),
+            &validators
+        ));
+        assert!(!if_range_matches(Some("garbage"), &validators));
+    }
+}
@@ -0,0 +1,103 @@
+use std::path::PathBuf;
+
+use crate::domain::http_request::percent_decode_bytes;
+
+#[derive(Debug, thiserror::Error, PartialEq)]
+pub enum PathError {
+    #[error("Path `{0}` doesn't start with `/`")]
+    NotAbsolute(String),
+    #[error("Path `{0}` is not valid UTF-8 once decoded")]
+    InvalidEncoding(String),
+    #[error("Path `{0}` contains a NUL byte")]
+    NulByte(String),
+    #[error("Path `{0}` goes above the document root")]
+    Traversal(String),
+}
+
+/// Turns a request path into a path relative to the document root.
+///
+/// The path is percent-decoded before it's normalized, so `%2e%2e` is handled like `..`, and
+/// empty and `.` segments are dropped. A `..` that would leave the root is an error rather than
+/// being clamped, as no legitimate client sends one.
+pub fn sanitize_path(path: &str) -> Result<PathBuf, PathError> {
+    if !path.starts_with('/') {
+        return Err(PathError::NotAbsolute(path.to_string()));
+    }
+    let decoded = String::from_utf8(percent_decode_bytes(path, false))
+        .map_err(|_| PathError::InvalidEncoding(path.to_string()))?;
+    if decoded.contains('\0') {
+        return Err(PathError::NulByte(path.to_string()));
+    }
+
+    let mut segments = Vec::new();
+    for segment in decoded.split('/') {
+        match segment {
+            "" | "." => {}
+            ".." => {
+                segments
+                    .pop()
+                    .ok_or_else(|| PathError::Traversal(path.to_string()))?;
+            }
+            segment => segments.push(segment),
+        }
+    }
+    Ok(segments.iter().collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use super::{PathError, sanitize_path};
+
+    #[test]
+    fn sanitize_path_should_decode_and_normalize() {
+        let cases = vec![
+            ("/", ""),
+            ("/index.html", "index.html"),
+            ("//foobar/./aloha.html", "foobar/aloha.html"),
+            ("/foobar/../index.html", "index.html"),
+            ("/foobar/%2E%2e/index.html", "index.html"),
+            ("/hello%20world.txt", "hello world.txt"),
+            ("/a+b", "a+b"),
+            ("/%2fetc/passwd", "etc/passwd"),
+            ("/..%5cCargo.toml", "..\\Cargo.toml"),
+        ];
+
+        for (path, expected) in cases {
+            assert_eq!(sanitize_path(path), Ok(PathBuf::from(expected)), "{}", path);
+        }
+    }
+
+    #[test]
+    fn sanitize_path_should_reject_escapes() {
+        let cases = vec![
+            (
+                "/../Cargo.toml",
+                PathError::Traversal("/../Cargo.toml".to_string()),
+            ),
+            (
+                "/%2e%2e/Cargo.toml",
+                PathError::Traversal("/%2e%2e/Cargo.toml".to_string()),
+            ),
+            (
+                "/foobar/../../x",
+                PathError::Traversal("/foobar/../../x".to_string()),
+            ),
+            (
+                "/%2E%2E%2fCargo.toml",
+                PathError::Traversal("/%2E%2E%2fCargo.toml".to_string()),
+            ),
+            (
+                "/index.html%00.png",
+                PathError::NulByte("/index.html%00.png".to_string()),
+            ),
+            ("/%ff", PathError::InvalidEncoding("/%ff".to_string())),
+            ("*", PathError::NotAbsolute("*".to_string())),
+        ];
+
+        for (path, expected) in cases {
+            assert_eq!(sanitize_path(path), Err(expected), "{}", path);
+        }
+    }
+}
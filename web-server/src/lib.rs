@@ -1,2 +1,8 @@
+pub mod config;
 pub mod domain;
+pub mod middleware;
+pub mod router;
 pub mod startup;
+pub mod static_files;
+pub mod tls;
+pub mod virtual_hosts;
@@ -1,12 +1,96 @@
-use std::net::{IpAddr, Ipv4Addr, SocketAddr};
+use std::{net::SocketAddr, sync::Arc};
 
+use clap::Parser;
 use tokio::net::TcpListener;
-use web_server::startup::run_server;
+use web_server::{
+    config::{Cli, Config},
+    middleware::{AccessLog, HttpsRedirect, RequestTiming},
+    router::Router,
+    startup::{ServerSettings, run_https_server, run_server_with_hosts},
+    static_files::StaticFiles,
+    tls::Certificates,
+    virtual_hosts::VirtualHosts,
+};
 
 #[tokio::main]
 async fn main() -> Result<(), std::io::Error> {
-    let addr = SocketAddr::new(IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1)), 8080);
-    let listener = TcpListener::bind(addr).await?;
+    let config = match Config::load(&Cli::parse()) {
+        Ok(config) => config,
+        Err(error) => {
+            eprintln!("{}", error);
+            std::process::exit(2);
+        }
+    };
 
-    run_server(listener).await
+    let listeners = bind(&config.listen).await?;
+    let Some(tls) = &config.tls else {
+        let hosts = hosts(&config).await?;
+        return run_server_with_hosts(listeners, config.settings, hosts).await;
+    };
+
+    let mut certificates =
+        Certificates::new(tls.certificate.clone()).map_err(std::io::Error::other)?;
+    for host in &config.virtual_hosts {
+        if let Some(certificate) = &host.certificate {
+            certificates = certificates
+                .host(&host.server_names, certificate.clone())
+                .map_err(std::io::Error::other)?;
+        }
+    }
+    let certificates = Arc::new(certificates);
+    let acceptor = certificates.acceptor().map_err(std::io::Error::other)?;
+    certificates.watch(tls.reload_interval);
+
+    let tls_listeners = bind(&tls.listen).await?;
+    let plain_hosts = match tls.redirect {
+        true => VirtualHosts::new(Router::new().layer(HttpsRedirect {
+            port: tls.listen[0].port(),
+        })),
+        false => hosts(&config).await?,
+    };
+    tokio::try_join!(
+        run_server_with_hosts(listeners, config.settings.clone(), plain_hosts),
+        run_https_server(
+            tls_listeners,
+            config.settings.clone(),
+            hosts(&config).await?,
+            acceptor
+        ),
+    )?;
+    Ok(())
+}
+
+async fn bind(addresses: &[SocketAddr]) -> std::io::Result<Vec<TcpListener>> {
+    let mut listeners = Vec::new();
+    for address in addresses {
+        let listener = TcpListener::bind(address).await.map_err(|error| {
+            std::io::Error::new(
+                error.kind(),
+                format!("Unable to listen on {}: {}", address, error),
+            )
+        })?;
+        listeners.push(listener);
+    }
+    Ok(listeners)
+}
+
+async fn hosts(config: &Config) -> std::io::Result<VirtualHosts> {
+    let mut hosts = VirtualHosts::new(site(&config.settings).await?);
+    for host in &config.virtual_hosts {
+        let router = site(&host.settings(&config.settings)).await?;
+        hosts = hosts.host(&host.server_names, router);
+    }
+    Ok(hosts)
+}
+
+async fn site(settings: &ServerSettings) -> std::io::Result<Router> {
+    let files = StaticFiles::from_settings(settings).await?;
+    let mut router = Router::new().mount("/", files).layer(AccessLog::default());
+    if settings.timing {
+        router = router.layer(RequestTiming);
+    }
+    if let Some(security_headers) = &settings.security_headers {
+        router = router.layer(security_headers.clone());
+    }
+    Ok(router)
 }
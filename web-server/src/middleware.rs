@@ -0,0 +1,441 @@
+use std::{
+    future::Future,
+    sync::Arc,
+    time::{Instant, SystemTime},
+};
+
+use crate::{
+    domain::{
+        http_request::HttpRequest,
+        http_response::{HttpResponse, StatusCodes},
+    },
+    router::{HandlerFuture, Router},
+    virtual_hosts::normalize,
+};
+
+/// Behaviour wrapped around every request a [`Router`] answers, added with [`Router::layer`].
+///
+/// A middleware gets the request before the handler does and decides whether to pass it on with
+/// [`Next::run`], so it can change the request, answer it itself, or change the response on its
+/// way back. Async closures taking the request and [`Next`] are middleware too.
+pub trait Middleware: Send + Sync + 'static {
+    fn call(&self, request: HttpRequest, next: Next) -> HandlerFuture;
+}
+
+impl<F, Fut> Middleware for F
+where
+    F: Fn(HttpRequest, Next) -> Fut + Send + Sync + 'static,
+    Fut: Future<Output = std::io::Result<HttpResponse>> + Send + 'static,
+{
+    fn call(&self, request: HttpRequest, next: Next) -> HandlerFuture {
+        Box::pin(self(request, next))
+    }
+}
+
+/// The rest of the chain after a middleware: the layers added after it, then the route.
+pub struct Next {
+    router: Arc<Router>,
+    index: usize,
+}
+
+impl Next {
+    pub(crate) fn new(router: Arc<Router>) -> Self {
+        Next { router, index: 0 }
+    }
+
+    pub fn run(self, request: HttpRequest) -> HandlerFuture {
+        match self.router.layers().get(self.index) {
+            Some(layer) => {
+                let layer = layer.clone();
+                let next = Next {
+                    router: self.router,
+                    index: self.index + 1,
+                };
+                layer.call(request, next)
+            }
+            None => Box::pin(async move { self.router.dispatch(request).await }),
+        }
+    }
+}
+
+/// Prints a line per request, close to the Common Log Format: the client address, the request
+/// line, the status code, the length of the body and how long the answer took.
+#[derive(Clone)]
+pub struct AccessLog {
+    sink: Arc<dyn Fn(String) + Send + Sync>,
+}
+
+impl Default for AccessLog {
+    fn default() -> Self {
+        AccessLog::to(|line| println!("{}", line))
+    }
+}
+
+impl AccessLog {
+    /// Sends each line to `sink` rather than printing it.
+    pub fn to(sink: impl Fn(String) + Send + Sync + 'static) -> Self {
+        AccessLog {
+            sink: Arc::new(sink),
+        }
+    }
+}
+
+impl Middleware for AccessLog {
+    fn call(&self, request: HttpRequest, next: Next) -> HandlerFuture {
+        let sink = self.sink.clone();
+        Box::pin(async move {
+            let started = Instant::now();
+            let remote_address = request
+                .remote_address
+                .map_or("-".to_string(), |address| address.ip().to_string());
+            let request_line = format!(
+                "{} {} {}",
+                request.method.as_str(),
+                request.path,
+                request.version
+            );
+            let response = next.run(request).await?;
+            sink(format!(
+                "{} - - [{}] \"{}\" {} {} {:.3}ms",
+                remote_address,
+                httpdate::fmt_http_date(SystemTime::now()),
+                request_line,
+                response.status_code().code(),
+                response.body().len(),
+                started.elapsed().as_secs_f64() * 1000.0
+            ));
+            Ok(response)
+        })
+    }
+}
+
+/// Sends how long the answer took in a `Server-Timing` header, which browsers show in their
+/// developer tools.
+#[derive(Debug, Clone, Default)]
+pub struct RequestTiming;
+
+impl Middleware for RequestTiming {
+    fn call(&self, request: HttpRequest, next: Next) -> HandlerFuture {
+        Box::pin(async move {
+            let started = Instant::now();
+            let response = next.run(request).await?;
+            let duration = started.elapsed().as_secs_f64() * 1000.0;
+            Ok(response.with_header("Server-Timing", &format!("total;dur={:.3}", duration)))
+        })
+    }
+}
+
+/// Headers asking browsers to restrict what the content served can do. Each is only added when
+/// the handler didn't set it already, and `None` leaves it out.
+#[derive(Debug, Clone)]
+pub struct SecurityHeaders {
+    /// `X-Content-Type-Options: nosniff`, so browsers stick to the `Content-Type` sent.
+    pub content_type_options: bool,
+    pub content_security_policy: Option<String>,
+    /// `Strict-Transport-Security`, which browsers only honour over HTTPS. Sending it makes
+    /// them refuse plain HTTP to the host for as long as `max-age`, so it's off by default.
+    pub strict_transport_security: Option<String>,
+}
+
+impl Default for SecurityHeaders {
+    fn default() -> Self {
+        SecurityHeaders {
+            content_type_options: true,
+            content_security_policy: Some("default-src 'self'".to_string()),
+            strict_transport_security: None,
+        }
+    }
+}
+
+impl Middleware for SecurityHeaders {
+    fn call(&self, request: HttpRequest, next: Next) -> HandlerFuture {
+        let content_type_options = self.content_type_options.then(|| "nosniff".to_string());
+        let headers = [
+            ("X-Content-Type-Options", content_type_options),
+            (
+                "Content-Security-Policy",
+                self.content_security_policy.clone(),
+            ),
+            (
+                "Strict-Transport-Security",
+                self.strict_transport_security.clone(),
+            ),
+        ];
+        Box::pin(async move {
+            let mut response = next.run(request).await?;
+            for (name, value) in headers {
+                if let Some(value) = value {
+                    if !response.headers().contains(name) {
+                        response = response.with_header(name, &value);
+                    }
+                }
+            }
+            Ok(response)
+        })
+    }
+}
+
+/// Answers every request with a permanent redirect to the same URL over HTTPS, for the plain
+/// HTTP listeners of a server whose sites are served over TLS. Requests without a `Host`, or
+/// with one that isn't a domain name or an IP address, can't be redirected and get a 400.
+#[derive(Debug, Clone)]
+pub struct HttpsRedirect {
+    /// Port the HTTPS listener is on, left out of the URL when it's 443.
+    pub port: u16,
+}
+
+impl Default for HttpsRedirect {
+    fn default() -> Self {
+        HttpsRedirect { port: 443 }
+    }
+}
+
+impl Middleware for HttpsRedirect {
+    fn call(&self, request: HttpRequest, _: Next) -> HandlerFuture {
+        let port = self.port;
+        Box::pin(async move {
+            let host = request.headers.get("Host").map(normalize);
+            let Some(host) = host.filter(|host| is_host(host)) else {
+                return Ok(HttpResponse::new(StatusCodes::BadRequest, None));
+            };
+            // `OPTIONS *` has no path of its own
+            let path = match request.path.starts_with('/') {
+                true => request.path.as_str(),
+                false => "/",
+            };
+            let mut location = match port {
+                443 => format!("https://{}{}", host, path),
+                port => format!("https://{}:{}{}", host, port, path),
+            };
+            if let Some(query) = &request.raw_query {
+                location.push('?');
+                location.push_str(query);
+            }
+            let response = HttpResponse::builder(StatusCodes::PermanentRedirect)
+                .header("Location", &location)
+                .build();
+            Ok(response.unwrap_or_else(|_| HttpResponse::new(StatusCodes::BadRequest, None)))
+        })
+    }
+}
+
+/// Whether a host, its port stripped, is a domain name, an IPv4 address or a bracketed IPv6
+/// one, so it's safe to put in a URL.
+fn is_host(host: &str) -> bool {
+    if let Some(literal) = host
+        .strip_prefix('[')
+        .and_then(|host| host.strip_suffix(']'))
+    {
+        return literal.parse::<std::net::Ipv6Addr>().is_ok();
+    }
+    !host.is_empty()
+        && host.split('.').all(|label| {
+            !label.is_empty()
+                && label.len() <= 63
+                && label
+                    .bytes()
+                    .all(|byte| byte.is_ascii_alphanumeric() || byte == b'-')
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{Arc, Mutex};
+
+    use super::{AccessLog, HttpsRedirect, Next, RequestTiming, SecurityHeaders};
+    use crate::{
+        domain::{
+            headers::Headers,
+            http_request::{HttpRequest, Method},
+            http_response::{HttpResponse, StatusCodes},
+        },
+        router::Router,
+    };
+
+    fn request(path: &str) -> HttpRequest {
+        HttpRequest {
+            method: Method::GET,
+            path: path.to_string(),
+            query: Vec::new(),
+            raw_query: None,
+            params: Vec::new(),
+            version: "HTTP/1.1".to_string(),
+            remote_address: Some("127.0.0.1:5000".parse().unwrap()),
+            headers: Headers::new(),
+            body: Vec::new(),
+        }
+    }
+
+    async fn hello(_: HttpRequest) -> std::io::Result<HttpResponse> {
+        Ok(
+            HttpResponse::new(StatusCodes::OK, Some("hello".to_string()))
+                .with_header("Content-Security-Policy", "default-src 'none'"),
+        )
+    }
+
+    #[tokio::test]
+    async fn layers_should_run_in_order_around_the_route() {
+        let order = Arc::new(Mutex::new(Vec::new()));
+        let layer = |name: &'static str| {
+            let order = order.clone();
+            move |request: HttpRequest, next: Next| {
+                let order = order.clone();
+                async move {
+                    order.lock().unwrap().push(format!("before {}", name));
+                    let response = next.run(request).await?;
+                    order.lock().unwrap().push(format!("after {}", name));
+                    Ok(response)
+                }
+            }
+        };
+        let router = Arc::new(
+            Router::new()
+                .get("/", hello)
+                .layer(layer("outer"))
+                .layer(layer("inner")),
+        );
+
+        let response = router.handle(request("/")).await.unwrap();
+
+        assert_eq!(response.status_code(), StatusCodes::OK);
+        assert_eq!(*order.lock().unwrap(), vec![
+            "before outer",
+            "before inner",
+            "after inner",
+            "after outer"
+        ]);
+    }
+
+    #[tokio::test]
+    async fn layers_should_be_able_to_answer_themselves() {
+        let router = Arc::new(Router::new().get("/", hello).layer(
+            |request: HttpRequest, next: Next| async move {
+                match request.headers.get("Authorization") {
+                    Some(_) => next.run(request).await,
+                    None => Ok(HttpResponse::new(StatusCodes::Unauthorized, None)),
+                }
+            },
+        ));
+
+        let response = router.handle(request("/")).await.unwrap();
+        assert_eq!(response.status_code(), StatusCodes::Unauthorized);
+
+        let mut authorized = request("/");
+        authorized.headers.append("Authorization", "Bearer token");
+        let response = router.handle(authorized).await.unwrap();
+        assert_eq!(response.status_code(), StatusCodes::OK);
+    }
+
+    #[tokio::test]
+    async fn access_log_should_record_requests() {
+        let lines = Arc::new(Mutex::new(Vec::new()));
+        let sink = lines.clone();
+        let router = Arc::new(
+            Router::new()
+                .get("/", hello)
+                .layer(AccessLog::to(move |line| sink.lock().unwrap().push(line))),
+        );
+
+        router.handle(request("/")).await.unwrap();
+        router.handle(request("/missing")).await.unwrap();
+
+        let lines = lines.lock().unwrap();
+        assert_eq!(lines.len(), 2);
+        assert!(lines[0].starts_with("127.0.0.1 - - ["), "{}", lines[0]);
+        assert!(
+            lines[0].contains("] \"GET / HTTP/1.1\" 200 5 "),
+            "{}",
+            lines[0]
+        );
+        assert!(
+            lines[1].contains("\"GET /missing HTTP/1.1\" 404 0 "),
+            "{}",
+            lines[1]
+        );
+    }
+
+    #[tokio::test]
+    async fn timing_and_security_headers_should_be_added() {
+        let router = Arc::new(Router::new().get("/", hello).layer(RequestTiming).layer(
+            SecurityHeaders {
+                strict_transport_security: Some("max-age=31536000".to_string()),
+                ..SecurityHeaders::default()
+            },
+        ));
+
+        let response = router.handle(request("/")).await.unwrap();
+        let headers = response.headers();
+
+        assert!(
+            headers
+                .get("Server-Timing")
+                .unwrap()
+                .starts_with("total;dur=")
+        );
+        assert_eq!(headers.get("X-Content-Type-Options"), Some("nosniff"));
+        // The handler's own policy is kept
+        assert_eq!(
+            headers.get("Content-Security-Policy"),
+            Some("default-src 'none'")
+        );
+        assert_eq!(
+            headers.get("Strict-Transport-Security"),
+            Some("max-age=31536000")
+        );
+    }
+
+    #[tokio::test]
+    async fn https_redirect_should_keep_the_host_path_and_query() {
+        let router = Arc::new(
+            Router::new()
+                .get("/", hello)
+                .layer(HttpsRedirect { port: 8443 }),
+        );
+        let mut redirected = request("/search");
+        redirected.raw_query = Some("q=a%20b".to_string());
+        redirected.headers.append("Host", "Example.com:8080");
+
+        let response = router.handle(redirected).await.unwrap();
+        assert_eq!(response.status_code(), StatusCodes::PermanentRedirect);
+        assert_eq!(
+            response.headers().get("Location"),
+            Some("https://example.com:8443/search?q=a%20b")
+        );
+
+        let response = router.handle(request("/")).await.unwrap();
+        assert_eq!(response.status_code(), StatusCodes::BadRequest);
+
+        let mut ipv6 = request("/");
+        ipv6.headers.append("Host", "[::1]:8080");
+        let response = router.handle(ipv6).await.unwrap();
+        assert_eq!(
+            response.headers().get("Location"),
+            Some("https://[::1]:8443/")
+        );
+    }
+
+    #[tokio::test]
+    async fn https_redirect_should_reject_invalid_hosts() {
+        let router = Arc::new(Router::new().layer(HttpsRedirect::default()));
+        for host in [
+            "evil.com\rset-cookie/x",
+            "evil.com/x",
+            "user@evil.com",
+            "a..b",
+            "[::1",
+            "",
+        ] {
+            let mut redirected = request("/");
+            redirected.headers.append("Host", host);
+
+            let response = router.handle(redirected).await.unwrap();
+            assert_eq!(
+                response.status_code(),
+                StatusCodes::BadRequest,
+                "{:?}",
+                host
+            );
+            assert_eq!(response.headers().get("Location"), None);
+        }
+    }
+}
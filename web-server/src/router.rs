@@ -0,0 +1,419 @@
+use std::{future::Future, pin::Pin, sync::Arc};
+
+use crate::{
+    domain::{
+        http_request::{HttpRequest, Method, percent_decode},
+        http_response::{HttpResponse, StatusCodes},
+    },
+    middleware::{Middleware, Next},
+};
+
+pub type HandlerFuture = Pin<Box<dyn Future<Output = std::io::Result<HttpResponse>> + Send>>;
+
+/// Something that answers requests, such as [`crate::static_files::StaticFiles`] or an async
+/// closure taking the request.
+///
+/// Errors are reserved for failures of the connection itself, which close it without an answer.
+/// Anything the client should know about has to be answered with a status code instead.
+pub trait Handler: Send + Sync + 'static {
+    fn call(&self, request: HttpRequest) -> HandlerFuture;
+}
+
+impl<F, Fut> Handler for F
+where
+    F: Fn(HttpRequest) -> Fut + Send + Sync + 'static,
+    Fut: Future<Output = std::io::Result<HttpResponse>> + Send + 'static,
+{
+    fn call(&self, request: HttpRequest) -> HandlerFuture {
+        Box::pin(self(request))
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Segment {
+    Literal(String),
+    /// `:name`, which matches any one segment.
+    Param(String),
+    /// `*name`, or a bare `*`, which matches the rest of the path. It can only come last.
+    Wildcard(String),
+}
+
+/// A path pattern, such as `/users/:id` or `/static/*path`.
+///
+/// Patterns are matched against whole segments, ignoring empty ones, so `/users/:id` matches
+/// `/users/42/` as well.
+#[derive(Debug, Clone, PartialEq)]
+struct Pattern(Vec<Segment>);
+
+impl Pattern {
+    fn parse(pattern: &str) -> Self {
+        let segments: Vec<Segment> = pattern
+            .split('/')
+            .filter(|segment| !segment.is_empty())
+            .map(|segment| {
+                if let Some(name) = segment.strip_prefix(':') {
+                    Segment::Param(name.to_string())
+                } else if let Some(name) = segment.strip_prefix('*') {
+                    let name = if name.is_empty() { "*" } else { name };
+                    Segment::Wildcard(name.to_string())
+                } else {
+                    Segment::Literal(segment.to_string())
+                }
+            })
+            .collect();
+        if let Some(position) = segments
+            .iter()
+            .position(|segment| matches!(segment, Segment::Wildcard(_)))
+        {
+            assert!(
+                position == segments.len() - 1,
+                "A wildcard can only be the last segment of {}",
+                pattern
+            );
+        }
+        Pattern(segments)
+    }
+
+    /// The parameters captured from `path`, or `None` when it doesn't match.
+    ///
+    /// `:name` parameters are percent-decoded, while a wildcard keeps the rest of the path as it
+    /// was sent, so it can be sanitized as a path in turn.
+    fn matches(&self, path: &str) -> Option<Vec<(String, String)>> {
+        let mut segments = path.split('/').filter(|segment| !segment.is_empty());
+        let mut params = Vec::new();
+        for expected in &self.0 {
+            match expected {
+                Segment::Wildcard(name) => {
+                    let rest: Vec<&str> = segments.collect();
+                    params.push((name.clone(), rest.join("/")));
+                    return Some(params);
+                }
+                Segment::Literal(literal) => {
+                    if segments.next()? != literal {
+                        return None;
+                    }
+                }
+                Segment::Param(name) => {
+                    let value = segments.next()?;
+                    params.push((name.clone(), percent_decode(value, false)));
+                }
+            }
+        }
+        segments.next().is_none().then_some(params)
+    }
+}
+
+struct Route {
+    method: Method,
+    pattern: Pattern,
+    handler: Arc<dyn Handler>,
+}
+
+/// Dispatches requests to the handler of the first route matching their method and path.
+///
+/// `HEAD` requests are answered by the `GET` route, as the body is left out when the response
+/// is written, and `OPTIONS` ones with the methods the path allows unless a route handles them.
+/// A path matched by routes for other methods only is answered with `405 Method Not Allowed`,
+/// and one matched by no route with `404 Not Found`.
+///
+/// Every request goes through the [`Middleware`] added with [`Router::layer`] first, whether a
+/// route matches it or not.
+#[derive(Default)]
+pub struct Router {
+    routes: Vec<Route>,
+    layers: Vec<Arc<dyn Middleware>>,
+}
+
+impl Router {
+    pub fn new() -> Self {
+        Router {
+            routes: Vec::new(),
+            layers: Vec::new(),
+        }
+    }
+
+    /// Wraps `middleware` around the routes. Layers run in the order they are added, so the
+    /// first one sees the request first and the response last.
+    pub fn layer(mut self, middleware: impl Middleware) -> Self {
+        self.layers.push(Arc::new(middleware));
+        self
+    }
+
+    pub(crate) fn layers(&self) -> &[Arc<dyn Middleware>] {
+        &self.layers
+    }
+
+    /// Routes `method` requests whose path matches `pattern` to `handler`, which can read the
+    /// parameters captured with [`HttpRequest::param`].
+    ///
+    /// Panics if a wildcard isn't the last segment of `pattern`.
+    pub fn route(mut self, method: Method, pattern: &str, handler: impl Handler) -> Self {
+        self.routes.push(Route {
+            method,
+            pattern: Pattern::parse(pattern),
+            handler: Arc::new(handler),
+        });
+        self
+    }
+
+    pub fn get(self, pattern: &str, handler: impl Handler) -> Self {
+        self.route(Method::GET, pattern, handler)
+    }
+
+    pub fn post(self, pattern: &str, handler: impl Handler) -> Self {
+        self.route(Method::POST, pattern, handler)
+    }
+
+    pub fn put(self, pattern: &str, handler: impl Handler) -> Self {
+        self.route(Method::PUT, pattern, handler)
+    }
+
+    pub fn patch(self, pattern: &str, handler: impl Handler) -> Self {
+        self.route(Method::PATCH, pattern, handler)
+    }
+
+    pub fn delete(self, pattern: &str, handler: impl Handler) -> Self {
+        self.route(Method::DELETE, pattern, handler)
+    }
+
+    /// Routes `GET` requests for `prefix` and everything below it to `handler`, with the rest
+    /// of the path as the `*` parameter. This is how [`crate::static_files::StaticFiles`] is
+    /// served.
+    pub fn mount(self, prefix: &str, handler: impl Handler) -> Self {
+        let pattern = format!("{}/*", prefix.trim_end_matches('/'));
+        self.get(&pattern, handler)
+    }
+
+    /// Answers `request`, running it through the layers and then the route that matches it.
+    pub async fn handle(self: &Arc<Self>, request: HttpRequest) -> std::io::Result<HttpResponse> {
+        Next::new(self.clone()).run(request).await
+    }
+
+    pub(crate) async fn dispatch(&self, mut request: HttpRequest) -> std::io::Result<HttpResponse> {
+        // The asterisk form asks about the server as a whole
+        if request.path == "*" {
+            return Ok(match request.method {
+                Method::OPTIONS => options(self.routes.iter().map(|route| route.method)),
+                _ => HttpResponse::new(StatusCodes::BadRequest, None),
+            });
+        }
+
+        let matching: Vec<(&Route, Vec<(String, String)>)> = self
+            .routes
+            .iter()
+            .filter_map(|route| Some((route, route.pattern.matches(&request.path)?)))
+            .collect();
+        if matching.is_empty() {
+            return Ok(HttpResponse::new(StatusCodes::NotFound, None));
+        }
+
+        let found = matching
+            .iter()
+            .find(|(route, _)| route.method == request.method)
+            .or_else(|| {
+                matching.iter().find(|(route, _)| {
+                    request.method == Method::HEAD && route.method == Method::GET
+                })
+            });
+        if let Some((route, params)) = found {
+            request.params = params.clone();
+            return route.handler.call(request).await;
+        }
+
+        let allowed = matching.iter().map(|(route, _)| route.method);
+        Ok(match request.method {
+            Method::OPTIONS => options(allowed),
+            _ => HttpResponse::new(StatusCodes::MethodNotAllowed, None)
+                .with_header("Allow", &allow(allowed)),
+        })
+    }
+}
+
+fn options(methods: impl Iterator<Item = Method>) -> HttpResponse {
+    HttpResponse::new(StatusCodes::NoContent, None).with_header("Allow", &allow(methods))
+}
+
+/// The `Allow` value for routes with `methods`, adding `HEAD` where `GET` is allowed and
+/// `OPTIONS`, which is always answered.
+fn allow(methods: impl Iterator<Item = Method>) -> String {
+    let mut allowed: Vec<Method> = Vec::new();
+    for method in methods.chain([Method::OPTIONS]) {
+        let implied = match method {
+            Method::GET => vec![Method::GET, Method::HEAD],
+            method => vec![method],
+        };
+        for method in implied {
+            if !allowed.contains(&method) {
+                allowed.push(method);
+            }
+        }
+    }
+    allowed
+        .iter()
+        .map(Method::as_str)
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use super::{Pattern, Router};
+    use crate::domain::{
+        headers::Headers,
+        http_request::{HttpRequest, Method},
+        http_response::{HttpResponse, StatusCodes},
+    };
+
+    fn request(method: Method, path: &str) -> HttpRequest {
+        HttpRequest {
+            method,
+            path: path.to_string(),
+            query: Vec::new(),
+            raw_query: None,
+            params: Vec::new(),
+            version: "HTTP/1.1".to_string(),
+            remote_address: None,
+            headers: Headers::new(),
+            body: Vec::new(),
+        }
+    }
+
+    fn params(pairs: &[(&str, &str)]) -> Option<Vec<(String, String)>> {
+        Some(
+            pairs
+                .iter()
+                .map(|(name, value)| (name.to_string(), value.to_string()))
+                .collect(),
+        )
+    }
+
+    #[test]
+    fn pattern_should_capture_parameters() {
+        let cases = vec![
+            ("/", "/", params(&[])),
+            ("/", "/users", None),
+            ("/users", "/users/", params(&[])),
+            ("/users/:id", "/users/42", params(&[("id", "42")])),
+            ("/users/:id", "/users/a%20b", params(&[("id", "a b")])),
+            ("/users/:id", "/users", None),
+            ("/users/:id", "/users/42/posts", None),
+            (
+                "/users/:id/posts/:post",
+                "/users/42/posts/7",
+                params(&[("id", "42"), ("post", "7")]),
+            ),
+            (
+                "/static/*path",
+                "/static/css/a%20b.css",
+                params(&[("path", "css/a%20b.css")]),
+            ),
+            ("/static/*", "/static", params(&[("*", "")])),
+            ("/*", "/index.html", params(&[("*", "index.html")])),
+            ("/static/*", "/other/a.css", None),
+        ];
+
+        for (pattern, path, expected) in cases {
+            assert_eq!(
+                Pattern::parse(pattern).matches(path),
+                expected,
+                "{} {}",
+                pattern,
+                path
+            );
+        }
+    }
+
+    #[test]
+    #[should_panic]
+    fn pattern_should_reject_wildcards_before_the_end() {
+        Pattern::parse("/*/users");
+    }
+
+    async fn echo(request: HttpRequest) -> std::io::Result<HttpResponse> {
+        let id = request.param("id").unwrap_or_default().to_string();
+        Ok(HttpResponse::new(
+            StatusCodes::OK,
+            Some(format!("{:?} {}", request.method, id)),
+        ))
+    }
+
+    fn router() -> Arc<Router> {
+        Arc::new(
+            Router::new()
+                .get("/users/:id", echo)
+                .delete("/users/:id", echo)
+                .post("/users", |_| async {
+                    Ok(HttpResponse::new(StatusCodes::Created, None))
+                }),
+        )
+    }
+
+    fn body(response: &HttpResponse) -> String {
+        response.response_string()
+    }
+
+    #[tokio::test]
+    async fn router_should_dispatch_by_method_and_path() {
+        let router = router();
+
+        let response = router
+            .handle(request(Method::GET, "/users/42"))
+            .await
+            .unwrap();
+        assert!(body(&response).ends_with("GET 42"), "{}", body(&response));
+
+        let response = router
+            .handle(request(Method::HEAD, "/users/42"))
+            .await
+            .unwrap();
+        assert!(body(&response).ends_with("HEAD 42"), "{}", body(&response));
+
+        let response = router
+            .handle(request(Method::DELETE, "/users/7"))
+            .await
+            .unwrap();
+        assert!(body(&response).ends_with("DELETE 7"), "{}", body(&response));
+
+        let response = router
+            .handle(request(Method::POST, "/users"))
+            .await
+            .unwrap();
+        assert_eq!(response.status_code(), StatusCodes::Created);
+
+        let response = router.handle(request(Method::GET, "/posts")).await.unwrap();
+        assert_eq!(response.status_code(), StatusCodes::NotFound);
+    }
+
+    #[tokio::test]
+    async fn router_should_answer_options_and_unknown_methods() {
+        let router = router();
+
+        let response = router
+            .handle(request(Method::OPTIONS, "/users/42"))
+            .await
+            .unwrap();
+        assert_eq!(response.status_code(), StatusCodes::NoContent);
+        assert_eq!(
+            response.headers().get("Allow"),
+            Some("GET, HEAD, DELETE, OPTIONS")
+        );
+
+        let response = router
+            .handle(request(Method::PUT, "/users/42"))
+            .await
+            .unwrap();
+        assert_eq!(response.status_code(), StatusCodes::MethodNotAllowed);
+        assert_eq!(
+            response.headers().get("Allow"),
+            Some("GET, HEAD, DELETE, OPTIONS")
+        );
+
+        let response = router.handle(request(Method::OPTIONS, "*")).await.unwrap();
+        assert_eq!(
+            response.headers().get("Allow"),
+            Some("GET, HEAD, DELETE, POST, OPTIONS")
+        );
+    }
+}
@@ -1,57 +1,282 @@
-use std::path::Path;
+use std::{
+    net::SocketAddr,
+    path::PathBuf,
+    sync::Arc,
+    time::{Duration, SystemTime},
+};
 
 use tokio::{
-    io::{AsyncBufReadExt, AsyncWriteExt, BufReader},
-    net::{TcpListener, TcpStream},
+    io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt, BufReader},
+    net::TcpListener,
+    task::JoinSet,
 };
+use tokio_rustls::TlsAcceptor;
 
-use crate::domain::{
-    http_request::{HttpRequest, Method},
-    http_response::{HttpResponse, StatusCodes},
+use crate::{
+    domain::{
+        autoindex::Autoindex,
+        caching::{CacheControlRules, EtagStrategy},
+        compression::CompressionSettings,
+        http_request::{HttpRequest, Method, RequestError, RequestLimits},
+        http_response::{HttpResponse, StatusCodes},
+        mime::MimeTypes,
+    },
+    middleware::SecurityHeaders,
+    router::Router,
+    static_files::StaticFiles,
+    virtual_hosts::VirtualHosts,
 };
 
+/// How connections are handled once accepted.
+#[derive(Debug, Clone)]
+pub struct ServerSettings {
+    /// How long a connection may stay open waiting for its next request.
+    pub idle_timeout: Duration,
+    /// Requests served on one connection before it is closed, so no client holds one forever.
+    pub max_requests_per_connection: usize,
+    pub limits: RequestLimits,
+    /// `Content-Type` of the files served, by extension.
+    pub mime_types: MimeTypes,
+    /// Directory files are served from, relative to the working directory unless absolute.
+    /// Nothing outside of it is served, even through symlinks.
+    pub document_root: PathBuf,
+    /// Files sent for a directory, the first one found winning.
+    pub index_files: Vec<String>,
+    /// How the `ETag` of the files served is computed.
+    pub etag: EtagStrategy,
+    /// `Cache-Control` sent with the files served, by path pattern.
+    pub cache_control: CacheControlRules,
+    pub compression: CompressionSettings,
+    /// Whether directories without an index file are listed.
+    pub autoindex: Autoindex,
+    /// Headers asking browsers to restrict the content served, or `None` to send none. Like
+    /// `timing`, applied by the `web-server` binary around each site.
+    pub security_headers: Option<SecurityHeaders>,
+    /// Whether responses say how long they took in a `Server-Timing` header.
+    pub timing: bool,
+}
+
+impl Default for ServerSettings {
+    fn default() -> Self {
+        ServerSettings {
+            idle_timeout: Duration::from_secs(5),
+            max_requests_per_connection: 100,
+            limits: RequestLimits::default(),
+            mime_types: MimeTypes::default(),
+            document_root: PathBuf::from("www"),
+            index_files: vec!["index.html".to_string()],
+            etag: EtagStrategy::Metadata,
+            cache_control: CacheControlRules::new(),
+            compression: CompressionSettings::default(),
+            autoindex: Autoindex::default(),
+            security_headers: Some(SecurityHeaders::default()),
+            timing: false,
+        }
+    }
+}
+
 pub async fn run_server(listener: TcpListener) -> std::io::Result<()> {
+    run_server_with(listener, ServerSettings::default()).await
+}
+
+/// Serves the files in the document root of `settings`, as [`run_server_with_router`] does with
+/// a router where [`StaticFiles`] is mounted at `/`.
+pub async fn run_server_with(
+    listener: TcpListener,
+    settings: ServerSettings,
+) -> std::io::Result<()> {
+    let files = StaticFiles::from_settings(&settings).await?;
+    run_server_with_router(listener, settings, Router::new().mount("/", files)).await
+}
+
+/// Accepts connections on `listener`, answering their requests with `router`.
+pub async fn run_server_with_router(
+    listener: TcpListener,
+    settings: ServerSettings,
+    router: Router,
+) -> std::io::Result<()> {
+    run_server_on(vec![listener], settings, router).await
+}
+
+/// Accepts connections on every one of `listeners`, answering their requests with `router`.
+/// Returns once any of them fails.
+pub async fn run_server_on(
+    listeners: Vec<TcpListener>,
+    settings: ServerSettings,
+    router: Router,
+) -> std::io::Result<()> {
+    run_server_with_hosts(listeners, settings, VirtualHosts::new(router)).await
+}
+
+/// Accepts connections on every one of `listeners`, answering each request with the router of
+/// the site it's for. Returns once any of them fails.
+pub async fn run_server_with_hosts(
+    listeners: Vec<TcpListener>,
+    settings: ServerSettings,
+    hosts: VirtualHosts,
+) -> std::io::Result<()> {
+    serve(listeners, settings, hosts, None).await
+}
+
+/// Like [`run_server_with_hosts`], but over TLS: each connection is handed to `acceptor` for
+/// the handshake before its requests are read. Connections failing it are dropped.
+pub async fn run_https_server(
+    listeners: Vec<TcpListener>,
+    settings: ServerSettings,
+    hosts: VirtualHosts,
+    acceptor: TlsAcceptor,
+) -> std::io::Result<()> {
+    serve(listeners, settings, hosts, Some(acceptor)).await
+}
+
+async fn serve(
+    listeners: Vec<TcpListener>,
+    settings: ServerSettings,
+    hosts: VirtualHosts,
+    acceptor: Option<TlsAcceptor>,
+) -> std::io::Result<()> {
+    let settings = Arc::new(settings);
+    let hosts = Arc::new(hosts);
+    let mut accepting = JoinSet::new();
+    for listener in listeners {
+        accepting.spawn(accept(
+            listener,
+            settings.clone(),
+            hosts.clone(),
+            acceptor.clone(),
+        ));
+    }
+    match accepting.join_next().await {
+        Some(result) => result.map_err(std::io::Error::other)?,
+        None => Ok(()),
+    }
+}
+
+async fn accept(
+    listener: TcpListener,
+    settings: Arc<ServerSettings>,
+    hosts: Arc<VirtualHosts>,
+    acceptor: Option<TlsAcceptor>,
+) -> std::io::Result<()> {
     let address = listener.local_addr()?;
     println!(
-        "Server started at host {} and port {}",
+        "Server started at host {} and port {}{}",
         address.ip(),
-        address.port()
+        address.port(),
+        if acceptor.is_some() { " with TLS" } else { "" }
     );
     loop {
-        let (stream, _) = listener.accept().await?;
-        tokio::spawn(async move { handle_client(stream).await });
+        let (stream, remote_address) = listener.accept().await?;
+        let settings = settings.clone();
+        let hosts = hosts.clone();
+        let acceptor = acceptor.clone();
+        tokio::spawn(async move {
+            let Some(acceptor) = acceptor else {
+                return handle_client(stream, remote_address, &settings, &hosts).await;
+            };
+            let handshake = acceptor.accept(stream);
+            match tokio::time::timeout(settings.idle_timeout, handshake).await {
+                Ok(Ok(stream)) => handle_client(stream, remote_address, &settings, &hosts).await,
+                Ok(Err(error)) => {
+                    println!("TLS handshake with {} failed: {}", remote_address, error);
+                    Ok(())
+                }
+                Err(_) => Ok(()),
+            }
+        });
     }
 }
 
-async fn handle_client(stream: TcpStream) -> std::io::Result<()> {
+/// Serves requests on a connection until the client asks to close it, goes idle or reaches the
+/// request cap. Pipelined requests are answered in order, as each one is read only after the
+/// previous response is written.
+async fn handle_client<S>(
+    stream: S,
+    remote_address: SocketAddr,
+    settings: &ServerSettings,
+    hosts: &VirtualHosts,
+) -> std::io::Result<()>
+where
+    S: AsyncRead + AsyncWrite + Unpin,
+{
     println!("Server received a connection!");
     let mut stream = BufReader::new(stream);
 
-    let mut first_line = String::new();
-    stream.read_line(&mut first_line).await?;
+    for served in 1..=settings.max_requests_per_connection {
+        let read = HttpRequest::read_from(&mut stream, &settings.limits);
+        let mut request = match tokio::time::timeout(settings.idle_timeout, read).await {
+            Ok(Ok(Some(request))) => request,
+            Ok(Ok(None) | Err(RequestError::UnexpectedEof | RequestError::IoError(_))) | Err(_) => {
+                return Ok(());
+            }
+            Ok(Err(error)) => {
+                println!("Rejecting malformed request: {}", error);
+                let http_response = HttpResponse::new(error.status_code(), None)
+                    .with_header("Connection", "close")
+                    .with_server_headers(SystemTime::now());
+                http_response.write_to(&mut stream).await?;
+                return lingering_close(stream).await;
+            }
+        };
 
-    let HttpRequest { method, path } = first_line.parse::<HttpRequest>()?;
-    let http_response = handle_request(method, path).await?;
-    stream
-        .write_all(http_response.response_string().as_bytes())
-        .await
+        // RFC 9112 section 3.2 requires exactly one `Host` in HTTP/1.1 requests
+        if request.version == "HTTP/1.1" && request.headers.get_all("Host").count() != 1 {
+            println!("Rejecting request without a single Host header");
+            let http_response = HttpResponse::new(StatusCodes::BadRequest, None)
+                .with_header("Connection", "close")
+                .with_server_headers(SystemTime::now());
+            http_response.write_to(&mut stream).await?;
+            break;
+        }
+        request.remote_address = Some(remote_address);
+        let router = hosts.router_for(request.headers.get("Host"));
+        let keep_alive =
+            wants_keep_alive(&request) && served < settings.max_requests_per_connection;
+        let version = request.version.clone();
+        let head_only = request.method == Method::HEAD;
+        let mut http_response = router
+            .handle(request)
+            .await?
+            .with_server_headers(SystemTime::now());
+        if !keep_alive {
+            http_response = http_response.with_header("Connection", "close");
+        } else if version == "HTTP/1.0" {
+            http_response = http_response.with_header("Connection", "keep-alive");
+        }
+        if head_only {
+            http_response.write_head_to(&mut stream).await?;
+        } else {
+            http_response.write_to(&mut stream).await?;
+        }
+        if !keep_alive {
+            break;
+        }
+    }
+    stream.shutdown().await
 }
 
-async fn handle_request(method: Method, path: String) -> std::io::Result<HttpResponse> {
-    match (method, path) {
-        (Method::GET, path) => match load_file(path).await {
-            Ok(content) => Ok(HttpResponse::new(StatusCodes::OK, Some(content))),
-            Err(_) => Ok(HttpResponse::new(StatusCodes::NotFound, None)),
-        },
-        (_, _) => Ok(HttpResponse::new(StatusCodes::NotFound, None)),
+/// HTTP/1.1 connections are persistent unless `Connection: close` is sent, while HTTP/1.0 ones
+/// have to ask for `Connection: keep-alive`.
+fn wants_keep_alive(request: &HttpRequest) -> bool {
+    if request.headers.has_token("Connection", "close") {
+        return false;
     }
+    request.version == "HTTP/1.1" || request.headers.has_token("Connection", "keep-alive")
 }
 
-async fn load_file(path: String) -> std::io::Result<String> {
-    let final_path = std::env::current_dir()?.join(Path::new(&format!("www{}", path)));
-    if final_path.is_file() {
-        tokio::fs::read_to_string(final_path).await
-    } else {
-        tokio::fs::read_to_string(final_path.join("index.html")).await
-    }
+/// Closes a connection whose request was rejected part way through. Closing a socket with unread
+/// data resets it, which can discard the error response before the client reads it, so the rest
+/// of the request is drained for a short while first.
+async fn lingering_close<S>(mut stream: BufReader<S>) -> std::io::Result<()>
+where
+    S: AsyncRead + AsyncWrite + Unpin,
+{
+    stream.shutdown().await?;
+    let mut buffer = [0; 8 * 1024];
+    let drain = async {
+        while stream.read(&mut buffer).await? > 0 {}
+        Ok::<_, std::io::Error>(())
+    };
+    let _ = tokio::time::timeout(Duration::from_secs(2), drain).await;
+    Ok(())
 }
@@ -0,0 +1,350 @@
+use std::{
+    fs::Metadata,
+    io::ErrorKind,
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+
+use tokio::{fs::File, io::AsyncReadExt};
+
+use crate::{
+    domain::{
+        autoindex::{Autoindex, prefers_json, read_directory, render_html, render_json},
+        caching::{CacheControlRules, EtagStrategy, Validators},
+        compression::{CompressionSettings, Encoding, negotiate},
+        http_request::{HttpRequest, Method},
+        http_response::{Body, HttpResponse, StatusCodes},
+        mime::MimeTypes,
+        range::{RangeRequest, if_range_matches, multipart_body, parse_range},
+        request_path::sanitize_path,
+    },
+    router::{Handler, HandlerFuture},
+    startup::ServerSettings,
+};
+
+/// Serves the files in a document root, as a [`Handler`] to mount on a
+/// [`crate::router::Router`].
+///
+/// The path served is the `*` parameter of the route when there is one, so the files can be
+/// mounted under any prefix, and the whole request path otherwise.
+#[derive(Debug, Clone)]
+pub struct StaticFiles(Arc<Inner>);
+
+#[derive(Debug)]
+struct Inner {
+    document_root: PathBuf,
+    index_files: Vec<String>,
+    mime_types: MimeTypes,
+    etag: EtagStrategy,
+    cache_control: CacheControlRules,
+    compression: CompressionSettings,
+    autoindex: Autoindex,
+}
+
+impl StaticFiles {
+    /// Serves the files in `document_root` as the default [`ServerSettings`] would.
+    pub async fn new(document_root: impl Into<PathBuf>) -> std::io::Result<Self> {
+        Self::from_settings(&ServerSettings {
+            document_root: document_root.into(),
+            ..ServerSettings::default()
+        })
+        .await
+    }
+
+    /// Serves the files in the document root of `settings`, failing when it can't be opened.
+    pub async fn from_settings(settings: &ServerSettings) -> std::io::Result<Self> {
+        // Resolved once, so requests are checked against the real location of the root
+        let document_root = tokio::fs::canonicalize(&settings.document_root)
+            .await
+            .map_err(|error| {
+                std::io::Error::new(
+                    error.kind(),
+                    format!(
+                        "Unable to open document root {}: {}",
+                        settings.document_root.display(),
+                        error
+                    ),
+                )
+            })?;
+        Ok(StaticFiles(Arc::new(Inner {
+            document_root,
+            index_files: settings.index_files.clone(),
+            mime_types: settings.mime_types.clone(),
+            etag: settings.etag,
+            cache_control: settings.cache_control.clone(),
+            compression: settings.compression.clone(),
+            autoindex: settings.autoindex.clone(),
+        })))
+    }
+}
+
+impl Handler for StaticFiles {
+    fn call(&self, request: HttpRequest) -> HandlerFuture {
+        let inner = self.0.clone();
+        Box::pin(async move { inner.serve(request).await })
+    }
+}
+
+impl Inner {
+    async fn serve(&self, request: HttpRequest) -> std::io::Result<HttpResponse> {
+        let path = match request.param("*") {
+            Some(rest) => format!("/{}", rest),
+            None => request.path.clone(),
+        };
+        let relative_path = match sanitize_path(&path) {
+            Ok(relative_path) => relative_path,
+            Err(error) => {
+                println!("Rejecting request path: {}", error);
+                return Ok(HttpResponse::new(StatusCodes::BadRequest, None));
+            }
+        };
+        match load_file(&self.document_root, &relative_path, &self.index_files).await {
+            Ok((path, file, metadata)) => self.serve_file(&request, &path, file, &metadata).await,
+            Err(error) if error.kind() == ErrorKind::PermissionDenied => {
+                Ok(HttpResponse::new(StatusCodes::Forbidden, None))
+            }
+            Err(error) if error.kind() == ErrorKind::NotFound && self.autoindex.enabled => {
+                self.list_directory(&request, &relative_path).await
+            }
+            Err(_) => Ok(HttpResponse::new(StatusCodes::NotFound, None)),
+        }
+    }
+
+    /// Answers with a listing of the directory at `relative_path`, as JSON when the client
+    /// prefers it, or `404 Not Found` when it isn't a directory, or is hidden and hidden
+    /// entries aren't shown.
+    async fn list_directory(
+        &self,
+        request: &HttpRequest,
+        relative_path: &Path,
+    ) -> std::io::Result<HttpResponse> {
+        let hidden = relative_path
+            .components()
+            .any(|component| component.as_os_str().to_string_lossy().starts_with('.'));
+        if hidden && !self.autoindex.show_hidden {
+            return Ok(HttpResponse::new(StatusCodes::NotFound, None));
+        }
+        let root = &self.document_root;
+        let path = match resolve_within(root, &root.join(relative_path)).await {
+            Ok(path) if path.is_dir() => path,
+            Ok(_) => return Ok(HttpResponse::new(StatusCodes::NotFound, None)),
+            Err(error) if error.kind() == ErrorKind::PermissionDenied => {
+                return Ok(HttpResponse::new(StatusCodes::Forbidden, None));
+            }
+            Err(_) => return Ok(HttpResponse::new(StatusCodes::NotFound, None)),
+        };
+        let entries = read_directory(&path, self.autoindex.show_hidden).await?;
+
+        let (content_type, body) = if prefers_json(request.headers.get("Accept")) {
+            ("application/json", render_json(&entries))
+        } else {
+            let directory = served_path(root, &root.join(relative_path));
+            (
+                "text/html; charset=utf-8",
+                render_html(&directory, &entries),
+            )
+        };
+        Ok(HttpResponse::new(StatusCodes::OK, Some(body))
+            .with_header("Content-Type", content_type)
+            .with_header("Vary", "Accept"))
+    }
+
+    /// Answers with the file at `path`, the ranges of it asked for, or `304 Not Modified` when
+    /// the client's copy is still fresh.
+    async fn serve_file(
+        &self,
+        request: &HttpRequest,
+        path: &Path,
+        file: File,
+        metadata: &Metadata,
+    ) -> std::io::Result<HttpResponse> {
+        let served_path = served_path(&self.document_root, path);
+        let content_type = self.mime_types.content_type(path);
+        let compression = &self.compression;
+        let accept_encoding = request.headers.get("Accept-Encoding");
+        let compressible = compression.on_the_fly && compression.is_compressible(content_type);
+
+        // A precompressed sibling costs nothing to send, so it's preferred to compressing one
+        let siblings = if compression.precompressed {
+            precompressed_siblings(&self.document_root, path).await
+        } else {
+            Vec::new()
+        };
+        let available: Vec<Encoding> = siblings.iter().map(|(encoding, _)| *encoding).collect();
+        let sibling = negotiate(accept_encoding, &available)
+            .and_then(|encoding| siblings.iter().find(|(e, _)| *e == encoding));
+        let (file, metadata, encoding, on_the_fly) = match sibling {
+            Some((encoding, sibling_path)) => {
+                let file = File::open(sibling_path).await?;
+                let metadata = file.metadata().await?;
+                (file, metadata, Some(*encoding), false)
+            }
+            None => {
+                // Ranges of content compressed on the fly can't be served without compressing it
+                // all first, so the content is sent as it is instead
+                let length = metadata.len();
+                let encoding = (compressible
+                    && (compression.min_size..=compression.max_size).contains(&length)
+                    && !request.headers.contains("Range"))
+                .then(|| negotiate(accept_encoding, &Encoding::ALL))
+                .flatten();
+                (file, metadata.clone(), encoding, encoding.is_some())
+            }
+        };
+        let varies = compressible || !siblings.is_empty();
+        let length = metadata.len();
+
+        let mut validators = Validators::for_file(path, &metadata, self.etag).await?;
+        if let (Some(encoding), Some(etag)) = (encoding, &validators.etag) {
+            // Each encoding is a different representation, so it needs its own entity tag, even
+            // when it's a sibling whose tag comes from the content of the original file
+            validators.etag = Some(format!(
+                "{}-{}\"",
+                etag.trim_end_matches('"'),
+                encoding.token()
+            ));
+        }
+
+        let range = if validators.not_modified(&request.headers) {
+            None
+        } else if if_range_matches(request.headers.get("If-Range"), &validators) {
+            Some(parse_range(request.headers.get("Range"), length))
+        } else {
+            Some(RangeRequest::Full)
+        };
+        let not_modified = range.is_none();
+        let mut response = match range {
+            None => HttpResponse::builder(StatusCodes::NotModified),
+            // Compressing the whole file only to drop the result would be wasted work
+            Some(RangeRequest::Full) if on_the_fly && request.method == Method::HEAD => {
+                HttpResponse::builder(StatusCodes::OK)
+                    .header("Content-Type", content_type)
+                    .body_from(Body::Omitted)
+            }
+            Some(RangeRequest::Full) if on_the_fly => {
+                let encoding = encoding.unwrap_or(Encoding::Gzip);
+                let mut content = Vec::with_capacity(length as usize);
+                file.take(length).read_to_end(&mut content).await?;
+                let compressed = tokio::task::spawn_blocking(move || encoding.compress(&content))
+                    .await
+                    .map_err(std::io::Error::other)??;
+                HttpResponse::builder(StatusCodes::OK)
+                    .header("Content-Type", content_type)
+                    .body(compressed)
+            }
+            Some(RangeRequest::Full) => HttpResponse::builder(StatusCodes::OK)
+                .header("Content-Type", content_type)
+                .header("Accept-Ranges", "bytes")
+                .file(file, length),
+            Some(RangeRequest::Partial(ranges)) if ranges.len() == 1 => {
+                HttpResponse::builder(StatusCodes::PartialContent)
+                    .header("Content-Type", content_type)
+                    .header("Accept-Ranges", "bytes")
+                    .header("Content-Range", &ranges[0].content_range(length))
+                    .body_from(Body::File {
+                        file,
+                        start: ranges[0].first,
+                        length: ranges[0].length(),
+                    })
+            }
+            Some(RangeRequest::Partial(ranges)) => {
+                let (body, multipart_type) = multipart_body(file, &ranges, content_type, length);
+                HttpResponse::builder(StatusCodes::PartialContent)
+                    .header("Content-Type", &multipart_type)
+                    .header("Accept-Ranges", "bytes")
+                    .body_from(body)
+            }
+            Some(RangeRequest::Unsatisfiable) => {
+                HttpResponse::builder(StatusCodes::RangeNotSatisfiable)
+                    .header("Accept-Ranges", "bytes")
+                    .header("Content-Range", &format!("bytes */{}", length))
+            }
+        };
+        if let Some(encoding) = encoding.filter(|_| !not_modified) {
+            response = response.header("Content-Encoding", encoding.token());
+        }
+        if varies {
+            response = response.header("Vary", "Accept-Encoding");
+        }
+        for (name, value) in validators.headers() {
+            response = response.header(name, &value);
+        }
+        if let Some(cache_control) = self.cache_control.for_path(&served_path) {
+            response = response.header("Cache-Control", cache_control);
+        }
+        Ok(response.build().unwrap_or_else(|error| {
+            println!("Unable to serve {}: {}", path.display(), error);
+            HttpResponse::new(StatusCodes::InternalServerError, None)
+        }))
+    }
+}
+
+/// The precompressed versions of the file at `path` found next to it, as `app.js.br`.
+async fn precompressed_siblings(root: &Path, path: &Path) -> Vec<(Encoding, PathBuf)> {
+    let mut siblings = Vec::new();
+    for encoding in Encoding::ALL {
+        let mut sibling = path.as_os_str().to_os_string();
+        sibling.push(".");
+        sibling.push(encoding.extension());
+        if let Ok(sibling) = resolve_within(root, Path::new(&sibling)).await {
+            if sibling.is_file() {
+                siblings.push((encoding, sibling));
+            }
+        }
+    }
+    siblings
+}
+
+/// The path of a file within the document root, as `/foobar/index.html`.
+fn served_path(root: &Path, path: &Path) -> String {
+    let relative_path = path.strip_prefix(root).unwrap_or(path);
+    relative_path
+        .components()
+        .map(|component| format!("/{}", component.as_os_str().to_string_lossy()))
+        .collect()
+}
+
+/// Opens the file at `relative_path` in `root`, or the first of `index_files` found within it
+/// when it's a directory, and returns it with its path and metadata. `root` has to be canonical
+/// already.
+///
+/// Fails with `PermissionDenied` when a symlink resolves to somewhere outside of `root`.
+async fn load_file(
+    root: &Path,
+    relative_path: &Path,
+    index_files: &[String],
+) -> std::io::Result<(PathBuf, File, Metadata)> {
+    let mut final_path = resolve_within(root, &root.join(relative_path)).await?;
+    if final_path.is_dir() {
+        let mut index = Err(std::io::Error::new(ErrorKind::NotFound, "no index file"));
+        for index_file in index_files {
+            index = resolve_within(root, &final_path.join(index_file)).await;
+            if index.is_ok() {
+                break;
+            }
+        }
+        final_path = index?;
+    }
+    let file = File::open(&final_path).await?;
+    let metadata = file.metadata().await?;
+    if !metadata.is_file() {
+        return Err(std::io::Error::new(
+            ErrorKind::NotFound,
+            "not a regular file",
+        ));
+    }
+    Ok((final_path, file, metadata))
+}
+
+/// Resolves every symlink in `path`, making sure the result is still inside `root`.
+async fn resolve_within(root: &Path, path: &Path) -> std::io::Result<PathBuf> {
+    let resolved = tokio::fs::canonicalize(path).await?;
+    if resolved.starts_with(root) {
+        Ok(resolved)
+    } else {
+        Err(std::io::Error::new(
+            ErrorKind::PermissionDenied,
+            format!("{} resolves outside of the document root", path.display()),
+        ))
+    }
+}
@@ -0,0 +1,331 @@
+use std::{
+    io::BufReader,
+    path::{Path, PathBuf},
+    sync::{Arc, RwLock},
+    time::{Duration, SystemTime},
+};
+
+use rustls::{
+    ServerConfig,
+    crypto::CryptoProvider,
+    server::{ClientHello, ResolvesServerCert},
+    sign::CertifiedKey,
+};
+use thiserror::Error;
+use tokio::task::JoinHandle;
+use tokio_rustls::TlsAcceptor;
+
+use crate::virtual_hosts::{find_site, normalize};
+
+#[derive(Debug, Error)]
+pub enum TlsError {
+    #[error("Unable to read {path}: {source}")]
+    Read {
+        path: PathBuf,
+        source: std::io::Error,
+    },
+    #[error("No certificate found in {0}")]
+    NoCertificate(PathBuf),
+    #[error("No private key found in {0}")]
+    NoPrivateKey(PathBuf),
+    #[error("Invalid certificate {certificate} or private key {private_key}: {source}")]
+    InvalidKey {
+        certificate: PathBuf,
+        private_key: PathBuf,
+        source: rustls::Error,
+    },
+    #[error(transparent)]
+    Rustls(#[from] rustls::Error),
+}
+
+/// PEM files holding a certificate chain, the server's certificate first, and its private key.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CertificateFiles {
+    pub certificate: PathBuf,
+    pub private_key: PathBuf,
+}
+
+impl CertificateFiles {
+    pub fn new(certificate: impl Into<PathBuf>, private_key: impl Into<PathBuf>) -> Self {
+        CertificateFiles {
+            certificate: certificate.into(),
+            private_key: private_key.into(),
+        }
+    }
+
+    /// When either file was last changed, so new ones can be told apart from those loaded.
+    fn modified(&self) -> Option<(SystemTime, SystemTime)> {
+        let modified = |path: &Path| std::fs::metadata(path).and_then(|m| m.modified()).ok();
+        Some((modified(&self.certificate)?, modified(&self.private_key)?))
+    }
+
+    fn load(&self, provider: &CryptoProvider) -> Result<Arc<CertifiedKey>, TlsError> {
+        let open = |path: &Path| {
+            std::fs::File::open(path)
+                .map(BufReader::new)
+                .map_err(|source| TlsError::Read {
+                    path: path.to_path_buf(),
+                    source,
+                })
+        };
+        let read_error = |path: &Path| {
+            let path = path.to_path_buf();
+            move |source| TlsError::Read { path, source }
+        };
+
+        let certificates = rustls_pemfile::certs(&mut open(&self.certificate)?)
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(read_error(&self.certificate))?;
+        if certificates.is_empty() {
+            return Err(TlsError::NoCertificate(self.certificate.clone()));
+        }
+        let private_key = rustls_pemfile::private_key(&mut open(&self.private_key)?)
+            .map_err(read_error(&self.private_key))?
+            .ok_or_else(|| TlsError::NoPrivateKey(self.private_key.clone()))?;
+
+        CertifiedKey::from_der(certificates, private_key, provider)
+            .map(Arc::new)
+            .map_err(|source| TlsError::InvalidKey {
+                certificate: self.certificate.clone(),
+                private_key: self.private_key.clone(),
+                source,
+            })
+    }
+}
+
+/// A certificate as loaded, along with when its files were changed.
+#[derive(Debug)]
+struct Loaded {
+    key: Arc<CertifiedKey>,
+    modified: Option<(SystemTime, SystemTime)>,
+}
+
+#[derive(Debug)]
+struct Site {
+    files: CertificateFiles,
+    loaded: RwLock<Loaded>,
+}
+
+impl Site {
+    fn load(files: CertificateFiles, provider: &CryptoProvider) -> Result<Self, TlsError> {
+        let modified = files.modified();
+        let key = files.load(provider)?;
+        Ok(Site {
+            files,
+            loaded: RwLock::new(Loaded { key, modified }),
+        })
+    }
+
+    fn key(&self) -> Arc<CertifiedKey> {
+        self.loaded.read().unwrap().key.clone()
+    }
+
+    /// Loads the files again if they changed since they were last loaded. Returns whether they
+    /// were, the certificate in use being kept when they can't be.
+    fn reload(&self, provider: &CryptoProvider) -> Result<bool, TlsError> {
+        let modified = self.files.modified();
+        if modified.is_none() || modified == self.loaded.read().unwrap().modified {
+            return Ok(false);
+        }
+        let key = self.files.load(provider)?;
+        *self.loaded.write().unwrap() = Loaded { key, modified };
+        Ok(true)
+    }
+}
+
+/// The certificates sent to clients, picked by the name they ask for with SNI.
+///
+/// Names match as they do for [`VirtualHosts`](crate::virtual_hosts::VirtualHosts): exact names
+/// first, then `*.domain` ones. Clients asking for any other name, or for none, get the default
+/// certificate.
+#[derive(Debug)]
+pub struct Certificates {
+    provider: Arc<CryptoProvider>,
+    sites: Vec<(Vec<String>, Site)>,
+    default: Site,
+}
+
+impl Certificates {
+    pub fn new(default: CertificateFiles) -> Result<Self, TlsError> {
+        let provider = Arc::new(rustls::crypto::ring::default_provider());
+        let default = Site::load(default, &provider)?;
+        Ok(Certificates {
+            provider,
+            sites: Vec::new(),
+            default,
+        })
+    }
+
+    /// Sends the certificate in `files` to clients asking for any of `server_names`.
+    pub fn host<S: AsRef<str>>(
+        mut self,
+        server_names: &[S],
+        files: CertificateFiles,
+    ) -> Result<Self, TlsError> {
+        let server_names = server_names
+            .iter()
+            .map(|server_name| normalize(server_name.as_ref()))
+            .collect();
+        let site = Site::load(files, &self.provider)?;
+        self.sites.push((server_names, site));
+        Ok(self)
+    }
+
+    /// Loads again the certificates whose files changed, returning how many were. One that
+    /// fails to load keeps being used, and the first error is returned once every one is tried.
+    pub fn reload(&self) -> Result<usize, TlsError> {
+        let mut reloaded = 0;
+        let mut first_error = None;
+        let sites = self
+            .sites
+            .iter()
+            .map(|(_, site)| site)
+            .chain([&self.default]);
+        for site in sites {
+            match site.reload(&self.provider) {
+                Ok(true) => reloaded += 1,
+                Ok(false) => {}
+                Err(error) => {
+                    first_error.get_or_insert(error);
+                }
+            }
+        }
+        match first_error {
+            Some(error) => Err(error),
+            None => Ok(reloaded),
+        }
+    }
+
+    /// Checks every `interval` whether the certificate files changed, so renewed certificates
+    /// are picked up without a restart. The files are read on the blocking pool, so a slow
+    /// disk doesn't hold up requests.
+    pub fn watch(self: &Arc<Self>, interval: Duration) -> JoinHandle<()> {
+        let certificates = self.clone();
+        tokio::spawn(async move {
+            loop {
+                tokio::time::sleep(interval).await;
+                let certificates = certificates.clone();
+                match tokio::task::spawn_blocking(move || certificates.reload()).await {
+                    Ok(Ok(0)) => {}
+                    Ok(Ok(reloaded)) => println!("Reloaded {} certificate(s)", reloaded),
+                    Ok(Err(error)) => println!("Keeping the current certificate: {}", error),
+                    Err(error) => println!("Unable to reload the certificates: {}", error),
+                }
+            }
+        })
+    }
+
+    /// Accepts TLS 1.2 and 1.3 connections with these certificates.
+    pub fn acceptor(self: &Arc<Self>) -> Result<TlsAcceptor, TlsError> {
+        let mut config = ServerConfig::builder_with_provider(self.provider.clone())
+            .with_safe_default_protocol_versions()?
+            .with_no_client_auth()
+            .with_cert_resolver(self.clone());
+        config.alpn_protocols = vec![b"http/1.1".to_vec()];
+        Ok(TlsAcceptor::from(Arc::new(config)))
+    }
+
+    fn site_for(&self, server_name: Option<&str>) -> &Site {
+        server_name
+            .and_then(|server_name| find_site(&self.sites, server_name))
+            .unwrap_or(&self.default)
+    }
+}
+
+impl ResolvesServerCert for Certificates {
+    fn resolve(&self, client_hello: ClientHello<'_>) -> Option<Arc<CertifiedKey>> {
+        Some(self.site_for(client_hello.server_name()).key())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        path::Path,
+        sync::Arc,
+        time::{Duration, UNIX_EPOCH},
+    };
+
+    use rcgen::CertifiedKey as Generated;
+
+    use super::{CertificateFiles, Certificates, TlsError};
+
+    fn generate(directory: &Path, name: &str) -> CertificateFiles {
+        let Generated { cert, key_pair } =
+            rcgen::generate_simple_self_signed(vec![name.to_string()]).unwrap();
+        let files = CertificateFiles::new(
+            directory.join(format!("{}.pem", name)),
+            directory.join(format!("{}.key", name)),
+        );
+        std::fs::write(&files.certificate, cert.pem()).unwrap();
+        std::fs::write(&files.private_key, key_pair.serialize_pem()).unwrap();
+        files
+    }
+
+    /// Sets when `path` was changed, as the clock may not tick between two writes.
+    fn touch(path: &Path, secs: u64) {
+        let file = std::fs::File::options().write(true).open(path).unwrap();
+        file.set_modified(UNIX_EPOCH + Duration::from_secs(secs))
+            .unwrap();
+    }
+
+    fn certificate(certificates: &Certificates, server_name: Option<&str>) -> Vec<u8> {
+        certificates.site_for(server_name).key().cert[0].to_vec()
+    }
+
+    #[test]
+    fn certificates_should_be_picked_by_server_name() {
+        let directory = tempfile::tempdir().unwrap();
+        let default = generate(directory.path(), "localhost");
+        let example = generate(directory.path(), "example.com");
+        let wildcard = generate(directory.path(), "wildcard.example.com");
+        let certificates = Certificates::new(default)
+            .unwrap()
+            .host(&["*.example.com"], wildcard)
+            .unwrap()
+            .host(&["example.com", "www.example.com"], example)
+            .unwrap();
+        let default = certificate(&certificates, None);
+        let wildcard = certificate(&certificates, Some("blog.example.com"));
+        let example = certificate(&certificates, Some("example.com"));
+
+        assert_ne!(default, example);
+        assert_ne!(wildcard, example);
+        assert_eq!(certificate(&certificates, Some("WWW.example.com")), example);
+        assert_eq!(certificate(&certificates, Some("other.org")), default);
+    }
+
+    #[test]
+    fn reload_should_keep_the_certificate_when_the_new_one_is_invalid() {
+        let directory = tempfile::tempdir().unwrap();
+        let files = generate(directory.path(), "localhost");
+        let certificates = Arc::new(Certificates::new(files.clone()).unwrap());
+        let loaded = certificate(&certificates, None);
+        assert_eq!(certificates.reload().unwrap(), 0);
+
+        // A key that doesn't go with the certificate
+        let other = generate(directory.path(), "other");
+        std::fs::copy(&other.private_key, &files.private_key).unwrap();
+        touch(&files.private_key, 1);
+        assert!(matches!(
+            certificates.reload(),
+            Err(TlsError::InvalidKey { .. })
+        ));
+        assert_eq!(certificate(&certificates, None), loaded);
+
+        std::fs::copy(&other.certificate, &files.certificate).unwrap();
+        touch(&files.certificate, 1);
+        assert_eq!(certificates.reload().unwrap(), 1);
+        assert_ne!(certificate(&certificates, None), loaded);
+    }
+
+    #[test]
+    fn certificates_should_report_missing_keys() {
+        let directory = tempfile::tempdir().unwrap();
+        let files = generate(directory.path(), "localhost");
+        std::fs::write(&files.private_key, "").unwrap();
+
+        let error = Certificates::new(files).unwrap_err();
+        assert!(matches!(error, TlsError::NoPrivateKey(_)), "{}", error);
+    }
+}
@@ -0,0 +1,121 @@
+use std::sync::Arc;
+
+use crate::router::Router;
+
+/// Picks the [`Router`] answering a request from its `Host` header, so several sites can be
+/// served from one process.
+///
+/// Names match case-insensitively and regardless of the port. A name such as `*.example.com`
+/// matches any subdomain of `example.com`, but only when no site has the exact name. Requests
+/// for any other host, and HTTP/1.0 ones without `Host`, go to the default router.
+pub struct VirtualHosts {
+    hosts: Vec<(Vec<String>, Arc<Router>)>,
+    default: Arc<Router>,
+}
+
+impl VirtualHosts {
+    pub fn new(default: Router) -> Self {
+        VirtualHosts {
+            hosts: Vec::new(),
+            default: Arc::new(default),
+        }
+    }
+
+    /// Answers the requests for any of `server_names` with `router`. The first site added wins
+    /// when several have the same name.
+    pub fn host<S: AsRef<str>>(mut self, server_names: &[S], router: Router) -> Self {
+        let server_names = server_names
+            .iter()
+            .map(|server_name| normalize(server_name.as_ref()).to_string())
+            .collect();
+        self.hosts.push((server_names, Arc::new(router)));
+        self
+    }
+
+    pub fn router_for(&self, host: Option<&str>) -> &Arc<Router> {
+        host.and_then(|host| find_site(&self.hosts, host))
+            .unwrap_or(&self.default)
+    }
+}
+
+/// The value of the site `host` belongs to: the first with the exact name, or else the first
+/// with a `*.domain` name `host` is a subdomain of.
+pub(crate) fn find_site<'a, T>(sites: &'a [(Vec<String>, T)], host: &str) -> Option<&'a T> {
+    let host = normalize(host);
+    let exact = sites
+        .iter()
+        .find(|(server_names, _)| server_names.iter().any(|name| name == &host));
+    let wildcard = || {
+        sites.iter().find(|(server_names, _)| {
+            server_names.iter().any(|name| {
+                name.strip_prefix("*.").is_some_and(|domain| {
+                    host.strip_suffix(domain)
+                        .is_some_and(|subdomain| subdomain.ends_with('.') && subdomain.len() > 1)
+                })
+            })
+        })
+    };
+    exact.or_else(wildcard).map(|(_, value)| value)
+}
+
+/// Lowercases a host and strips its port and any trailing dot, so `Example.COM.:8080` is
+/// `example.com`. IPv6 literals keep their brackets.
+pub(crate) fn normalize(host: &str) -> String {
+    let host = host.trim();
+    let without_port = match host.rfind(':') {
+        Some(colon) if !host[colon..].contains(']') => &host[..colon],
+        _ => host,
+    };
+    without_port.trim_end_matches('.').to_ascii_lowercase()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use super::{VirtualHosts, normalize};
+    use crate::router::Router;
+
+    #[test]
+    fn normalize_should_strip_ports_and_case() {
+        let cases = vec![
+            ("example.com", "example.com"),
+            ("Example.COM:8080", "example.com"),
+            ("example.com.", "example.com"),
+            ("[::1]:8080", "[::1]"),
+            ("[::1]", "[::1]"),
+            ("127.0.0.1:80", "127.0.0.1"),
+        ];
+
+        for (host, expected) in cases {
+            assert_eq!(normalize(host), expected, "{}", host);
+        }
+    }
+
+    #[test]
+    fn router_for_should_match_names_then_wildcards() {
+        let hosts = VirtualHosts::new(Router::new())
+            .host(&["*.example.com"], Router::new())
+            .host(&["example.com", "www.example.com"], Router::new())
+            .host(&["other.org"], Router::new());
+        let default = &hosts.default;
+        let wildcard = &hosts.hosts[0].1;
+        let example = &hosts.hosts[1].1;
+        let other = &hosts.hosts[2].1;
+
+        let cases = vec![
+            (Some("example.com"), example),
+            (Some("WWW.example.com:8080"), example),
+            (Some("blog.example.com"), wildcard),
+            (Some("a.b.example.com"), wildcard),
+            (Some("notexample.com"), default),
+            (Some("other.org"), other),
+            (Some("unknown.net"), default),
+            (None, default),
+        ];
+
+        for (host, expected) in cases {
+            assert!(Arc::ptr_eq(hosts.router_for(host), expected), "{:?}", host);
+        }
+    }
+}
@@ -0,0 +1,164 @@
+use std::path::Path;
+
+use tempfile::TempDir;
+use tokio::{
+    io::{AsyncReadExt, AsyncWriteExt},
+    net::{TcpListener, TcpStream},
+};
+use web_server::startup::{ServerSettings, run_server_with};
+
+struct TestApp {
+    address: String,
+    port: u16,
+    // Removed once the test is over
+    _directory: TempDir,
+}
+
+/// Serves a document root with a secret file next to it, outside of the root:
+///
+/// ```text
+/// secret.txt
+/// site/index.html
+/// site/hello world.txt
+/// site/alias.html -> index.html
+/// site/leak.txt -> ../secret.txt
+/// site/parent -> ..
+/// ```
+async fn spawn_app() -> std::io::Result<TestApp> {
+    let directory = tempfile::tempdir()?;
+    let root = directory.path().join("site");
+    std::fs::create_dir(&root)?;
+    std::fs::write(directory.path().join("secret.txt"), "top secret")?;
+    std::fs::write(root.join("index.html"), "sandboxed index")?;
+    std::fs::write(root.join("hello world.txt"), "hello")?;
+    symlink("index.html", &root.join("alias.html"))?;
+    symlink("../secret.txt", &root.join("leak.txt"))?;
+    symlink("..", &root.join("parent"))?;
+
+    let listener = TcpListener::bind("127.0.0.1:0").await?;
+    let local_address = listener.local_addr()?;
+    let settings = ServerSettings {
+        document_root: root,
+        ..ServerSettings::default()
+    };
+    tokio::task::spawn(async move {
+        run_server_with(listener, settings)
+            .await
+            .expect("Unable to start server");
+    });
+
+    Ok(TestApp {
+        address: local_address.ip().to_string(),
+        port: local_address.port(),
+        _directory: directory,
+    })
+}
+
+#[cfg(unix)]
+fn symlink(target: &str, link: &Path) -> std::io::Result<()> {
+    std::os::unix::fs::symlink(target, link)
+}
+
+/// Requests `path` exactly as given, as HTTP clients would normalize dot segments away.
+async fn get(app: &TestApp, path: &str) -> String {
+    let mut stream = TcpStream::connect((app.address.as_str(), app.port))
+        .await
+        .expect("Failed to connect");
+    let request = format!(
+        "GET {} HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n\r\n",
+        path
+    );
+    stream
+        .write_all(request.as_bytes())
+        .await
+        .expect("Failed to send request");
+
+    let mut response = String::new();
+    stream
+        .read_to_string(&mut response)
+        .await
+        .expect("Failed to read response");
+    response
+}
+
+fn status_line(response: &str) -> &str {
+    response.lines().next().unwrap_or_default()
+}
+
+#[tokio::test]
+async fn server_should_serve_from_the_configured_document_root() {
+    let app = spawn_app().await.expect("Failed to start the app");
+
+    let response = get(&app, "/").await;
+    assert_eq!(status_line(&response), "HTTP/1.1 200 OK");
+    assert!(response.ends_with("sandboxed index"));
+
+    let response = get(&app, "/hello%20world.txt").await;
+    assert_eq!(status_line(&response), "HTTP/1.1 200 OK");
+    assert!(response.ends_with("hello"));
+}
+
+#[tokio::test]
+async fn server_should_reject_paths_escaping_the_document_root() {
+    let app = spawn_app().await.expect("Failed to start the app");
+
+    let paths = [
+        "/../secret.txt",
+        "/%2e%2e/secret.txt",
+        "/%2E%2E%2Fsecret.txt",
+        "/./../secret.txt",
+        "//../secret.txt",
+        "/alias.html/../../secret.txt",
+        "/index.html%00.txt",
+        "/%c0%ae%c0%ae/secret.txt",
+    ];
+    for path in paths {
+        let response = get(&app, path).await;
+        assert_eq!(
+            status_line(&response),
+            "HTTP/1.1 400 Bad Request",
+            "{}",
+            path
+        );
+        assert!(!response.contains("top secret"), "{}", path);
+    }
+}
+
+#[tokio::test]
+async fn server_should_normalize_paths_within_the_document_root() {
+    let app = spawn_app().await.expect("Failed to start the app");
+
+    for path in ["/missing/../index.html", "/./index.html", "//index.html"] {
+        let response = get(&app, path).await;
+        assert_eq!(status_line(&response), "HTTP/1.1 200 OK", "{}", path);
+    }
+    let response = get(&app, "/..%5csecret.txt").await;
+    assert_eq!(status_line(&response), "HTTP/1.1 404 Not Found");
+}
+
+#[tokio::test]
+async fn server_should_reject_symlinks_escaping_the_document_root() {
+    let app = spawn_app().await.expect("Failed to start the app");
+
+    for path in ["/leak.txt", "/parent/secret.txt", "/parent"] {
+        let response = get(&app, path).await;
+        assert_eq!(status_line(&response), "HTTP/1.1 403 Forbidden", "{}", path);
+        assert!(!response.contains("top secret"), "{}", path);
+    }
+
+    let response = get(&app, "/alias.html").await;
+    assert_eq!(status_line(&response), "HTTP/1.1 200 OK");
+    assert!(response.ends_with("sandboxed index"));
+}
+
+#[tokio::test]
+async fn server_should_fail_to_start_without_its_document_root() {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let settings = ServerSettings {
+        document_root: "does-not-exist".into(),
+        ..ServerSettings::default()
+    };
+
+    let error = run_server_with(listener, settings).await.unwrap_err();
+    assert!(error.to_string().contains("does-not-exist"));
+}
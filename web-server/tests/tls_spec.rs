@@ -0,0 +1,202 @@
+use std::{
+    net::SocketAddr,
+    path::Path,
+    sync::Arc,
+    time::{Duration, SystemTime},
+};
+
+use rustls::{ClientConfig, RootCertStore, pki_types::ServerName};
+use tempfile::TempDir;
+use tokio::{
+    io::{AsyncReadExt, AsyncWriteExt},
+    net::{TcpListener, TcpStream},
+};
+use tokio_rustls::TlsConnector;
+use web_server::{
+    middleware::HttpsRedirect,
+    router::Router,
+    startup::{ServerSettings, run_https_server, run_server_with_hosts},
+    static_files::StaticFiles,
+    tls::{CertificateFiles, Certificates},
+    virtual_hosts::VirtualHosts,
+};
+
+struct TestApp {
+    address: SocketAddr,
+    certificates: Arc<Certificates>,
+    // Removed once the test is over
+    directory: TempDir,
+}
+
+/// Writes a self-signed certificate for `name` and its key, returning the certificate in DER.
+fn generate(files: &CertificateFiles, name: &str) -> Vec<u8> {
+    let rcgen::CertifiedKey { cert, key_pair } =
+        rcgen::generate_simple_self_signed(vec![name.to_string()]).unwrap();
+    std::fs::write(&files.certificate, cert.pem()).unwrap();
+    std::fs::write(&files.private_key, key_pair.serialize_pem()).unwrap();
+    cert.der().to_vec()
+}
+
+fn files(directory: &Path, name: &str) -> CertificateFiles {
+    CertificateFiles::new(
+        directory.join(format!("{}.pem", name)),
+        directory.join(format!("{}.key", name)),
+    )
+}
+
+/// Serves two sites over TLS, `localhost` by default and `example.com`, each with a
+/// certificate of its own.
+async fn spawn_app() -> TestApp {
+    let directory = tempfile::tempdir().expect("Failed to create the sites");
+    for site in ["localhost", "example.com"] {
+        std::fs::create_dir(directory.path().join(site)).unwrap();
+        std::fs::write(
+            directory.path().join(site).join("index.html"),
+            format!("<p>{}</p>", site),
+        )
+        .unwrap();
+        generate(&files(directory.path(), site), site);
+    }
+    let site = |name: &str| {
+        let root = directory.path().join(name);
+        async move {
+            let files = StaticFiles::new(root).await.expect("Failed to open site");
+            Router::new().mount("/", files)
+        }
+    };
+    let hosts = VirtualHosts::new(site("localhost").await)
+        .host(&["example.com"], site("example.com").await);
+    let certificates = Certificates::new(files(directory.path(), "localhost"))
+        .and_then(|certificates| {
+            certificates.host(&["example.com"], files(directory.path(), "example.com"))
+        })
+        .expect("Failed to load the certificates");
+    let certificates = Arc::new(certificates);
+    let acceptor = certificates.acceptor().unwrap();
+
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let address = listener.local_addr().unwrap();
+    tokio::spawn(async move {
+        run_https_server(vec![listener], ServerSettings::default(), hosts, acceptor)
+            .await
+            .expect("Unable to start server");
+    });
+    TestApp {
+        address,
+        certificates,
+        directory,
+    }
+}
+
+/// Sends a request for `/` over TLS asking for `name`, trusting the certificates in the app's
+/// directory. Returns the certificate the server sent and its answer.
+async fn get(app: &TestApp, name: &str) -> (Vec<u8>, String) {
+    let mut roots = RootCertStore::empty();
+    for site in ["localhost", "example.com"] {
+        let pem = std::fs::read(files(app.directory.path(), site).certificate).unwrap();
+        for certificate in rustls_pemfile::certs(&mut pem.as_slice()) {
+            roots.add(certificate.unwrap()).unwrap();
+        }
+    }
+    let config =
+        ClientConfig::builder_with_provider(Arc::new(rustls::crypto::ring::default_provider()))
+            .with_safe_default_protocol_versions()
+            .unwrap()
+            .with_root_certificates(roots)
+            .with_no_client_auth();
+
+    let stream = TcpStream::connect(app.address).await.unwrap();
+    let server_name = ServerName::try_from(name.to_string()).unwrap();
+    let mut stream = TlsConnector::from(Arc::new(config))
+        .connect(server_name, stream)
+        .await
+        .expect("Failed the TLS handshake");
+    let request = format!(
+        "GET / HTTP/1.1\r\nHost: {}\r\nConnection: close\r\n\r\n",
+        name
+    );
+    stream.write_all(request.as_bytes()).await.unwrap();
+    let mut response = Vec::new();
+    stream.read_to_end(&mut response).await.unwrap();
+
+    let certificate = stream.get_ref().1.peer_certificates().unwrap()[0].to_vec();
+    (certificate, String::from_utf8_lossy(&response).to_string())
+}
+
+#[tokio::test]
+async fn https_should_send_the_certificate_of_the_site_asked_for() {
+    let app = spawn_app().await;
+
+    for site in ["localhost", "example.com"] {
+        let (certificate, response) = get(&app, site).await;
+
+        let pem = std::fs::read(files(app.directory.path(), site).certificate).unwrap();
+        let expected = rustls_pemfile::certs(&mut pem.as_slice())
+            .next()
+            .unwrap()
+            .unwrap();
+        assert_eq!(certificate, expected.to_vec(), "{}", site);
+        assert!(response.starts_with("HTTP/1.1 200 OK\r\n"), "{}", response);
+        assert!(
+            response.ends_with(&format!("<p>{}</p>", site)),
+            "{}",
+            response
+        );
+    }
+}
+
+#[tokio::test]
+async fn https_should_pick_up_renewed_certificates() {
+    let app = spawn_app().await;
+    let (before, _) = get(&app, "example.com").await;
+    app.certificates.watch(Duration::from_millis(50));
+
+    let files = files(app.directory.path(), "example.com");
+    let renewed = generate(&files, "example.com");
+    // The clock may not have ticked since the first certificate was written
+    let later = SystemTime::now() + Duration::from_secs(10);
+    for path in [&files.certificate, &files.private_key] {
+        let file = std::fs::File::options().write(true).open(path).unwrap();
+        file.set_modified(later).unwrap();
+    }
+
+    let mut served = before.clone();
+    for _ in 0..40 {
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        served = get(&app, "example.com").await.0;
+        if served != before {
+            break;
+        }
+    }
+    assert_eq!(served, renewed);
+}
+
+#[tokio::test]
+async fn http_listeners_should_redirect_to_https() {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let address = listener.local_addr().unwrap();
+    let hosts = VirtualHosts::new(Router::new().layer(HttpsRedirect { port: 8443 }));
+    tokio::spawn(async move {
+        run_server_with_hosts(vec![listener], ServerSettings::default(), hosts)
+            .await
+            .expect("Unable to start server");
+    });
+
+    let mut stream = TcpStream::connect(address).await.unwrap();
+    let request = b"POST /login?next=%2F HTTP/1.1\r\nHost: example.com\r\n\
+                    Content-Length: 0\r\nConnection: close\r\n\r\n";
+    stream.write_all(request).await.unwrap();
+    let mut response = String::new();
+    stream.read_to_string(&mut response).await.unwrap();
+
+    assert!(
+        response.starts_with("HTTP/1.1 308 Permanent Redirect\r\n"),
+        "{}",
+        response
+    );
+    assert!(
+        response.contains("\r\nLocation: https://example.com:8443/login?next=%2F\r\n"),
+        "{}",
+        response
+    );
+}
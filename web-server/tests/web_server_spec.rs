@@ -1,5 +1,30 @@
-use tokio::net::TcpListener;
-use web_server::startup::run_server;
+use std::{io::Read, time::Duration};
+
+use tokio::{
+    io::{AsyncBufReadExt, AsyncReadExt, AsyncWriteExt, BufReader},
+    net::{TcpListener, TcpStream},
+};
+use web_server::{
+    domain::{
+        autoindex::Autoindex,
+        caching::{CacheControlRules, EtagStrategy},
+        compression::Encoding,
+        http_request::HttpRequest,
+        http_response::{HttpResponse, StatusCodes},
+        mime::MimeTypes,
+    },
+    middleware::{RequestTiming, SecurityHeaders},
+    router::Router,
+    startup::{
+        ServerSettings,
+        run_server,
+        run_server_with,
+        run_server_with_hosts,
+        run_server_with_router,
+    },
+    static_files::StaticFiles,
+    virtual_hosts::VirtualHosts,
+};
 
 struct TestApp {
     address: String,
@@ -21,6 +46,73 @@ async fn spawn_app() -> std::io::Result<TestApp> {
     Ok(TestApp { address, port })
 }
 
+async fn spawn_app_with(settings: ServerSettings) -> std::io::Result<TestApp> {
+    let listener = TcpListener::bind("127.0.0.1:0").await?;
+    let local_address = listener.local_addr()?;
+
+    tokio::task::spawn(async move {
+        run_server_with(listener, settings)
+            .await
+            .expect("Unable to start server");
+    });
+
+    Ok(TestApp {
+        address: local_address.ip().to_string(),
+        port: local_address.port(),
+    })
+}
+
+async fn connect(app: &TestApp) -> BufReader<TcpStream> {
+    let stream = TcpStream::connect((app.address.as_str(), app.port))
+        .await
+        .expect("Failed to connect");
+    BufReader::new(stream)
+}
+
+/// Reads a single response, returning its head and body, or `None` if the connection was closed.
+async fn read_response(stream: &mut BufReader<TcpStream>) -> Option<(String, String)> {
+    let mut head = String::new();
+    loop {
+        let mut line = String::new();
+        if stream.read_line(&mut line).await.expect("Failed to read") == 0 {
+            return None;
+        }
+        if line == "\r\n" {
+            break;
+        }
+        head.push_str(&line);
+    }
+    let length: usize = head
+        .lines()
+        .find_map(|line| line.strip_prefix("Content-Length: "))
+        .and_then(|length| length.parse().ok())
+        .expect("Missing Content-Length");
+    let mut body = vec![0; length];
+    stream
+        .read_exact(&mut body)
+        .await
+        .expect("Failed to read body");
+    Some((head, String::from_utf8_lossy(&body).to_string()))
+}
+
+/// Sends raw bytes and returns everything the server answers until it closes the connection.
+async fn send_raw(app: &TestApp, request: &[u8]) -> String {
+    let mut stream = TcpStream::connect((app.address.as_str(), app.port))
+        .await
+        .expect("Failed to connect");
+    stream
+        .write_all(request)
+        .await
+        .expect("Failed to send request");
+
+    let mut response = Vec::new();
+    stream
+        .read_to_end(&mut response)
+        .await
+        .expect("Failed to read response");
+    String::from_utf8_lossy(&response).to_string()
+}
+
 #[tokio::test]
 async fn server_should_listen_for_connections() {
     let app = spawn_app().await.expect("Failed to start the app");
@@ -163,3 +255,1122 @@ async fn server_should_respond_not_found_for_invalid_path() {
     let status = response.status().as_u16();
     assert_eq!(status, 404);
 }
+
+#[tokio::test]
+async fn server_should_ignore_the_query_string_when_serving_files() {
+    let app = spawn_app().await.expect("Failed to start the app");
+    let client = reqwest::Client::new();
+
+    let response = client
+        .get(format!(
+            "http://{}:{}/foobar/aloha.html?lang=en&page=1",
+            &app.address, &app.port
+        ))
+        .send()
+        .await
+        .expect("Failed to execute request");
+
+    assert_eq!(response.status().as_u16(), 200);
+}
+
+#[tokio::test]
+async fn server_should_read_request_bodies() {
+    let app = spawn_app().await.expect("Failed to start the app");
+    let client = reqwest::Client::new();
+
+    let response = client
+        .post(format!("http://{}:{}/", &app.address, &app.port))
+        .body("x".repeat(64 * 1024))
+        .send()
+        .await
+        .expect("Failed to execute request");
+
+    assert_eq!(response.status().as_u16(), 405);
+}
+
+#[tokio::test]
+async fn server_should_read_chunked_request_bodies() {
+    let app = spawn_app().await.expect("Failed to start the app");
+
+    let response = send_raw(
+        &app,
+        b"POST / HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\nTransfer-Encoding: chunked\r\n\r\n\
+          5\r\nhello\r\n0\r\n\r\n",
+    )
+    .await;
+
+    assert!(response.starts_with("HTTP/1.1 405 Method Not Allowed\r\n"));
+}
+
+#[tokio::test]
+async fn server_should_respond_bad_request_for_malformed_requests() {
+    let app = spawn_app().await.expect("Failed to start the app");
+
+    let malformed = [
+        b"GET /\r\n\r\n".as_slice(),
+        b"GET / HTTP/1.1\r\nHost : localhost\r\n\r\n",
+        b"POST / HTTP/1.1\r\nContent-Length: nope\r\n\r\n",
+    ];
+    for request in malformed {
+        let response = send_raw(&app, request).await;
+        assert!(
+            response.starts_with("HTTP/1.1 400 Bad Request\r\n"),
+            "{:?}",
+            response
+        );
+    }
+}
+
+#[tokio::test]
+async fn server_should_reject_oversized_headers() {
+    let app = spawn_app().await.expect("Failed to start the app");
+
+    let request = format!(
+        "GET / HTTP/1.1\r\nX-Large: {}\r\n\r\n",
+        "a".repeat(64 * 1024)
+    );
+    let response = send_raw(&app, request.as_bytes()).await;
+
+    assert!(response.starts_with("HTTP/1.1 431 Request Header Fields Too Large\r\n"));
+}
+
+#[tokio::test]
+async fn server_should_keep_connections_alive() {
+    let app = spawn_app().await.expect("Failed to start the app");
+    let mut stream = connect(&app).await;
+
+    for path in ["/", "/foobar/aloha.html", "/invalid_path"] {
+        let request = format!("GET {} HTTP/1.1\r\nHost: localhost\r\n\r\n", path);
+        stream.write_all(request.as_bytes()).await.unwrap();
+        let (head, _) = read_response(&mut stream).await.expect("Connection closed");
+        assert!(!head.contains("Connection: close"), "{}", head);
+    }
+
+    stream
+        .write_all(b"GET / HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n\r\n")
+        .await
+        .unwrap();
+    let (head, body) = read_response(&mut stream).await.expect("Connection closed");
+    assert!(head.starts_with("HTTP/1.1 200 OK\r\n"));
+    assert!(head.contains("Connection: close"));
+    assert!(body.contains("My web server served this page"));
+    assert_eq!(read_response(&mut stream).await, None);
+}
+
+#[tokio::test]
+async fn server_should_answer_pipelined_requests_in_order() {
+    let app = spawn_app().await.expect("Failed to start the app");
+    let mut stream = connect(&app).await;
+
+    stream
+        .write_all(
+            b"GET /foobar/aloha.html HTTP/1.1\r\nHost: localhost\r\n\r\n\
+              POST /invalid_path HTTP/1.1\r\nHost: localhost\r\nContent-Length: 4\r\n\r\nbody\
+              GET /foobar HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n\r\n",
+        )
+        .await
+        .unwrap();
+
+    let (head, body) = read_response(&mut stream).await.unwrap();
+    assert!(head.starts_with("HTTP/1.1 200 OK"));
+    assert!(body.contains("This is the Aloha file within foobar directory"));
+    let (head, _) = read_response(&mut stream).await.unwrap();
+    assert!(head.starts_with("HTTP/1.1 405 Method Not Allowed"));
+    let (head, body) = read_response(&mut stream).await.unwrap();
+    assert!(head.starts_with("HTTP/1.1 200 OK"));
+    assert!(body.contains("This is the index file within foobar directory"));
+    assert_eq!(read_response(&mut stream).await, None);
+}
+
+#[tokio::test]
+async fn server_should_close_http_1_0_connections_unless_kept_alive() {
+    let app = spawn_app().await.expect("Failed to start the app");
+
+    let mut stream = connect(&app).await;
+    stream.write_all(b"GET / HTTP/1.0\r\n\r\n").await.unwrap();
+    let (head, _) = read_response(&mut stream).await.unwrap();
+    assert!(head.contains("Connection: close"));
+    assert_eq!(read_response(&mut stream).await, None);
+
+    let mut stream = connect(&app).await;
+    stream
+        .write_all(b"GET / HTTP/1.0\r\nConnection: keep-alive\r\n\r\n")
+        .await
+        .unwrap();
+    let (head, _) = read_response(&mut stream).await.unwrap();
+    assert!(head.contains("Connection: keep-alive"));
+    stream.write_all(b"GET / HTTP/1.0\r\n\r\n").await.unwrap();
+    assert!(read_response(&mut stream).await.is_some());
+}
+
+#[tokio::test]
+async fn server_should_close_idle_connections() {
+    let app = spawn_app_with(ServerSettings {
+        idle_timeout: Duration::from_millis(200),
+        ..ServerSettings::default()
+    })
+    .await
+    .expect("Failed to start the app");
+    let mut stream = connect(&app).await;
+
+    stream
+        .write_all(b"GET / HTTP/1.1\r\nHost: localhost\r\n\r\n")
+        .await
+        .unwrap();
+    assert!(read_response(&mut stream).await.is_some());
+
+    let closed = tokio::time::timeout(Duration::from_secs(2), read_response(&mut stream)).await;
+    assert_eq!(closed, Ok(None));
+}
+
+#[tokio::test]
+async fn server_should_cap_requests_per_connection() {
+    let app = spawn_app_with(ServerSettings {
+        max_requests_per_connection: 2,
+        ..ServerSettings::default()
+    })
+    .await
+    .expect("Failed to start the app");
+    let mut stream = connect(&app).await;
+
+    let request = b"GET / HTTP/1.1\r\nHost: localhost\r\n\r\n";
+    stream.write_all(&request.repeat(3)).await.unwrap();
+
+    let (head, _) = read_response(&mut stream).await.unwrap();
+    assert!(!head.contains("Connection: close"));
+    let (head, _) = read_response(&mut stream).await.unwrap();
+    assert!(head.contains("Connection: close"));
+}
+
+#[tokio::test]
+async fn server_should_serve_binary_files_with_their_content_type() {
+    let app = spawn_app().await.expect("Failed to start the app");
+    let client = reqwest::Client::new();
+
+    let response = client
+        .get(format!(
+            "http://{}:{}/foobar/pixel.png",
+            &app.address, &app.port
+        ))
+        .send()
+        .await
+        .expect("Failed to execute request");
+
+    assert_eq!(response.status().as_u16(), 200);
+    assert_eq!(response.headers()["content-type"], "image/png");
+    let content = response
+        .bytes()
+        .await
+        .expect("Failed to extract response content.");
+    let expected = std::fs::read("www/foobar/pixel.png").expect("Missing test image");
+    assert_eq!(content.as_ref(), expected.as_slice());
+}
+
+#[tokio::test]
+async fn server_should_serve_html_with_its_content_type() {
+    let app = spawn_app().await.expect("Failed to start the app");
+    let client = reqwest::Client::new();
+
+    let response = client
+        .get(format!("http://{}:{}/foobar", &app.address, &app.port))
+        .send()
+        .await
+        .expect("Failed to execute request");
+
+    assert_eq!(
+        response.headers()["content-type"],
+        "text/html; charset=utf-8"
+    );
+}
+
+#[tokio::test]
+async fn server_should_use_the_configured_mime_types() {
+    let mut mime_types = MimeTypes::default();
+    mime_types.insert("html", "application/xhtml+xml");
+    let app = spawn_app_with(ServerSettings {
+        mime_types,
+        ..ServerSettings::default()
+    })
+    .await
+    .expect("Failed to start the app");
+    let client = reqwest::Client::new();
+
+    let response = client
+        .get(format!(
+            "http://{}:{}/foobar/aloha.html",
+            &app.address, &app.port
+        ))
+        .send()
+        .await
+        .expect("Failed to execute request");
+
+    assert_eq!(response.headers()["content-type"], "application/xhtml+xml");
+}
+
+#[tokio::test]
+async fn server_should_send_date_and_server_headers() {
+    let app = spawn_app().await.expect("Failed to start the app");
+    let client = reqwest::Client::new();
+
+    for path in ["/", "/invalid_path"] {
+        let response = client
+            .get(format!("http://{}:{}{}", &app.address, &app.port, path))
+            .send()
+            .await
+            .expect("Failed to execute request");
+
+        let headers = response.headers();
+        let date = headers["date"].to_str().unwrap();
+        assert!(date.ends_with(" GMT"), "{}", date);
+        assert!(
+            headers["server"]
+                .to_str()
+                .unwrap()
+                .starts_with("web-server/")
+        );
+    }
+}
+
+#[tokio::test]
+async fn server_should_answer_not_modified_for_fresh_copies() {
+    let app = spawn_app().await.expect("Failed to start the app");
+    let client = reqwest::Client::new();
+    let url = format!("http://{}:{}/foobar/aloha.html", &app.address, &app.port);
+
+    let response = client
+        .get(&url)
+        .send()
+        .await
+        .expect("Failed to execute request");
+    assert_eq!(response.status().as_u16(), 200);
+    let etag = response.headers()["etag"].to_str().unwrap().to_string();
+    let last_modified = response.headers()["last-modified"]
+        .to_str()
+        .unwrap()
+        .to_string();
+
+    let conditional_requests = [
+        ("if-none-match", etag.clone()),
+        ("if-none-match", format!("\"other\", W/{}", etag)),
+        ("if-modified-since", last_modified.clone()),
+    ];
+    for (name, value) in conditional_requests {
+        let response = client
+            .get(&url)
+            .header(name, &value)
+            .send()
+            .await
+            .expect("Failed to execute request");
+        assert_eq!(response.status().as_u16(), 304, "{}: {}", name, value);
+        assert_eq!(response.headers()["etag"], etag.as_str());
+        assert!(response.headers().get("content-length").is_none());
+        assert!(response.bytes().await.unwrap().is_empty());
+    }
+
+    let response = client
+        .get(&url)
+        .header("if-none-match", "\"stale\"")
+        .header("if-modified-since", &last_modified)
+        .send()
+        .await
+        .expect("Failed to execute request");
+    assert_eq!(response.status().as_u16(), 200);
+}
+
+#[tokio::test]
+async fn server_should_use_content_based_etags_when_configured() {
+    let app = spawn_app_with(ServerSettings {
+        etag: EtagStrategy::Content,
+        ..ServerSettings::default()
+    })
+    .await
+    .expect("Failed to start the app");
+    let client = reqwest::Client::new();
+
+    // Both paths serve the same file
+    let mut etags = Vec::new();
+    for path in ["/foobar", "/foobar/index.html"] {
+        let response = client
+            .get(format!("http://{}:{}{}", &app.address, &app.port, path))
+            .send()
+            .await
+            .expect("Failed to execute request");
+        etags.push(response.headers()["etag"].to_str().unwrap().to_string());
+    }
+    assert_eq!(etags[0], etags[1]);
+    assert_eq!(etags[0].len(), 18);
+}
+
+#[tokio::test]
+async fn server_should_send_cache_control_by_path_pattern() {
+    let mut cache_control = CacheControlRules::new();
+    cache_control.add("**/*.png", "public, max-age=86400");
+    cache_control.add("/foobar/**", "no-cache");
+    let app = spawn_app_with(ServerSettings {
+        cache_control,
+        ..ServerSettings::default()
+    })
+    .await
+    .expect("Failed to start the app");
+    let client = reqwest::Client::new();
+
+    let cases = [
+        ("/foobar/pixel.png", Some("public, max-age=86400")),
+        ("/foobar", Some("no-cache")),
+        ("/", None),
+    ];
+    for (path, expected) in cases {
+        let response = client
+            .get(format!("http://{}:{}{}", &app.address, &app.port, path))
+            .send()
+            .await
+            .expect("Failed to execute request");
+        let cache_control = response
+            .headers()
+            .get("cache-control")
+            .map(|value| value.to_str().unwrap());
+        assert_eq!(cache_control, expected, "{}", path);
+    }
+}
+
+#[tokio::test]
+async fn server_should_answer_single_ranges_with_partial_content() {
+    let app = spawn_app().await.expect("Failed to start the app");
+    let client = reqwest::Client::new();
+    let url = format!("http://{}:{}/foobar/pixel.png", &app.address, &app.port);
+    let content = std::fs::read("www/foobar/pixel.png").expect("Missing test image");
+    let length = content.len();
+
+    let response = client
+        .get(&url)
+        .send()
+        .await
+        .expect("Failed to execute request");
+    assert_eq!(response.headers()["accept-ranges"], "bytes");
+
+    let cases = [
+        ("bytes=0-7", 0, 7),
+        ("bytes=10-", 10, length - 1),
+        ("bytes=-4", length - 4, length - 1),
+        ("bytes=12-100000", 12, length - 1),
+    ];
+    for (range, first, last) in cases {
+        let response = client
+            .get(&url)
+            .header("range", range)
+            .send()
+            .await
+            .expect("Failed to execute request");
+        assert_eq!(response.status().as_u16(), 206, "{}", range);
+        assert_eq!(
+            response.headers()["content-range"],
+            format!("bytes {}-{}/{}", first, last, length).as_str()
+        );
+        assert_eq!(response.headers()["content-type"], "image/png");
+        let body = response.bytes().await.unwrap();
+        assert_eq!(body.as_ref(), &content[first..=last], "{}", range);
+    }
+}
+
+#[tokio::test]
+async fn server_should_answer_multiple_ranges_with_a_multipart_body() {
+    let app = spawn_app().await.expect("Failed to start the app");
+    let client = reqwest::Client::new();
+    let url = format!("http://{}:{}/foobar/aloha.html", &app.address, &app.port);
+    let content = std::fs::read_to_string("www/foobar/aloha.html").expect("Missing test page");
+    let length = content.len();
+
+    let response = client
+        .get(&url)
+        .header("range", "bytes=0-4, -3")
+        .send()
+        .await
+        .expect("Failed to execute request");
+
+    assert_eq!(response.status().as_u16(), 206);
+    let content_type = response.headers()["content-type"].to_str().unwrap();
+    let boundary = content_type
+        .strip_prefix("multipart/byteranges; boundary=")
+        .expect("Not a multipart body")
+        .to_string();
+    let body = response.text().await.unwrap();
+    let expected = format!(
+        "\r\n--{boundary}\r\nContent-Type: text/html; charset=utf-8\r\n\
+         Content-Range: bytes 0-4/{length}\r\n\r\n{}\
+         \r\n--{boundary}\r\nContent-Type: text/html; charset=utf-8\r\n\
+         Content-Range: bytes {}-{}/{length}\r\n\r\n{}\
+         \r\n--{boundary}--\r\n",
+        &content[..5],
+        length - 3,
+        length - 1,
+        &content[length - 3..],
+    );
+    assert_eq!(body, expected);
+}
+
+#[tokio::test]
+async fn server_should_reject_unsatisfiable_ranges() {
+    let app = spawn_app().await.expect("Failed to start the app");
+    let client = reqwest::Client::new();
+    let url = format!("http://{}:{}/foobar/pixel.png", &app.address, &app.port);
+    let length = std::fs::metadata("www/foobar/pixel.png").unwrap().len();
+
+    let response = client
+        .get(&url)
+        .header("range", format!("bytes={}-", length))
+        .send()
+        .await
+        .expect("Failed to execute request");
+
+    assert_eq!(response.status().as_u16(), 416);
+    assert_eq!(
+        response.headers()["content-range"],
+        format!("bytes */{}", length).as_str()
+    );
+}
+
+#[tokio::test]
+async fn server_should_ignore_ranges_when_if_range_does_not_match() {
+    let app = spawn_app().await.expect("Failed to start the app");
+    let client = reqwest::Client::new();
+    let url = format!("http://{}:{}/foobar/pixel.png", &app.address, &app.port);
+
+    let etag = client.get(&url).send().await.unwrap().headers()["etag"]
+        .to_str()
+        .unwrap()
+        .to_string();
+    let cases = [
+        (etag.as_str(), 206),
+        ("\"stale\"", 200),
+        ("W/\"weak\"", 200),
+    ];
+    for (if_range, expected) in cases {
+        let response = client
+            .get(&url)
+            .header("range", "bytes=0-1")
+            .header("if-range", if_range)
+            .send()
+            .await
+            .expect("Failed to execute request");
+        assert_eq!(response.status().as_u16(), expected, "{}", if_range);
+    }
+}
+
+/// Serves a temporary document root with a compressible page, a precompressed script and an
+/// image. The directory is removed when the returned guard is dropped.
+async fn spawn_compression_app() -> (TestApp, tempfile::TempDir, String) {
+    let directory = tempfile::tempdir().expect("Failed to create the document root");
+    let page = "<p>All work and no play makes Jack a dull boy.</p>\n".repeat(100);
+    std::fs::write(directory.path().join("page.html"), &page).unwrap();
+    std::fs::write(directory.path().join("small.html"), "<p>tiny</p>").unwrap();
+    std::fs::write(
+        directory.path().join("app.js"),
+        "console.log('plain');".repeat(100),
+    )
+    .unwrap();
+    std::fs::write(directory.path().join("app.js.br"), "precompressed brotli").unwrap();
+    std::fs::copy("www/foobar/pixel.png", directory.path().join("pixel.png")).unwrap();
+
+    let app = spawn_app_with(ServerSettings {
+        document_root: directory.path().to_path_buf(),
+        ..ServerSettings::default()
+    })
+    .await
+    .expect("Failed to start the app");
+    (app, directory, page)
+}
+
+#[tokio::test]
+async fn server_should_compress_responses_on_the_fly() {
+    let (app, _directory, page) = spawn_compression_app().await;
+    let client = reqwest::Client::new();
+    let url = format!("http://{}:{}/page.html", &app.address, &app.port);
+
+    for (accept_encoding, expected) in [("gzip", Encoding::Gzip), ("gzip, br", Encoding::Brotli)] {
+        let response = client
+            .get(&url)
+            .header("accept-encoding", accept_encoding)
+            .send()
+            .await
+            .expect("Failed to execute request");
+
+        let headers = response.headers().clone();
+        assert_eq!(headers["content-encoding"], expected.token());
+        assert_eq!(headers["vary"], "Accept-Encoding");
+        let etag = headers["etag"].to_str().unwrap();
+        assert!(
+            etag.ends_with(&format!("-{}\"", expected.token())),
+            "{}",
+            etag
+        );
+
+        let body = response.bytes().await.unwrap();
+        let mut decompressed = String::new();
+        match expected {
+            Encoding::Gzip => flate2::read::GzDecoder::new(body.as_ref())
+                .read_to_string(&mut decompressed)
+                .unwrap(),
+            Encoding::Brotli => brotli::Decompressor::new(body.as_ref(), 4096)
+                .read_to_string(&mut decompressed)
+                .unwrap(),
+        };
+        assert_eq!(decompressed, page);
+        assert!(body.len() < page.len() / 10);
+
+        let response = client
+            .get(&url)
+            .header("accept-encoding", accept_encoding)
+            .header("if-none-match", etag)
+            .send()
+            .await
+            .expect("Failed to execute request");
+        assert_eq!(response.status().as_u16(), 304);
+        assert_eq!(response.headers()["vary"], "Accept-Encoding");
+    }
+}
+
+#[tokio::test]
+async fn server_should_send_identity_when_compression_does_not_apply() {
+    let (app, _directory, page) = spawn_compression_app().await;
+    let client = reqwest::Client::new();
+
+    let cases = [
+        ("/page.html", None, None, Some("Accept-Encoding")),
+        (
+            "/page.html",
+            Some("identity"),
+            None,
+            Some("Accept-Encoding"),
+        ),
+        (
+            "/page.html",
+            Some("gzip"),
+            Some("bytes=0-9"),
+            Some("Accept-Encoding"),
+        ),
+        ("/small.html", Some("gzip"), None, Some("Accept-Encoding")),
+        ("/pixel.png", Some("gzip, br"), None, None),
+    ];
+    for (path, accept_encoding, range, vary) in cases {
+        let mut request = client.get(format!("http://{}:{}{}", &app.address, &app.port, path));
+        if let Some(accept_encoding) = accept_encoding {
+            request = request.header("accept-encoding", accept_encoding);
+        }
+        if let Some(range) = range {
+            request = request.header("range", range);
+        }
+        let response = request.send().await.expect("Failed to execute request");
+
+        let headers = response.headers();
+        assert!(headers.get("content-encoding").is_none(), "{}", path);
+        assert_eq!(
+            headers.get("vary").map(|vary| vary.to_str().unwrap()),
+            vary,
+            "{}",
+            path
+        );
+        if range.is_some() {
+            assert_eq!(response.text().await.unwrap(), &page[..10]);
+        }
+    }
+}
+
+#[tokio::test]
+async fn server_should_prefer_precompressed_siblings() {
+    let (app, _directory, _) = spawn_compression_app().await;
+    let client = reqwest::Client::new();
+    let url = format!("http://{}:{}/app.js", &app.address, &app.port);
+
+    let response = client
+        .get(&url)
+        .header("accept-encoding", "gzip, br")
+        .send()
+        .await
+        .expect("Failed to execute request");
+    let headers = response.headers();
+    assert_eq!(headers["content-encoding"], "br");
+    assert_eq!(headers["content-type"], "text/javascript; charset=utf-8");
+    assert_eq!(headers["vary"], "Accept-Encoding");
+    assert_eq!(response.text().await.unwrap(), "precompressed brotli");
+
+    // Without a `.gz` sibling, gzip is produced on the fly
+    let response = client
+        .get(&url)
+        .header("accept-encoding", "gzip")
+        .send()
+        .await
+        .expect("Failed to execute request");
+    assert_eq!(response.headers()["content-encoding"], "gzip");
+
+    let response = client
+        .get(&url)
+        .send()
+        .await
+        .expect("Failed to execute request");
+    assert!(response.headers().get("content-encoding").is_none());
+    assert!(response.text().await.unwrap().starts_with("console.log"));
+}
+
+#[tokio::test]
+async fn server_should_tag_precompressed_siblings_apart_from_the_original() {
+    let directory = tempfile::tempdir().expect("Failed to create the document root");
+    std::fs::write(directory.path().join("app.js"), "console.log('plain');").unwrap();
+    std::fs::write(directory.path().join("app.js.gz"), "precompressed gzip").unwrap();
+    let app = spawn_app_with(ServerSettings {
+        document_root: directory.path().to_path_buf(),
+        etag: EtagStrategy::Content,
+        ..ServerSettings::default()
+    })
+    .await
+    .expect("Failed to start the app");
+    let client = reqwest::Client::new();
+    let url = format!("http://{}:{}/app.js", &app.address, &app.port);
+
+    let identity = client
+        .get(&url)
+        .send()
+        .await
+        .expect("Failed to execute request");
+    let identity_etag = identity.headers()["etag"].to_str().unwrap().to_string();
+    let gzip = client
+        .get(&url)
+        .header("accept-encoding", "gzip")
+        .send()
+        .await
+        .expect("Failed to execute request");
+    assert_eq!(gzip.headers()["content-encoding"], "gzip");
+    let gzip_etag = gzip.headers()["etag"].to_str().unwrap();
+    assert_eq!(
+        gzip_etag,
+        format!("{}-gzip\"", identity_etag.trim_end_matches('"'))
+    );
+
+    // Resuming the identity download must not splice in gzip bytes
+    let response = client
+        .get(&url)
+        .header("accept-encoding", "gzip")
+        .header("range", "bytes=5-")
+        .header("if-range", &identity_etag)
+        .send()
+        .await
+        .expect("Failed to execute request");
+    assert_eq!(response.status().as_u16(), 200);
+    assert_eq!(response.text().await.unwrap(), "precompressed gzip");
+}
+
+#[tokio::test]
+async fn server_should_answer_head_requests_without_a_body() {
+    let app = spawn_app().await.expect("Failed to start the app");
+    let mut stream = connect(&app).await;
+    let expected_length = std::fs::metadata("www/foobar/aloha.html").unwrap().len();
+
+    // The GET that follows on the same connection shows no body was sent for the HEAD
+    stream
+        .write_all(
+            b"HEAD /foobar/aloha.html HTTP/1.1\r\nHost: localhost\r\n\r\n\
+              GET /foobar/aloha.html HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n\r\n",
+        )
+        .await
+        .unwrap();
+
+    let mut head = String::new();
+    loop {
+        let mut line = String::new();
+        stream.read_line(&mut line).await.unwrap();
+        if line == "\r\n" {
+            break;
+        }
+        head.push_str(&line);
+    }
+    assert!(head.starts_with("HTTP/1.1 200 OK\r\n"));
+    assert!(head.contains(&format!("Content-Length: {}\r\n", expected_length)));
+    assert!(head.contains("Content-Type: text/html; charset=utf-8\r\n"));
+    assert!(head.contains("ETag: "));
+
+    let (head, body) = read_response(&mut stream)
+        .await
+        .expect("Missing GET response");
+    assert!(head.starts_with("HTTP/1.1 200 OK\r\n"));
+    assert_eq!(body.len() as u64, expected_length);
+}
+
+#[tokio::test]
+async fn server_should_not_compress_bodies_for_head_requests() {
+    let (app, _directory, page) = spawn_compression_app().await;
+    let mut stream = connect(&app).await;
+
+    stream
+        .write_all(
+            b"HEAD /page.html HTTP/1.1\r\nHost: localhost\r\nAccept-Encoding: gzip\r\n\r\n\
+              GET /page.html HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n\r\n",
+        )
+        .await
+        .unwrap();
+
+    let mut head = String::new();
+    loop {
+        let mut line = String::new();
+        stream.read_line(&mut line).await.unwrap();
+        if line == "\r\n" {
+            break;
+        }
+        head.push_str(&line);
+    }
+    assert!(head.starts_with("HTTP/1.1 200 OK\r\n"), "{}", head);
+    assert!(head.contains("Content-Encoding: gzip\r\n"), "{}", head);
+    // Its length would only be known by compressing the page
+    assert!(!head.contains("Content-Length"), "{}", head);
+
+    // The GET that follows on the same connection shows no body was sent for the HEAD
+    let (head, body) = read_response(&mut stream)
+        .await
+        .expect("Missing GET response");
+    assert!(head.starts_with("HTTP/1.1 200 OK\r\n"), "{}", head);
+    assert_eq!(body, page);
+}
+
+#[tokio::test]
+async fn server_should_report_allowed_methods_for_options() {
+    let app = spawn_app().await.expect("Failed to start the app");
+
+    for target in ["/", "/foobar/aloha.html", "*"] {
+        let request = format!(
+            "OPTIONS {} HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n\r\n",
+            target
+        );
+        let response = send_raw(&app, request.as_bytes()).await;
+        assert!(
+            response.starts_with("HTTP/1.1 204 No Content\r\n"),
+            "{}",
+            response
+        );
+        assert!(
+            response.contains("Allow: GET, HEAD, OPTIONS\r\n"),
+            "{}",
+            response
+        );
+    }
+}
+
+#[tokio::test]
+async fn server_should_reject_other_methods_with_method_not_allowed() {
+    let app = spawn_app().await.expect("Failed to start the app");
+    let client = reqwest::Client::new();
+    let url = format!("http://{}:{}/foobar/aloha.html", &app.address, &app.port);
+
+    let requests = [
+        client.post(&url).body("data"),
+        client.put(&url).body("data"),
+        client.delete(&url),
+        client.patch(&url).body("data"),
+    ];
+    for request in requests {
+        let response = request.send().await.expect("Failed to execute request");
+        assert_eq!(response.status().as_u16(), 405);
+        assert_eq!(response.headers()["allow"], "GET, HEAD, OPTIONS");
+    }
+}
+
+async fn spawn_autoindex_app(autoindex: Autoindex) -> (TestApp, tempfile::TempDir) {
+    let directory = tempfile::tempdir().expect("Failed to create the document root");
+    let files = directory.path().join("files");
+    std::fs::create_dir_all(files.join("nested")).unwrap();
+    std::fs::write(files.join("b.txt"), "bb").unwrap();
+    std::fs::write(files.join("a <1>.txt"), "a").unwrap();
+    std::fs::write(files.join(".env"), "SECRET=1").unwrap();
+
+    let app = spawn_app_with(ServerSettings {
+        document_root: directory.path().to_path_buf(),
+        autoindex,
+        ..ServerSettings::default()
+    })
+    .await
+    .expect("Failed to start the app");
+    (app, directory)
+}
+
+#[tokio::test]
+async fn server_should_not_list_directories_by_default() {
+    let (app, _directory) = spawn_autoindex_app(Autoindex::default()).await;
+
+    let response = reqwest::get(format!("http://{}:{}/files/", &app.address, &app.port))
+        .await
+        .expect("Failed to execute request");
+
+    assert_eq!(response.status().as_u16(), 404);
+}
+
+#[tokio::test]
+async fn server_should_list_directories_as_html() {
+    let (app, _directory) = spawn_autoindex_app(Autoindex {
+        enabled: true,
+        show_hidden: false,
+    })
+    .await;
+
+    let response = reqwest::get(format!("http://{}:{}/files", &app.address, &app.port))
+        .await
+        .expect("Failed to execute request");
+
+    assert_eq!(response.status().as_u16(), 200);
+    assert_eq!(
+        response.headers()["content-type"],
+        "text/html; charset=utf-8"
+    );
+    assert_eq!(response.headers()["vary"], "Accept");
+    let body = response.text().await.unwrap();
+    assert!(body.contains("<title>Index of /files/</title>"), "{}", body);
+    let nested = body
+        .find("href=\"/files/nested/\"")
+        .expect("Missing nested/");
+    let a = body
+        .find("href=\"/files/a%20%3C1%3E.txt\">a &lt;1&gt;.txt</a>")
+        .expect("Missing a <1>.txt");
+    let b = body.find("href=\"/files/b.txt\"").expect("Missing b.txt");
+    assert!(nested < a && a < b, "{}", body);
+    assert!(body.contains("<td>2</td>"), "{}", body);
+    assert!(!body.contains(".env"), "{}", body);
+}
+
+#[tokio::test]
+async fn server_should_list_directories_as_json_when_asked() {
+    let (app, _directory) = spawn_autoindex_app(Autoindex {
+        enabled: true,
+        show_hidden: true,
+    })
+    .await;
+
+    let response = reqwest::Client::new()
+        .get(format!("http://{}:{}/files/", &app.address, &app.port))
+        .header("Accept", "application/json")
+        .send()
+        .await
+        .expect("Failed to execute request");
+
+    assert_eq!(response.status().as_u16(), 200);
+    assert_eq!(response.headers()["content-type"], "application/json");
+    let body = response.text().await.unwrap();
+    assert!(
+        body.starts_with("[{\"name\":\"nested\",\"type\":\"directory\""),
+        "{}",
+        body
+    );
+    assert!(
+        body.contains("{\"name\":\".env\",\"type\":\"file\",\"size\":8,"),
+        "{}",
+        body
+    );
+    assert!(
+        body.contains("{\"name\":\"b.txt\",\"type\":\"file\",\"size\":2,"),
+        "{}",
+        body
+    );
+}
+
+#[tokio::test]
+async fn server_should_not_list_hidden_directories() {
+    let (app, directory) = spawn_autoindex_app(Autoindex {
+        enabled: true,
+        show_hidden: false,
+    })
+    .await;
+    std::fs::create_dir_all(directory.path().join(".git/objects")).unwrap();
+    std::fs::write(directory.path().join(".git/HEAD"), "ref: refs/heads/main").unwrap();
+
+    for path in ["/.git/", "/.git", "/.git/objects/"] {
+        let response = reqwest::get(format!("http://{}:{}{}", &app.address, &app.port, path))
+            .await
+            .expect("Failed to execute request");
+
+        assert_eq!(response.status().as_u16(), 404, "{}", path);
+    }
+}
+
+#[tokio::test]
+async fn server_should_prefer_index_html_to_listing() {
+    let (app, directory) = spawn_autoindex_app(Autoindex {
+        enabled: true,
+        show_hidden: false,
+    })
+    .await;
+    std::fs::write(directory.path().join("files/index.html"), "<p>index</p>").unwrap();
+
+    let response = reqwest::get(format!("http://{}:{}/files/", &app.address, &app.port))
+        .await
+        .expect("Failed to execute request");
+
+    assert_eq!(response.status().as_u16(), 200);
+    assert_eq!(response.text().await.unwrap(), "<p>index</p>");
+}
+
+async fn user(request: HttpRequest) -> std::io::Result<HttpResponse> {
+    let id = request.param("id").unwrap_or_default();
+    Ok(
+        HttpResponse::new(StatusCodes::OK, Some(format!("user {}", id)))
+            .with_header("Content-Type", "text/plain; charset=utf-8"),
+    )
+}
+
+#[tokio::test]
+async fn server_should_route_requests_to_handlers() {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let address = listener.local_addr().unwrap();
+    let files = StaticFiles::new("www").await.expect("Failed to open www");
+    let router = Router::new()
+        .get("/users/:id", user)
+        .post("/users", |request: HttpRequest| async move {
+            let body = String::from_utf8_lossy(&request.body).to_string();
+            Ok(HttpResponse::new(StatusCodes::Created, Some(body)))
+        })
+        .mount("/static", files);
+    tokio::spawn(async move {
+        run_server_with_router(listener, ServerSettings::default(), router)
+            .await
+            .expect("Unable to start server");
+    });
+    let client = reqwest::Client::new();
+    let url = |path: &str| format!("http://{}{}", address, path);
+
+    let response = client.get(url("/users/a%20b")).send().await.unwrap();
+    assert_eq!(response.status().as_u16(), 200);
+    assert_eq!(response.text().await.unwrap(), "user a b");
+
+    let response = client.post(url("/users")).body("ada").send().await.unwrap();
+    assert_eq!(response.status().as_u16(), 201);
+    assert_eq!(response.text().await.unwrap(), "ada");
+
+    let response = client
+        .get(url("/static/foobar/aloha.html"))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status().as_u16(), 200);
+    assert_eq!(
+        response.headers()["content-type"],
+        "text/html; charset=utf-8"
+    );
+
+    let response = client.delete(url("/users/1")).send().await.unwrap();
+    assert_eq!(response.status().as_u16(), 405);
+    assert_eq!(response.headers()["allow"], "GET, HEAD, OPTIONS");
+
+    let response = client.get(url("/foobar/aloha.html")).send().await.unwrap();
+    assert_eq!(response.status().as_u16(), 404);
+}
+
+#[tokio::test]
+async fn server_should_run_middleware_around_routes() {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let address = listener.local_addr().unwrap();
+    let files = StaticFiles::new("www").await.expect("Failed to open www");
+    let router = Router::new()
+        .mount("/", files)
+        .layer(SecurityHeaders::default())
+        .layer(RequestTiming);
+    tokio::spawn(async move {
+        run_server_with_router(listener, ServerSettings::default(), router)
+            .await
+            .expect("Unable to start server");
+    });
+
+    for path in ["/", "/missing.html"] {
+        let response = reqwest::get(format!("http://{}{}", address, path))
+            .await
+            .expect("Failed to execute request");
+
+        let headers = response.headers();
+        assert_eq!(headers["x-content-type-options"], "nosniff");
+        assert_eq!(headers["content-security-policy"], "default-src 'self'");
+        assert!(!headers.contains_key("strict-transport-security"));
+        assert!(
+            headers["server-timing"]
+                .to_str()
+                .unwrap()
+                .starts_with("total;dur=")
+        );
+    }
+}
+
+async fn spawn_virtual_hosts_app() -> (TestApp, tempfile::TempDir) {
+    let directory = tempfile::tempdir().expect("Failed to create the sites");
+    for site in ["default", "example", "blog"] {
+        std::fs::create_dir(directory.path().join(site)).unwrap();
+        std::fs::write(
+            directory.path().join(site).join("index.html"),
+            format!("<p>{}</p>", site),
+        )
+        .unwrap();
+    }
+    let site = |name: &str| {
+        let root = directory.path().join(name);
+        async move {
+            let files = StaticFiles::new(root).await.expect("Failed to open site");
+            Router::new().mount("/", files)
+        }
+    };
+    let hosts = VirtualHosts::new(site("default").await)
+        .host(&["example.com", "www.example.com"], site("example").await)
+        .host(&["*.blog.example.com"], site("blog").await);
+
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let address = listener.local_addr().unwrap();
+    tokio::spawn(async move {
+        run_server_with_hosts(vec![listener], ServerSettings::default(), hosts)
+            .await
+            .expect("Unable to start server");
+    });
+    let app = TestApp {
+        address: address.ip().to_string(),
+        port: address.port(),
+    };
+    (app, directory)
+}
+
+#[tokio::test]
+async fn server_should_route_requests_by_host() {
+    let (app, _directory) = spawn_virtual_hosts_app().await;
+
+    let cases = [
+        ("example.com", "example"),
+        ("WWW.Example.com:8080", "example"),
+        ("alice.blog.example.com", "blog"),
+        ("localhost", "default"),
+    ];
+    for (host, site) in cases {
+        let request = format!(
+            "GET / HTTP/1.1\r\nHost: {}\r\nConnection: close\r\n\r\n",
+            host
+        );
+        let response = send_raw(&app, request.as_bytes()).await;
+        assert!(response.starts_with("HTTP/1.1 200 OK\r\n"), "{}", response);
+        assert!(
+            response.ends_with(&format!("<p>{}</p>", site)),
+            "{} {}",
+            host,
+            response
+        );
+    }
+
+    // HTTP/1.0 doesn't need a Host, so the default site answers
+    let response = send_raw(&app, b"GET / HTTP/1.0\r\n\r\n").await;
+    assert!(response.ends_with("<p>default</p>"), "{}", response);
+}
+
+#[tokio::test]
+async fn server_should_reject_http_1_1_requests_without_a_single_host() {
+    let (app, _directory) = spawn_virtual_hosts_app().await;
+
+    let requests: [&[u8]; 2] = [
+        b"GET / HTTP/1.1\r\n\r\n",
+        b"GET / HTTP/1.1\r\nHost: example.com\r\nHost: other.org\r\n\r\n",
+    ];
+    for request in requests {
+        let response = send_raw(&app, request).await;
+        assert!(
+            response.starts_with("HTTP/1.1 400 Bad Request\r\n"),
+            "{}",
+            response
+        );
+        assert!(response.contains("Connection: close\r\n"), "{}", response);
+    }
+}